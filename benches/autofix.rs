@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use zepter::autofix::{AutoFixer, EntryMatch};
+
+/// A manifest with `features` features that each reference `deps` dependencies.
+fn synthetic_manifest(features: usize, deps: usize) -> String {
+	let mut raw = String::from("[features]\n");
+	for f in 0..features {
+		raw.push_str(&format!("f{} = [\n", f));
+		for d in 0..deps {
+			raw.push_str(&format!("\t\"dep{}/f{}\",\n\t\"dep{}?/std\",\n", d, f, d));
+		}
+		raw.push_str("]\n");
+	}
+	raw
+}
+
+fn remove_feature(c: &mut Criterion) {
+	let raw = synthetic_manifest(100, 250);
+	// Like `transpose features strip-dev-only` with every fifth dependency being dev-only.
+	let patterns = (0..250)
+		.step_by(5)
+		.flat_map(|d| [format!("dep{}/", d), format!("dep{}?/", d)])
+		.collect::<Vec<_>>();
+
+	c.bench_function("AutoFixer / remove_feature / 100x500 one by one", |b| {
+		b.iter_batched(
+			|| AutoFixer::from_raw(&raw).unwrap(),
+			|mut fixer| {
+				for pattern in patterns.iter() {
+					fixer.remove_feature(pattern);
+				}
+				black_box(fixer)
+			},
+			BatchSize::LargeInput,
+		);
+	});
+
+	let patterns = patterns.iter().map(String::as_str).collect::<Vec<_>>();
+	c.bench_function("AutoFixer / remove_feature / 100x500 single pass", |b| {
+		b.iter_batched(
+			|| AutoFixer::from_raw(&raw).unwrap(),
+			|mut fixer| {
+				fixer.remove_from_features(&patterns, EntryMatch::Prefix);
+				black_box(fixer)
+			},
+			BatchSize::LargeInput,
+		);
+	});
+}
+
+criterion_group!(benches, remove_feature);
+criterion_main!(benches);
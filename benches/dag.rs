@@ -3,7 +3,10 @@
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rand::{Rng, SeedableRng};
-use zepter::{cmd::lint::build_feature_dag, prelude::*};
+use zepter::{
+	cmd::{lint::build_feature_dag, PackageIndex},
+	prelude::*,
+};
 
 fn build_dag(nodes: usize, edges: usize) -> Dag<usize> {
 	let mut rng = rand::rngs::StdRng::seed_from_u64(42);
@@ -24,6 +27,26 @@ fn any_path(dag: &Dag<usize>) {
 	dag.any_path(&0, &1);
 }
 
+/// Reachability from every node to node `1`, which is what the lints do for each crate.
+fn all_nodes(c: &mut Criterion, name: &str, dag: &Dag<usize>) {
+	let nodes = dag.lhs_iter().copied().collect::<Vec<_>>();
+	c.bench_function(&format!("{} / all nodes: uncached", name), |b| {
+		b.iter(|| {
+			for node in nodes.iter() {
+				black_box(dag.reachable(node, &1));
+			}
+		});
+	});
+	c.bench_function(&format!("{} / all nodes: cached", name), |b| {
+		b.iter(|| {
+			let reachability = dag.reachability();
+			for node in nodes.iter() {
+				black_box(reachability.reachable(node, &1));
+			}
+		});
+	});
+}
+
 fn dag(c: &mut Criterion) {
 	let dag = build_dag(1000, 1000);
 	c.bench_function("DAG 1k/1k", |b| {
@@ -53,6 +76,8 @@ fn dag(c: &mut Criterion) {
 			black_box(());
 		});
 	});
+
+	all_nodes(c, "DAG 1k/5k", &build_dag(1000, 5000));
 }
 
 fn polkadot_sdk(c: &mut Criterion) {
@@ -62,15 +87,43 @@ fn polkadot_sdk(c: &mut Criterion) {
 	let meta = serde_json::from_str::<cargo_metadata::Metadata>(&file).unwrap();
 
 	let pkgs = &meta.packages;
-	let dag = build_feature_dag(&meta, pkgs);
+	let index = PackageIndex::new(&meta);
+	let dag = build_feature_dag(&index, pkgs);
 
 	c.bench_function("Polkadot-SDK / DAG / setup", |b| {
 		b.iter(|| {
-			let dag = build_feature_dag(&meta, pkgs);
+			let index = PackageIndex::new(&meta);
+			let dag = build_feature_dag(&index, pkgs);
 			black_box(dag)
 		});
 	});
 
+	// Look up every resolved dependency once; this is what the commands do in their loops.
+	let ids = meta
+		.resolve
+		.as_ref()
+		.unwrap()
+		.nodes
+		.iter()
+		.flat_map(|n| n.deps.iter().map(|d| d.pkg.repr.as_str()))
+		.collect::<Vec<_>>();
+
+	c.bench_function("Polkadot-SDK / lookup / linear", |b| {
+		b.iter(|| {
+			for id in ids.iter() {
+				black_box(pkgs.iter().find(|p| p.id.repr == *id));
+			}
+		});
+	});
+
+	c.bench_function("Polkadot-SDK / lookup / index", |b| {
+		b.iter(|| {
+			for id in ids.iter() {
+				black_box(index.get(id));
+			}
+		});
+	});
+
 	let from = dag.lhs_iter().find(|c| c.0.starts_with("kitchensink-runtime ")).unwrap();
 	let to = dag.rhs_iter().find(|c| c.0.starts_with("sp-io ")).unwrap();
 	assert!(dag.lhs_contains(from), "LHS:\n{:?}", dag.lhs_nodes().collect::<Vec<_>>());
@@ -82,6 +135,30 @@ fn polkadot_sdk(c: &mut Criterion) {
 			black_box(p)
 		});
 	});
+
+	let reachability = dag.reachability();
+	c.bench_function("Polkadot-SDK / DAG / reachability: false, cached", |b| {
+		b.iter(|| black_box(reachability.reachable(from, to)));
+	});
+
+	// What `never-implies` does: one query from every LHS node.
+	let lhs = dag.lhs_iter().collect::<Vec<_>>();
+	c.bench_function("Polkadot-SDK / DAG / all LHS: uncached", |b| {
+		b.iter(|| {
+			for node in lhs.iter() {
+				black_box(dag.any_path(node, to));
+			}
+		});
+	});
+	c.bench_function("Polkadot-SDK / DAG / all LHS: cached", |b| {
+		b.iter(|| {
+			// Includes filling the cache, since lints do that once per run.
+			let reachability = dag.reachability();
+			for node in lhs.iter() {
+				black_box(reachability.reachable(node, to));
+			}
+		});
+	});
 }
 
 criterion_group!(benches, polkadot_sdk, dag);
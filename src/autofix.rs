@@ -5,16 +5,90 @@
 
 use crate::{
 	cmd::{fmt::Mode, transpose::SourceLocationSelector},
+	grammar::plural,
 	log,
+	manifest::{self, DEPENDENCY_KINDS},
 };
 use cargo_metadata::{Dependency, DependencyKind};
 use std::{
 	collections::BTreeMap as Map,
+	fmt::{self, Write as _},
+	fs::{File, OpenOptions},
+	io::{ErrorKind, Write},
 	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
 };
-use toml_edit::{table, value, Array, DocumentMut, Formatted, InlineTable, Item, Table, Value};
+use toml_edit::{
+	table, value, Array, DocumentMut, Formatted, InlineTable, Item, Key, Table, TableLike, Value,
+};
+
+/// Serializes the manifest writes of all threads of this process.
+static WRITER: Mutex<()> = Mutex::new(());
+
+/// Counter to give the write probes of [`AutoFixer::validate`] and broken locks unique names.
+static PROBES: AtomicUsize = AtomicUsize::new(0);
+
+/// How long to wait for another process to release the lock of a manifest.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Why a manifest could not be loaded or saved.
+#[derive(Debug)]
+pub enum ManifestError {
+	/// Accessing the file failed.
+	Io {
+		path: PathBuf,
+		/// What was done with the file, like `read` or `write`.
+		action: &'static str,
+		error: std::io::Error,
+	},
+	/// The content is not valid TOML.
+	Parse { path: Option<PathBuf>, error: toml_edit::TomlError },
+	/// Another process did not release the lock of the manifest in time.
+	Locked { lock: PathBuf },
+	/// The manifest on disk differs from the one that the fixes are based on.
+	Changed { path: PathBuf },
+}
+
+impl fmt::Display for ManifestError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io { path, action, error } =>
+				write!(f, "Failed to {} manifest {}: {}", action, path.display(), error),
+			Self::Parse { path: Some(path), error } =>
+				write!(f, "Failed to parse manifest {}: {}", path.display(), error),
+			Self::Parse { path: None, error } => write!(f, "Failed to parse manifest: {}", error),
+			Self::Locked { lock } => write!(
+				f,
+				"Timed out waiting for the lock file {}. Delete it if no other Zepter process is running.",
+				lock.display()
+			),
+			Self::Changed { path } =>
+				write!(f, "Manifest {} was modified since it was read", path.display()),
+		}
+	}
+}
+
+impl std::error::Error for ManifestError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io { error, .. } => Some(error),
+			Self::Parse { error, .. } => Some(error),
+			Self::Locked { .. } | Self::Changed { .. } => None,
+		}
+	}
+}
+
+impl From<ManifestError> for String {
+	fn from(e: ManifestError) -> Self {
+		e.to_string()
+	}
+}
 
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 #[cfg_attr(feature = "testing", derive(Default))]
 pub struct AutoFixerArgs {
 	/// Try to automatically fix the problems.
@@ -22,6 +96,45 @@ pub struct AutoFixerArgs {
 	pub enable: bool,
 }
 
+/// How to resolve feature entries that only differ by a `?`, like `foo/std` and `foo?/std`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConflictResolution {
+	/// Keep the weak `foo?/std` that does not enable an optional `foo`.
+	PreferWeak,
+	/// Keep the strong `foo/std` that also enables an optional `foo`.
+	PreferStrong,
+	/// Report the conflict to be resolved manually.
+	#[default]
+	Error,
+}
+
+/// How the entries of a feature are matched against a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryMatch {
+	/// The entry starts with the pattern, like `foo/` for `foo/std`.
+	Prefix,
+	/// The entry is exactly the pattern.
+	Exact,
+}
+
+impl EntryMatch {
+	pub fn matches(self, entry: &str, pattern: &str) -> bool {
+		match self {
+			Self::Prefix => entry.starts_with(pattern),
+			Self::Exact => entry == pattern,
+		}
+	}
+}
+
+/// An entry that was removed from a feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedEntry {
+	/// The feature that contained the entry.
+	pub feature: String,
+	/// The removed entry, like `foo?/std`.
+	pub entry: String,
+}
+
 pub struct AutoFixer {
 	pub manifest: Option<PathBuf>,
 	doc: Option<DocumentMut>,
@@ -29,22 +142,42 @@ pub struct AutoFixer {
 }
 
 impl AutoFixer {
-	pub fn from_manifest<P: AsRef<Path>>(manifest: P) -> Result<Self, String> {
-		let raw = std::fs::read_to_string(&manifest)
-			.map_err(|e| format!("Failed to read manifest: {e}"))?;
-		let doc = raw
-			.parse::<DocumentMut>()
-			.map_err(|e| format!("Failed to parse manifest: {e}"))?;
-		Ok(Self { raw, manifest: Some(manifest.as_ref().to_path_buf()), doc: Some(doc) })
+	pub fn from_manifest<P: AsRef<Path>>(manifest: P) -> Result<Self, ManifestError> {
+		let path = manifest.as_ref().to_path_buf();
+		let raw = match std::fs::read_to_string(&path) {
+			Ok(raw) => raw,
+			Err(error) => return Err(ManifestError::Io { path, action: "read", error }),
+		};
+		let doc = match raw.parse::<DocumentMut>() {
+			Ok(doc) => doc,
+			Err(error) => return Err(ManifestError::Parse { path: Some(path), error }),
+		};
+		Ok(Self { raw, manifest: Some(path), doc: Some(doc) })
 	}
 
-	pub fn from_raw(raw: &str) -> Result<Self, String> {
+	pub fn from_raw(raw: &str) -> Result<Self, ManifestError> {
 		let doc = raw
 			.parse::<DocumentMut>()
-			.map_err(|e| format!("Failed to parse manifest: {e}"))?;
+			.map_err(|error| ManifestError::Parse { path: None, error })?;
 		Ok(Self { raw: raw.into(), manifest: None, doc: Some(doc) })
 	}
 
+	/// The parsed manifest, for example to inspect it with the helpers of [`manifest`].
+	pub fn document(&self) -> &DocumentMut {
+		self.doc.as_ref().unwrap()
+	}
+
+	/// The parsed manifest, for example to edit it with the helpers of [`manifest`].
+	pub fn document_mut(&mut self) -> &mut DocumentMut {
+		self.doc.as_mut().unwrap()
+	}
+
+	/// The `package.name` of the manifest, if any.
+	pub fn package_name(&self) -> Option<String> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		doc.get("package")?.get("name")?.as_str().map(Into::into)
+	}
+
 	// Assumes sorting
 	pub fn dedub_feature(cname: &str, fname: &str, feature: &mut Array) -> Result<(), String> {
 		let _ = cname;
@@ -90,6 +223,61 @@ impl AutoFixer {
 		Ok(())
 	}
 
+	/// Remove the entries of `feature` that conflict with another one only by a `?`.
+	///
+	/// Does nothing for [`ConflictResolution::Error`], since [`Self::dedub_feature`] reports them.
+	pub fn resolve_conflicts(
+		cname: &str,
+		fname: &str,
+		feature: &mut Array,
+		resolution: ConflictResolution,
+	) -> Result<(), String> {
+		let _ = cname;
+		let keep_weak = match resolution {
+			ConflictResolution::Error => return Ok(()),
+			ConflictResolution::PreferWeak => true,
+			ConflictResolution::PreferStrong => false,
+		};
+		// The entries without `?` and whether they had one.
+		let entries = feature
+			.iter()
+			.map(|v| {
+				let s = v.as_str().unwrap_or_default();
+				(s.replace('?', ""), s.contains('?'))
+			})
+			.collect::<Vec<_>>();
+
+		for (i, (entry, weak)) in entries.iter().enumerate().rev() {
+			let kept = entries.iter().any(|(other, w)| other == entry && *w == keep_weak);
+			if *weak == keep_weak || !kept {
+				continue
+			}
+			let value = feature.get(i).unwrap();
+			let prefix = value.decor().prefix().and_then(|p| p.as_str()).unwrap_or_default();
+			let suffix = value.decor().suffix().and_then(|s| s.as_str()).unwrap_or_default();
+			if !prefix.trim().is_empty() || !suffix.trim().is_empty() {
+				return Err(format!(
+					"feature '{fname}': has a comment '{}'",
+					value.as_str().unwrap()
+				))
+			}
+			feature.remove(i);
+			log::debug!("Removed conflicting entry of '{entry}' from '{cname}' / '{fname}'");
+		}
+		Ok(())
+	}
+
+	/// Resolve the conflicting entries of the feature `fname` with [`Self::resolve_conflicts`].
+	pub fn resolve_feature_conflicts(
+		&mut self,
+		cname: &str,
+		fname: &str,
+		resolution: ConflictResolution,
+	) -> Result<(), String> {
+		let Ok(feature) = self.get_feature_mut(fname) else { return Ok(()) };
+		Self::resolve_conflicts(cname, fname, feature, resolution)
+	}
+
 	pub fn sort_feature(feature: &mut Array) {
 		let mut values = feature.iter().cloned().collect::<Vec<_>>();
 		// DOGSHIT CODE
@@ -235,7 +423,19 @@ impl AutoFixer {
 			feature.push_formatted(value.clone());
 		}
 		feature.set_trailing_comma(false);
-		feature.set_trailing(feature.trailing().as_str().unwrap().to_string().trim());
+		// Comments behind the last comma. They need their own lines to not swallow the `]`.
+		let trailing = feature
+			.trailing()
+			.as_str()
+			.unwrap()
+			.lines()
+			.map(str::trim)
+			.filter(|l| !l.is_empty())
+			.fold(String::new(), |mut out, l| {
+				let _ = writeln!(out, "\t{}", l);
+				out
+			});
+		feature.set_trailing(trailing);
 		feature.decor_mut().clear();
 
 		Ok(())
@@ -290,6 +490,7 @@ impl AutoFixer {
 		fname: &str,
 		modes: &[Mode],
 		line_width: u32,
+		resolution: ConflictResolution,
 		feature: &mut Array,
 	) -> Result<(), String> {
 		if modes.contains(&Mode::None) {
@@ -299,6 +500,7 @@ impl AutoFixer {
 			Self::sort_feature(feature);
 		}
 		if modes.is_empty() || modes.contains(&Mode::Dedub) {
+			Self::resolve_conflicts(cname, fname, feature, resolution)?;
 			Self::dedub_feature(cname, fname, feature)?;
 		}
 		if modes.is_empty() || modes.contains(&Mode::Canonicalize) {
@@ -312,6 +514,7 @@ impl AutoFixer {
 		cname: &str,
 		mode_per_feature: &Map<String, Vec<Mode>>,
 		line_width: u32,
+		resolution: ConflictResolution,
 	) -> Result<(), Vec<String>> {
 		let features = self.get_all_features();
 		let mut errors = Vec::new();
@@ -320,8 +523,9 @@ impl AutoFixer {
 			let feature = self.get_feature_mut(fname).unwrap();
 			let modes = mode_per_feature.get(fname).cloned().unwrap_or_default();
 
-			let _ = Self::canonicalize_feature(cname, fname, &modes, line_width, feature)
-				.map_err(|e| errors.push(e));
+			let _ =
+				Self::canonicalize_feature(cname, fname, &modes, line_width, resolution, feature)
+					.map_err(|e| errors.push(e));
 		}
 
 		if errors.is_empty() {
@@ -337,13 +541,18 @@ impl AutoFixer {
 		fname: &str,
 		mode_per_feature: &Map<String, Vec<Mode>>,
 		line_width: u32,
+		resolution: ConflictResolution,
 	) -> Result<bool, String> {
 		let modes = mode_per_feature.get(fname).cloned().unwrap_or_default();
 
 		let orig = self.get_feature(fname).unwrap();
 		let mut modified = orig.clone();
 
-		Self::canonicalize_feature(cname, fname, &modes, line_width, &mut modified)?;
+		Self::canonicalize_feature(cname, fname, &modes, line_width, resolution, &mut modified)?;
+		// The space between the `=` and the array is not part of the feature.
+		let mut orig = orig.clone();
+		orig.decor_mut().clear();
+		modified.decor_mut().clear();
 		Ok(orig.to_string() == modified.to_string())
 	}
 
@@ -382,6 +591,7 @@ impl AutoFixer {
 
 	/// Add something to a feature. Creates that feature if it does not exist.
 	pub fn add_to_feature(&mut self, feature: &str, v: &str) -> Result<(), String> {
+		let indent = format!("\n{}", self.feature_indent());
 		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
 
 		if !doc.contains_table("features") {
@@ -415,8 +625,8 @@ impl AutoFixer {
 				None => "".into(),
 				Some(p) => p.as_str().unwrap().into(),
 			};
-			if !prefix.ends_with("\n\t") {
-				prefix = format!("{}\n\t", prefix.trim_end());
+			if !prefix.ends_with(&indent) {
+				prefix = format!("{}{}", prefix.trim_end(), indent);
 			}
 			let mut suffix: String = match value.decor().suffix() {
 				None => "".into(),
@@ -433,7 +643,7 @@ impl AutoFixer {
 		}
 		let mut value: Value = v.into();
 		let suffix = "\n";
-		value = value.decorated("\n\t", suffix);
+		value = value.decorated(&indent, suffix);
 		new_vals.push(value);
 
 		for i in 1..new_vals.len() {
@@ -454,14 +664,19 @@ impl AutoFixer {
 		Ok(())
 	}
 
+	/// Reference the workspace version of a dependency instead of declaring it.
+	///
+	/// The member keeps its `features` unless `features` is `Some`, in which case they are replaced
+	/// by these. An empty list removes them.
 	pub fn lift_dependency(
 		&mut self,
 		dname: &str,
 		kind: &DependencyKind,
 		default_feats: Option<bool>,
+		features: Option<&[String]>,
 		location: &SourceLocationSelector,
 	) -> Result<(), String> {
-		let kind = crate::kind_to_str(kind);
+		let kind = manifest::kind_to_str(kind);
 		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
 
 		if !doc.contains_table(kind) {
@@ -475,7 +690,7 @@ impl AutoFixer {
 		}
 
 		let dep = deps.get_mut(dname).unwrap();
-		Self::lift_some_dependency(dep, default_feats, location)?;
+		Self::lift_some_dependency(dep, default_feats, features, location)?;
 
 		Ok(())
 	}
@@ -483,6 +698,7 @@ impl AutoFixer {
 	pub fn lift_some_dependency(
 		dep: &mut Item,
 		default_feats: Option<bool>,
+		features: Option<&[String]>,
 		location: &SourceLocationSelector,
 	) -> Result<(), String> {
 		if let Some(as_str) = dep.as_str() {
@@ -501,7 +717,7 @@ impl AutoFixer {
 			table.set_dotted(false);
 
 			*dep = Item::Value(Value::InlineTable(table));
-		} else if let Some(as_table) = dep.as_inline_table_mut() {
+		} else if let Some(as_table) = dep.as_table_like() {
 			if as_table.contains_key("git") {
 				return Err("Cannot lift git dependencies".into())
 			}
@@ -516,19 +732,33 @@ impl AutoFixer {
 						return Err("Lifting dependency would change it from a crates-io dependency to a local dependency".into())
 					},
 			}
+			// `workspace` takes the place of the source to keep the key order.
+			let source = ["version", "path"].into_iter().find(|k| as_table.contains_key(k));
 
-			as_table.remove("path");
-			as_table.remove("version");
-			as_table.remove("package");
-
-			as_table.insert("workspace", Value::Boolean(Formatted::new(true)));
-			if let Some(default_feats) = default_feats {
-				as_table.insert("default-features", Value::Boolean(Formatted::new(default_feats)));
-			} else {
-				as_table.remove("default-features");
+			for key in ["path", "version", "package"] {
+				if Some(key) != source {
+					manifest::remove_table_value(dep, key);
+				}
+			}
+			match source {
+				Some(source) => manifest::replace_table_key(dep, source, "workspace", true.into()),
+				None => manifest::set_table_value(dep, "workspace", true.into()),
+			}
+			match default_feats {
+				Some(default_feats) =>
+					manifest::set_table_value(dep, "default-features", default_feats.into()),
+				None => manifest::remove_table_value(dep, "default-features"),
+			}
+			match features {
+				Some([]) => manifest::remove_table_value(dep, "features"),
+				Some(features) => {
+					let features = features.iter().map(String::as_str).collect::<Array>();
+					manifest::set_table_value(dep, "features", Value::Array(features));
+				},
+				None => (),
 			}
 		} else {
-			return Err("Dependency is not a string or an inline table".into())
+			return Err("Dependency is not a string or a table".into())
 		}
 		Ok(())
 	}
@@ -538,6 +768,7 @@ impl AutoFixer {
 		dep: &Dependency,
 		maybe_rename: Option<&str>,
 		default_feats: bool,
+		features: Option<&[String]>,
 		local: Option<&str>,
 	) -> Result<(), String> {
 		self.add_workspace_dep_inner(
@@ -545,6 +776,7 @@ impl AutoFixer {
 			maybe_rename,
 			&dep.req.to_string(),
 			default_feats,
+			features,
 			local,
 		)
 	}
@@ -555,6 +787,7 @@ impl AutoFixer {
 		maybe_rename: Option<&str>,
 		dep_version: &str,
 		default_feats: bool,
+		features: Option<&[String]>,
 		local: Option<&str>,
 	) -> Result<(), String> {
 		// The carrot is implicit in cargo.
@@ -571,7 +804,6 @@ impl AutoFixer {
 		}
 
 		let deps = workspace["dependencies"].as_table_mut().unwrap();
-		let mut t = InlineTable::new();
 
 		let found_orig = deps.get(dep_name);
 		let found_rename = maybe_rename.and_then(|r| deps.get(r));
@@ -605,122 +837,745 @@ impl AutoFixer {
 			}
 		}
 
+		let new_name = maybe_rename.unwrap_or(dep_name);
+		let found_key =
+			if found_rename.is_some() { maybe_rename } else { found_orig.map(|_| dep_name) };
+		let mut item = Item::Value(Value::InlineTable(InlineTable::new()));
 		if let Some(found) = found_rename.or(found_orig) {
-			if let Some(found) = found.as_inline_table() {
-				if let Some(version) = found.get("version") {
-					if remove_carrot(version.as_str().unwrap()) != version_str {
-						return Err(format!(
-							"Dependency '{}' already exists in the workspace with a different 'version' field: '{}' vs '{}'",
-							dep_name,
-							version.as_str().unwrap(),
-							dep_version
-						))
-					}
+			let Some(table) = found.as_table_like() else {
+				return Err(format!("Dependency '{}' already exists in the workspace but could not validate its compatibility", dep_name))
+			};
+			if let Some(version) = table.get("version") {
+				if remove_carrot(version.as_str().unwrap()) != version_str {
+					return Err(format!(
+						"Dependency '{}' already exists in the workspace with a different 'version' field: '{}' vs '{}'",
+						dep_name,
+						version.as_str().unwrap(),
+						dep_version
+					))
 				}
+			}
 
-				if let Some(local) = local {
-					if let Some(path) = found.get("path") {
-						let l1 = Self::sanitize_path(local);
-						let l2 = Self::sanitize_path(path.as_str().unwrap());
-
-						if l1 != l2 {
-							return Err(format!(
-								"Dependency '{}' already exists in the workspace with a different 'path' field: '{}' vs '{}'",
-								dep_name,
-								local,
-								path.as_str().unwrap()
-							))
-						}
-					}
-				}
+			if let Some(local) = local {
+				if let Some(path) = table.get("path") {
+					let l1 = Self::sanitize_path(local);
+					let l2 = Self::sanitize_path(path.as_str().unwrap());
 
-				if let Some(default) = found.get("default-features") {
-					if default.as_bool().unwrap() != default_feats {
+					if l1 != l2 {
 						return Err(format!(
-							"Dependency '{}' already exists in the workspace with a different 'default-features' fields: '{}' vs '{}'",
+							"Dependency '{}' already exists in the workspace with a different 'path' field: '{}' vs '{}'",
 							dep_name,
-							default.as_bool().unwrap(),
-							default_feats
+							local,
+							path.as_str().unwrap()
 						))
 					}
 				}
+			}
 
-				// We checked that:
-				// - There is either no version or its compatible
-				// - There is either no default-features or its compatible
-				t = found.clone();
-			} else {
-				return Err(format!("Dependency '{}' already exists in the workspace but could not validate its compatibility", dep_name))
+			if let Some(default) = table.get("default-features") {
+				if default.as_bool().unwrap() != default_feats {
+					return Err(format!(
+						"Dependency '{}' already exists in the workspace with a different 'default-features' fields: '{}' vs '{}'",
+						dep_name,
+						default.as_bool().unwrap(),
+						default_feats
+					))
+				}
+			}
+
+			if let (Some(existing), Some(features)) =
+				(table.get("features").and_then(Item::as_array), features)
+			{
+				let mut existing = existing.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+				let mut wanted = features.iter().map(String::as_str).collect::<Vec<_>>();
+				existing.sort();
+				wanted.sort();
+
+				if existing != wanted {
+					return Err(format!(
+						"Dependency '{}' already exists in the workspace with a different 'features' field: '{}' vs '{}'",
+						dep_name,
+						existing.join(", "),
+						wanted.join(", ")
+					))
+				}
 			}
+
+			// We checked that:
+			// - There is either no version or its compatible
+			// - There is either no default-features or its compatible
+			// - There are either no features or they are the same
+			// Cloning the item keeps its style, which can be an inline or an expanded table.
+			item = found.clone();
 		}
 
-		t.insert("version", Value::String(Formatted::new(version_str)));
-		if let Some(local) = local {
-			t.insert("path", Value::String(Formatted::new(local.into())));
-			// Local deps dont need a version.
-			t.remove("version");
+		if let Some(rename) = maybe_rename {
+			log::info!("Renaming workspace dependency '{}' to '{}'", dep_name, rename);
 		}
-		if !default_feats {
-			t.insert("default-features", Value::Boolean(Formatted::new(default_feats)));
+		if dep_name != new_name {
+			deps.remove(dep_name);
 		}
+		let package = maybe_rename.map(|_| dep_name);
 
-		let name = if maybe_rename.is_some() {
-			log::info!(
-				"Renaming workspace dependency '{}' to '{}'",
-				dep_name,
-				maybe_rename.unwrap()
+		// Update an existing entry in place to keep its position and the comments around it.
+		if found_key == Some(new_name) {
+			let Some(item) = deps.get_mut(new_name) else {
+				return Err(format!("Dependency '{}' vanished from the workspace", new_name))
+			};
+			Self::complete_workspace_dep(
+				item,
+				&version_str,
+				local,
+				default_feats,
+				features,
+				package,
 			);
-			t.insert("package", Value::String(Formatted::new(dep_name.to_string())));
-			maybe_rename.unwrap()
-		} else {
-			dep_name
-		};
-
-		let new_name = maybe_rename.unwrap_or(name);
-		if dep_name != new_name {
-			deps.remove(dep_name);
+			return Ok(())
 		}
-		deps.insert(new_name, Item::Value(Value::InlineTable(t)));
+
+		Self::complete_workspace_dep(
+			&mut item,
+			&version_str,
+			local,
+			default_feats,
+			features,
+			package,
+		);
+		manifest::insert_sorted(deps, new_name, item);
 
 		Ok(())
 	}
 
+	/// Add the fields of a workspace dependency that its `item` does not have yet.
+	///
+	/// Fields that are already present were checked for compatibility and are left untouched.
+	fn complete_workspace_dep(
+		item: &mut Item,
+		version: &str,
+		local: Option<&str>,
+		default_feats: bool,
+		features: Option<&[String]>,
+		package: Option<&str>,
+	) {
+		let has =
+			|item: &Item, key: &str| item.as_table_like().map_or(false, |t| t.contains_key(key));
+
+		match local {
+			// Local deps dont need a version.
+			Some(local) if !has(item, "path") =>
+				manifest::set_table_value(item, "path", local.into()),
+			None if !has(item, "version") =>
+				manifest::set_table_value(item, "version", version.into()),
+			_ => (),
+		}
+		if !default_feats && !has(item, "default-features") {
+			manifest::set_table_value(item, "default-features", default_feats.into());
+		}
+		match features {
+			Some(features) if !features.is_empty() && !has(item, "features") => {
+				let features = features.iter().map(String::as_str).collect::<Array>();
+				manifest::set_table_value(item, "features", Value::Array(features));
+			},
+			_ => (),
+		}
+		if let Some(package) = package.filter(|_| !has(item, "package")) {
+			manifest::set_table_value(item, "package", package.into());
+		}
+	}
+
 	fn sanitize_path(p: &str) -> String {
-		p.trim_start_matches("./").trim_end_matches("/").to_string()
+		p.trim_start_matches("./").trim_end_matches('/').to_string()
 	}
 
+	/// Remove all entries that start with `name` from all features.
 	pub fn remove_feature(&mut self, name: &str) {
+		self.remove_from_features(&[name], EntryMatch::Prefix);
+	}
+
+	/// Remove all entries that match one of the `patterns` from all features.
+	///
+	/// Every feature is filtered in a single pass, so removing many patterns at once is not
+	/// quadratic in the number of entries. Returns the removed entries in manifest order.
+	pub fn remove_from_features(
+		&mut self,
+		patterns: &[&str],
+		matching: EntryMatch,
+	) -> Vec<RemovedEntry> {
 		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_mut()) else {
+			return Vec::new()
+		};
 
-		if !doc.contains_table("features") {
-			return
+		let mut removed = Vec::new();
+		for (name, feature) in features.iter_mut() {
+			let Some(feature) = feature.as_array_mut() else { continue };
+			let name = name.get();
+			feature.retain(|value| {
+				let Some(entry) = value.as_str() else { return true };
+				if !patterns.iter().any(|p| matching.matches(entry, p)) {
+					return true
+				}
+				removed.push(RemovedEntry { feature: name.into(), entry: entry.into() });
+				false
+			});
 		}
-		let features = doc["features"].as_table_mut().unwrap();
+		removed
+	}
 
-		for feature in features.iter_mut() {
-			let feature = feature.1.as_array_mut().unwrap();
+	/// Replace the value `old` with `new` in a feature while keeping its formatting.
+	pub fn replace_in_feature(
+		&mut self,
+		feature: &str,
+		old: &str,
+		new: &str,
+	) -> Result<(), String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
 
-			// remove all values that start with `name`
-			let mut i = 0;
-			while i < feature.len() {
-				let value = feature.get(i).unwrap().as_str().unwrap();
-				if value.starts_with(name) {
-					feature.remove(i);
+		let Some(values) = doc
+			.get_mut("features")
+			.and_then(|f| f.get_mut(feature))
+			.and_then(|f| f.as_array_mut())
+		else {
+			return Err(format!("Feature '{}' not found", feature))
+		};
+		let Some(value) = values.iter_mut().find(|v| v.as_str() == Some(old)) else {
+			return Err(format!("Value '{}' not found in feature '{}'", old, feature))
+		};
+
+		let decor = value.decor().clone();
+		*value = new.into();
+		*value.decor_mut() = decor;
+		Ok(())
+	}
+
+	/// Remove the value `value` from a feature while keeping the formatting of the others.
+	///
+	/// Returns whether the value was found.
+	pub fn remove_from_feature(&mut self, feature: &str, value: &str) -> Result<bool, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+
+		let Some(values) = doc
+			.get_mut("features")
+			.and_then(|f| f.get_mut(feature))
+			.and_then(|f| f.as_array_mut())
+		else {
+			return Err(format!("Feature '{}' not found", feature))
+		};
+		let Some(pos) = values.iter().position(|v| v.as_str() == Some(value)) else {
+			return Ok(false)
+		};
+
+		values.remove(pos);
+		Ok(true)
+	}
+
+	/// All features as written in the manifest, without the implicit ones of optional
+	/// dependencies.
+	/// Indentation of the existing multi-line feature entries or a tab if there are none.
+	fn feature_indent(&self) -> String {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let Some(features) = doc.get("features").and_then(|f| f.as_table_like()) else {
+			return "\t".into()
+		};
+
+		features
+			.iter()
+			.filter_map(|(_, values)| values.as_array())
+			.flat_map(|values| values.iter())
+			.find_map(|value| {
+				let (_, indent) = value.decor().prefix()?.as_str()?.rsplit_once('\n')?;
+				let is_indent = !indent.is_empty() && indent.chars().all(|c| c == ' ' || c == '\t');
+				is_indent.then(|| indent.to_string())
+			})
+			.unwrap_or_else(|| "\t".into())
+	}
+
+	pub fn features(&self) -> Map<String, Vec<String>> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let Some(features) = doc.get("features").and_then(|f| f.as_table_like()) else {
+			return Map::new()
+		};
+
+		features
+			.iter()
+			.map(|(name, values)| {
+				let values = values
+					.as_array()
+					.map(|a| a.iter().filter_map(|v| v.as_str().map(Into::into)).collect())
+					.unwrap_or_default();
+				(name.to_string(), values)
+			})
+			.collect()
+	}
+
+	/// The comment lines directly above the key of `feature`, without the leading `#`.
+	///
+	/// Returns `None` if the feature does not exist.
+	pub fn feature_doc(&self, feature: &str) -> Option<Vec<String>> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let key = doc.get("features")?.as_table_like()?.get_key_value(feature)?.0;
+		let prefix = key.leaf_decor().prefix().and_then(|p| p.as_str()).unwrap_or_default();
+
+		let mut lines = prefix
+			.lines()
+			.rev()
+			.map(str::trim)
+			.take_while(|l| l.starts_with('#'))
+			.map(|l| l.trim_start_matches('#').trim().to_string())
+			.collect::<Vec<_>>();
+		lines.reverse();
+		Some(lines)
+	}
+
+	/// Insert the comment `comment` directly above the key of `feature`.
+	pub fn document_feature(&mut self, feature: &str, comment: &str) -> Result<(), String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let Some(mut key) = doc
+			.get_mut("features")
+			.and_then(|f| f.as_table_like_mut())
+			.and_then(|f| f.key_mut(feature))
+		else {
+			return Err(format!("Feature '{}' not found", feature))
+		};
+
+		let prefix = key.leaf_decor().prefix().and_then(|p| p.as_str()).unwrap_or_default();
+		let prefix = format!("{}# {}\n", prefix, comment);
+		key.leaf_decor_mut().set_prefix(prefix);
+		Ok(())
+	}
+
+	/// Rename the dependency `from` to `to` in all dependency tables and feature references.
+	///
+	/// The original crate is kept by setting the `package` field. Returns whether anything was
+	/// renamed.
+	pub fn rename_dependency(&mut self, from: &str, to: &str) -> Result<bool, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let mut renamed = false;
+
+		Self::try_for_each_dependency_table(doc, |deps| {
+			renamed |= Self::rename_dependency_in(deps, from, to)?;
+			Ok(())
+		})?;
+
+		if !renamed {
+			return Ok(false)
+		}
+		let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_mut()) else {
+			return Ok(true)
+		};
+		// A plain `from` in a feature only refers to the dependency if there is no such feature.
+		let rename_plain = !features.contains_key(from);
+
+		for (_, feature) in features.iter_mut() {
+			let Some(values) = feature.as_array_mut() else { continue };
+
+			for value in values.iter_mut() {
+				let Some(old) = value.as_str() else { continue };
+				let new = if let Some(rest) = old.strip_prefix(&format!("{}/", from)) {
+					format!("{}/{}", to, rest)
+				} else if let Some(rest) = old.strip_prefix(&format!("{}?/", from)) {
+					format!("{}?/{}", to, rest)
+				} else if old == format!("dep:{}", from) {
+					format!("dep:{}", to)
+				} else if old == from && rename_plain {
+					to.into()
 				} else {
-					i += 1;
+					continue
+				};
+
+				let decor = value.decor().clone();
+				*value = new.into();
+				*value.decor_mut() = decor;
+			}
+		}
+
+		Ok(true)
+	}
+
+	/// Rename the key of a single dependency while keeping its position in the table.
+	fn rename_dependency_in(deps: &mut Table, from: &str, to: &str) -> Result<bool, String> {
+		if !deps.contains_key(from) {
+			return Ok(false)
+		}
+		if deps.contains_key(to) {
+			return Err(format!("Dependency '{}' already exists", to))
+		}
+
+		let order = deps.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+		let (key, mut item) = deps.remove_entry(from).unwrap();
+
+		if let Some(mut version) = item.as_value().filter(|v| v.is_str()).cloned() {
+			let mut inline = InlineTable::new();
+			inline.decor_mut().clone_from(version.decor());
+			version.decor_mut().clear();
+			inline.insert("version", version);
+			item = Item::Value(Value::InlineTable(inline));
+		}
+		let Some(dep) = item.as_table_like_mut() else {
+			return Err(format!("Dependency '{}' has an unexpected format", from))
+		};
+		// The package is inherited from the workspace in this case.
+		let inherited = dep.get("workspace").and_then(|w| w.as_bool()) == Some(true);
+		let package = dep.get("package").and_then(|p| p.as_str()).map(ToString::to_string);
+
+		match package {
+			Some(package) if package == to => {
+				dep.remove("package");
+			},
+			None if !inherited => match item.as_inline_table_mut() {
+				Some(inline) => manifest::push_inline_value(inline, "package", from.into()),
+				None => item["package"] = value(from),
+			},
+			_ => (),
+		}
+
+		deps.insert_formatted(&Key::new(to).with_leaf_decor(key.leaf_decor().clone()), item);
+		let position = |k: &Key| {
+			let k = if k.get() == to { from } else { k.get() };
+			order.iter().position(|o| o == k).unwrap_or(usize::MAX)
+		};
+		deps.sort_values_by(|a, _, b, _| position(a).cmp(&position(b)));
+
+		Ok(true)
+	}
+
+	/// Merge the feature `from` into `into` and update all references to `from`.
+	///
+	/// References like `dep/from` and `dep?/from` become `dep/into`, a plain `from` only if the
+	/// crate has such a feature. The feature itself is renamed to `into`, or its entries are added
+	/// to `into` if that already exists.
+	pub fn merge_feature(&mut self, from: &str, into: &str) -> Result<FeatureMerge, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let mut merge = FeatureMerge::default();
+		let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_mut()) else {
+			return Ok(merge)
+		};
+		let has_from = features.contains_key(from);
+		let has_into = features.contains_key(into);
+
+		for (name, feature) in features.iter_mut() {
+			let Some(values) = feature.as_array_mut() else {
+				return Err(format!("Feature '{}' is not an array", name))
+			};
+			let renamed = name.get() == from;
+			// The name of the feature after the merge.
+			let name = if renamed { into } else { name.get() };
+
+			let mut i = 0;
+			while i < values.len() {
+				let Some(old) = values.get(i).and_then(|v| v.as_str()).map(String::from) else {
+					return Err(format!("Feature '{}' contains a non-string value", name))
+				};
+				let new = merged_reference(&old, from, into, has_from).unwrap_or(old.clone());
+				let changed = new != old;
+				merge.references += changed as usize;
+
+				// Drop self-references and duplicates that are created by the merge.
+				let duplicate = values
+					.iter()
+					.enumerate()
+					.any(|(j, v)| j != i && v.as_str() == Some(new.as_str()));
+				if (changed || renamed) && (new == name || duplicate) {
+					values.remove(i);
+					continue
+				}
+				if changed {
+					let value = values.get_mut(i).unwrap();
+					let decor = value.decor().clone();
+					*value = new.into();
+					*value.decor_mut() = decor;
 				}
+				i += 1;
 			}
 		}
+		if !has_from {
+			return Ok(merge)
+		}
+
+		if !has_into {
+			let order = features.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+			let (key, item) = features.remove_entry(from).unwrap();
+			features
+				.insert_formatted(&Key::new(into).with_leaf_decor(key.leaf_decor().clone()), item);
+			let position = |k: &Key| {
+				let k = if k.get() == into { from } else { k.get() };
+				order.iter().position(|o| o == k).unwrap_or(usize::MAX)
+			};
+			features.sort_values_by(|a, _, b, _| position(a).cmp(&position(b)));
+
+			merge.renamed = true;
+			return Ok(merge)
+		}
+
+		let (_, item) = features.remove_entry(from).unwrap();
+		let existing = features[into].as_array().map_or_else(Vec::new, |values| {
+			values.iter().filter_map(|v| v.as_str()).map(String::from).collect()
+		});
+		let missing = item
+			.as_array()
+			.into_iter()
+			.flat_map(|values| values.iter())
+			.filter_map(|v| v.as_str())
+			.filter(|v| !existing.iter().any(|e| e == v))
+			.map(String::from)
+			.collect::<Vec<_>>();
+
+		for value in missing {
+			self.add_to_feature(into, &value)?;
+		}
+		merge.merged = true;
+		Ok(merge)
 	}
 
+	/// Call `f` on all dependency tables, including target specific and workspace ones.
+	fn try_for_each_dependency_table<F>(doc: &mut DocumentMut, mut f: F) -> Result<(), String>
+	where
+		F: FnMut(&mut Table) -> Result<(), String>,
+	{
+		for kind in DEPENDENCY_KINDS {
+			if let Some(deps) = doc.get_mut(kind).and_then(|d| d.as_table_mut()) {
+				f(deps)?;
+			}
+			for (_, target) in doc
+				.get_mut("target")
+				.and_then(|t| t.as_table_mut())
+				.into_iter()
+				.flat_map(|t| t.iter_mut())
+			{
+				if let Some(deps) = target.get_mut(kind).and_then(|d| d.as_table_mut()) {
+					f(deps)?;
+				}
+			}
+		}
+		if let Some(deps) = doc
+			.get_mut("workspace")
+			.and_then(|w| w.get_mut("dependencies"))
+			.and_then(|d| d.as_table_mut())
+		{
+			f(deps)?;
+		}
+		Ok(())
+	}
+
+	/// All dependency tables of the manifest and whether they are `workspace.dependencies`.
+	fn dependency_tables(doc: &DocumentMut) -> Vec<(&dyn TableLike, bool)> {
+		let targets = doc
+			.get("target")
+			.and_then(|t| t.as_table())
+			.map(|t| t.iter().map(|(_, target)| target).collect::<Vec<_>>())
+			.unwrap_or_default();
+
+		DEPENDENCY_KINDS
+			.iter()
+			.flat_map(|kind| {
+				doc.get(kind).into_iter().chain(targets.iter().filter_map(move |t| t.get(kind)))
+			})
+			.filter_map(|t| t.as_table_like().map(|t| (t, false)))
+			.chain(
+				doc.get("workspace")
+					.and_then(|w| w.get("dependencies"))
+					.and_then(|t| t.as_table_like())
+					.map(|t| (t, true)),
+			)
+			.collect()
+	}
+
+	/// All dependencies that are specified with a `path`.
+	pub fn path_dependencies(&self) -> Vec<PathDependency> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+
+		let mut found = Vec::new();
+		for (key, dep) in Self::dependency_tables(doc).into_iter().flat_map(|(t, _)| t.iter()) {
+			let Some(dep) = dep.as_table_like() else { continue };
+			let Some(path) = dep.get("path").and_then(|p| p.as_str()) else { continue };
+			let package = dep.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+
+			found.push(PathDependency {
+				key: key.into(),
+				package: package.into(),
+				path: path.into(),
+			});
+		}
+		found
+	}
+
+	/// All dependencies that are specified with a `git` URL.
+	pub fn git_dependencies(&self) -> Vec<GitDependency> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let get = |dep: &dyn TableLike, key: &str| -> Option<String> {
+			dep.get(key).and_then(|v| v.as_str()).map(Into::into)
+		};
+
+		let mut found = Vec::new();
+		for (table, workspace) in Self::dependency_tables(doc) {
+			for (key, dep) in table.iter() {
+				let Some(dep) = dep.as_table_like() else { continue };
+				let Some(git) = get(dep, "git") else { continue };
+
+				found.push(GitDependency {
+					key: key.into(),
+					package: get(dep, "package").unwrap_or_else(|| key.into()),
+					git,
+					rev: get(dep, "rev"),
+					branch: get(dep, "branch"),
+					tag: get(dep, "tag"),
+					workspace,
+				});
+			}
+		}
+		found
+	}
+
+	/// Dependencies that disable their default features but enable the `default` feature.
+	///
+	/// Cargo enables the default features in this case. A dependency that conflicts in multiple
+	/// tables of the members, like `dependencies` and `dev-dependencies`, is only returned once.
+	pub fn default_feature_conflicts(&self) -> Vec<DefaultFeatureConflict> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+
+		let mut found = Vec::<DefaultFeatureConflict>::new();
+		for (table, workspace) in Self::dependency_tables(doc) {
+			for (key, dep) in table.iter() {
+				let Some(dep) = dep.as_table_like() else { continue };
+				if !has_default_feature_conflict(dep) ||
+					found.iter().any(|f| f.key == key && f.workspace == workspace)
+				{
+					continue
+				}
+				found.push(DefaultFeatureConflict { key: key.into(), workspace });
+			}
+		}
+		found
+	}
+
+	/// Resolve the conflict of [`Self::default_feature_conflicts`] for the dependency `dep`.
+	///
+	/// Removes either the `"default"` feature or the `default-features = false` key, depending
+	/// on `remove`. Returns whether anything was changed.
+	pub fn resolve_default_feature_conflict(
+		&mut self,
+		dep: &str,
+		remove: DefaultFeatureConflictFix,
+	) -> Result<bool, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let mut resolved = false;
+
+		Self::try_for_each_dependency_table(doc, |deps| {
+			let Some(item) = deps.get_mut(dep) else { return Ok(()) };
+			if !item.as_table_like().map_or(false, has_default_feature_conflict) {
+				return Ok(())
+			}
+			resolved = true;
+
+			match remove {
+				DefaultFeatureConflictFix::DefaultFeature => {
+					let features = item
+						.as_table_like_mut()
+						.and_then(|t| t.get_mut("features"))
+						.and_then(|f| f.as_array_mut())
+						.expect("Checked by has_default_feature_conflict");
+					loop {
+						let Some(pos) = features.iter().position(|v| v.as_str() == Some("default"))
+						else {
+							break
+						};
+						let removed = features.remove(pos);
+						// The new first value should not start with the separating whitespace.
+						if let (0, Some(first)) = (pos, features.get_mut(0)) {
+							let prefix = removed.decor().prefix().cloned().unwrap_or_default();
+							first.decor_mut().set_prefix(prefix);
+						}
+					}
+					if features.is_empty() {
+						manifest::remove_table_value(item, "features");
+					}
+				},
+				DefaultFeatureConflictFix::DefaultFeaturesKey =>
+					for key in ["default-features", "default_features"] {
+						manifest::remove_table_value(item, key);
+					},
+			}
+			Ok(())
+		})?;
+
+		Ok(resolved)
+	}
+
+	/// Pin a git dependency to the commit `rev`.
+	///
+	/// A `branch` or `tag` is replaced since cargo only accepts one of them. Returns whether
+	/// anything was changed.
+	pub fn pin_git_dependency(&mut self, dep: &str, rev: &str) -> Result<bool, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let mut pinned = false;
+
+		Self::try_for_each_dependency_table(doc, |deps| {
+			let Some(item) = deps.get_mut(dep) else { return Ok(()) };
+			let Some(table) = item.as_table_like() else { return Ok(()) };
+			if !table.contains_key("git") || table.contains_key("rev") {
+				return Ok(())
+			}
+			pinned = true;
+
+			match ["branch", "tag"].into_iter().find(|k| table.contains_key(k)) {
+				Some(reference) => manifest::replace_table_key(item, reference, "rev", rev.into()),
+				None => manifest::set_table_value(item, "rev", rev.into()),
+			}
+			Ok(())
+		})?;
+
+		Ok(pinned)
+	}
+
+	/// Replace the `path` of a dependency with a `version`.
+	///
+	/// The `path` is only removed if there is already a version. Returns whether anything was
+	/// changed.
+	pub fn replace_path_with_version(&mut self, dep: &str, version: &str) -> Result<bool, String> {
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let mut replaced = false;
+
+		Self::try_for_each_dependency_table(doc, |deps| {
+			let Some(item) = deps.get_mut(dep) else { return Ok(()) };
+			let Some(table) = item.as_table_like() else { return Ok(()) };
+			if !table.contains_key("path") {
+				return Ok(())
+			}
+			replaced = true;
+
+			if table.contains_key("version") {
+				manifest::remove_table_value(item, "path");
+			} else {
+				manifest::replace_table_key(item, "path", "version", version.into());
+			}
+			Ok(())
+		})?;
+
+		Ok(replaced)
+	}
+
+	/// Set `default-features = false` for a normal dependency.
+	///
+	/// Target specific dependencies are only considered if there is no plain one.
 	pub fn disable_default_features(&mut self, dep: &str) -> Result<(), String> {
 		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let has_dep = |item: &Item| item.get("dependencies").and_then(|d| d.get(dep)).is_some();
 
-		if !doc.contains_table("dependencies") {
+		let target = if has_dep(doc.as_item()) {
+			None
+		} else {
+			doc.get("target")
+				.and_then(|t| t.as_table())
+				.and_then(|t| t.iter().find(|(_, t)| has_dep(t)))
+				.map(|(name, _)| name.to_string())
+		};
+		let deps = match &target {
+			Some(target) => doc["target"][target.as_str()].get_mut("dependencies"),
+			None => doc.get_mut("dependencies"),
+		};
+		let Some(deps) = deps.and_then(|d| d.as_table_mut()) else {
 			return Err("No dependencies entry found".into())
-		}
-
-		let deps = doc["dependencies"].as_table_mut().unwrap();
+		};
 		let Some(dep) = deps.get_mut(dep) else {
 			return Err(format!("Dependency '{}' not found", dep))
 		};
@@ -733,18 +1588,405 @@ impl AutoFixer {
 		}
 	}
 
+	/// Dependencies that are listed as normal and as dev dependency of the same target.
+	pub fn duplicate_dependencies(&self) -> Vec<DuplicateDependency> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let targets = doc
+			.get("target")
+			.and_then(|t| t.as_table_like())
+			.map(|t| t.iter().map(|(name, t)| (Some(name.to_string()), t)).collect::<Vec<_>>())
+			.unwrap_or_default();
+
+		let mut found = Vec::new();
+		for (target, item) in std::iter::once((None, doc.as_item())).chain(targets) {
+			let (Some(normal), Some(dev)) = (
+				item.get("dependencies").and_then(|d| d.as_table_like()),
+				item.get("dev-dependencies").and_then(|d| d.as_table_like()),
+			) else {
+				continue
+			};
+
+			for (key, dev_dep) in dev.iter() {
+				let Some(normal_dep) = normal.get(key) else { continue };
+
+				found.push(DuplicateDependency {
+					key: key.into(),
+					target: target.clone(),
+					resolution: DependencySpec::resolution(normal_dep, dev_dep),
+				});
+			}
+		}
+		found
+	}
+
+	/// Remove the dev entry of a duplicate dependency and merge its features into the normal one.
+	///
+	/// Returns whether anything was changed. Conflicting entries are never changed.
+	pub fn resolve_duplicate_dependency(
+		&mut self,
+		dup: &DuplicateDependency,
+	) -> Result<bool, String> {
+		let features = match &dup.resolution {
+			DuplicateResolution::Remove => Vec::new(),
+			DuplicateResolution::MergeFeatures(features) => features.clone(),
+			DuplicateResolution::Conflict(_) => return Ok(false),
+		};
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let root: &mut dyn TableLike = match &dup.target {
+			None => doc.as_table_mut(),
+			Some(target) => doc
+				.get_mut("target")
+				.and_then(|t| t.as_table_like_mut())
+				.and_then(|t| t.get_mut(target))
+				.and_then(|t| t.as_table_like_mut())
+				.ok_or_else(|| format!("Target '{}' not found", target))?,
+		};
+
+		if !features.is_empty() {
+			let normal = root
+				.get_mut("dependencies")
+				.and_then(|d| d.as_table_like_mut())
+				.and_then(|d| d.get_mut(&dup.key))
+				.ok_or_else(|| format!("Dependency '{}' not found", dup.key))?;
+			manifest::add_dependency_features(normal, &features)?;
+		}
+
+		let dev = root
+			.get_mut("dev-dependencies")
+			.and_then(|d| d.as_table_like_mut())
+			.ok_or_else(|| format!("Dev dependency '{}' not found", dup.key))?;
+		dev.remove(&dup.key);
+		if dev.is_empty() {
+			root.remove("dev-dependencies");
+		}
+		Ok(true)
+	}
+
+	/// The `package.rust-version` of the manifest, if any.
+	pub fn rust_version(&self) -> Option<RustVersion> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let item = doc.get("package")?.get("rust-version")?;
+
+		match item.get("workspace").and_then(|w| w.as_bool()) {
+			Some(true) => Some(RustVersion::Workspace),
+			_ => item.as_str().map(|v| RustVersion::Version(v.into())),
+		}
+	}
+
+	/// The `workspace.package.rust-version` of a workspace manifest, if any.
+	pub fn workspace_rust_version(&self) -> Option<String> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		doc.get("workspace")?
+			.get("package")?
+			.get("rust-version")?
+			.as_str()
+			.map(Into::into)
+	}
+
+	/// The `features` of all `workspace.dependencies` that list any, by dependency key.
+	pub fn workspace_dependency_features(&self) -> Map<String, Vec<String>> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+		let Some(deps) = doc
+			.get("workspace")
+			.and_then(|w| w.get("dependencies"))
+			.and_then(|d| d.as_table_like())
+		else {
+			return Map::new()
+		};
+
+		deps.iter()
+			.filter_map(|(key, dep)| {
+				let features = dep.as_table_like()?.get("features")?.as_array()?;
+				let features =
+					features.iter().filter_map(|f| f.as_str()).map(Into::into).collect::<Vec<_>>();
+				(!features.is_empty()).then(|| (key.into(), features))
+			})
+			.collect()
+	}
+
+	/// The keys of all dependencies that are inherited with `workspace = true`.
+	pub fn inherited_dependencies(&self) -> Vec<String> {
+		let doc: &DocumentMut = self.doc.as_ref().unwrap();
+
+		let mut found = Self::dependency_tables(doc)
+			.into_iter()
+			.filter(|(_, workspace)| !workspace)
+			.flat_map(|(t, _)| t.iter())
+			.filter(|(_, dep)| {
+				dep.as_table_like().and_then(|d| d.get("workspace")).and_then(|w| w.as_bool()) ==
+					Some(true)
+			})
+			.map(|(key, _)| key.to_string())
+			.collect::<Vec<_>>();
+		found.sort();
+		found.dedup();
+		found
+	}
+
+	/// Set `rust-version.workspace = true` in the `package` table.
+	///
+	/// Replaces an explicit version in place. Returns whether anything was changed.
+	pub fn inherit_rust_version(&mut self) -> Result<bool, String> {
+		if self.rust_version() == Some(RustVersion::Workspace) {
+			return Ok(false)
+		}
+		let doc: &mut DocumentMut = self.doc.as_mut().unwrap();
+		let package = doc
+			.get_mut("package")
+			.and_then(|p| p.as_table_mut())
+			.ok_or("No package table found")?;
+
+		let mut inherit = InlineTable::new();
+		inherit.insert("workspace", true.into());
+		inherit.set_dotted(true);
+		package.insert("rust-version", Item::Value(Value::InlineTable(inherit)));
+		Ok(true)
+	}
+
+	/// The content of the manifest before any modification.
+	pub fn original(&self) -> &str {
+		&self.raw
+	}
+
 	pub fn modified(&self) -> bool {
 		self.doc.as_ref().unwrap().to_string() != self.raw
 	}
 
-	pub fn save(&mut self) -> Result<(), String> {
+	/// Write the modified manifest back to disk.
+	///
+	/// Writes are serialized within the process and guarded by a lock file against other Zepter
+	/// processes. The content is written to a temporary file first and then moved over the
+	/// manifest, so that readers never see a partial write and a crash leaves either the old or
+	/// the new manifest behind.
+	pub fn save(&mut self) -> Result<(), ManifestError> {
 		if let (Some(doc), Some(path)) = (self.doc.take(), &self.manifest) {
-			std::fs::write(path, doc.to_string())
-				.map_err(|e| format!("Failed to write manifest: {:?}: {:?}", path.display(), e))?;
+			write_manifest(path, &doc.to_string())?;
 			log::debug!("Modified manifest {:?}", path.display());
 		}
 		Ok(())
 	}
+
+	/// Check that [`Self::save`] can write the modified manifest without actually writing it.
+	///
+	/// The manifest must still be unchanged on disk, the modified document must be valid TOML and
+	/// the directory must allow to create the temporary file of the write.
+	pub fn validate(&self) -> Result<(), ManifestError> {
+		let (Some(doc), Some(path)) = (self.doc.as_ref(), &self.manifest) else { return Ok(()) };
+
+		let current = std::fs::read_to_string(path).map_err(|error| ManifestError::Io {
+			path: path.clone(),
+			action: "read",
+			error,
+		})?;
+		if current != self.raw {
+			return Err(ManifestError::Changed { path: path.clone() })
+		}
+		doc.to_string()
+			.parse::<DocumentMut>()
+			.map_err(|error| ManifestError::Parse { path: Some(path.clone()), error })?;
+
+		// Not the file of `write_manifest` since we do not hold the lock.
+		let probe =
+			format!("probe-{}-{}", std::process::id(), PROBES.fetch_add(1, Ordering::Relaxed));
+		let tmp = sibling(path, &probe);
+		File::create(&tmp)
+			.and_then(|_| std::fs::remove_file(&tmp))
+			.map_err(|error| ManifestError::Io { path: path.clone(), action: "write", error })
+	}
+}
+
+/// Fixes of multiple manifests that are written all or nothing.
+///
+/// Fixers are collected while checking the workspace. [`Self::commit`] validates all of them before
+/// the first manifest is written and restores the already written ones if a write fails anyway.
+/// The manifests are written in the order of their paths, independent of the order in which they
+/// were pushed.
+#[derive(Default)]
+pub struct FixTransaction {
+	fixers: Vec<AutoFixer>,
+}
+
+impl FixTransaction {
+	/// Add a fixer to the transaction. Unmodified fixers are ignored.
+	pub fn push(&mut self, fixer: AutoFixer) {
+		if fixer.modified() {
+			self.fixers.push(fixer);
+		}
+	}
+
+	/// Number of manifests that will be written.
+	pub fn len(&self) -> usize {
+		self.fixers.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.fixers.is_empty()
+	}
+
+	/// Paths of the manifests that will be written.
+	pub fn manifests(&self) -> impl Iterator<Item = &Path> {
+		self.fixers.iter().filter_map(|f| f.manifest.as_deref())
+	}
+
+	/// Write all manifests with `save` or none of them.
+	///
+	/// Returns the number of written manifests. Nothing is written if any manifest fails to
+	/// validate. If a write fails, the already written manifests are restored to their original
+	/// content.
+	pub fn commit(
+		mut self,
+		mut save: impl FnMut(&mut AutoFixer) -> Result<(), String>,
+	) -> Result<usize, String> {
+		self.fixers.sort_by(|a, b| a.manifest.cmp(&b.manifest));
+		for fixer in self.fixers.iter() {
+			fixer.validate().map_err(|e| {
+				let n = self.fixers.len();
+				format!("Did not write any of the {} fixed manifest{}: {}", n, plural(n), e)
+			})?;
+		}
+
+		for i in 0..self.fixers.len() {
+			let Err(err) = save(&mut self.fixers[i]) else { continue };
+
+			let mut failed = Vec::new();
+			for fixer in self.fixers[..i].iter() {
+				let Some(path) = fixer.manifest.as_ref() else { continue };
+				if let Err(e) = write_manifest(path, fixer.original()) {
+					failed.push(e.to_string());
+				}
+			}
+			return Err(if failed.is_empty() {
+				format!("{}. Restored {} already written manifest{}", err, i, plural(i))
+			} else {
+				format!(
+					"{}. Failed to restore {} of {} already written manifests:\n  {}",
+					err,
+					failed.len(),
+					i,
+					failed.join("\n  ")
+				)
+			})
+		}
+		Ok(self.fixers.len())
+	}
+}
+
+/// Replace the manifest at `path` with `content`.
+///
+/// Holds the [`ManifestLock`] of the manifest and writes it atomically, like [`AutoFixer::save`].
+pub(crate) fn write_manifest(path: &Path, content: &str) -> Result<(), ManifestError> {
+	let _queue = WRITER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+	let _lock = ManifestLock::acquire(path)?;
+
+	let tmp = sibling(path, "tmp");
+	replace_file(path, &tmp, content).map_err(|error| {
+		let _ = std::fs::remove_file(&tmp);
+		ManifestError::Io { path: path.into(), action: "write", error }
+	})
+}
+
+/// Write `content` to `tmp` and move it over `path`.
+///
+/// The content is synced to disk before the rename and `tmp` gets the permissions of `path`, if it
+/// exists.
+fn replace_file(path: &Path, tmp: &Path, content: &str) -> std::io::Result<()> {
+	let mut file = File::create(tmp)?;
+	file.write_all(content.as_bytes())?;
+	file.sync_all()?;
+	if let Ok(meta) = std::fs::metadata(path) {
+		std::fs::set_permissions(tmp, meta.permissions())?;
+	}
+	std::fs::rename(tmp, path)
+}
+
+/// Advisory lock on a manifest that is held while writing it.
+///
+/// This is a lock file next to the manifest since the standard library has no file locking for our
+/// MSRV. It contains the PID of its owner and is removed when dropped.
+struct ManifestLock(PathBuf);
+
+impl ManifestLock {
+	/// Wait until no other process holds the lock of `manifest` and then take it.
+	///
+	/// Locks of processes that crashed while writing are removed.
+	fn acquire(manifest: &Path) -> Result<Self, ManifestError> {
+		let path = sibling(manifest, "lock");
+		let start = Instant::now();
+
+		loop {
+			match OpenOptions::new().write(true).create_new(true).open(&path) {
+				Ok(mut file) => {
+					let _ = write!(file, "{}", std::process::id());
+					return Ok(Self(path))
+				},
+				Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+					if Self::is_stale(&path) {
+						Self::break_stale(&path);
+						continue
+					}
+					if start.elapsed() > LOCK_TIMEOUT {
+						return Err(ManifestError::Locked { lock: path })
+					}
+					std::thread::sleep(Duration::from_millis(10));
+				},
+				Err(error) =>
+					return Err(ManifestError::Io { path: manifest.into(), action: "lock", error }),
+			}
+		}
+	}
+
+	/// Whether the lock file at `path` was left behind by a process that crashed while holding it.
+	///
+	/// That is only the case if its owner is no longer running. The age of a lock says nothing
+	/// since a write can block for a long time on a slow file system.
+	fn is_stale(path: &Path) -> bool {
+		// Empty while the owner did not yet write its PID.
+		let owner = std::fs::read_to_string(path)
+			.ok()
+			.and_then(|pid| pid.trim().parse::<u32>().ok());
+		owner.map_or(false, |pid| !process_exists(pid))
+	}
+
+	/// Remove the stale lock at `path` without racing other processes that do the same.
+	///
+	/// The lock is moved to a unique name first, so that only one process gets hold of it, and it
+	/// is only removed if it is still stale there. Otherwise it was taken by a running process
+	/// since it was checked and is put back, unless yet another process took the lock in the
+	/// meantime.
+	fn break_stale(path: &Path) {
+		let mut broken = path.as_os_str().to_owned();
+		broken.push(format!("-{}-{}", std::process::id(), PROBES.fetch_add(1, Ordering::Relaxed)));
+		let broken = PathBuf::from(broken);
+
+		// Already removed or released otherwise.
+		if std::fs::rename(path, &broken).is_err() {
+			return
+		}
+		if Self::is_stale(&broken) {
+			log::warn!("Removed the stale lock file {}", path.display());
+		} else {
+			let _ = std::fs::hard_link(&broken, path);
+		}
+		let _ = std::fs::remove_file(&broken);
+	}
+}
+
+/// Whether a process with this PID is running. Always true where this cannot be checked.
+fn process_exists(pid: u32) -> bool {
+	let proc = Path::new("/proc");
+	!proc.is_dir() || proc.join(pid.to_string()).exists()
+}
+
+impl Drop for ManifestLock {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.0);
+	}
+}
+
+/// Hidden file next to `manifest` like `.Cargo.toml.zepter-lock`.
+fn sibling(manifest: &Path, suffix: &str) -> PathBuf {
+	let name = manifest.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+	manifest.with_file_name(format!(".{}.zepter-{}", name, suffix))
 }
 
 impl ToString for AutoFixer {
@@ -753,6 +1995,192 @@ impl ToString for AutoFixer {
 	}
 }
 
+/// Rewrite a feature entry that references the feature `from` to reference `into` instead.
+///
+/// `plain` controls whether a bare `from` refers to the feature of the crate itself.
+fn merged_reference(value: &str, from: &str, into: &str, plain: bool) -> Option<String> {
+	match value.split_once('/') {
+		Some((dep, feature)) if feature == from => Some(format!("{}/{}", dep, into)),
+		Some(_) => None,
+		None => (plain && value == from).then(|| into.into()),
+	}
+}
+
+/// Changes that [`AutoFixer::merge_feature`] made to a single manifest.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FeatureMerge {
+	/// The feature was renamed since there was no feature to merge it into.
+	pub renamed: bool,
+	/// The entries of the feature were added to an existing one.
+	pub merged: bool,
+	/// Number of updated references to the feature.
+	pub references: usize,
+}
+
+/// A dependency that is listed as normal and as dev dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateDependency {
+	/// The key of the dependency in both tables.
+	pub key: String,
+	/// The target of the tables like `cfg(unix)`, if they are target specific.
+	pub target: Option<String>,
+	/// How the dev entry can be removed.
+	pub resolution: DuplicateResolution,
+}
+
+/// How to get rid of the dev entry of a [`DuplicateDependency`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuplicateResolution {
+	/// The dev entry is a subset of the normal one and can be removed.
+	Remove,
+	/// The dev entry only enables these additional features.
+	MergeFeatures(Vec<String>),
+	/// The entries differ in more than their features, for the given reason.
+	Conflict(String),
+}
+
+/// The parts of a dependency entry that are compared for duplicates.
+struct DependencySpec {
+	/// Everything that selects the crate, like `version`, `path`, `package` or `workspace`.
+	source: Map<String, String>,
+	default_features: bool,
+	features: Vec<String>,
+	optional: bool,
+}
+
+impl DependencySpec {
+	fn from_item(item: &Item) -> Option<Self> {
+		let mut spec = Self {
+			source: Map::new(),
+			default_features: true,
+			features: Vec::new(),
+			optional: false,
+		};
+		if let Some(version) = item.as_str() {
+			spec.source.insert("version".into(), format!("{:?}", version));
+			return Some(spec)
+		}
+
+		for (key, value) in item.as_table_like()?.iter() {
+			match key {
+				"default-features" | "default_features" =>
+					spec.default_features = value.as_bool()?,
+				"optional" => spec.optional = value.as_bool()?,
+				"features" =>
+					spec.features = value
+						.as_array()?
+						.iter()
+						.map(|f| f.as_str().map(Into::into))
+						.collect::<Option<_>>()?,
+				_ => {
+					spec.source.insert(key.into(), value.as_value()?.to_string().trim().into());
+				},
+			}
+		}
+		Some(spec)
+	}
+
+	/// How the `dev` entry can be merged into the `normal` one.
+	fn resolution(normal: &Item, dev: &Item) -> DuplicateResolution {
+		let (Some(normal), Some(dev)) = (Self::from_item(normal), Self::from_item(dev)) else {
+			return DuplicateResolution::Conflict("the entries cannot be compared".into())
+		};
+
+		if normal.source != dev.source {
+			return DuplicateResolution::Conflict("the versions or sources differ".into())
+		}
+		if normal.optional {
+			return DuplicateResolution::Conflict("the normal dependency is optional".into())
+		}
+		if dev.default_features && !normal.default_features {
+			return DuplicateResolution::Conflict(
+				"only the dev dependency enables the default features".into(),
+			)
+		}
+		let missing = dev
+			.features
+			.into_iter()
+			.filter(|f| !normal.features.contains(f))
+			.collect::<Vec<_>>();
+
+		if missing.is_empty() {
+			DuplicateResolution::Remove
+		} else {
+			DuplicateResolution::MergeFeatures(missing)
+		}
+	}
+}
+
+/// A dependency that is specified with a `path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathDependency {
+	/// The key of the dependency in the manifest. Differs from `package` for renamed ones.
+	pub key: String,
+	/// Name of the crate that is depended on.
+	pub package: String,
+	/// The path as written in the manifest.
+	pub path: String,
+}
+
+/// A dependency that is specified with a `git` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitDependency {
+	/// The key of the dependency in the manifest. Differs from `package` for renamed ones.
+	pub key: String,
+	/// Name of the crate that is depended on.
+	pub package: String,
+	/// The URL of the repository.
+	pub git: String,
+	/// The pinned commit, if any.
+	pub rev: Option<String>,
+	/// The branch to follow, if any.
+	pub branch: Option<String>,
+	/// The tag to use, if any.
+	pub tag: Option<String>,
+	/// Whether the dependency is declared in `workspace.dependencies`.
+	pub workspace: bool,
+}
+
+/// A dependency with `default-features = false` and `features = ["default"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultFeatureConflict {
+	/// The key of the dependency in the manifest.
+	pub key: String,
+	/// Whether the dependency is declared in `workspace.dependencies`.
+	pub workspace: bool,
+}
+
+/// What to remove to resolve a [`DefaultFeatureConflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DefaultFeatureConflictFix {
+	/// Remove the `"default"` feature. This disables the default features.
+	DefaultFeature,
+	/// Remove the `default-features = false` key. This keeps the default features enabled, just
+	/// like Cargo does now.
+	DefaultFeaturesKey,
+}
+
+/// Whether a dependency disables its default features but enables the `default` feature.
+fn has_default_feature_conflict(dep: &dyn TableLike) -> bool {
+	let disabled = ["default-features", "default_features"]
+		.into_iter()
+		.any(|k| dep.get(k).and_then(|v| v.as_bool()) == Some(false));
+	let enabled = dep
+		.get("features")
+		.and_then(|f| f.as_array())
+		.map_or(false, |f| f.iter().any(|v| v.as_str() == Some("default")));
+	disabled && enabled
+}
+
+/// The `package.rust-version` of a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RustVersion {
+	/// Inherited with `rust-version.workspace = true`.
+	Workspace,
+	/// Set explicitly to this version.
+	Version(String),
+}
+
 fn remove_carrot(version: &str) -> &str {
 	version.strip_prefix('^').unwrap_or(version)
 }
@@ -1,13 +1,27 @@
 // SPDX-License-Identifier: GPL-3.0-only
 // SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
 
+pub mod model;
+pub mod snapshot;
+
 use super::GlobalArgs;
-use crate::cmd::lint::build_feature_dag;
-#[cfg(feature = "debugging")]
-use crate::{cmd::lint::CrateAndFeature, prelude::Dag};
+use crate::{
+	cmd::{
+		lint::{build_feature_dag, resolve_features, typos},
+		PackageIndex,
+	},
+	config::search_config,
+	grammar::plural,
+	graph::CrateAndFeature,
+	prelude::Dag,
+};
 
-use cargo_metadata::Metadata;
-use std::time::{Duration, Instant};
+use cargo_metadata::{Metadata, Package};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	path::PathBuf,
+	time::{Duration, Instant},
+};
 
 #[derive(Debug, clap::Parser)]
 pub struct DebugCmd {
@@ -20,13 +34,150 @@ pub struct DebugCmd {
 
 	#[clap(long)]
 	no_root: bool,
+
+	/// Write the resolved model of the workspace as JSON into this file.
+	///
+	/// Contains the packages, their resolved and renamed dependencies, the workspace membership
+	/// and the feature graph. Printed instead of the statistics.
+	#[clap(long, value_name = "PATH")]
+	json_metadata_out: Option<PathBuf>,
+
+	#[clap(subcommand)]
+	sub: Option<DebugSubCmd>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum DebugSubCmd {
+	/// List the crates with the most feature edges.
+	Hotspots(HotspotsCmd),
+	/// Summarize the workspace.
+	Workspace(WorkspaceCmd),
+	/// Snapshot the feature graph or compare it to an older snapshot.
+	Snapshot(snapshot::SnapshotCmd),
+	/// Compute the features that a feature selection enables.
+	Resolve(ResolveCmd),
+	/// Count how every feature name is used across the workspace.
+	Features(FeaturesCmd),
+	/// Show which features would no longer be enabled after removing edges or features.
+	WhatIf(WhatIfCmd),
+}
+
+/// List the crates with the most feature edges.
+///
+/// Crates with many edges dominate the time that it takes to run the lints.
+#[derive(Debug, clap::Parser)]
+pub struct HotspotsCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: super::CargoArgs,
+
+	/// Number of crates to show.
+	#[clap(long, short = 'n', default_value_t = 10)]
+	top: usize,
+}
+
+/// Summarize the workspace as first-run diagnostic.
+#[derive(Debug, clap::Parser)]
+pub struct WorkspaceCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: super::CargoArgs,
+
+	/// Number of feature arrays to show.
+	#[clap(long, short = 'n', default_value_t = 5)]
+	top: usize,
+}
+
+/// Compute the features that a feature selection of a package enables.
+///
+/// The result is derived from the feature graph of Zepter and printed grouped per crate. Compare
+/// it with `cargo tree -e features` to find discrepancies to the cargo resolver.
+#[derive(Debug, clap::Parser)]
+pub struct ResolveCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: super::CargoArgs,
+
+	/// The package to resolve the features of.
+	#[clap(long, short)]
+	package: String,
+
+	/// Features of the package to enable.
+	#[clap(long, value_delimiter = ',')]
+	features: Vec<String>,
+
+	/// Do not enable the `default` feature of the package.
+	#[clap(long)]
+	no_default_features: bool,
+}
+
+/// Count how every feature name is used across the workspace.
+///
+/// Lists how many workspace members define, forward and receive each feature, and which features
+/// are never enabled by any other feature. Helps to plan the consolidation of features.
+#[derive(Debug, clap::Parser)]
+pub struct FeaturesCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: super::CargoArgs,
+}
+
+/// Estimate the blast radius of removing edges or features from the feature graph.
+///
+/// Prints the features that the features next to the removed parts no longer enable. Nothing is
+/// written to the manifests.
+#[derive(Debug, clap::Parser)]
+pub struct WhatIfCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: super::CargoArgs,
+
+	/// Remove the edge between two features, like `crateA/std->crateB/std`.
+	///
+	/// Can be passed multiple times.
+	#[clap(long, value_name = "FROM->TO")]
+	remove_edge: Vec<String>,
+
+	/// Remove a feature together with all of its edges, like `crateA/std`.
+	///
+	/// Can be passed multiple times.
+	#[clap(long, value_name = "CRATE/FEATURE")]
+	remove_node: Vec<String>,
 }
 
 impl DebugCmd {
-	pub fn run(&self, g: &GlobalArgs) {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
 		g.warn_unstable();
+		match &self.sub {
+			Some(DebugSubCmd::Hotspots(cmd)) => {
+				cmd.run(g);
+				return Ok(())
+			},
+			Some(DebugSubCmd::Workspace(cmd)) => {
+				cmd.run(g);
+				return Ok(())
+			},
+			Some(DebugSubCmd::Snapshot(cmd)) => return cmd.run(g),
+			Some(DebugSubCmd::Resolve(cmd)) => return cmd.run(g),
+			Some(DebugSubCmd::Features(cmd)) => return cmd.run(g),
+			Some(DebugSubCmd::WhatIf(cmd)) => return cmd.run(g),
+			None => (),
+		}
 		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
-		let dag = build_feature_dag(&meta, &meta.packages);
+		if let Some(path) = &self.json_metadata_out {
+			let model = model::WorkspaceModel::from_metadata(&meta);
+			model.to_path(path)?;
+			println!(
+				"Wrote the model of {} package{} and {} feature edge{} to {}.",
+				model.packages.len(),
+				plural(model.packages.len()),
+				model.feature_edges.len(),
+				plural(model.feature_edges.len()),
+				path.display()
+			);
+			return Ok(())
+		}
+		let dag = build_feature_dag(&PackageIndex::new(&meta), &meta.packages);
 
 		if !self.no_root {
 			println!("Root: {}", meta.workspace_root);
@@ -34,6 +185,9 @@ impl DebugCmd {
 		println!("Num workspace members: {}", meta.workspace_members.len());
 		println!("Num dependencies: {}", meta.packages.len());
 		println!("DAG nodes: {}, links: {}", dag.num_nodes(), dag.num_edges());
+		if let Some(stats) = dag.degree_stats() {
+			println!("DAG out-degree: {}", stats);
+		}
 
 		#[cfg(feature = "debugging")]
 		self.connectivity_buckets(&dag);
@@ -42,6 +196,7 @@ impl DebugCmd {
 			let (took, points) = Self::measure(&meta);
 			println!("DAG setup time: {:.2?} (avg from {} runs)", took, points);
 		}
+		Ok(())
 	}
 
 	#[cfg(feature = "debugging")]
@@ -71,7 +226,342 @@ impl DebugCmd {
 
 	fn measure_once(meta: &Metadata) -> Duration {
 		let start = Instant::now();
-		let _ = build_feature_dag(meta, &meta.packages);
+		let _ = build_feature_dag(&PackageIndex::new(meta), &meta.packages);
 		start.elapsed()
 	}
 }
+
+impl HotspotsCmd {
+	pub fn run(&self, _g: &GlobalArgs) {
+		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
+		let dag = build_feature_dag(&PackageIndex::new(&meta), &meta.packages);
+		let names = meta
+			.packages
+			.iter()
+			.map(|p| (p.id.to_string(), p.name.as_str()))
+			.collect::<BTreeMap<_, _>>();
+
+		// Crate name -> (outgoing, incoming) feature edges.
+		let mut edges = BTreeMap::<&str, (usize, usize)>::new();
+		for (CrateAndFeature(id, _), degree) in dag.out_degrees() {
+			let name = names.get(id).copied().unwrap_or(id);
+			edges.entry(name).or_default().0 += degree;
+		}
+		for (CrateAndFeature(id, _), degree) in dag.in_degrees() {
+			let name = names.get(id).copied().unwrap_or(id);
+			edges.entry(name).or_default().1 += degree;
+		}
+
+		let mut edges = edges.into_iter().collect::<Vec<_>>();
+		edges.sort_by_key(|(name, (out, inc))| (core::cmp::Reverse(out + inc), *name));
+
+		let shown = self.top.min(edges.len());
+		println!("Top {} crate{} by number of feature edges:", shown, plural(shown));
+		for (name, (out, inc)) in edges.iter().take(self.top) {
+			println!("  {}: {} (out {}, in {})", name, out + inc, out, inc);
+		}
+	}
+}
+
+impl WorkspaceCmd {
+	pub fn run(&self, _g: &GlobalArgs) {
+		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
+		let (members, external): (Vec<_>, Vec<_>) =
+			meta.packages.iter().partition(|p| meta.workspace_members.contains(&p.id));
+
+		println!("Members: {}", members.len());
+
+		let mut sources = BTreeMap::<&str, usize>::new();
+		for pkg in external.iter() {
+			let source = match pkg.source.as_ref().map(|s| s.repr.as_str()) {
+				None => "path",
+				Some(s) if s.starts_with("git+") => "git",
+				Some(_) => "registry",
+			};
+			*sources.entry(source).or_default() += 1;
+		}
+		let sources = sources.iter().map(|(s, n)| format!("{} {}", s, n)).collect::<Vec<_>>();
+		print!("External dependencies: {}", external.len());
+		if !sources.is_empty() {
+			print!(" ({})", sources.join(", "));
+		}
+		println!();
+
+		let mut histogram = BTreeMap::<usize, usize>::new();
+		for pkg in members.iter() {
+			*histogram.entry(pkg.features.len()).or_default() += 1;
+		}
+		println!("Features per member:");
+		for (features, crates) in histogram {
+			println!("  {}: {} crate{}", features, crates, plural(crates));
+		}
+
+		let mut no_std = members
+			.iter()
+			.filter(|p| !p.features.contains_key("std"))
+			.map(|p| p.name.as_str())
+			.collect::<Vec<_>>();
+		no_std.sort();
+		println!("Members without 'std' feature: {}", no_std.len());
+		for name in no_std {
+			println!("  {}", name);
+		}
+
+		let mut arrays = members
+			.iter()
+			.flat_map(|p| p.features.iter().map(move |(f, v)| (v.len(), p.name.as_str(), f)))
+			.filter(|(len, _, _)| *len > 0)
+			.collect::<Vec<_>>();
+		arrays.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| (a.1, a.2).cmp(&(b.1, b.2))));
+		println!("Largest feature arrays:");
+		for (len, krate, feature) in arrays.iter().take(self.top) {
+			println!("  {}/{}: {}", krate, feature, len);
+		}
+
+		match search_config(meta.workspace_root.as_std_path()) {
+			Ok(path) => {
+				let root = meta.workspace_root.as_std_path().canonicalize();
+				let path = root
+					.ok()
+					.and_then(|root| path.strip_prefix(root).ok().map(|p| p.to_path_buf()))
+					.unwrap_or(path);
+				println!("Config file: {}", path.display());
+			},
+			Err(_) => println!("Config file: none"),
+		}
+	}
+}
+
+impl ResolveCmd {
+	pub fn run(&self, _g: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let index = PackageIndex::new(&meta);
+		let pkg = match index.by_name(&self.package) {
+			[] => return Err(format!("Package '{}' not found", self.package)),
+			[pkg] => *pkg,
+			_ => return Err(format!("Package '{}' is ambiguous", self.package)),
+		};
+		if let Some(feature) = self.features.iter().find(|f| !pkg.features.contains_key(*f)) {
+			let mut err = format!("Package '{}' does not have feature '{}'", pkg.name, feature);
+			match typos::closest(feature, pkg.features.keys(), 2).as_slice() {
+				[] => (),
+				[one] => err.push_str(&format!(", did you mean '{}'?", one)),
+				many => err.push_str(&format!(", did you mean one of '{}'?", many.join("', '"))),
+			}
+			return Err(err)
+		}
+
+		let dag = build_feature_dag(&index, &meta.packages);
+		let enabled = resolve_features(
+			&dag,
+			&pkg.id.to_string(),
+			self.features.iter().cloned(),
+			!self.no_default_features,
+		);
+
+		// Crate name and version -> enabled features.
+		let mut by_name = BTreeMap::new();
+		for (id, features) in enabled.iter() {
+			let key = match index.get(id) {
+				Some(p) if index.by_name(&p.name).len() > 1 =>
+					(p.name.clone(), Some(p.version.to_string())),
+				Some(p) => (p.name.clone(), None),
+				None => (id.clone(), None),
+			};
+			by_name.insert(key, features);
+		}
+
+		println!("Features enabled in {} crate{}:", by_name.len(), plural(by_name.len()));
+		for ((name, version), features) in by_name {
+			let features = features.iter().map(String::as_str).collect::<Vec<_>>().join(", ");
+			match version {
+				Some(version) => println!("  {} v{}: {}", name, version, features),
+				None => println!("  {}: {}", name, features),
+			}
+		}
+		Ok(())
+	}
+}
+
+impl WhatIfCmd {
+	pub fn run(&self, _g: &GlobalArgs) -> Result<(), String> {
+		if self.remove_edge.is_empty() && self.remove_node.is_empty() {
+			return Err("Nothing to remove. Pass `--remove-edge` or `--remove-node`.".into())
+		}
+		let meta = self.cargo_args.load_metadata()?;
+		let index = PackageIndex::new(&meta);
+		let before = build_feature_dag(&index, &meta.packages);
+		let mut after = before.clone();
+		// Features whose outgoing edges change.
+		let mut roots = BTreeSet::new();
+
+		for edge in self.remove_edge.iter() {
+			let (from, to) = edge.split_once("->").ok_or_else(|| {
+				format!("Invalid edge '{}'. Expected `crate/feature->crate/feature`.", edge)
+			})?;
+			let (from, to) = (from.trim(), to.trim());
+			let tos = feature_nodes(&index, to)?;
+			let mut removed = false;
+			for from in feature_nodes(&index, from)? {
+				for to in tos.iter() {
+					if after.remove_edge(&from, to) {
+						roots.insert(from.clone());
+						removed = true;
+					}
+				}
+			}
+			if !removed {
+				return Err(format!("There is no edge from '{}' to '{}'", from, to))
+			}
+		}
+		for node in self.remove_node.iter() {
+			let mut removed = false;
+			for node in feature_nodes(&index, node)? {
+				roots.extend(before.inverse_lookup(&node).cloned());
+				removed |= after.remove_node(&node);
+			}
+			if !removed {
+				return Err(format!("There is no feature '{}'", node))
+			}
+		}
+
+		// Unresolved crates are only known by name.
+		let show = |CrateAndFeature(id, feature): &CrateAndFeature| {
+			let name = index.get(id).map_or(id.as_str(), |p| p.name.as_str());
+			format!("{}/{}", name, feature)
+		};
+		// Skip internal helper nodes like `#entrypoint`.
+		for root in roots.iter().filter(|r| !r.1.starts_with('#')) {
+			let still = after.reachable_from([root.clone()]);
+			let lost = before
+				.reachable_from([root.clone()])
+				.into_iter()
+				.filter(|n| !n.1.starts_with('#') && !still.contains(n))
+				.collect::<Vec<_>>();
+
+			if lost.is_empty() {
+				println!("{} still enables the same features", show(root));
+				continue
+			}
+			println!(
+				"{} no longer enables {} feature{}:",
+				show(root),
+				lost.len(),
+				plural(lost.len())
+			);
+			for node in lost.iter() {
+				println!("  {}", show(node));
+			}
+		}
+		Ok(())
+	}
+}
+
+/// All nodes of the feature graph for a `crate/feature` pair, one per version of the crate.
+fn feature_nodes(index: &PackageIndex<'_>, node: &str) -> Result<Vec<CrateAndFeature>, String> {
+	let (krate, feature) = node
+		.split_once('/')
+		.ok_or_else(|| format!("Invalid feature '{}'. Expected `crate/feature`.", node))?;
+	let pkgs = index.by_name(krate);
+	if pkgs.is_empty() {
+		return Err(format!("Package '{}' not found", krate))
+	}
+	Ok(pkgs.iter().map(|p| CrateAndFeature(p.id.to_string(), feature.into())).collect())
+}
+
+/// How often a feature name is used by the workspace members.
+#[derive(Debug, Default, PartialEq)]
+pub struct FeatureUsage<'a> {
+	/// Members that define the feature.
+	pub defined: BTreeSet<&'a str>,
+	/// Members that enable the feature of the same name on a dependency.
+	pub forwarded: BTreeSet<&'a str>,
+	/// Dependencies whose feature is enabled by a member.
+	pub received: BTreeSet<&'a str>,
+}
+
+impl FeaturesCmd {
+	pub fn run(&self, _g: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let dag = build_feature_dag(&PackageIndex::new(&meta), &meta.packages);
+		let members = meta
+			.packages
+			.iter()
+			.filter(|p| meta.workspace_members.contains(&p.id))
+			.collect::<Vec<_>>();
+		let (usage, never_enabled) = feature_usage(&members, &dag);
+
+		println!(
+			"Usage of {} feature{} by {} member{}:",
+			usage.len(),
+			plural(usage.len()),
+			members.len(),
+			plural(members.len())
+		);
+		for (feature, usage) in usage.iter() {
+			println!(
+				"  {}: defined by {}, forwarded by {}, received by {}",
+				feature,
+				usage.defined.len(),
+				usage.forwarded.len(),
+				usage.received.len()
+			);
+		}
+
+		println!("Features that are never enabled by another feature: {}", never_enabled.len());
+		for (krate, feature) in never_enabled {
+			println!("  {}/{}", krate, feature);
+		}
+		Ok(())
+	}
+}
+
+/// Aggregate the [`FeatureUsage`] of all feature names of the `members`.
+///
+/// Also returns the `(crate, feature)` pairs that no feature in the graph enables. The `default`
+/// feature is excluded from them since cargo enables it implicitly.
+pub fn feature_usage<'a>(
+	members: &[&'a Package],
+	dag: &'a Dag<CrateAndFeature>,
+) -> (BTreeMap<&'a str, FeatureUsage<'a>>, Vec<(&'a str, &'a str)>) {
+	let mut usage = BTreeMap::<&str, FeatureUsage<'_>>::new();
+	for pkg in members.iter() {
+		for feature in pkg.features.keys() {
+			usage.entry(feature).or_default().defined.insert(&pkg.name);
+		}
+	}
+
+	let names = members
+		.iter()
+		.map(|p| (p.id.repr.as_str(), p.name.as_str()))
+		.collect::<BTreeMap<_, _>>();
+	// Nodes as `(crate id, feature)` that some feature enables.
+	let mut enabled = BTreeSet::<(&str, &str)>::new();
+	// Skip internal helper nodes like `#entrypoint`.
+	for (lhs, rhss) in dag.edges.iter().filter(|(lhs, _)| !lhs.1.starts_with('#')) {
+		let Some(&name) = names.get(lhs.0.as_str()) else { continue };
+		for rhs in rhss.iter() {
+			enabled.insert((rhs.0.as_str(), rhs.1.as_str()));
+			if rhs.0 == lhs.0 {
+				continue
+			}
+			let entry = usage.entry(&rhs.1).or_default();
+			entry.received.insert(&rhs.0);
+			if rhs.1 == lhs.1 {
+				entry.forwarded.insert(name);
+			}
+		}
+	}
+
+	let mut never_enabled = Vec::new();
+	for pkg in members.iter() {
+		for feature in pkg.features.keys().filter(|f| *f != "default") {
+			if !enabled.contains(&(pkg.id.repr.as_str(), feature.as_str())) {
+				never_enabled.push((pkg.name.as_str(), feature.as_str()));
+			}
+		}
+	}
+	never_enabled.sort();
+
+	(usage, never_enabled)
+}
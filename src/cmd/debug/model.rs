@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! The resolved model of a workspace as stable JSON for other tools.
+//!
+//! Contains the packages with their dependencies as resolved by Zepter, including renames, and
+//! the edges of the feature graph. Fields are only ever added within a [`SCHEMA_VERSION`].
+
+use crate::{
+	cmd::{lint::build_feature_dag, PackageIndex},
+	graph::CrateAndFeature,
+};
+
+use cargo_metadata::{DependencyKind, Metadata};
+use std::{collections::BTreeMap, path::Path};
+
+/// Version of the JSON schema. Bumped on every change that is not purely additive.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The packages and the feature graph of a workspace.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct WorkspaceModel {
+	pub schema_version: u32,
+	pub workspace_root: String,
+	/// All packages of the metadata, sorted by ID.
+	pub packages: Vec<PackageModel>,
+	/// Edges of the feature graph without internal helper nodes.
+	pub feature_edges: Vec<FeatureEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PackageModel {
+	pub id: String,
+	pub name: String,
+	pub version: String,
+	/// Whether the package is a workspace member.
+	pub member: bool,
+	pub manifest_path: String,
+	pub features: BTreeMap<String, Vec<String>>,
+	pub dependencies: Vec<DependencyModel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DependencyModel {
+	/// The name under which the dependency is used. Differs from `package` if it is renamed.
+	pub name: String,
+	/// The name of the crate that is depended on.
+	pub package: String,
+	/// Either `normal`, `dev` or `build`.
+	pub kind: String,
+	pub optional: bool,
+	pub uses_default_features: bool,
+	pub features: Vec<String>,
+	/// The target platform, like `cfg(unix)`, if the dependency is target specific.
+	pub target: Option<String>,
+	/// The ID of the package that the dependency resolves to, if any.
+	pub resolved: Option<String>,
+}
+
+/// An edge of the feature graph where `from` enables `to`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FeatureEdge {
+	pub from: FeatureNode,
+	pub to: FeatureNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FeatureNode {
+	/// The package ID, or the crate name if the dependency could not be resolved.
+	pub package: String,
+	pub feature: String,
+	/// Whether the feature is only enabled if the optional dependency is, like `dep?/feature`.
+	pub weak: bool,
+}
+
+impl WorkspaceModel {
+	pub fn from_metadata(meta: &Metadata) -> Self {
+		let index = PackageIndex::new(meta);
+		let mut packages = meta
+			.packages
+			.iter()
+			.map(|pkg| PackageModel {
+				id: pkg.id.to_string(),
+				name: pkg.name.clone(),
+				version: pkg.version.to_string(),
+				member: meta.workspace_members.contains(&pkg.id),
+				manifest_path: pkg.manifest_path.to_string(),
+				features: pkg.features.clone().into_iter().collect(),
+				dependencies: pkg
+					.dependencies
+					.iter()
+					.map(|dep| DependencyModel {
+						name: dep.rename.clone().unwrap_or_else(|| dep.name.clone()),
+						package: dep.name.clone(),
+						kind: kind_name(&dep.kind).into(),
+						optional: dep.optional,
+						uses_default_features: dep.uses_default_features,
+						features: dep.features.clone(),
+						target: dep.target.as_ref().map(ToString::to_string),
+						resolved: index.resolve_dep(pkg, dep).map(|r| r.pkg.id.to_string()),
+					})
+					.collect(),
+			})
+			.collect::<Vec<_>>();
+		packages.sort_by(|a, b| a.id.cmp(&b.id));
+
+		let dag = build_feature_dag(&index, &meta.packages);
+		let feature_edges = dag
+			.edges
+			.iter()
+			.flat_map(|(from, tos)| tos.iter().map(move |to| (from, to)))
+			.filter_map(|(from, to)| Some(FeatureEdge { from: node(from)?, to: node(to)? }))
+			.collect();
+
+		Self {
+			schema_version: SCHEMA_VERSION,
+			workspace_root: meta.workspace_root.to_string(),
+			packages,
+			feature_edges,
+		}
+	}
+
+	pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+		let path = path.as_ref();
+		let json = serde_json::to_string_pretty(self)
+			.map_err(|e| format!("Failed to serialize the model: {}", e))?;
+		std::fs::write(path, format!("{}\n", json))
+			.map_err(|e| format!("Failed to write the model to {:?}: {}", path, e))
+	}
+}
+
+/// Convert a node of the feature graph. Internal helper nodes like `#entrypoint` are skipped.
+fn node(CrateAndFeature(id, feature): &CrateAndFeature) -> Option<FeatureNode> {
+	if feature.starts_with('#') {
+		return None
+	}
+	let (package, weak) = match id.strip_suffix('?') {
+		Some(id) => (id, true),
+		None => (id.as_str(), false),
+	};
+	Some(FeatureNode { package: package.into(), feature: feature.clone(), weak })
+}
+
+fn kind_name(kind: &DependencyKind) -> &'static str {
+	match kind {
+		DependencyKind::Normal => "normal",
+		DependencyKind::Development => "dev",
+		DependencyKind::Build => "build",
+		_ => "unknown",
+	}
+}
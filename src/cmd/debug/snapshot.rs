@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Snapshots of the feature graph that can be compared across revisions.
+//!
+//! Nodes are identified by crate name instead of package ID, so that snapshots of different
+//! checkouts can be compared with each other.
+
+use crate::{
+	cmd::{
+		lint::{baseline::Baseline, try_build_feature_dag},
+		GlobalArgs, PackageIndex,
+	},
+	grammar::plural,
+	graph::CrateAndFeature,
+	prelude::Dag,
+};
+
+use cargo_metadata::Metadata;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	path::{Path, PathBuf},
+};
+
+/// Serialize the feature graph and compare it to an older snapshot.
+#[derive(Debug, clap::Parser)]
+pub struct SnapshotCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: crate::cmd::CargoArgs,
+
+	/// Write the snapshot into this file instead of printing it.
+	#[clap(long, short)]
+	output: Option<PathBuf>,
+
+	/// Compare against an older snapshot and print the difference.
+	#[clap(long)]
+	compare: Option<PathBuf>,
+
+	/// Include the issues of a baseline file that was written by `lint propagate-feature`.
+	#[clap(long)]
+	issues: Option<PathBuf>,
+}
+
+/// The feature graph and the issues of a workspace at a specific point in time.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+	/// Feature edges in the form of `crate/feature`.
+	#[serde(default)]
+	pub edges: BTreeMap<String, BTreeSet<String>>,
+	/// Human readable descriptions of all issues.
+	#[serde(default)]
+	pub issues: BTreeSet<String>,
+}
+
+/// Difference between two snapshots.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+	pub added_edges: Vec<(String, String)>,
+	pub removed_edges: Vec<(String, String)>,
+	pub new_issues: Vec<String>,
+	pub resolved_issues: Vec<String>,
+}
+
+impl SnapshotCmd {
+	pub fn run(&self, _g: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let mut snapshot = Snapshot::from_metadata(&meta);
+		if let Some(path) = &self.issues {
+			let baseline = Baseline::from_path(path)?;
+			snapshot.issues.extend(baseline.issues.iter().map(ToString::to_string));
+		}
+
+		if let Some(path) = &self.output {
+			snapshot.to_path(path)?;
+			println!(
+				"Wrote snapshot with {} edge{} and {} issue{} to {}.",
+				snapshot.num_edges(),
+				plural(snapshot.num_edges()),
+				snapshot.issues.len(),
+				plural(snapshot.issues.len()),
+				path.display()
+			);
+		}
+
+		match &self.compare {
+			Some(path) => {
+				let old = Snapshot::from_path(path)?;
+				print!("{}", old.diff(&snapshot));
+			},
+			None if self.output.is_none() => print!("{}", snapshot.to_yaml()?),
+			None => (),
+		}
+
+		Ok(())
+	}
+}
+
+impl Snapshot {
+	/// Create a snapshot of the feature graph of all packages in the metadata.
+	pub fn from_metadata(meta: &Metadata) -> Self {
+		let (dag, malformed) = try_build_feature_dag(&PackageIndex::new(meta), &meta.packages);
+		let mut snapshot = Self::from_dag(meta, &dag);
+		snapshot.issues.extend(malformed.iter().map(ToString::to_string));
+		snapshot
+	}
+
+	/// Convert the nodes of a feature [`Dag`] from package IDs to crate names.
+	///
+	/// Internal helper nodes like `#entrypoint` are skipped.
+	pub fn from_dag(meta: &Metadata, dag: &Dag<CrateAndFeature>) -> Self {
+		let names = meta
+			.packages
+			.iter()
+			.map(|p| (p.id.to_string(), p.name.as_str()))
+			.collect::<BTreeMap<_, _>>();
+		let node = |CrateAndFeature(id, feature): &CrateAndFeature| {
+			if feature.starts_with('#') {
+				return None
+			}
+			let (id, optional) = match id.strip_suffix('?') {
+				Some(id) => (id, "?"),
+				None => (id.as_str(), ""),
+			};
+			let name = names.get(id).copied().unwrap_or(id);
+			Some(format!("{}{}/{}", name, optional, feature))
+		};
+
+		let mut edges = BTreeMap::<String, BTreeSet<String>>::new();
+		for (from, tos) in dag.edges.iter() {
+			let Some(from) = node(from) else { continue };
+			for to in tos.iter().filter_map(node) {
+				edges.entry(from.clone()).or_default().insert(to);
+			}
+		}
+
+		Self { edges, issues: BTreeSet::new() }
+	}
+
+	/// Load a snapshot from a YAML file.
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+		let path = path.as_ref();
+		let content = std::fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read snapshot file {:?}: {}", path, e))?;
+
+		serde_yaml::from_str(&content)
+			.map_err(|e| format!("Failed to parse snapshot file {:?}: {}", path, e))
+	}
+
+	/// Write the snapshot as YAML file.
+	pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+		let path = path.as_ref();
+		std::fs::write(path, self.to_yaml()?)
+			.map_err(|e| format!("Failed to write snapshot file {:?}: {}", path, e))
+	}
+
+	pub fn to_yaml(&self) -> Result<String, String> {
+		serde_yaml::to_string(self).map_err(|e| format!("Failed to serialize snapshot: {}", e))
+	}
+
+	pub fn num_edges(&self) -> usize {
+		self.edges.values().map(BTreeSet::len).sum()
+	}
+
+	fn edge_set(&self) -> BTreeSet<(&String, &String)> {
+		self.edges
+			.iter()
+			.flat_map(|(from, tos)| tos.iter().map(move |to| (from, to)))
+			.collect()
+	}
+
+	/// What changed when going from `self` to `new`.
+	pub fn diff(&self, new: &Self) -> SnapshotDiff {
+		let (old_edges, new_edges) = (self.edge_set(), new.edge_set());
+		let owned = |(from, to): (&String, &String)| (from.clone(), to.clone());
+
+		SnapshotDiff {
+			added_edges: new_edges.difference(&old_edges).copied().map(owned).collect(),
+			removed_edges: old_edges.difference(&new_edges).copied().map(owned).collect(),
+			new_issues: new.issues.difference(&self.issues).cloned().collect(),
+			resolved_issues: self.issues.difference(&new.issues).cloned().collect(),
+		}
+	}
+}
+
+impl SnapshotDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added_edges.is_empty() &&
+			self.removed_edges.is_empty() &&
+			self.new_issues.is_empty() &&
+			self.resolved_issues.is_empty()
+	}
+}
+
+impl std::fmt::Display for SnapshotDiff {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.is_empty() {
+			return writeln!(f, "Feature graph unchanged.")
+		}
+
+		for (title, edges) in
+			[("Added edges", &self.added_edges), ("Removed edges", &self.removed_edges)]
+		{
+			if !edges.is_empty() {
+				writeln!(f, "{} ({}):", title, edges.len())?;
+				for (from, to) in edges.iter() {
+					writeln!(f, "  {} -> {}", from, to)?;
+				}
+			}
+		}
+		for (title, issues) in
+			[("New issues", &self.new_issues), ("Resolved issues", &self.resolved_issues)]
+		{
+			if !issues.is_empty() {
+				writeln!(f, "{} ({}):", title, issues.len())?;
+				for issue in issues.iter() {
+					writeln!(f, "  {}", issue)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn snapshot_diff_works() {
+		let old: Snapshot = serde_yaml::from_str(
+			"edges:\n  A/std:\n  - B/std\n  - C/std\nissues:\n- old issue\n- kept issue\n",
+		)
+		.unwrap();
+		let new: Snapshot =
+			serde_yaml::from_str("edges:\n  A/std:\n  - B/std\n  A?/std:\n  - D/std\nissues:\n- kept issue\n- new issue\n")
+				.unwrap();
+
+		let diff = old.diff(&new);
+		assert_eq!(diff.added_edges, vec![("A?/std".into(), "D/std".into())]);
+		assert_eq!(diff.removed_edges, vec![("A/std".into(), "C/std".into())]);
+		assert_eq!(diff.new_issues, vec!["new issue".to_string()]);
+		assert_eq!(diff.resolved_issues, vec!["old issue".to_string()]);
+		assert!(old.diff(&old).is_empty());
+	}
+}
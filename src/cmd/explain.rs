@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use super::GlobalArgs;
+use crate::issues::{self, ISSUES};
+
+/// Explain what an issue code means and how to fix it.
+#[derive(Debug, clap::Parser)]
+pub struct ExplainCmd {
+	/// The code to explain, like `Z0001`. Lists all codes if omitted.
+	#[clap(name = "CODE", index = 1)]
+	code: Option<String>,
+}
+
+impl ExplainCmd {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		let Some(code) = self.code.as_ref() else {
+			for issue in ISSUES {
+				println!("{}  {:<30}  {}", issue.code, issue.lint, issue.title);
+			}
+			return Ok(())
+		};
+		let issue = issues::find(code).ok_or_else(|| {
+			format!("Unknown issue code '{}'. Run `zepter explain` to list all codes.", code)
+		})?;
+
+		println!("{}: {}\n", g.emphasis(issue.code), issue.title);
+		println!("Lint: {}\n", issue.lint);
+		println!("{}\n", issue.explanation);
+		println!("Example:");
+		for line in issue.example.lines() {
+			println!("  {}", line);
+		}
+		Ok(())
+	}
+}
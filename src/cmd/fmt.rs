@@ -3,11 +3,20 @@
 
 //! Format features in the crate manifest.
 
-use super::GlobalArgs;
-use crate::{autofix::*, cmd::parse_key_val, grammar::*, log};
+use super::{serve, GlobalArgs};
+use crate::{
+	autofix::*,
+	cmd::{
+		lint::error_stats, output::Finding, parse_key_val, progress::Progress, timing, FixCommand,
+	},
+	config::severity::Severity,
+	grammar::*,
+	log, out,
+};
 
 use cargo_metadata::Metadata;
-use std::{collections::BTreeMap as Map, fs::canonicalize, path::PathBuf, str::FromStr};
+use clap::ValueEnum;
+use std::{collections::BTreeMap as Map, fs::canonicalize, io::Read, path::PathBuf, str::FromStr};
 
 /// Format the features in your manifest files.
 #[derive(Debug, clap::Parser)]
@@ -30,6 +39,10 @@ pub struct FormatFeaturesCmd {
 	#[clap(flatten)]
 	cargo_args: super::CargoArgs,
 
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	packages: super::PackageSpec,
+
 	/// Include dependencies in the formatting check.
 	///
 	/// They will not be modified, unless their path is included in `--modify-paths`.
@@ -40,9 +53,13 @@ pub struct FormatFeaturesCmd {
 	#[clap(long)]
 	modify_paths: Vec<PathBuf>,
 
-	/// DEPRECATED AND IGNORED
-	#[clap(long = "check", short = 'c')]
-	unused_check: bool,
+	/// Only check the formatting without modifying any files. This is the default.
+	#[clap(long, short, conflicts_with = "fix")]
+	check: bool,
+
+	/// Exit with code zero in check mode, even if features are not formatted.
+	#[clap(long, conflicts_with = "fix")]
+	check_only_exit_zero: bool,
 
 	/// Fix the formatting errors automatically.
 	#[clap(long, short)]
@@ -68,6 +85,22 @@ pub struct FormatFeaturesCmd {
 	/// Also print the paths of the offending Cargo.toml files.
 	#[clap(long)]
 	print_paths: bool,
+
+	/// Format a single manifest from stdin and print it to stdout.
+	///
+	/// This does not load the cargo metadata and can be used as formatter backend for editors.
+	#[clap(long, conflicts_with_all = ["fix", "file"], verbatim_doc_comment)]
+	stdin: bool,
+
+	/// Format a single manifest file and print it to stdout.
+	///
+	/// This does not load the cargo metadata and does not modify the file.
+	#[clap(long, value_name = "PATH", conflicts_with = "fix", verbatim_doc_comment)]
+	file: Option<PathBuf>,
+
+	/// How to resolve feature entries that only differ by a `?`, like `foo/std` and `foo?/std`.
+	#[clap(long, value_enum, value_name = "POLICY", default_value_t = ConflictResolution::Error)]
+	conflict_resolution: ConflictResolution,
 }
 
 /// How to format the entries of a feature.
@@ -85,6 +118,24 @@ pub enum Mode {
 	Canonicalize,
 }
 
+/// Whether [`FormatFeaturesCmd`] only checks the formatting or also fixes it.
+///
+/// Errors that need to be fixed manually, like duplicate entries, exit with the error code in both
+/// modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+	/// Report unformatted features without modifying any files.
+	///
+	/// Exits with the error code if any were found, or with zero if `--check-only-exit-zero` is
+	/// passed.
+	Check,
+	/// Format the features in place.
+	///
+	/// Exits with zero if all of them were formatted and with the error code if some could not be,
+	/// for example because they are outside of the workspace and `--modify-paths`.
+	Fix,
+}
+
 impl FromStr for Mode {
 	type Err = std::string::ParseError;
 
@@ -104,15 +155,61 @@ impl FormatCmd {
 			SubCommand::Features(cmd) => cmd.run(global),
 		}
 	}
+
+	pub(crate) fn describe(&self) -> Vec<String> {
+		match &self.subcommand {
+			SubCommand::Features(cmd) => cmd.describe(),
+		}
+	}
+
+	/// Whether the manifests are formatted instead of only checked.
+	pub(crate) fn writes_manifests(&self) -> bool {
+		match &self.subcommand {
+			SubCommand::Features(cmd) => cmd.fix,
+		}
+	}
 }
 
 impl FormatFeaturesCmd {
-	pub fn run(&self, global: &GlobalArgs) {
-		if self.unused_check {
-			log::warn!("The `--check` is now implicit and ignored");
+	/// What this command enforces, for `zepter run --describe`.
+	pub(crate) fn describe(&self) -> Vec<String> {
+		let mut lines = vec![format!(
+			"Features are formatted canonically with at most {} characters per line.",
+			self.line_width
+		)];
+		lines.extend(self.packages.describe());
+		if self.no_workspace {
+			lines.push("Dependencies outside of the workspace are checked as well.".into());
+		}
+		let unformatted = self
+			.parse_mode_per_feature()
+			.into_iter()
+			.filter(|(_, modes)| modes.contains(&Mode::None))
+			.map(|(feature, _)| feature)
+			.collect::<Vec<_>>();
+		if !unformatted.is_empty() {
+			lines.push(format!(
+				"Feature{} {} {} not formatted.",
+				plural(unformatted.len()),
+				enumerate(&unformatted),
+				if unformatted.len() == 1 { "is" } else { "are" }
+			));
 		}
+		match self.conflict_resolution {
+			ConflictResolution::PreferWeak =>
+				lines.push("Entries that only differ by a `?` keep the weak one.".into()),
+			ConflictResolution::PreferStrong =>
+				lines.push("Entries that only differ by a `?` keep the strong one.".into()),
+			ConflictResolution::Error => (),
+		}
+		lines
+	}
 
+	pub fn run(&self, global: &GlobalArgs) {
 		let modes = self.parse_mode_per_feature();
+		if self.stdin || self.file.is_some() {
+			return self.run_single(&modes, global)
+		}
 		let meta = self.load_metadata(global);
 		// Allowed dir that we can write to.
 		let allowed_dir = canonicalize(meta.workspace_root.as_std_path()).unwrap();
@@ -121,59 +218,89 @@ impl FormatFeaturesCmd {
 		// (path, crate) -> errors
 		let mut errors = Map::<(PathBuf, String), Vec<String>>::new();
 
-		log::debug!("Checking {} crate{}", meta.packages.len(), plural(meta.packages.len()));
+		let pkgs = self.packages.select(&meta).unwrap_or_else(|err| {
+			out::eprintln!("{}", global.error(&err));
+			serve::exit(1)
+		});
+		log::debug!("Checking {} crate{}", pkgs.len(), plural(pkgs.len()));
 
-		for pkg in meta.packages.iter() {
+		let progress = Progress::bar(pkgs.len(), "Checking manifests");
+		let phase = timing::Phase::start("analysis");
+		for pkg in pkgs.iter() {
+			progress.inc();
 			let path = canonicalize(pkg.manifest_path.clone().into_std_path_buf()).unwrap();
 
 			let mut fixer = AutoFixer::from_manifest(&path).unwrap();
-			if let Err(errs) = fixer.canonicalize_features(&pkg.name, &modes, self.line_width) {
+			if let Err(errs) = fixer.canonicalize_features(
+				&pkg.name,
+				&modes,
+				self.line_width,
+				self.conflict_resolution,
+			) {
 				let path = path.strip_prefix(&allowed_dir).unwrap().to_path_buf();
 				errors.entry((path.clone(), pkg.name.clone())).or_default().extend(errs);
 			} else if fixer.modified() {
 				offenders.push((path, &pkg.name, fixer));
 			}
 		}
+		drop((phase, progress));
 		if !errors.is_empty() {
 			let num_errors = errors.values().map(|errs| errs.len()).sum::<usize>();
-			println!(
+			if global.summary_only() {
+				Self::print_summary(num_errors, 0, self.fix, None, global);
+				global.exit(global.error_code())
+			}
+			global.write_stats(num_errors, 0, 0);
+			out::println!(
 				"Please fix {} error{} in {} crate{} manually:",
-				global.red(&num_errors.to_string()),
+				global.error(&num_errors.to_string()),
 				plural(num_errors),
-				global.red(&errors.len().to_string()),
+				global.error(&errors.len().to_string()),
 				plural(errors.len())
 			);
 			for ((path, pkg), errs) in errors.iter() {
-				println!("  {} ({})", global.bold(pkg), path.display());
+				out::println!("  {} ({})", global.emphasis(pkg), path.display());
 				for err in errs.iter() {
-					println!("    {err}");
+					out::println!("    {err}");
 				}
 			}
-			std::process::exit(global.error_code())
+			global.exit(global.error_code())
 		}
 
 		if offenders.is_empty() {
-			log::debug!(
-				"Checked {} crate{}: all formatted",
-				meta.packages.len(),
-				plural(meta.packages.len())
-			);
+			if global.summary_only() {
+				Self::print_summary(0, 0, self.fix, None, global);
+			} else {
+				global.write_stats(0, 0, 0);
+			}
+			log::debug!("Checked {} crate{}: all formatted", pkgs.len(), plural(pkgs.len()));
 			return
 		}
 
 		let mut fixed = 0;
-		println!(
-			"Found {} crate{} with unformatted features:",
-			global.red(&offenders.len().to_string()),
-			plural(offenders.len())
-		);
+		let details = global.details();
+		if details {
+			out::println!(
+				"Found {} crate{} with unformatted features:",
+				global.error(&offenders.len().to_string()),
+				plural(offenders.len())
+			);
+		}
 		for (path, pkg, fixer) in offenders.iter_mut() {
 			// trim of the allowed_dir, if possible:
 			let psuffix =
 				self.print_paths.then(|| format!(" {}", path.display())).unwrap_or_default();
-			println!("  {}{}", global.bold(pkg), psuffix);
-
-			if !self.fix {
+			if details {
+				out::println!("  {}{}", global.emphasis(pkg), psuffix);
+			}
+			global.annotate(Finding {
+				issue: None,
+				severity: Severity::Deny,
+				file: path.clone(),
+				message: format!("Features of crate '{}' are not formatted", pkg),
+			});
+
+			if self.run_mode() == RunMode::Check {
 				continue
 			}
 
@@ -188,32 +315,137 @@ impl FormatFeaturesCmd {
 				continue
 			}
 
-			fixer.save().unwrap();
+			if let Err(err) = global.save_fixer(fixer) {
+				out::eprintln!("{}", global.error(&err));
+				serve::exit(1)
+			}
 			fixed += 1;
 		}
 
-		if self.fix {
+		let fix_cmd = self.fix_command(offenders.iter().map(|(_, pkg, _)| pkg.as_str()));
+		let code = self.exit_code(offenders.len() - fixed, global);
+		if !details {
+			Self::print_summary(offenders.len(), fixed, self.fix, Some(&fix_cmd), global);
+			global.exit(code)
+		}
+		global.write_stats(offenders.len(), fixed, 0);
+		if self.run_mode() == RunMode::Fix {
 			if fixed == offenders.len() {
-				println!(
+				out::println!(
 					"Formatted {} crate{} (all fixed).",
-					global.green(&fixed.to_string()),
+					global.success(&fixed.to_string()),
 					plural(fixed)
 				);
 			} else {
-				println!(
+				out::println!(
 					"Formatted {} crate{} ({} could not be fixed).",
-					global.green(&fixed.to_string()),
+					global.success(&fixed.to_string()),
 					plural(fixed),
-					global.red(&(offenders.len() - fixed).to_string())
+					global.error(&(offenders.len() - fixed).to_string())
 				);
 			}
+		} else if let Some(hint) =
+			global.fix_hint(|| format!("Run again with `{}` to format them.", fix_cmd))
+		{
+			out::println!("{}", hint);
+		}
+
+		global.exit(code)
+	}
+
+	pub fn run_mode(&self) -> RunMode {
+		if self.fix {
+			RunMode::Fix
+		} else {
+			RunMode::Check
+		}
+	}
+
+	/// The exit code after `unformatted` crates were neither formatted nor fixed.
+	fn exit_code(&self, unformatted: usize, global: &GlobalArgs) -> i32 {
+		match self.run_mode() {
+			_ if unformatted == 0 => 0,
+			RunMode::Check if self.check_only_exit_zero => 0,
+			RunMode::Check | RunMode::Fix => global.error_code(),
+		}
+	}
+
+	fn print_summary(
+		issues: usize,
+		fixed: usize,
+		fix: bool,
+		fix_cmd: Option<&FixCommand>,
+		global: &GlobalArgs,
+	) {
+		out::println!(
+			"{}",
+			error_stats(issues, 0, fixed, fix, fix_cmd, global).unwrap_or_default()
+		);
+	}
+
+	/// The command that formats the features of these packages.
+	fn fix_command<'a>(&self, packages: impl Iterator<Item = &'a str>) -> FixCommand {
+		let mut packages = packages.collect::<Vec<_>>();
+		packages.sort();
+		packages.dedup();
+
+		let mut cmd = FixCommand::new("format features", &self.cargo_args)
+			.arg("--packages", packages.join(","));
+		if self.no_workspace {
+			cmd = cmd.flag("--no-workspace");
+		}
+		if self.line_width != 80 {
+			cmd = cmd.arg("--line-width", self.line_width.to_string());
+		}
+		if let Some(modes) = &self.mode_per_feature {
+			let modes = modes
+				.iter()
+				.filter_map(|(f, m)| Some(format!("{}:{}", f, m.to_possible_value()?.get_name())))
+				.collect::<Vec<_>>();
+			cmd = cmd.arg("--mode-per-feature", modes.join(","));
+		}
+		if !self.ignore_feature.is_empty() {
+			cmd = cmd.arg("--ignore-feature", self.ignore_feature.join(","));
+		}
+		if let Some(policy) = self.conflict_resolution.to_possible_value() {
+			if self.conflict_resolution != ConflictResolution::Error {
+				cmd = cmd.arg("--conflict-resolution", policy.get_name());
+			}
+		}
+		cmd.flag("--fix")
+	}
 
-			std::process::exit(0);
-		} else if global.show_hints() {
-			println!("Run again with `--fix` to format them.");
+	/// Format a single manifest without loading the metadata.
+	fn run_single(&self, modes: &Map<String, Vec<Mode>>, global: &GlobalArgs) {
+		let raw = if let Some(path) = &self.file {
+			std::fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))
+		} else {
+			let mut raw = String::new();
+			std::io::stdin()
+				.read_to_string(&mut raw)
+				.map(|_| raw)
+				.map_err(|e| format!("Failed to read stdin: {}", e))
+		};
+		let mut fixer = match raw.and_then(|raw| AutoFixer::from_raw(&raw).map_err(Into::into)) {
+			Ok(fixer) => fixer,
+			Err(err) => {
+				out::eprintln!("{}", global.error(&err));
+				serve::exit(1)
+			},
+		};
+
+		let name = fixer.package_name().unwrap_or_default();
+		if let Err(errs) =
+			fixer.canonicalize_features(&name, modes, self.line_width, self.conflict_resolution)
+		{
+			for err in errs.iter() {
+				out::eprintln!("{err}");
+			}
+			global.exit(global.error_code())
 		}
 
-		std::process::exit(global.error_code())
+		out::print!("{}", fixer.to_string());
 	}
 
 	fn parse_mode_per_feature(&self) -> Map<String, Vec<Mode>> {
@@ -235,14 +467,14 @@ impl FormatFeaturesCmd {
 	fn load_metadata(&self, global: &GlobalArgs) -> Metadata {
 		let mut args = self.cargo_args.clone();
 		if args.workspace {
-			println!("{}", global.yellow("WARNING: --workspace is the default now"));
+			out::println!("{}", global.warning("WARNING: --workspace is the default now"));
 		}
 		args.workspace = !self.no_workspace;
 		match args.load_metadata() {
 			Ok(meta) => meta,
 			Err(err) => {
-				println!("{}", global.red(&err));
-				std::process::exit(1)
+				out::println!("{}", global.error(&err));
+				serve::exit(1)
 			},
 		}
 	}
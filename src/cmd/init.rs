@@ -0,0 +1,158 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Scaffold the config file and a CI job for a workspace.
+
+use super::GlobalArgs;
+use crate::{
+	config::{search_config, ConfigArgs},
+	log,
+};
+use clap::ValueEnum;
+use std::{
+	fs::canonicalize,
+	path::{Path, PathBuf},
+};
+
+/// Where the config file is created, relative to the workspace root.
+const CONFIG_PATH: &str = ".config/zepter.yaml";
+
+/// Create a config file from a preset and optionally a CI job that checks it.
+///
+/// Refuses to overwrite existing files unless `--force` is passed.
+#[derive(Debug, clap::Parser)]
+pub struct InitCmd {
+	/// Cargo manifest path of the workspace.
+	///
+	/// Defaults to the workspace of the current directory.
+	#[clap(long, verbatim_doc_comment)]
+	manifest_path: Option<PathBuf>,
+
+	/// The preset to create the config file from.
+	#[clap(long, value_enum, value_name = "PRESET", default_value_t = Preset::Default)]
+	preset: Preset,
+
+	/// Also create a CI job that runs the `check` workflow of the config file.
+	#[clap(long, value_enum, value_name = "PROVIDER")]
+	ci: Option<CiProvider>,
+
+	/// Overwrite files that already exist.
+	#[clap(long)]
+	force: bool,
+}
+
+/// A config file to start from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preset {
+	/// Check the propagation of `std` and format the features.
+	Default,
+	/// The config of the Polkadot-SDK for runtime crates.
+	Polkadot,
+}
+
+/// Where the CI job runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CiProvider {
+	/// A GitHub Actions workflow in `.github/workflows/zepter.yaml`.
+	Github,
+}
+
+impl Preset {
+	pub fn content(self) -> &'static str {
+		match self {
+			Self::Default => include_str!("../../presets/default.yaml"),
+			Self::Polkadot => include_str!("../../presets/polkadot.yaml"),
+		}
+	}
+}
+
+impl CiProvider {
+	/// The path of the CI job, relative to the workspace root.
+	pub fn path(self) -> &'static str {
+		match self {
+			Self::Github => ".github/workflows/zepter.yaml",
+		}
+	}
+
+	/// The CI job that installs this version of Zepter and runs the `check` workflow.
+	pub fn job(self) -> String {
+		match self {
+			Self::Github => format!(
+				r#"# Checks the feature configuration of the workspace with Zepter.
+# <https://crates.io/crates/zepter>
+name: Zepter
+
+on:
+  pull_request:
+  push:
+    branches: [main, master]
+
+jobs:
+  zepter:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install Zepter
+        run: cargo install zepter --version {} --locked -q
+      - name: Check features
+        run: zepter run check --format github
+"#,
+				env!("CARGO_PKG_VERSION")
+			),
+		}
+	}
+}
+
+impl InitCmd {
+	pub fn run(&self, _global: &GlobalArgs) -> Result<(), String> {
+		let args = ConfigArgs { manifest_path: self.manifest_path.clone(), ..Default::default() };
+		let root = args.locate_workspace()?;
+
+		let mut files = vec![(CONFIG_PATH, self.preset.content().to_string())];
+		if let Some(ci) = self.ci {
+			files.push((ci.path(), ci.job()));
+		}
+		// Check all files first so that nothing is written if one of them exists.
+		if !self.force {
+			if let Ok(existing) = search_config(&root) {
+				return Err(already_exists("Config file", &existing))
+			}
+			if let Some((path, _)) = files.iter().skip(1).find(|(p, _)| root.join(p).exists()) {
+				return Err(already_exists("CI job", &root.join(path)))
+			}
+		}
+
+		for (path, content) in files.iter() {
+			let full = root.join(path);
+			if let Some(dir) = full.parent() {
+				std::fs::create_dir_all(dir)
+					.map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+			}
+			std::fs::write(&full, content)
+				.map_err(|e| format!("Failed to write {}: {}", full.display(), e))?;
+		}
+
+		let preset = self.preset.to_possible_value().expect("Presets are not skipped");
+		println!("Created {} from preset '{}'.", CONFIG_PATH, preset.get_name());
+		if let Some(ci) = self.ci {
+			println!("Created {}.", ci.path());
+		}
+		// The search prefers other locations, so a forced config could be shadowed.
+		if let Ok(used) = search_config(&root) {
+			if canonicalize(root.join(CONFIG_PATH)).map_or(false, |created| created != used) {
+				log::warn!("The existing config file {} takes precedence", used.display());
+			}
+		}
+
+		println!("\nNext steps:");
+		println!("  1. Adjust the features in {} to your workspace.", CONFIG_PATH);
+		println!("  2. Run `zepter run check` to find issues without changing anything.");
+		println!("  3. Run `zepter` to fix them.");
+		println!("  4. Commit the created files.");
+		Ok(())
+	}
+}
+
+fn already_exists(what: &str, path: &Path) -> String {
+	format!("{} {} already exists. Pass `--force` to overwrite it.", what, path.display())
+}
@@ -3,29 +3,79 @@
 
 //! Lint your feature usage by analyzing crate metadata.
 
+pub mod baseline;
+pub mod build_deps;
+pub mod default_feature;
+pub mod default_features_conflict;
+pub mod default_features_consistency;
+pub mod duplicate_deps;
+pub mod feature_docs;
+pub mod git_deps;
 pub mod nostd;
+pub mod optional_deps;
+pub mod path_deps;
+pub mod rules;
+pub mod rust_version;
+pub mod typos;
+pub mod weak_features;
+pub use build_deps::BuildDepFeaturesCmd;
+pub use default_feature::DefaultFeatureCmd;
+pub use default_features_conflict::DefaultFeaturesConflictCmd;
+pub use default_features_consistency::DefaultFeaturesPolicy;
+pub use duplicate_deps::DuplicateDepsCmd;
+pub use feature_docs::FeatureDocsCmd;
+pub use git_deps::GitDepsCmd;
 pub use nostd::*;
+pub use optional_deps::OptionalDepsCmd;
+pub use path_deps::NoPathDepsOutsideWorkspaceCmd;
+pub use rules::RulesCmd;
+pub use rust_version::RustVersionCmd;
+pub use typos::FeatureTyposCmd;
+pub use weak_features::WeakFeaturesCmd;
+// Moved to `crate::graph`, kept here for backwards compatibility.
+pub use crate::graph::CrateAndFeature;
 
 use crate::{
 	autofix::*,
-	cmd::{parse_key_val, resolve_dep, RenamedPackage},
+	cmd::{
+		lint::baseline::{Baseline, BaselineIssue, BaselineIssueKind},
+		output::Finding,
+		parse_key_val,
+		progress::Progress,
+		timing, PackageIndex, RenamedPackage,
+	},
+	config::{
+		allow::AllowedFindings,
+		feature_mapping::FeatureMapping,
+		ignore::IgnoredDependencies,
+		only_enables::{Exemption, OnlyEnablesConfig},
+		rules::{Rule, RuleKind},
+		search_config,
+		severity::{IssueKind, Severity, SeverityTable},
+		workflow::{expand_env_vars, WorkflowFile, WORKFLOW_DEFAULT_NAME},
+		CONFIG_ENV,
+	},
 	grammar::*,
-	log,
+	issues, log, out,
 	prelude::*,
 	CrateId,
 };
-use cargo_metadata::{Metadata, Package, PackageId};
+use cargo_metadata::{Metadata, Package};
+use clap::ValueEnum;
 use core::{
 	fmt,
 	fmt::{Display, Formatter},
 };
 use std::{
-	collections::{BTreeMap, BTreeSet, HashMap},
+	collections::{BTreeMap, BTreeSet},
 	fs::canonicalize,
-	path::PathBuf,
+	path::{Path, PathBuf},
 };
 
-use super::GlobalArgs;
+use super::{serve, FixCommand, GlobalArgs};
+
+/// Value of `--features` that takes the features from the config file.
+const CONFIG_FEATURES: &str = "@config";
 
 /// Lint your feature usage by analyzing crate metadata.
 #[derive(Debug, clap::Parser)]
@@ -36,6 +86,7 @@ pub struct LintCmd {
 
 /// Sub-commands of the [Lint](LintCmd) command.
 #[derive(Debug, clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
 pub enum SubCommand {
 	/// Check whether features are properly propagated.
 	PropagateFeature(PropagateFeatureCmd),
@@ -45,9 +96,100 @@ pub enum SubCommand {
 	NeverImplies(NeverImpliesCmd),
 	/// A specific feature is only implied by a specific set of other features.
 	OnlyEnables(OnlyEnablesCmd),
+	/// List the features that directly enable a feature of a crate.
 	WhyEnabled(WhyEnabledCmd),
 	/// Check the crates for sane no-std feature configuration.
 	NoStd(NoStdCmd),
+	/// Find feature references and names that are likely typos.
+	FeatureTypos(FeatureTyposCmd),
+	/// Find path dependencies that point outside of the workspace.
+	NoPathDepsOutsideWorkspace(NoPathDepsOutsideWorkspaceCmd),
+	/// Find dependencies that are listed as normal and as dev dependency.
+	DuplicateDeps(DuplicateDepsCmd),
+	/// Find features that enable features of build-dependencies.
+	BuildDepFeatures(BuildDepFeaturesCmd),
+	/// Find features that are not documented.
+	FeatureDocs(FeatureDocsCmd),
+	/// Find git dependencies that violate the policy of the workspace.
+	GitDeps(GitDepsCmd),
+	/// Check the `rust-version` of all workspace members.
+	RustVersion(RustVersionCmd),
+	/// Find weak feature references to dependencies that are always enabled.
+	WeakFeatures(WeakFeaturesCmd),
+	/// Find crates without an explicit `default` feature.
+	DefaultFeature(DefaultFeatureCmd),
+	/// Find dependencies with `default-features = false` that enable the `default` feature.
+	DefaultFeaturesConflict(DefaultFeaturesConflictCmd),
+	/// Find optional dependencies without a feature of the same name that enables them.
+	OptionalDeps(OptionalDepsCmd),
+	/// Check all forbidden feature implications of the config file in one go.
+	Rules(RulesCmd),
+}
+
+// Restricts the feature graph to one build configuration. Not a doc comment since clap would use
+// it as about of the commands that flatten it.
+#[derive(Debug, clap::Parser)]
+pub struct EnabledFeaturesArgs {
+	/// Only consider feature edges that are reachable from these features.
+	///
+	/// Prunes the feature graph to a concrete build instead of all possible ones. Takes
+	/// `crate/feature` pairs like `node-cli/std,sp-io/default`. The `default` feature also
+	/// covers the dependencies that are always enabled.
+	#[clap(long, value_delimiter = ',', value_name = "CRATE/FEATURE")]
+	pub enabled_features: Vec<String>,
+}
+
+impl EnabledFeaturesArgs {
+	/// Which build is considered, for `zepter run --describe`.
+	pub fn describe(&self) -> Vec<String> {
+		if self.enabled_features.is_empty() {
+			return Vec::new()
+		}
+		vec![format!(
+			"Only the build with {} enabled is considered.",
+			enumerate(&self.enabled_features)
+		)]
+	}
+
+	/// Remove all edges from the `dag` that are not reachable from the enabled features.
+	///
+	/// Returns the `dag` unchanged if no features were passed.
+	pub fn prune(
+		&self,
+		index: &PackageIndex<'_>,
+		dag: Dag<CrateAndFeature>,
+	) -> Result<Dag<CrateAndFeature>, String> {
+		if self.enabled_features.is_empty() {
+			return Ok(dag)
+		}
+
+		let mut starts = Vec::new();
+		for entry in self.enabled_features.iter() {
+			let Some((krate, feature)) = entry.split_once('/') else {
+				return Err(format!(
+					"Invalid enabled feature '{}'. Expected `crate/feature`.",
+					entry
+				))
+			};
+			let pkgs = index.by_name(krate);
+			if pkgs.is_empty() {
+				return Err(format!(
+					"Could not find crate '{}' of enabled feature '{}'",
+					krate, entry
+				))
+			}
+			for pkg in pkgs {
+				if feature != "default" && !pkg.features.contains_key(feature) {
+					return Err(format!("Crate '{}' has no feature '{}'", krate, feature))
+				}
+				starts.push(CrateAndFeature(pkg.id.to_string(), feature.into()));
+			}
+		}
+
+		let reachable = dag.reachable_from(starts);
+		log::debug!("Pruned the feature graph to {} reachable features", reachable.len());
+		Ok(dag.sub(|node| reachable.contains(node)))
+	}
 }
 
 #[derive(Debug, clap::Parser)]
@@ -56,6 +198,10 @@ pub struct WhyEnabledCmd {
 	#[clap(flatten)]
 	cargo_args: super::CargoArgs,
 
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	enabled_features: EnabledFeaturesArgs,
+
 	#[clap(long, short)]
 	package: String,
 
@@ -74,6 +220,13 @@ pub struct OnlyEnablesCmd {
 
 	#[clap(long)]
 	only_enables: String,
+
+	/// A crate or a single feature of it that may enable the feature anyway.
+	///
+	/// Like `test-utils` or `mock/runtime-benchmarks`. Can be passed multiple times and extends
+	/// the `only-enables.allow` list of the config file.
+	#[clap(long, value_name = "CRATE[/FEATURE]", verbatim_doc_comment)]
+	allow: Vec<Exemption>,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -113,21 +266,21 @@ pub struct NeverImpliesCmd {
 	#[clap(long, required = true)]
 	stays_disabled: String,
 
-	/// Show the source location of crates in the output.
-	#[clap(long)]
-	show_source: bool,
-
-	/// Show the version of the crates in the output.
-	#[clap(long)]
-	show_version: bool,
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	display: super::DisplayArgs,
 
 	/// Delimiter for rendering dependency paths.
 	#[clap(long, default_value = " -> ")]
 	path_delimiter: String,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	enabled_features: EnabledFeaturesArgs,
 }
 
 /// Verifies that rust features are properly propagated.
-#[derive(Debug, clap::Parser)]
+#[derive(Debug, Clone, clap::Parser)]
 pub struct PropagateFeatureCmd {
 	#[allow(missing_docs)]
 	#[clap(flatten)]
@@ -137,9 +290,9 @@ pub struct PropagateFeatureCmd {
 	#[clap(long, alias = "feature", value_delimiter = ',', required = true)]
 	features: Vec<String>,
 
-	/// The packages to check. If empty, all packages are checked.
-	#[clap(long, short, num_args(0..))]
-	packages: Vec<String>,
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	packages: super::PackageSpec,
 
 	/// The auto-fixer will enables the feature of the dependencies as non-optional.
 	///
@@ -173,13 +326,9 @@ pub struct PropagateFeatureCmd {
 	#[clap(long, value_name = "KIND/MUTE_SETTING", value_parser = parse_key_val::<String, String>, value_delimiter = ',', verbatim_doc_comment, default_value = "normal:check,dev:check,build:check")]
 	dep_kinds: Option<Vec<(String, String)>>,
 
-	/// Show crate versions in the output.
-	#[clap(long)]
-	show_version: bool,
-
-	/// Show crate manifest paths in the output.
-	#[clap(long)]
-	show_path: bool,
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	display: super::DisplayArgs,
 
 	#[allow(missing_docs)]
 	#[clap(flatten)]
@@ -195,6 +344,85 @@ pub struct PropagateFeatureCmd {
 	/// Fix only issues with this package as feature source.
 	#[clap(long)]
 	fix_package: Option<String>,
+
+	/// Only report issues that are not already listed in this baseline file.
+	#[clap(long, value_name = "FILE")]
+	baseline: Option<PathBuf>,
+
+	/// Write all found issues into this baseline file.
+	///
+	/// The command does not fail in this mode. Use `--baseline` in subsequent runs to only fail on
+	/// new issues.
+	#[clap(long, value_name = "FILE", verbatim_doc_comment)]
+	write_baseline: Option<PathBuf>,
+
+	/// Stop the analysis once this many issues were found.
+	///
+	/// Useful on first adoption in large workspaces to get quick feedback. Issues are still fixed
+	/// up to this point if `--fix` is passed.
+	#[clap(long, value_name = "N", conflicts_with = "write_baseline", verbatim_doc_comment)]
+	max_issues: Option<usize>,
+
+	/// Only check crates that are dependencies of this crate, and the crate itself.
+	///
+	/// Restricts the analysis to the propagation chains that pass through it, like a runtime
+	/// crate. Dependency kinds that are ignored by `--dep-kinds` are not followed.
+	#[clap(long, value_name = "CRATE", verbatim_doc_comment)]
+	only_enabled_via: Option<String>,
+
+	/// Treat a differently named feature of a dependency as the checked feature.
+	///
+	/// Passing `--feature-mapping x/runtime-benchmarks:bench` accepts `x/bench` as propagation
+	/// of `runtime-benchmarks` to `x` and the fixer inserts `x/bench` instead. Extends the
+	/// `feature-mapping` of the config file.
+	#[clap(long, value_name = "CRATE/FEATURE:MAPPED", value_parser = parse_key_val::<String, String>, value_delimiter = ',', verbatim_doc_comment)]
+	feature_mapping: Option<Vec<(String, String)>>,
+
+	/// How the fixer resolves entries that only differ by a `?`, like `foo/std` and `foo?/std`.
+	///
+	/// Applies to the checked feature of every crate that gets fixed. `error` leaves them to
+	/// `format features`, which reports them.
+	#[clap(long, value_enum, value_name = "POLICY", default_value_t = ConflictResolution::Error, verbatim_doc_comment)]
+	conflict_resolution: ConflictResolution,
+
+	/// Respect the `features` of `[workspace.dependencies]` in the root manifest.
+	///
+	/// They are enabled for every member that inherits the dependency with `workspace = true`,
+	/// so the member does not need to propagate them.
+	#[clap(long, verbatim_doc_comment)]
+	workspace_features: bool,
+
+	/// Report the issues of path dependencies outside of the workspace as warnings.
+	///
+	/// They are checked even with `--left-side-outside-workspace=ignore`, marked as external and
+	/// never fixed. Meant for repos that span several checkouts.
+	#[clap(long, verbatim_doc_comment)]
+	report_external_path_deps: bool,
+
+	/// Check these path dependencies as if they were published.
+	///
+	/// Their dev-dependencies that only have a path are ignored since cargo removes them when
+	/// publishing. Features of `[workspace.dependencies]` no longer count as always enabled for
+	/// them, since crates outside of the workspace do not get them.
+	#[clap(long, value_name = "CRATE", value_delimiter = ',', verbatim_doc_comment)]
+	assume_published: Vec<String>,
+
+	/// Check all path dependencies as if they were published, see `--assume-published`.
+	#[clap(long)]
+	all_path_deps: bool,
+
+	/// Check that all workspace members use the same `default-features` for a dependency.
+	///
+	/// Cargo enables the default features of a dependency for the whole workspace as soon as one
+	/// member does. Only plain `[dependencies]` are compared.
+	#[clap(long, verbatim_doc_comment)]
+	check_default_features_consistency: bool,
+
+	/// Which `default-features` the fixer aligns inconsistent members to.
+	///
+	/// Requires `--check-default-features-consistency`.
+	#[clap(long, value_enum, value_name = "POLICY", default_value_t = DefaultFeaturesPolicy::Majority, requires = "check_default_features_consistency", verbatim_doc_comment)]
+	default_features_policy: DefaultFeaturesPolicy,
 }
 
 /// Can be used to change the default error reporting behaviour of a lint.
@@ -245,6 +473,19 @@ impl From<DepKind> for cargo_metadata::DependencyKind {
 	}
 }
 
+impl TryFrom<cargo_metadata::DependencyKind> for DepKind {
+	type Error = String;
+
+	fn try_from(kind: cargo_metadata::DependencyKind) -> Result<Self, Self::Error> {
+		match kind {
+			cargo_metadata::DependencyKind::Normal => Ok(Self::Normal),
+			cargo_metadata::DependencyKind::Development => Ok(Self::Dev),
+			cargo_metadata::DependencyKind::Build => Ok(Self::Build),
+			_ => Err(format!("Unknown dependency kind '{:?}'", kind)),
+		}
+	}
+}
+
 impl core::str::FromStr for IgnoreSetting {
 	type Err = String;
 
@@ -261,103 +502,163 @@ impl LintCmd {
 	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		match &self.subcommand {
 			SubCommand::PropagateFeature(cmd) => cmd.run(global),
-			SubCommand::NeverEnables(cmd) => {
-				cmd.run(global);
-				Ok(())
-			},
+			SubCommand::NeverEnables(cmd) => cmd.run(global),
+			SubCommand::NeverImplies(cmd) => cmd.run(global),
+			SubCommand::WhyEnabled(cmd) => cmd.run(global),
+			SubCommand::OnlyEnables(cmd) => cmd.run(global),
+			SubCommand::NoStd(cmd) => cmd.run(global),
+			SubCommand::FeatureTypos(cmd) => cmd.run(global),
+			SubCommand::NoPathDepsOutsideWorkspace(cmd) => cmd.run(global),
+			SubCommand::DuplicateDeps(cmd) => cmd.run(global),
+			SubCommand::BuildDepFeatures(cmd) => cmd.run(global),
+			SubCommand::FeatureDocs(cmd) => cmd.run(global),
+			SubCommand::GitDeps(cmd) => cmd.run(global),
+			SubCommand::RustVersion(cmd) => cmd.run(global),
+			SubCommand::WeakFeatures(cmd) => cmd.run(global),
+			SubCommand::DefaultFeature(cmd) => cmd.run(global),
+			SubCommand::DefaultFeaturesConflict(cmd) => cmd.run(global),
+			SubCommand::OptionalDeps(cmd) => cmd.run(global),
+			SubCommand::Rules(cmd) => cmd.run(global),
+		}
+	}
+
+	/// Describe what the lint enforces, for `zepter run --describe`.
+	pub(crate) fn describe(&self) -> Option<Vec<String>> {
+		match &self.subcommand {
+			SubCommand::PropagateFeature(cmd) => Some(cmd.describe()),
+			SubCommand::NeverEnables(cmd) => Some(vec![Rule {
+				precondition: cmd.precondition.clone(),
+				stays_disabled: cmd.stays_disabled.clone(),
+				kind: RuleKind::NeverEnables,
+			}
+			.describe()]),
 			SubCommand::NeverImplies(cmd) => {
-				cmd.run(global);
-				Ok(())
-			},
-			SubCommand::WhyEnabled(cmd) => {
-				cmd.run(global);
-				Ok(())
+				let rule = Rule {
+					precondition: cmd.precondition.clone(),
+					stays_disabled: cmd.stays_disabled.clone(),
+					kind: RuleKind::NeverImplies,
+				};
+				let mut lines = vec![rule.describe()];
+				lines.extend(cmd.enabled_features.describe());
+				Some(lines)
 			},
 			SubCommand::OnlyEnables(cmd) => {
-				cmd.run(global);
-				Ok(())
+				let mut lines = vec![format!(
+					"Only feature `{}` enables `{}` on dependencies.",
+					cmd.precondition, cmd.only_enables
+				)];
+				if !cmd.allow.is_empty() {
+					let allow = cmd.allow.iter().map(ToString::to_string).collect::<Vec<_>>();
+					lines.push(format!("{} may enable it anyway.", enumerate(&allow)));
+				}
+				Some(lines)
 			},
-			SubCommand::NoStd(cmd) => cmd.run(global),
+			SubCommand::Rules(cmd) => Some(cmd.describe()),
+			_ => None,
 		}
 	}
-}
 
-#[derive(Clone, Eq, Ord, PartialEq, PartialOrd, Debug, Hash)]
-pub struct CrateAndFeature(pub String, pub String);
-
-impl Display for CrateAndFeature {
-	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-		write!(f, "{}/{}", self.0, self.1)
+	/// Whether the lint modifies manifests, which is the case with `--fix`.
+	pub(crate) fn writes_manifests(&self) -> bool {
+		match &self.subcommand {
+			SubCommand::PropagateFeature(cmd) => cmd.fixer_args.enable,
+			SubCommand::NoStd(cmd) => cmd.writes_manifests(),
+			SubCommand::FeatureTypos(cmd) => cmd.fixer_args.enable,
+			SubCommand::NoPathDepsOutsideWorkspace(cmd) => cmd.fixer_args.enable,
+			SubCommand::DuplicateDeps(cmd) => cmd.fixer_args.enable,
+			SubCommand::BuildDepFeatures(cmd) => cmd.fixer_args.enable,
+			SubCommand::FeatureDocs(cmd) => cmd.fixer_args.enable,
+			SubCommand::GitDeps(cmd) => cmd.fixer_args.enable,
+			SubCommand::RustVersion(cmd) => cmd.fixer_args.enable,
+			SubCommand::WeakFeatures(cmd) => cmd.fixer_args.enable,
+			SubCommand::DefaultFeature(cmd) => cmd.fixer_args.enable,
+			SubCommand::DefaultFeaturesConflict(cmd) => cmd.fixer_args.enable,
+			SubCommand::OptionalDeps(cmd) => cmd.fixer_args.enable,
+			SubCommand::NeverEnables(_) |
+			SubCommand::NeverImplies(_) |
+			SubCommand::OnlyEnables(_) |
+			SubCommand::WhyEnabled(_) |
+			SubCommand::Rules(_) => false,
+		}
 	}
 }
 
 impl NeverImpliesCmd {
-	pub fn run(&self, _global: &GlobalArgs) {
+	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
 		log::info!(
 			"Checking that feature '{}' never implies '{}'",
 			self.precondition,
 			self.stays_disabled
 		);
-		let pkgs = &meta.packages;
-		let dag = build_feature_dag(&meta, pkgs);
+		let index = PackageIndex::new(&meta);
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let dag = without_ignored(&meta, build_feature_dag(&index, &meta.packages), &ignored);
+		let dag = self.enabled_features.prune(&index, dag)?;
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+		let finding = ["never-implies", self.precondition.as_str(), self.stays_disabled.as_str()];
+		// Most nodes share their descendants, so the path is only searched once we know it exists.
+		let reachability = dag.reachability();
+		let disabled =
+			|CrateAndFeature(_, enabled): &CrateAndFeature| enabled == &self.stays_disabled;
 
 		for CrateAndFeature(pkg, feature) in dag.lhs_nodes() {
 			let crate_and_feature = CrateAndFeature(pkg.clone(), feature.clone());
-			if feature == &self.precondition {
-				let Some(path) = dag
-					.reachable_predicate(&crate_and_feature, |CrateAndFeature(_, enabled)| {
-						enabled == &self.stays_disabled
-					})
-				else {
+			let allows = index.get(pkg).map_or(false, |p| allowed.allows(&p.name, &finding));
+			if feature == &self.precondition && !allows {
+				if !reachability.any_reachable(&crate_and_feature, disabled) {
+					continue
+				}
+				let Some(path) = dag.reachable_predicate(&crate_and_feature, disabled) else {
 					continue
 				};
 
-				// TODO cleanup this cluster fuck
+				// Unresolved crates are only known by name.
 				let lookup = |id: &str| {
-					pkgs.iter()
-						.find(|pkg| {
-							pkg.id.to_string().split(' ').next().unwrap() ==
-								id.split(' ').next().unwrap()
-						})
+					index
+						.get(id)
+						.or_else(|| index.by_name(id).first().copied())
 						.unwrap_or_else(|| panic!("Could not find crate '{id}' in the metadata."))
 				};
 
 				let delimiter = self.path_delimiter.replace("\\n", "\n").replace("\\t", "\t");
+				let renames = dependency_renames(&index, &meta.packages);
 				let mut out = String::new();
-				let mut is_first = true;
+				let mut prev: Option<CrateId> = None;
 
 				path.for_each(|CrateAndFeature(id, feature)| {
 					let krate = lookup(id);
-					if !is_first {
-						out.push_str(&delimiter);
-					}
-					is_first = false;
-					out.push_str(&format!("{}/{}", krate.name, feature));
-					if self.show_version {
-						out.push_str(&format!(" v{}", krate.version));
-					}
-					if self.show_source {
-						if let Some(source) = krate.source.as_ref() {
-							out.push_str(&format!(" ({})", source.repr));
-						} else {
-							out.push_str(" (local)");
-						}
+					let rename = match prev.replace(id.clone()) {
+						Some(prev) => {
+							out.push_str(&delimiter);
+							renames.get(&(prev, id.clone()))
+						},
+						None => None,
+					};
+					out.push_str(&format!("{}/{}", rename.unwrap_or(&krate.name), feature));
+					if rename.is_some() {
+						out.push_str(&format!(" (renamed from {})", krate.name));
 					}
+					out.push_str(&self.display.details(krate));
 				});
-				println!(
-					"Feature '{}' implies '{}' via path:\n  {}",
-					self.precondition, self.stays_disabled, out
+				out::println!(
+					"Feature '{}' implies '{}' via path:{}\n  {}",
+					self.precondition,
+					self.stays_disabled,
+					global.code(&issues::NEVER_IMPLIES),
+					out
 				);
 
-				std::process::exit(0);
+				serve::exit(0);
 			}
 		}
+		Ok(())
 	}
 }
 
 impl NeverEnablesCmd {
-	pub fn run(&self, _global: &GlobalArgs) {
+	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
 		log::info!(
 			"Checking that feature {:?} never enables {:?}",
@@ -365,14 +666,20 @@ impl NeverEnablesCmd {
 			self.stays_disabled
 		);
 		let pkgs = &meta.packages;
+		let index = PackageIndex::new(&meta);
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let allows = |krate: &str, dep: &str| {
+			allowed.allows(krate, &["never-enables", &self.precondition, &self.stays_disabled, dep])
+		};
 		// (Crate -> dependencies) that invalidate the assumption.
 		let mut offenders = BTreeMap::<CrateId, BTreeSet<RenamedPackage>>::new();
 
-		for lhs in pkgs.iter() {
+		for lhs in pkgs.iter().filter(|p| !ignored.contains(&p.name)) {
 			let Some(enabled) = lhs.features.get(&self.precondition) else { continue };
 
 			// TODO do the same in other command.
-			if enabled.contains(&self.stays_disabled) {
+			if enabled.contains(&self.stays_disabled) && !allows(&lhs.name, &lhs.name) {
 				offenders.entry(lhs.id.to_string()).or_default().insert(RenamedPackage::new(
 					(*lhs).clone(),
 					None,
@@ -387,168 +694,490 @@ impl NeverEnablesCmd {
 			}
 
 			for rhs in lhs.dependencies.iter() {
-				let Some(rhs) = resolve_dep(lhs, rhs, &meta) else { continue };
+				let Some(rhs) = index.resolve_dep(lhs, rhs) else { continue };
+				if ignored.contains(&rhs.pkg.name) || allows(&lhs.name, &rhs.name()) {
+					continue
+				}
 
 				if enabled.contains(&format!("{}/{}", rhs.name(), self.stays_disabled)) {
 					offenders.entry(lhs.id.to_string()).or_default().insert(rhs);
 				}
 			}
 		}
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
 
 		for (lhs, rhss) in offenders {
 			// TODO hack
-			println!(
+			out::println!(
 				"crate {:?}\n  feature {:?}",
 				lhs.split(' ').next().unwrap(),
 				self.precondition
 			);
 			// TODO support multiple left/right side features.
-			println!("    enables feature {:?} on dependencies:", self.stays_disabled);
+			out::println!(
+				"    enables feature {:?} on dependencies:{}",
+				self.stays_disabled,
+				global.code(&issues::NEVER_ENABLES)
+			);
 
 			for rhs in rhss {
-				match &rhs.rename {
-					Some(_) => {
-						println!("      {} (renamed from {})", rhs.pkg.name, rhs.name());
-					},
-					None => {
-						println!("      {}", rhs.name());
-					},
-				}
+				out::println!("      {}", rhs.display_name());
 			}
 		}
+		Ok(())
 	}
 }
 
 impl PropagateFeatureCmd {
+	/// What this lint enforces, for `zepter run --describe`.
+	pub(crate) fn describe(&self) -> Vec<String> {
+		let n = self.features.len();
+		let mut lines = vec![format!(
+			"Feature{} {} must be propagated to all dependencies that have {}.",
+			plural(n),
+			enumerate(&self.features),
+			if n == 1 { "it" } else { "them" }
+		)];
+		lines.extend(self.packages.describe());
+		if let Some(root) = &self.only_enabled_via {
+			lines.push(format!("Only `{}` and its dependencies are checked.", root));
+		}
+
+		if self.left_side_feature_missing == MuteSetting::Ignore {
+			lines.push(format!("A crate does not need to have the feature{} itself.", plural(n)));
+		}
+		if self.left_side_outside_workspace == MuteSetting::Ignore {
+			lines.push("Crates outside of the workspace are not checked.".into());
+		}
+		for (kind, setting) in self.parse_dep_kinds().unwrap_or_default() {
+			if setting == IgnoreSetting::Ignore {
+				let kind = match kind {
+					DepKind::Normal => "Normal dependencies",
+					DepKind::Dev => "Dev-dependencies",
+					DepKind::Build => "Build-dependencies",
+				};
+				lines.push(format!("{} are not checked.", kind));
+			}
+		}
+		for (feature, dep) in self.feature_enables_dep.iter().flatten() {
+			lines.push(format!(
+				"Feature `{}` enables `{}` as non-optional dependency.",
+				feature, dep
+			));
+		}
+		for (from, to) in self.ignore_missing_propagate.iter().flatten() {
+			lines.push(format!("`{}` does not need to propagate to `{}`.", from, to));
+		}
+		for (from, to) in self.feature_mapping.iter().flatten() {
+			let krate = from.split_once('/').map_or(from.as_str(), |(krate, _)| krate);
+			lines.push(format!("`{}` is satisfied by `{}/{}`.", from, krate, to));
+		}
+		if self.workspace_features {
+			lines.push("Features of `[workspace.dependencies]` count as always enabled.".into());
+		}
+		if self.report_external_path_deps {
+			lines.push("Path dependencies outside of the workspace are only reported.".into());
+		}
+		if self.check_default_features_consistency {
+			lines.push(match self.default_features_policy {
+				DefaultFeaturesPolicy::Majority =>
+					"Dependencies use the same `default-features` in all workspace members.".into(),
+				DefaultFeaturesPolicy::Enabled =>
+					"Dependencies with inconsistent `default-features` must enable them.".into(),
+				DefaultFeaturesPolicy::Disabled =>
+					"Dependencies with inconsistent `default-features` must disable them.".into(),
+			});
+		}
+		if self.all_path_deps {
+			lines.push("All path dependencies are checked as if they were published.".into());
+		} else if !self.assume_published.is_empty() {
+			lines.push(format!(
+				"{} {} checked as if published.",
+				enumerate(&self.assume_published),
+				if self.assume_published.len() == 1 { "is" } else { "are" }
+			));
+		}
+		lines
+	}
+
 	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.load_metadata()?;
-		let dag = build_feature_dag(&meta, &meta.packages);
+		match self.config_workflow() {
+			Some(workflow) => self
+				.with_config(meta.workspace_root.as_std_path(), workflow)?
+				.check(global, meta),
+			None => self.check(global, meta),
+		}
+	}
+
+	/// The workflow to take the features from, if `--features @config[:WORKFLOW]` was passed.
+	fn config_workflow(&self) -> Option<&str> {
+		let [features] = self.features.as_slice() else { return None };
+		match features.strip_prefix(CONFIG_FEATURES)? {
+			"" => Some(WORKFLOW_DEFAULT_NAME),
+			rest => rest.strip_prefix(':'),
+		}
+	}
+
+	/// Take the features and mute settings from the `lint propagate-feature` step of `workflow`.
+	///
+	/// All other arguments, like `--fix` or `--packages`, are kept from the command line. The
+	/// config file is the one of [`CONFIG_ENV`] when run as a workflow step.
+	fn with_config(&self, root: &Path, workflow: &str) -> Result<Self, String> {
+		let path = match std::env::var_os(CONFIG_ENV) {
+			Some(path) if !path.is_empty() => PathBuf::from(path),
+			_ => search_config(root).map_err(|searched| {
+				let searched = searched.iter().map(|p| p.display().to_string()).collect::<Vec<_>>();
+				format!(
+					"Could not find a config file for `--features {}` in any of: {}",
+					CONFIG_FEATURES,
+					searched.join(", ")
+				)
+			})?,
+		};
+		let steps = WorkflowFile::from_path(&path)?
+			.workflow(workflow)
+			.ok_or_else(|| format!("Unknown workflow '{}' in {}", workflow, path.display()))?
+			.steps;
+
+		let env = |name: &str| std::env::var(name).ok();
+		let mut found = Vec::new();
+		for step in steps.iter() {
+			let args = step
+				.args
+				.iter()
+				.map(|a| expand_env_vars(a, env))
+				.collect::<Result<Vec<_>, _>>()?;
+			let Ok(super::Command { subcommand: Some(super::SubCommand::Lint(lint)), .. }) =
+				<super::Command as clap::Parser>::try_parse_from(
+					std::iter::once("zepter".into()).chain(args),
+				)
+			else {
+				continue
+			};
+			if let LintCmd { subcommand: SubCommand::PropagateFeature(cmd) } = *lint {
+				found.push(cmd);
+			}
+		}
+		let [step] = <[_; 1]>::try_from(found).map_err(|found| {
+			format!(
+				"Workflow '{}' has {} `lint propagate-feature` steps, but `--features {}` needs exactly one",
+				workflow,
+				found.len(),
+				CONFIG_FEATURES
+			)
+		})?;
+		log::debug!(
+			"Using the features {} of workflow '{}' in {}",
+			step.features.join(", "),
+			workflow,
+			path.display()
+		);
+
+		Ok(Self {
+			features: step.features,
+			feature_enables_dep: step.feature_enables_dep,
+			left_side_feature_missing: step.left_side_feature_missing,
+			ignore_missing_propagate: step.ignore_missing_propagate,
+			left_side_outside_workspace: step.left_side_outside_workspace,
+			dep_kinds: step.dep_kinds,
+			feature_mapping: step.feature_mapping,
+			..self.clone()
+		})
+	}
+
+	fn check(&self, global: &GlobalArgs, meta: Metadata) -> Result<(), String> {
+		let index = PackageIndex::new(&meta)
+			.assume_published(&self.assume_published, self.all_path_deps)?;
+		let (mut dag, malformed) = try_build_feature_dag(&index, &meta.packages);
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+		if self.workspace_features {
+			add_workspace_features(&index, &mut dag)?;
+		}
+		if !malformed.is_empty() && !global.summary_only() {
+			out::println!("Malformed feature references:");
+			for error in malformed.iter() {
+				out::println!("  {}", error);
+			}
+		}
+		let baseline = match &self.baseline {
+			Some(path) => Baseline::from_path(path)?,
+			None => Baseline::default(),
+		};
+		let mut found = Baseline::default();
+		let severity = SeverityTable::from_workspace(meta.workspace_root.as_std_path())?;
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let mapping = self.feature_mapping(meta.workspace_root.as_std_path())?;
+		let mut to_check = self.packages.select(&meta)?;
+		if let Some(root) = &self.only_enabled_via {
+			let via = self.crates_enabled_via(&index, root)?;
+			to_check.retain(|pkg| via.contains(&pkg.id.repr));
+		}
+		if to_check.is_empty() {
+			return Err("No packages selected".into())
+		}
+
+		// Checked first since the feature checks exit on the first failing feature.
+		let mut inconsistent = false;
+		if self.check_default_features_consistency {
+			let severity = severity.get(IssueKind::DefaultFeaturesConsistency);
+			let stats = default_features_consistency::check(
+				&index,
+				self.default_features_policy,
+				self.fixer_args.enable,
+				severity,
+				&ignored,
+				&allowed,
+				global,
+			)?;
+			let (errors, warnings) =
+				if severity == Severity::Warn { (0, stats.issues) } else { (stats.issues, 0) };
+			let fix_cmd = self.consistency_fix_command();
+			if let Some(e) = error_stats(
+				errors,
+				warnings,
+				stats.fixes,
+				self.fixer_args.enable,
+				Some(&fix_cmd),
+				global,
+			) {
+				out::println!("{}", e);
+			}
+			inconsistent = errors > stats.fixes;
+		}
+
+		let mut reported = 0;
 
 		for feature in self.features.iter() {
-			self.run_feature(&meta, &dag, feature.clone(), global);
+			if self.max_issues.map_or(false, |max| reported >= max) {
+				break
+			}
+			self.run_feature(
+				&index,
+				&dag,
+				&to_check,
+				feature.clone(),
+				&baseline,
+				&mut found,
+				&severity,
+				&ignored,
+				&allowed,
+				&mapping,
+				&mut reported,
+				global,
+			)?;
 		}
 
+		if let Some(path) = &self.write_baseline {
+			found.to_path(path)?;
+			if !global.summary_only() {
+				out::println!(
+					"Wrote {} issue{} to baseline {}.",
+					found.len(),
+					plural(found.len()),
+					path.display()
+				);
+			}
+		}
+
+		if !malformed.is_empty() && self.write_baseline.is_none() {
+			let s = plural(malformed.len());
+			return Err(format!("Found {} malformed feature reference{s}.", malformed.len()))
+		}
+		if inconsistent {
+			global.exit(global.error_code());
+		}
 		Ok(())
 	}
 
+	/// Ids of all crates that `root` depends on, including itself.
+	fn crates_enabled_via(
+		&self,
+		index: &PackageIndex<'_>,
+		root: &str,
+	) -> Result<BTreeSet<CrateId>, String> {
+		let roots = index.by_name(root);
+		if roots.is_empty() {
+			return Err(format!("Crate '{}' of `--only-enabled-via` not found", root))
+		}
+		let dep_kinds = self.parse_dep_kinds()?;
+		let dag = index.crate_dag(|dep| {
+			DepKind::try_from(dep.kind)
+				.ok()
+				.and_then(|kind| dep_kinds.get(&kind))
+				.map_or(true, |mute| mute == &IgnoreSetting::Check)
+		});
+
+		let via = dag.reachable_from(roots.iter().map(|pkg| pkg.id.repr.clone()));
+		log::debug!("Checking {} crate{} enabled via '{}'", via.len(), plural(via.len()), root);
+		Ok(via)
+	}
+
+	#[allow(clippy::too_many_arguments)]
 	fn run_feature(
 		&self,
-		meta: &Metadata,
+		index: &PackageIndex<'_>,
 		dag: &Dag<CrateAndFeature>,
+		to_check: &[&Package],
 		feature: String,
+		baseline: &Baseline,
+		found: &mut Baseline,
+		severity: &SeverityTable,
+		ignored: &IgnoredDependencies,
+		allowed: &AllowedFindings,
+		mapping: &FeatureMapping,
+		reported: &mut usize,
 		global: &GlobalArgs,
-	) {
+	) -> Result<(), String> {
+		let meta = index.meta();
 		// Allowed dir that we can write to.
 		let allowed_dir = canonicalize(meta.workspace_root.as_std_path()).unwrap();
 
-		let pkgs = meta.packages.iter().collect::<Vec<_>>();
-		let mut to_check = pkgs.clone();
-		if !self.packages.is_empty() {
-			to_check =
-				pkgs.iter().filter(|pkg| self.packages.contains(&pkg.name)).cloned().collect();
-		}
-		if to_check.is_empty() {
-			panic!("No packages found: {:?}", self.packages);
-		}
-
-		let lookup = |id: &str| {
-			let id = PackageId { repr: id.to_string() }; // TODO optimize
-			pkgs.iter()
-				.find(|pkg| pkg.id == id)
-				.unwrap_or_else(|| panic!("Could not find crate {id} in the metadata"))
-		};
+		let mut to_check = to_check.to_vec();
 
-		// (Crate that is not forwarding the feature) -> (Dependency that it is not forwarded to)
-		let mut propagate_missing = BTreeMap::<CrateId, BTreeSet<RenamedPackage>>::new();
 		let ignore_missing_propagate = self.ignore_missing_propagate();
 		let dep_kinds = self.parse_dep_kinds().expect("Parse dependency kinds");
-		// (Crate that missing the feature) -> (Dependency that has it)
-		let mut feature_missing = BTreeMap::<CrateId, BTreeSet<RenamedPackage>>::new();
-
-		for pkg in to_check.iter() {
+		let feature_missing_severity = severity.get(IssueKind::LeftSideMissing);
+		let propagate_missing_severity = severity.get(IssueKind::PropagateMissing);
+		let mut stats = IssueStats::default();
+		let mut stopped = false;
+		// Fixes are only written once all crates were checked.
+		let mut transaction = FixTransaction::default();
+		// Crates with issues that a re-run with `--fix` would address.
+		let mut offenders = BTreeSet::<&str>::new();
+		// Only the edges within a crate, since the multi-hop propagation stays in the dependency.
+		let internal = dag.sub_edges(|CrateAndFeature(a, _), CrateAndFeature(b, _)| a == b);
+		let internal = internal.reachability();
+
+		// Every crate is reported as soon as it was checked to give feedback on large workspaces.
+		to_check.sort_by(|a, b| a.name.cmp(&b.name));
+		let progress = Progress::bar(to_check.len(), format!("Checking feature '{}'", feature));
+		let phase = timing::Phase::start("analysis");
+		for krate in to_check {
+			progress.inc();
 			// TODO that it does not enable other features.
-			let in_workspace = meta.workspace_members.iter().any(|m| m == &pkg.id);
-			if !in_workspace && self.left_side_outside_workspace == MuteSetting::Ignore {
+			let in_workspace = meta.workspace_members.iter().any(|m| m == &krate.id);
+			// Path dependencies outside of the workspace have no source.
+			let external =
+				self.report_external_path_deps && !in_workspace && krate.source.is_none();
+			if !in_workspace && !external && self.left_side_outside_workspace == MuteSetting::Ignore
+			{
 				continue
 			}
-
-			for dep in pkg.dependencies.iter() {
-				let mute = dep_kinds.get(&dep.kind).unwrap_or(&IgnoreSetting::Check);
+			if ignored.contains(&krate.name) {
+				continue
+			}
+			// External crates cannot be fixed from here and therefore never fail the lint.
+			let downgrade = |severity| match severity {
+				Severity::Deny if external => Severity::Warn,
+				severity => severity,
+			};
+			// Dependencies that the crate is not forwarding the feature to.
+			let mut propagate_missing = BTreeSet::<RenamedPackage>::new();
+			// Dependencies that have the feature while the crate is missing it.
+			let mut feature_missing = BTreeSet::<RenamedPackage>::new();
+
+			for dep in krate.dependencies.iter() {
+				let mute = DepKind::try_from(dep.kind)
+					.ok()
+					.and_then(|kind| dep_kinds.get(&kind))
+					.unwrap_or(&IgnoreSetting::Check);
 				if mute == &IgnoreSetting::Ignore {
 					continue
 				}
 				// TODO handle default features.
 				// Resolve the dep according to the metadata.
-				let Some(dep) = resolve_dep(pkg, dep, meta) else {
+				let Some(dep) = index.resolve_dep(krate, dep) else {
 					// Either outside workspace or not resolved, possibly due to not being used at
 					// all because of the target or whatever.
 					continue
 				};
+				if ignored.contains(&dep.pkg.name) {
+					continue
+				}
 
-				if !dep.pkg.features.contains_key(&feature) {
+				// The name of the feature on the dependency side, which can differ for third-party
+				// crates.
+				let dep_feature = mapping.get(&dep.pkg.name, &feature);
+				if !dep.pkg.features.contains_key(dep_feature) {
+					continue
+				}
+				if allowed.allows(&krate.name, &["propagate-feature", &feature, &dep.name()]) {
 					continue
 				}
-				if pkg.features.get(&feature).is_none() {
-					if self.left_side_feature_missing != MuteSetting::Ignore {
-						feature_missing.entry(pkg.id.to_string()).or_default().insert(dep);
+				if krate.features.get(&feature).is_none() {
+					if self.left_side_feature_missing != MuteSetting::Ignore &&
+						feature_missing_severity != Severity::Allow
+					{
+						feature_missing.insert(dep);
 					}
 					continue
 				}
 
 				// TODO check that optional deps are only enabled as optional unless
 				// overwritten with `--feature-enables-dep`.
-				let target = CrateAndFeature(dep.pkg.id.repr.clone(), feature.clone());
-				let want_opt = CrateAndFeature(format!("{}?", &pkg.id), feature.clone());
-				let want_req = CrateAndFeature(pkg.id.repr.clone(), feature.clone());
+				let target = CrateAndFeature(dep.pkg.id.repr.clone(), dep_feature.into());
+				let want_opt = CrateAndFeature(format!("{}?", &krate.id), feature.clone());
+				let want_req = CrateAndFeature(krate.id.repr.clone(), feature.clone());
 
 				if dag.adjacent(&want_opt, &target) || dag.adjacent(&want_req, &target) {
 					// Easy case, all good.
 					continue
 				}
-				let default_entrypoint = CrateAndFeature(pkg.id.repr.clone(), "#entrypoint".into());
+				let default_entrypoint =
+					CrateAndFeature(krate.id.repr.clone(), "#entrypoint".into());
 				// Now the more complicated case where `pkg/F -> dep/G .. -> dep/F`. So to say a
 				// multi-hop internal transitive propagation of the feature on the dependency side.
-				let sub_dag = dag.sub(|CrateAndFeature(p, f)| {
-					(p == &pkg.id.repr && f == "#entrypoint") || (p == &dep.pkg.id.repr)
-				});
-				if let Some(p) = sub_dag.any_path(&default_entrypoint, &target) {
-					let _ = p;
+				let mut enabled = dag
+					.edges
+					.get(&default_entrypoint)
+					.into_iter()
+					.flatten()
+					.filter(|CrateAndFeature(p, _)| p == &dep.pkg.id.repr);
+				if enabled.any(|enabled| internal.reachable(enabled, &target)) {
 					// Easy case, all good.
-					log::debug!(
-						"Reachable from the default entrypoint: {:?} vis {:?}",
-						target,
-						p.0
-					);
+					log::debug!("Reachable from the default entrypoint: {:?}", target);
 					continue
 				}
 
-				if let Some((_, lhs_ignore)) = ignore_missing_propagate
-					.iter()
-					.find(|(c, _)| pkg.id.repr.starts_with(&format!("{} ", c.0)) && c.1 == feature)
-				{
+				if let Some((_, lhs_ignore)) = ignore_missing_propagate.iter().find(|(c, _)| {
+					krate.id.repr.starts_with(&format!("{} ", c.0)) && c.1 == feature
+				}) {
 					if lhs_ignore.iter().any(|i| {
-						dep.pkg.id.repr.starts_with(&format!("{} ", i.0)) && i.1 == feature
+						dep.pkg.id.repr.starts_with(&format!("{} ", i.0)) && i.1 == dep_feature
 					}) {
 						continue
 					}
 				}
 
-				propagate_missing.entry(pkg.id.to_string()).or_default().insert(dep);
+				if propagate_missing_severity != Severity::Allow {
+					propagate_missing.insert(dep);
+				}
+			}
+			// Record all issues and then drop the ones that are already known.
+			for (kind, deps) in [
+				(BaselineIssueKind::FeatureMissing, &mut feature_missing),
+				(BaselineIssueKind::PropagateMissing, &mut propagate_missing),
+			] {
+				deps.retain(|dep| {
+					let issue = BaselineIssue::new(kind, &krate.name, &feature, &dep.name());
+					let known = baseline.contains(&issue);
+					found.insert(issue);
+					!known
+				});
+			}
+			if feature_missing.is_empty() && propagate_missing.is_empty() {
+				continue
+			}
+			if !external {
+				offenders.insert(&krate.name);
 			}
-		}
-		let faulty_crates: BTreeSet<CrateId> =
-			propagate_missing.keys().chain(feature_missing.keys()).cloned().collect();
-		let mut faulty_crates =
-			faulty_crates.into_iter().map(|id| (lookup(&id), id)).collect::<Vec<_>>();
-		faulty_crates.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
 
-		let (mut errors, mut fixes) = (0, 0);
-		for (krate, _) in faulty_crates {
-			let in_workspace = meta.workspace_members.iter().any(|m| m == &krate.id);
 			// check if we can modify in allowed_dir
 			let krate_path = canonicalize(krate.manifest_path.clone().into_std_path_buf()).unwrap();
 			// TODO move down
-			let mut fixer = if self.fixer_args.enable {
+			let mut fixer = if self.fixer_args.enable && !external {
 				if krate_path.starts_with(&allowed_dir) ||
 					self.modify_paths.iter().any(|p| krate_path.starts_with(p))
 				{
@@ -565,46 +1194,82 @@ impl PropagateFeatureCmd {
 				None
 			};
 
-			let mut krate_str = format!("'{}'", krate.name);
-			if self.show_path {
-				krate_str.push_str(&format!(" ({})", krate.manifest_path));
+			// Show which version a finding refers to if the name is ambiguous.
+			let display = |pkg: &Package| {
+				self.display.with_version(index.has_multiple_versions(&pkg.name)).details(pkg)
+			};
+			let mut krate_str = format!("'{}'{}", krate.name, display(krate));
+			if external {
+				krate_str.push_str(" (external, not fixable)");
 			}
 
-			println!("crate {krate_str}\n  feature '{}'", feature);
+			let display_dep =
+				|dep: &RenamedPackage| format!("{}{}", dep.display_name(), display(&dep.pkg));
+			let details = global.details();
+			if details {
+				progress.println(format_args!("crate {krate_str}\n  feature '{}'", feature));
+			}
 
-			if let Some(deps) = feature_missing.get(&krate.id.to_string()) {
-				let mut named = deps.iter().map(RenamedPackage::display_name).collect::<Vec<_>>();
+			if !feature_missing.is_empty() {
+				let deps = &feature_missing;
+				let mut named = deps.iter().map(display_dep).collect::<Vec<_>>();
 				named.sort();
-				println!(
-					"    is required by {} dependenc{}:\n      {}",
-					deps.len(),
-					if deps.len() == 1 { "y" } else { "ies" },
-					named.join("\n      "),
-				);
-
+				if details {
+					progress.println(format_args!(
+						"    is required by {} dependenc{}:{}\n      {}",
+						deps.len(),
+						if deps.len() == 1 { "y" } else { "ies" },
+						global.code(&issues::LEFT_SIDE_MISSING),
+						named.join("\n      "),
+					));
+				}
+				global.annotate(Finding::new(
+					&issues::LEFT_SIDE_MISSING,
+					downgrade(feature_missing_severity),
+					&krate_path,
+					format!("feature '{}' is required by: {}", feature, named.join(", ")),
+				));
+
+				let mut fixed = 0;
 				if self.fixer_args.enable &&
-					self.fix_package.as_ref().map_or(true, |p| p == &krate.name) &&
+					!external && self.fix_package.as_ref().map_or(true, |p| p == &krate.name) &&
 					self.left_side_feature_missing == MuteSetting::Fix &&
 					(self.left_side_outside_workspace == MuteSetting::Fix || in_workspace)
 				{
+					let _phase = timing::Phase::start("fixing");
 					let Some(fixer) = fixer.as_mut() else { continue };
 					fixer.add_feature(&feature).unwrap();
 
 					log::info!("Inserted feature '{}' into '{}'", &feature, &krate.name);
-					fixes += 1;
+					fixed += 1;
 				}
 
-				errors += 1;
+				stats.count(downgrade(feature_missing_severity), 1, fixed);
 			}
 
-			if let Some(deps) = propagate_missing.get(&krate.id.to_string()) {
-				let mut named = deps.iter().map(RenamedPackage::display_name).collect::<Vec<_>>();
+			if !propagate_missing.is_empty() {
+				let deps = &propagate_missing;
+				let mut named = deps.iter().map(display_dep).collect::<Vec<_>>();
 				named.sort();
-				println!("    must propagate to:\n      {}", named.join("\n      "));
-
+				if details {
+					progress.println(format_args!(
+						"    must propagate to:{}\n      {}",
+						global.code(&issues::PROPAGATE_MISSING),
+						named.join("\n      ")
+					));
+				}
+				global.annotate(Finding::new(
+					&issues::PROPAGATE_MISSING,
+					downgrade(propagate_missing_severity),
+					&krate_path,
+					format!("feature '{}' must propagate to: {}", feature, named.join(", ")),
+				));
+
+				let mut fixed = 0;
 				if self.fixer_args.enable &&
 					self.fix_package.as_ref().map_or(true, |p| p == &krate.name)
 				{
+					let _phase = timing::Phase::start("fixing");
 					for dep in deps.iter() {
 						let dep_name = dep.name();
 						if !self.fix_dependency.as_ref().map_or(true, |d| d == &dep_name) {
@@ -615,33 +1280,132 @@ impl PropagateFeatureCmd {
 							.feature_enables_dep
 							.as_ref()
 							.map_or(false, |v| v.contains(&(feature.clone(), dep_name.clone())));
-						let opt = if !non_optional && dep.optional { "?" } else { "" };
+						let opt = if !non_optional && is_optional_dep(krate, &dep_name) {
+							"?"
+						} else {
+							""
+						};
+
+						let dep_feature = mapping.get(&dep.pkg.name, &feature);
 
 						fixer
 							.add_to_feature(
 								&feature,
-								format!("{}{}/{}", dep_name, opt, feature).as_str(),
+								format!("{}{}/{}", dep_name, opt, dep_feature).as_str(),
 							)
 							.unwrap();
-						log::info!("Inserted '{dep_name}/{feature}' into '{}'", krate.name);
-						fixes += 1;
+						log::info!("Inserted '{dep_name}/{dep_feature}' into '{}'", krate.name);
+						fixed += 1;
 					}
 				}
-				errors += deps.len();
+				stats.count(downgrade(propagate_missing_severity), deps.len(), fixed);
 			}
-			if let Some(fixer) = fixer.as_mut() {
-				if fixes > 0 {
-					fixer.save().unwrap();
-				}
+			if let Some(mut fixer) = fixer {
+				fixer.resolve_feature_conflicts(&krate.name, &feature, self.conflict_resolution)?;
+				transaction.push(fixer);
 			}
+
+			if self.max_issues.map_or(false, |max| *reported + stats.issues() >= max) {
+				stopped = true;
+				break
+			}
+		}
+		drop((phase, progress));
+		*reported += stats.issues();
+		global.commit_fixes(transaction)?;
+
+		if stopped && !global.summary_only() {
+			out::println!(
+				"Stopped after {} issue{} because of `--max-issues`. There may be more.",
+				*reported,
+				plural(*reported)
+			);
 		}
-		if let Some(e) = error_stats(errors, 0, fixes, self.fixer_args.enable, global) {
-			println!("{}", e);
+		let IssueStats { errors, warnings, fixes, fixed_errors } = stats;
+		// External crates are not offenders, since the fix command cannot fix them.
+		let fix_cmd = (!offenders.is_empty()).then(|| self.fix_command(&feature, &offenders));
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, fix_cmd.as_ref(), global)
+		{
+			out::println!("{}", e);
 		}
 
-		if errors > fixes {
-			std::process::exit(global.error_code());
+		if errors > fixed_errors && self.write_baseline.is_none() {
+			global.exit(global.error_code());
 		}
+		Ok(())
+	}
+
+	/// The command that fixes the issues of `feature` in the `offenders` crates.
+	fn fix_command(&self, feature: &str, offenders: &BTreeSet<&str>) -> FixCommand {
+		let mut cmd = FixCommand::new("lint propagate-feature", &self.cargo_args)
+			.arg("--features", feature)
+			.arg("--packages", offenders.iter().copied().collect::<Vec<_>>().join(","));
+
+		let enables_dep = self
+			.feature_enables_dep
+			.iter()
+			.flatten()
+			.filter(|(f, _)| f == feature)
+			.map(|(f, dep)| format!("{}:{}", f, dep))
+			.collect::<Vec<_>>();
+		if !enables_dep.is_empty() {
+			cmd = cmd.arg("--feature-enables-dep", enables_dep.join(","));
+		}
+		let mapping = self
+			.feature_mapping
+			.iter()
+			.flatten()
+			.filter(|(from, _)| from.split_once('/').map_or(false, |(_, f)| f == feature))
+			.map(|(from, to)| format!("{}:{}", from, to))
+			.collect::<Vec<_>>();
+		if !mapping.is_empty() {
+			cmd = cmd.arg("--feature-mapping", mapping.join(","));
+		}
+		if let Some(policy) = self.conflict_resolution.to_possible_value() {
+			if self.conflict_resolution != ConflictResolution::Error {
+				cmd = cmd.arg("--conflict-resolution", policy.get_name());
+			}
+		}
+		if self.workspace_features {
+			cmd = cmd.flag("--workspace-features");
+		}
+		if self.report_external_path_deps {
+			cmd = cmd.flag("--report-external-path-deps");
+		}
+		if self.all_path_deps {
+			cmd = cmd.flag("--all-path-deps");
+		} else if !self.assume_published.is_empty() {
+			cmd = cmd.arg("--assume-published", self.assume_published.join(","));
+		}
+		cmd.flag("--fix")
+	}
+
+	/// The command that aligns the `default-features` of all workspace members.
+	fn consistency_fix_command(&self) -> FixCommand {
+		let mut cmd = FixCommand::new("lint propagate-feature", &self.cargo_args)
+			.arg("--features", self.features.join(","))
+			.flag("--check-default-features-consistency");
+		if self.default_features_policy != DefaultFeaturesPolicy::Majority {
+			let policy = self.default_features_policy.to_possible_value().expect("Not skipped");
+			cmd = cmd.arg("--default-features-policy", policy.get_name());
+		}
+		cmd.flag("--fix")
+	}
+
+	/// The feature mapping of the config file extended by `--feature-mapping`.
+	fn feature_mapping(&self, root: &Path) -> Result<FeatureMapping, String> {
+		let mut mapping = FeatureMapping::from_workspace(root)?;
+		for (from, to) in self.feature_mapping.iter().flatten() {
+			let Some((krate, feature)) = from.split_once('/') else {
+				return Err(format!(
+					"Invalid feature mapping '{}:{}', expected CRATE/FEATURE:MAPPED",
+					from, to
+				))
+			};
+			mapping.insert(krate, feature, to);
+		}
+		Ok(mapping)
 	}
 
 	fn ignore_missing_propagate(&self) -> BTreeMap<CrateAndFeature, BTreeSet<CrateAndFeature>> {
@@ -662,26 +1426,59 @@ impl PropagateFeatureCmd {
 		map
 	}
 
-	fn parse_dep_kinds(
-		&self,
-	) -> Result<HashMap<cargo_metadata::DependencyKind, IgnoreSetting>, String> {
-		let mut map = HashMap::new();
+	fn parse_dep_kinds(&self) -> Result<BTreeMap<DepKind, IgnoreSetting>, String> {
+		let mut map = BTreeMap::new();
 		if let Some(kinds) = &self.dep_kinds {
 			for (kind, mute) in kinds {
-				map.insert(kind.parse::<DepKind>()?.into(), mute.parse()?);
+				map.insert(kind.parse::<DepKind>()?, mute.parse()?);
 			}
 		}
 		Ok(map)
 	}
 }
 
-fn error_stats(
+/// Issue counters of a single lint run.
+///
+/// Fixes are tracked separately for errors since only those influence the exit code.
+#[derive(Default)]
+struct IssueStats {
+	errors: usize,
+	warnings: usize,
+	fixes: usize,
+	fixed_errors: usize,
+}
+
+impl IssueStats {
+	fn count(&mut self, severity: Severity, issues: usize, fixed: usize) {
+		if severity == Severity::Warn {
+			self.warnings += issues;
+		} else {
+			self.errors += issues;
+			self.fixed_errors += fixed;
+		}
+		self.fixes += fixed;
+	}
+
+	fn issues(&self) -> usize {
+		self.errors + self.warnings
+	}
+}
+
+/// Summarize the found issues in a single line.
+///
+/// Hints at `fix_cmd` to fix them, or at `--fix` in general if there is no such command.
+pub(crate) fn error_stats(
 	errors: usize,
 	warnings: usize,
 	fixes: usize,
 	fix: bool,
+	fix_cmd: Option<&FixCommand>,
 	global: &GlobalArgs,
 ) -> Option<String> {
+	global.write_stats(errors, fixes, warnings);
+	if global.summary_only() {
+		return Some(format!("issues={} fixed={} warnings={}", errors, fixes, warnings))
+	}
 	if errors + warnings + fixes == 0 {
 		return None
 	}
@@ -689,11 +1486,12 @@ fn error_stats(
 	let mut ret: String = "Found ".into();
 	if errors > 0 {
 		let issues = format!("{} issue{}", errors, plural(errors));
-		ret.push_str(&global.red(&issues));
+		ret.push_str(&global.error(&issues));
 	}
 	if warnings > 0 {
-		let warn = format!(", {} warning{}", warnings, plural(warnings));
-		ret.push_str(&global.yellow(&warn));
+		let sep = if errors > 0 { ", " } else { "" };
+		let warn = format!("{}{} warning{}", sep, warnings, plural(warnings));
+		ret.push_str(&global.warning(&warn));
 	}
 	if fix {
 		if warnings + errors > 0 {
@@ -701,7 +1499,7 @@ fn error_stats(
 		}
 		let fixed = format!(" fixed {}", fixes);
 		if fixes > 0 {
-			ret.push_str(&global.green(&fixed));
+			ret.push_str(&global.success(&fixed));
 			if fixes == warnings + errors {
 				ret.push_str(" (all fixed)");
 			}
@@ -711,22 +1509,46 @@ fn error_stats(
 
 		if fixes < errors {
 			let could_not = format!(" ({} could not be fixed)", errors - fixes);
-			ret.push_str(&global.red(&could_not));
+			ret.push_str(&global.error(&could_not));
 		}
-	} else if global.show_hints() {
-		ret.push_str(" (run with `--fix` to fix)");
+	} else if let Some(hint) = global.fix_hint(|| match fix_cmd {
+		Some(cmd) => format!("run `{}` to fix", cmd),
+		None => "run with `--fix` to fix".into(),
+	}) {
+		ret.push_str(&format!(" ({})", hint));
 	}
 	Some(format!("{}.", ret))
 }
 
 impl OnlyEnablesCmd {
-	pub fn run(&self, _global: &GlobalArgs) {
+	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
-		let pkgs = &meta.packages;
+		let index = PackageIndex::new(&meta);
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let config =
+			OnlyEnablesConfig::from_workspace(meta.workspace_root.as_std_path())?.exemptions()?;
+		// Exempt findings are still printed, so that audits can see what was skipped and why.
+		let exempt_by = |krate: &str, feature: &str| {
+			self.allow
+				.iter()
+				.find(|e| e.matches(krate, feature))
+				.map(|e| format!("`--allow {}`", e))
+				.or_else(|| {
+					config
+						.iter()
+						.find(|e| e.matches(krate, feature))
+						.map(|e| format!("config entry '{}'", e))
+				})
+		};
 
-		for pkg in pkgs.iter() {
+		for pkg in meta.packages.iter().filter(|p| !ignored.contains(&p.name)) {
 			for dep in pkg.dependencies.iter() {
-				let Some(dep) = resolve_dep(pkg, dep, &meta) else { continue };
+				let Some(dep) = index.resolve_dep(pkg, dep) else { continue };
+				let finding = ["only-enables", &self.precondition, &self.only_enables, &dep.name()];
+				if ignored.contains(&dep.pkg.name) || allowed.allows(&pkg.name, &finding) {
+					continue
+				}
 				if !dep.pkg.features.contains_key(&self.only_enables) {
 					continue
 				}
@@ -740,154 +1562,359 @@ impl OnlyEnablesCmd {
 					let opt = if dep.optional { "?" } else { "" };
 					let bad_opt = format!("{}{}/{}", dep.name(), opt, self.only_enables);
 					let bad = format!("{}/{}", dep.name(), self.only_enables);
-					if imply.contains(&bad) || imply.contains(&bad_opt) {
-						println!(
-							"{}/{} enables {}/{}",
+					if !imply.contains(&bad) && !imply.contains(&bad_opt) {
+						continue
+					}
+					let enabled = dep.display_feature(&self.only_enables);
+					match exempt_by(&pkg.name, feat) {
+						Some(by) => out::println!(
+							"{}/{} enables {} (exempt by {})",
 							pkg.name,
 							feat,
-							dep.name(),
-							self.only_enables
-						);
+							enabled,
+							by
+						),
+						None => out::println!(
+							"{}/{} enables {}{}",
+							pkg.name,
+							feat,
+							enabled,
+							global.code(&issues::ONLY_ENABLES)
+						),
 					}
 				}
 			}
 		}
+		index.report_unresolved(self.cargo_args.unresolved_deps)
 	}
 }
 
 impl WhyEnabledCmd {
-	pub fn run(&self, _global: &GlobalArgs) {
+	pub fn run(&self, _global: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
-		let dag = build_feature_dag(&meta, &meta.packages);
-		let pkgs = meta.packages;
+		let index = PackageIndex::new(&meta);
+		let dag = self.enabled_features.prune(&index, build_feature_dag(&index, &meta.packages))?;
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+		let renames = dependency_renames(&index, &meta.packages);
 		let mut found_crate_and_feature = false;
 		let mut found_crate = false;
 		let mut enabled_by = vec![];
+		let mut known_features = BTreeSet::new();
 
-		let lookup = |id: &str| pkgs.iter().find(|pkg| pkg.id.to_string() == id);
+		let lookup = |id: &str| index.get(id);
 
-		for (lhs, rhs) in dag.edges.iter() {
+		// Skip internal helper nodes like `#entrypoint`.
+		for (lhs, rhs) in dag.edges.iter().filter(|(lhs, _)| !lhs.1.starts_with('#')) {
 			for rhs in rhs.iter() {
-				// A bit ghetto, but i don't want to loose unresolved rhs crates.
+				// Unresolved rhs crates are only known by name.
 				let resolved = lookup(&rhs.0).map(|r| r.name.clone()).unwrap_or(rhs.0.clone());
 				if resolved == self.package {
 					found_crate = true;
+					known_features.insert(rhs.1.clone());
 				}
 
 				if resolved == self.package && rhs.1 == self.feature {
 					let lhs_resolved = lookup(&lhs.0).unwrap();
 					found_crate_and_feature = true;
-					enabled_by.push((lhs_resolved.name.clone(), lhs.1.clone()));
+					let rename = renames.get(&(lhs.0.clone(), rhs.0.clone()));
+					enabled_by.push((lhs_resolved.name.clone(), lhs.1.clone(), rename));
 				}
 			}
 		}
 
 		if !found_crate {
-			println!("Did not find package {} on the rhs of the dependency tree", self.package);
-			std::process::exit(1);
+			out::println!(
+				"Did not find package {} on the rhs of the dependency tree",
+				self.package
+			);
+			serve::exit(1);
 		}
 		if !found_crate_and_feature {
-			// TODO find edit distance to the closest one
-			println!("Package {} does not have feature {}", self.package, self.feature);
-			std::process::exit(1);
+			out::print!("Package {} does not have feature {}", self.package, self.feature);
+			match typos::closest(&self.feature, &known_features, 2).as_slice() {
+				[] => out::println!(),
+				[one] => out::println!(", did you mean '{}'?", one),
+				many => out::println!(", did you mean one of '{}'?", many.join("', '")),
+			}
+			serve::exit(1);
 		}
 		debug_assert!(!enabled_by.is_empty());
-		println!("Feature {}/{} is enabled by:", self.feature, self.package);
-		for (name, feature) in enabled_by {
-			println!("  {}/{}", name, feature);
+		out::println!("Feature {}/{} is enabled by:", self.feature, self.package);
+		for (name, feature, rename) in enabled_by {
+			match rename {
+				Some(rename) => out::println!("  {}/{} (as {})", name, feature, rename),
+				None => out::println!("  {}/{}", name, feature),
+			}
 		}
+		Ok(())
 	}
 }
 
-// Complexity is `O(x ^ 4) with x=pkgs.len()`.
-pub fn build_feature_dag(meta: &Metadata, pkgs: &[Package]) -> Dag<CrateAndFeature> {
+/// A malformed feature entry that was skipped while building the feature [Dag].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DagBuildError {
+	/// Name of the crate that contains the entry.
+	pub krate: String,
+	/// The feature that contains the entry.
+	pub feature: String,
+	/// The entry as written in the manifest.
+	pub entry: String,
+	/// Why the entry could not be added.
+	pub reason: String,
+}
+
+impl Display for DagBuildError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"crate '{}' feature '{}': {} in '{}'",
+			self.krate, self.feature, self.reason, self.entry
+		)
+	}
+}
+
+/// Build the feature [Dag] and log a warning for every malformed entry.
+/// Add the `features` of `[workspace.dependencies]` as always enabled for inheriting members.
+///
+/// Cargo merges them into the features of the member dependency, which makes them look like they
+/// would only be enabled by the `default` feature of the member.
+fn add_workspace_features(
+	index: &PackageIndex<'_>,
+	dag: &mut Dag<CrateAndFeature>,
+) -> Result<(), String> {
+	let meta = index.meta();
+	let root = AutoFixer::from_manifest(meta.workspace_root.join("Cargo.toml"))?;
+	let features = root.workspace_dependency_features();
+	if features.is_empty() {
+		return Ok(())
+	}
+
+	for pkg in meta.workspace_packages() {
+		let inherited = AutoFixer::from_manifest(&pkg.manifest_path)?.inherited_dependencies();
+		for dep in pkg.dependencies.iter() {
+			let key = dep.rename.as_ref().unwrap_or(&dep.name);
+			let Some(features) = features.get(key) else { continue };
+			if !inherited.contains(key) {
+				continue
+			}
+			let Some(resolved) = index.resolve_dep(pkg, dep) else { continue };
+			// Crates outside of the workspace do not get these features.
+			if index.is_assumed_published(&resolved.pkg) {
+				continue
+			}
+
+			for feature in features {
+				dag.add_edge(
+					CrateAndFeature(pkg.id.to_string(), "#entrypoint".into()),
+					CrateAndFeature(resolved.pkg.id.to_string(), feature.clone()),
+				);
+			}
+		}
+	}
+	Ok(())
+}
+
+pub fn build_feature_dag(index: &PackageIndex<'_>, pkgs: &[Package]) -> Dag<CrateAndFeature> {
+	let (dag, errors) = try_build_feature_dag(index, pkgs);
+	for error in errors.iter() {
+		log::warn!("Skipping malformed feature entry: {}", error);
+	}
+	dag
+}
+
+/// Build the feature [Dag] and collect all malformed entries instead of aborting.
+///
+/// Complexity is linear in the number of packages since all lookups go through the `index`. Reuses
+/// the graph of `zepter serve` when it was built from the same packages.
+pub fn try_build_feature_dag(
+	index: &PackageIndex<'_>,
+	pkgs: &[Package],
+) -> (Dag<CrateAndFeature>, Vec<DagBuildError>) {
+	if let Some(cached) = serve::cached_feature_dag(index, pkgs) {
+		return cached
+	}
+	build_uncached_feature_dag(index, pkgs)
+}
+
+/// Like [`try_build_feature_dag`], but always builds the graph.
+pub(crate) fn build_uncached_feature_dag(
+	index: &PackageIndex<'_>,
+	pkgs: &[Package],
+) -> (Dag<CrateAndFeature>, Vec<DagBuildError>) {
 	let mut dag = Dag::new();
+	let mut errors = Vec::new();
+	let progress = Progress::bar(pkgs.len(), "Building feature graph");
+	let _phase = timing::Phase::start("feature graph");
 
 	for pkg in pkgs.iter() {
+		progress.inc();
 		for dep in &pkg.dependencies {
+			let resolved = index.resolve_dep(pkg, dep);
+			// Unresolved dependencies stay a dead-end that is identified by name.
+			let dep_id = match &resolved {
+				Some(resolved) => resolved.pkg.id.to_string(),
+				None => dep.name.clone(),
+			};
+
 			if dep.uses_default_features {
 				dag.add_edge(
 					CrateAndFeature(pkg.id.to_string(), "default".into()),
-					CrateAndFeature(dep.name.clone(), "default".into()),
+					CrateAndFeature(dep_id.clone(), "default".into()),
 				);
 
-				let Some(dep_id) = resolve_dep(pkg, dep, meta) else { continue };
-
-				// Hacky…
-				dag.add_edge(
-					CrateAndFeature(pkg.id.to_string(), "#entrypoint".into()),
-					CrateAndFeature(dep_id.pkg.id.repr.clone(), "default".into()),
-				);
+				if resolved.is_some() {
+					// Hacky…
+					dag.add_edge(
+						CrateAndFeature(pkg.id.to_string(), "#entrypoint".into()),
+						CrateAndFeature(dep_id.clone(), "default".into()),
+					);
+				}
 			}
 			for feature in &dep.features {
 				dag.add_edge(
 					CrateAndFeature(pkg.id.to_string(), "default".into()),
-					CrateAndFeature(dep.name.clone(), feature.into()),
+					CrateAndFeature(dep_id.clone(), feature.into()),
 				);
 			}
 		}
 
-		for (feature, deps) in pkg.features.iter() {
-			for dep in deps {
-				if dep.contains(':') {
-					let mut splits = dep.split(':');
-					let dep = splits.nth(1).unwrap();
-					let dep_feature = "default";
-					let dep = pkg
-						.dependencies
-						.iter()
-						.find(|d| d.rename.as_ref().unwrap_or(&d.name) == dep)
-						.unwrap();
-
-					let dep_id = match resolve_dep(pkg, dep, meta) {
-						None => {
-							// This can happen for optional dependencies who are not enabled, or
-							// a weird `target` is specified or it is a dev dependency.
-							// In this case we just go by name. It is a dead-end anyway.
-							dep.name.clone()
-						},
-						Some(dep) => dep.pkg.id.to_string(), // TODO rename
-					};
-					dag.add_edge(
-						CrateAndFeature(pkg.id.to_string(), feature.clone()),
-						CrateAndFeature(dep_id.clone(), dep_feature.into()),
-					);
-				} else if dep.contains('/') {
-					let mut splits = dep.split('/');
-					let dep = splits.next().unwrap().replace('?', "");
-					let dep_feature = splits.next().unwrap();
+		for (feature, entries) in pkg.features.iter() {
+			for entry in entries {
+				let error = |reason: String| DagBuildError {
+					krate: pkg.name.clone(),
+					feature: feature.clone(),
+					entry: entry.clone(),
+					reason,
+				};
 
-					let dep = pkg
-						.dependencies
-						.iter()
-						.find(|d| d.rename.as_ref().unwrap_or(&d.name) == &dep)
-						.unwrap_or_else(|| panic!("Could not resolve dep {} of {}", dep, pkg.id));
-
-					let dep_id = match resolve_dep(pkg, dep, meta) {
-						None => {
-							// This can happen for optional dependencies who are not enabled, or
-							// a weird `target` is specified or it is a dev dependency.
-							// In this case we just go by name. It is a dead-end anyway.
-							dep.name.clone()
-						},
-						Some(dep) => dep.pkg.id.to_string(), // TODO rename
-					};
-					dag.add_edge(
-						CrateAndFeature(pkg.id.to_string(), feature.clone()),
-						CrateAndFeature(dep_id.clone(), dep_feature.into()),
-					);
+				let (dep, dep_feature) = if let Some(dep) = entry.strip_prefix("dep:") {
+					(dep.to_string(), "default")
+				} else if let Some((dep, dep_feature)) = entry.split_once('/') {
+					(dep.replace('?', ""), dep_feature)
 				} else {
-					let dep_feature = dep;
-					// Sanity check
-					debug_assert!(pkg.features.contains_key(dep_feature));
+					if !pkg.features.contains_key(entry) {
+						errors.push(error(format!("unknown feature '{}'", entry)));
+						continue
+					}
 					// Enables one of its own features.
 					dag.add_edge(
 						CrateAndFeature(pkg.id.to_string(), feature.clone()),
-						CrateAndFeature(pkg.id.to_string(), dep_feature.into()),
+						CrateAndFeature(pkg.id.to_string(), entry.clone()),
 					);
-				}
+					continue
+				};
+
+				let Some(dep) =
+					pkg.dependencies.iter().find(|d| d.rename.as_ref().unwrap_or(&d.name) == &dep)
+				else {
+					errors.push(error(format!("unknown dependency '{}'", dep)));
+					continue
+				};
+
+				let dep_id = match index.resolve_dep(pkg, dep) {
+					None => {
+						// This can happen for optional dependencies who are not enabled, or
+						// a weird `target` is specified or it is a dev dependency.
+						// In this case we just go by name. It is a dead-end anyway.
+						dep.name.clone()
+					},
+					Some(dep) => dep.pkg.id.to_string(),
+				};
+				dag.add_edge(
+					CrateAndFeature(pkg.id.to_string(), feature.clone()),
+					CrateAndFeature(dep_id, dep_feature.into()),
+				);
 			}
 		}
 	}
-	dag
+
+	errors.sort();
+	(dag, errors)
+}
+
+/// Whether `krate` has to enable features of its dependency `name` with `name?/feature`.
+///
+/// Dev-dependencies cannot be optional. A dependency that is also a dev-dependency is therefore
+/// only optional if one of its normal or build entries is.
+fn is_optional_dep(krate: &Package, name: &str) -> bool {
+	krate
+		.dependencies
+		.iter()
+		.filter(|d| d.rename.as_ref().unwrap_or(&d.name) == name)
+		.any(|d| d.optional && d.kind != cargo_metadata::DependencyKind::Development)
+}
+
+/// The features of each crate that are enabled by the `features` of the crate `id`.
+///
+/// This follows the feature [Dag] and therefore does not know about weak dependency features or
+/// the target and dependency kind. Unresolved crates are identified by name.
+pub fn resolve_features(
+	dag: &Dag<CrateAndFeature>,
+	id: &str,
+	features: impl IntoIterator<Item = String>,
+	default_features: bool,
+) -> BTreeMap<CrateId, BTreeSet<String>> {
+	let mut roots = vec![CrateAndFeature(id.into(), "#entrypoint".into())];
+	if default_features {
+		roots.push(CrateAndFeature(id.into(), "default".into()));
+	}
+	roots.extend(features.into_iter().map(|f| CrateAndFeature(id.into(), f)));
+
+	let mut enabled = BTreeMap::<CrateId, BTreeSet<String>>::new();
+	for CrateAndFeature(krate, feature) in dag.reachable_from(roots) {
+		// The entrypoint is a helper node and not a real feature.
+		if feature != "#entrypoint" {
+			enabled.entry(krate).or_default().insert(feature);
+		}
+	}
+	enabled
+}
+
+/// Remove all nodes of ignored crates from the feature [Dag].
+pub(crate) fn without_ignored(
+	meta: &Metadata,
+	dag: Dag<CrateAndFeature>,
+	ignored: &IgnoredDependencies,
+) -> Dag<CrateAndFeature> {
+	let ignored_ids = meta
+		.packages
+		.iter()
+		.filter(|p| ignored.contains(&p.name))
+		.map(|p| p.id.to_string())
+		.collect::<BTreeSet<_>>();
+	// Unresolved nodes are identified by name.
+	let is_ignored = |CrateAndFeature(id, _): &CrateAndFeature| {
+		let id = id.trim_end_matches('?');
+		ignored_ids.contains(id) || ignored.contains(id)
+	};
+
+	let mut filtered = Dag::new();
+	for (from, tos) in dag.edges.into_iter().filter(|(from, _)| !is_ignored(from)) {
+		for to in tos.into_iter().filter(|to| !is_ignored(to)) {
+			filtered.add_edge(from.clone(), to);
+		}
+	}
+	filtered
+}
+
+/// Renamed dependencies as `(crate id, dependency id) -> rename`.
+///
+/// The feature [Dag] only contains package IDs, this carries the names under which the
+/// dependencies are known to their dependant.
+pub type DependencyRenames = BTreeMap<(CrateId, CrateId), String>;
+
+/// Collect the renames of all resolved dependencies of `pkgs`.
+pub fn dependency_renames(index: &PackageIndex<'_>, pkgs: &[Package]) -> DependencyRenames {
+	let mut renames = DependencyRenames::new();
+
+	for pkg in pkgs.iter() {
+		for dep in pkg.dependencies.iter() {
+			let Some(rename) = &dep.rename else { continue };
+			let Some(resolved) = index.resolve_dep(pkg, dep) else { continue };
+
+			renames.insert((pkg.id.to_string(), resolved.pkg.id.to_string()), rename.clone());
+		}
+	}
+
+	renames
 }
@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Baseline files to suppress known issues.
+//!
+//! A baseline records the issues that already exist in a workspace. Subsequent runs then only
+//! report issues that are not in the baseline. This allows to adopt a lint incrementally.
+
+use std::{collections::BTreeSet, path::Path};
+
+/// A set of known issues that should not be reported.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Baseline {
+	pub issues: BTreeSet<BaselineIssue>,
+}
+
+/// A single known issue of a lint.
+#[derive(
+	Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub struct BaselineIssue {
+	/// The kind of the issue.
+	pub kind: BaselineIssueKind,
+	/// Name of the crate that has the issue.
+	#[serde(rename = "crate")]
+	pub krate: String,
+	/// The feature that is affected.
+	pub feature: String,
+	/// The dependency that is involved.
+	pub dependency: String,
+}
+
+#[derive(
+	Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum BaselineIssueKind {
+	/// The crate does not have a feature that one of its dependencies has.
+	FeatureMissing,
+	/// The crate does not propagate a feature to one of its dependencies.
+	PropagateMissing,
+}
+
+impl Baseline {
+	/// Load a baseline from a YAML file.
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+		let path = path.as_ref();
+		let content = std::fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read baseline file {:?}: {}", path, e))?;
+
+		serde_yaml::from_str(&content)
+			.map_err(|e| format!("Failed to parse baseline file {:?}: {}", path, e))
+	}
+
+	/// Write the baseline as YAML file.
+	pub fn to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+		let path = path.as_ref();
+		let content = serde_yaml::to_string(self)
+			.map_err(|e| format!("Failed to serialize baseline: {}", e))?;
+
+		std::fs::write(path, content)
+			.map_err(|e| format!("Failed to write baseline file {:?}: {}", path, e))
+	}
+
+	pub fn insert(&mut self, issue: BaselineIssue) {
+		self.issues.insert(issue);
+	}
+
+	/// Whether the issue is already known.
+	pub fn contains(&self, issue: &BaselineIssue) -> bool {
+		self.issues.contains(issue)
+	}
+
+	pub fn len(&self) -> usize {
+		self.issues.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.issues.is_empty()
+	}
+}
+
+impl BaselineIssue {
+	pub fn new(kind: BaselineIssueKind, krate: &str, feature: &str, dependency: &str) -> Self {
+		Self { kind, krate: krate.into(), feature: feature.into(), dependency: dependency.into() }
+	}
+}
+
+impl std::fmt::Display for BaselineIssue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "crate '{}' feature '{}' ", self.krate, self.feature)?;
+		match self.kind {
+			BaselineIssueKind::FeatureMissing => write!(f, "is required by '{}'", self.dependency),
+			BaselineIssueKind::PropagateMissing =>
+				write!(f, "must propagate to '{}'", self.dependency),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn baseline_yaml_roundtrip_works() {
+		let mut baseline = Baseline::default();
+		baseline.insert(BaselineIssue::new(BaselineIssueKind::PropagateMissing, "A", "std", "B"));
+		baseline.insert(BaselineIssue::new(BaselineIssueKind::FeatureMissing, "B", "std", "C"));
+
+		let yaml = serde_yaml::to_string(&baseline).unwrap();
+		assert_eq!(
+			yaml,
+			"issues:\n- kind: feature-missing\n  crate: B\n  feature: std\n  dependency: C\n- kind: propagate-missing\n  crate: A\n  feature: std\n  dependency: B\n"
+		);
+		assert_eq!(serde_yaml::from_str::<Baseline>(&yaml).unwrap(), baseline);
+	}
+}
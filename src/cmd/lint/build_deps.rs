@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find features that enable features of build-dependencies.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::{DependencyKind, Package};
+use std::fs::canonicalize;
+
+/// Find features that enable features of build-dependencies.
+///
+/// Features of build-dependencies only affect the build script and are not propagated to the
+/// normal dependencies. An entry like `build-dep/std` in the `std` feature is therefore misleading.
+/// The fix removes these entries.
+#[derive(Debug, clap::Parser)]
+pub struct BuildDepFeaturesCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+/// A feature entry that references a build-dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BuildDepFeature {
+	/// The feature that contains the entry.
+	feature: String,
+	/// The entry as written in the manifest.
+	entry: String,
+	/// The name under which the build-dependency is imported.
+	dependency: String,
+}
+
+impl BuildDepFeaturesCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::BuildDepFeatures);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let found = Self::build_dep_features(pkg)
+				.into_iter()
+				.filter(|f| !ignored.contains(&f.dependency))
+				.filter(|f| {
+					!allowed.allows(&pkg.name, &["build-dep-features", &f.feature, &f.dependency])
+				})
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			let mut fixer = if self.fixer_args.enable {
+				Some(AutoFixer::from_manifest(&pkg.manifest_path)?)
+			} else {
+				None
+			};
+			for f in found.iter() {
+				issues += 1;
+				let msg = format!(
+					"feature '{}' enables '{}' of build-dependency '{}'",
+					f.feature, f.entry, f.dependency
+				);
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::BuildDepFeatures.issue()));
+				}
+				global.annotate(Finding::new(
+					IssueKind::BuildDepFeatures.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				if let Some(fixer) = fixer.as_mut() {
+					if fixer.remove_from_feature(&f.feature, &f.entry)? {
+						fixes += 1;
+					}
+				}
+			}
+			if let Some(mut fixer) = fixer.filter(AutoFixer::modified) {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint build-dep-features", &self.cargo_args).flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+
+	/// All feature entries of `pkg` that only reference a build-dependency.
+	///
+	/// Dependencies that are also a normal or dev dependency are skipped since the entry has an
+	/// effect on them.
+	fn build_dep_features(pkg: &Package) -> Vec<BuildDepFeature> {
+		let mut found = Vec::new();
+
+		for (feature, entries) in pkg.features.iter() {
+			for entry in entries {
+				let Some((dep, _)) = entry.split_once('/') else { continue };
+				let dep = dep.trim_end_matches('?');
+				let mut kinds = pkg
+					.dependencies
+					.iter()
+					.filter(|d| d.rename.as_ref().unwrap_or(&d.name) == dep)
+					.map(|d| d.kind)
+					.peekable();
+
+				if kinds.peek().is_some() && kinds.all(|k| k == DependencyKind::Build) {
+					found.push(BuildDepFeature {
+						feature: feature.clone(),
+						entry: entry.clone(),
+						dependency: dep.into(),
+					});
+				}
+			}
+		}
+		found
+	}
+}
@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Check that all workspace members declare an explicit `default` feature.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::Package;
+use std::fs::canonicalize;
+
+/// Check that all workspace members declare an explicit `default` feature.
+///
+/// An empty `default = []` is enough. With `--require-std` the `default` feature of crates that
+/// have a `std` feature must also enable it. The fix inserts the missing feature and entry.
+#[derive(Debug, clap::Parser)]
+pub struct DefaultFeatureCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// Require that `default` enables `std` in crates that have a `std` feature.
+	#[clap(long)]
+	require_std: bool,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+/// Something that is wrong with the `default` feature of a crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Missing {
+	/// The crate has no `default` feature.
+	Default,
+	/// The `default` feature does not enable `std`.
+	Std,
+}
+
+impl DefaultFeatureCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::DefaultFeature);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let Some(missing) = self.missing(pkg) else { continue };
+			if allowed.allows(&pkg.name, &allow_key(missing)) {
+				continue
+			}
+			issues += 1;
+
+			let msg = match missing {
+				Missing::Default => "has no explicit 'default' feature",
+				Missing::Std => "feature 'default' does not enable 'std'",
+			};
+			if global.details() {
+				out::println!("crate '{}'", pkg.name);
+				out::println!("  {}{}", msg, global.code(IssueKind::DefaultFeature.issue()));
+			}
+			global.annotate(Finding::new(
+				IssueKind::DefaultFeature.issue(),
+				severity,
+				pkg.manifest_path.as_std_path(),
+				msg,
+			));
+
+			if self.fixer_args.enable {
+				let mut fixer = AutoFixer::from_manifest(&pkg.manifest_path)?;
+				if self.require_std && pkg.features.contains_key("std") {
+					fixer.add_to_feature("default", "std")?;
+				} else {
+					fixer.add_feature("default")?;
+				}
+				global.save_fixer(&mut fixer)?;
+				fixes += 1;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let mut fix_cmd = FixCommand::new("lint default-feature", &self.cargo_args);
+		if self.require_std {
+			fix_cmd = fix_cmd.flag("--require-std");
+		}
+		let fix_cmd = fix_cmd.flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+
+	/// What is missing from the `default` feature of `pkg`, if anything.
+	fn missing(&self, pkg: &Package) -> Option<Missing> {
+		let Some(default) = pkg.features.get("default") else { return Some(Missing::Default) };
+		let std = self.require_std && pkg.features.contains_key("std");
+		(std && !default.iter().any(|e| e == "std")).then_some(Missing::Std)
+	}
+}
+
+/// The parts of a finding that `package.metadata.zepter` can allow.
+fn allow_key(missing: Missing) -> [&'static str; 2] {
+	match missing {
+		Missing::Default => ["default-feature", "missing"],
+		Missing::Std => ["default-feature", "std"],
+	}
+}
@@ -0,0 +1,130 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find dependencies with `default-features = false` that enable the `default` feature.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs, DefaultFeatureConflictFix},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use clap::ValueEnum;
+use std::{collections::BTreeSet, fs::canonicalize};
+
+/// Find dependencies with `default-features = false` that enable the `default` feature.
+///
+/// Cargo enables the default features in this case, which is likely not what was intended. The
+/// fix removes either the `"default"` feature or the `default-features = false` key.
+#[derive(Debug, clap::Parser)]
+pub struct DefaultFeaturesConflictCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+
+	/// What to remove when fixing.
+	#[clap(long, value_enum, default_value_t = DefaultFeatureConflictFix::DefaultFeaturesKey)]
+	remove: DefaultFeatureConflictFix,
+}
+
+impl DefaultFeaturesConflictCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity =
+			SeverityTable::from_workspace(&root)?.get(IssueKind::DefaultFeaturesConflict);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		// The root manifest can contain `workspace.dependencies` with the conflict.
+		let manifests = meta
+			.packages
+			.iter()
+			.filter(|p| meta.workspace_members.contains(&p.id))
+			.map(|p| p.manifest_path.clone().into_std_path_buf())
+			.chain(std::iter::once(root.join("Cargo.toml")))
+			.filter_map(|p| canonicalize(p).ok())
+			.collect::<BTreeSet<_>>();
+
+		let (mut issues, mut fixes) = (0, 0);
+		for manifest in manifests.iter() {
+			let mut fixer = AutoFixer::from_manifest(manifest)?;
+			let krate = fixer.package_name().unwrap_or_default();
+			let found = fixer
+				.default_feature_conflicts()
+				.into_iter()
+				.filter(|dep| !ignored.contains(&dep.key))
+				.filter(|dep| !allowed.allows(&krate, &["default-features-conflict", &dep.key]))
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			if global.details() {
+				match fixer.package_name() {
+					Some(name) => out::println!("crate '{}'", name),
+					None => out::println!("workspace root"),
+				}
+			}
+			for dep in found.iter() {
+				issues += 1;
+				let msg = format!(
+					"dependency '{}' has `default-features = false` but enables the `default` feature",
+					dep.key
+				);
+				if global.details() {
+					out::println!(
+						"  {}{}",
+						msg,
+						global.code(IssueKind::DefaultFeaturesConflict.issue())
+					);
+				}
+				global.annotate(Finding::new(
+					IssueKind::DefaultFeaturesConflict.issue(),
+					severity,
+					manifest,
+					msg,
+				));
+
+				if self.fixer_args.enable &&
+					fixer.resolve_default_feature_conflict(&dep.key, self.remove)?
+				{
+					fixes += 1;
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let mut fix_cmd = FixCommand::new("lint default-features-conflict", &self.cargo_args);
+		if self.remove != DefaultFeatureConflictFix::DefaultFeaturesKey {
+			let remove = self.remove.to_possible_value().expect("Values are not skipped");
+			fix_cmd = fix_cmd.arg("--remove", remove.get_name());
+		}
+		let fix_cmd = fix_cmd.flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
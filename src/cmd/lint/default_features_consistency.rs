@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find dependencies that workspace members use with and without their default features.
+//!
+//! Run by `lint propagate-feature --check-default-features-consistency`.
+
+use crate::{
+	autofix::{AutoFixer, FixTransaction},
+	cmd::{output::Finding, GlobalArgs, PackageIndex},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity},
+	},
+	log, manifest, out,
+};
+use cargo_metadata::{DependencyKind, Package};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs::canonicalize,
+	path::PathBuf,
+};
+
+/// Which `default-features` the fixer aligns the members to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DefaultFeaturesPolicy {
+	/// Whatever most members use. A tie enables them, since cargo does so anyway.
+	#[default]
+	Majority,
+	/// Enable the default features everywhere.
+	Enabled,
+	/// Disable the default features everywhere.
+	Disabled,
+}
+
+impl DefaultFeaturesPolicy {
+	/// Whether the default features should be enabled given how many members use them.
+	fn enabled(self, enabled: usize, disabled: usize) -> bool {
+		match self {
+			Self::Majority => enabled >= disabled,
+			Self::Enabled => true,
+			Self::Disabled => false,
+		}
+	}
+}
+
+/// A normal dependency of a workspace member.
+struct Usage<'a> {
+	krate: &'a Package,
+	/// The key in the manifest, which differs from the crate name for renamed dependencies.
+	key: String,
+	default_features: bool,
+}
+
+/// The outcome of [`check`].
+#[derive(Debug, Default)]
+pub(crate) struct ConsistencyStats {
+	pub issues: usize,
+	pub fixes: usize,
+}
+
+/// Report the dependencies that members use with and without default features.
+///
+/// Only plain `[dependencies]` are compared, since target specific and dev-dependencies are often
+/// meant to differ. Members that inherit the dependency with `workspace = true` are reported but
+/// not fixed.
+pub(crate) fn check(
+	index: &PackageIndex<'_>,
+	policy: DefaultFeaturesPolicy,
+	fix: bool,
+	severity: Severity,
+	ignored: &IgnoredDependencies,
+	allowed: &AllowedFindings,
+	global: &GlobalArgs,
+) -> Result<ConsistencyStats, String> {
+	let meta = index.meta();
+	let mut stats = ConsistencyStats::default();
+	if severity == Severity::Allow {
+		return Ok(stats)
+	}
+
+	// Grouped by the resolved package, since different versions are built separately.
+	let mut usages = BTreeMap::<&str, (&Package, Vec<Usage<'_>>)>::new();
+	for krate in meta.packages.iter().filter(|p| meta.workspace_members.contains(&p.id)) {
+		if ignored.contains(&krate.name) {
+			continue
+		}
+		for dep in krate.dependencies.iter() {
+			if dep.kind != DependencyKind::Normal || dep.target.is_some() {
+				continue
+			}
+			let Some(resolved) = index.resolve_dep(krate, dep) else { continue };
+			let Some(pkg) = index.get(&resolved.pkg.id.repr) else { continue };
+			if ignored.contains(&pkg.name) ||
+				allowed.allows(&krate.name, &["default-features-consistency", &pkg.name])
+			{
+				continue
+			}
+			usages.entry(&pkg.id.repr).or_insert_with(|| (pkg, Vec::new())).1.push(Usage {
+				krate,
+				key: resolved.name(),
+				default_features: dep.uses_default_features,
+			});
+		}
+	}
+
+	let issue = IssueKind::DefaultFeaturesConsistency.issue();
+	// The fixes of each manifest.
+	let mut to_fix = BTreeMap::<PathBuf, Vec<(String, bool)>>::new();
+	for (pkg, usages) in usages.values() {
+		let (enabled, disabled): (Vec<_>, Vec<_>) = usages.iter().partition(|u| u.default_features);
+		if enabled.is_empty() || disabled.is_empty() {
+			continue
+		}
+		let want = policy.enabled(enabled.len(), disabled.len());
+		let mut name = pkg.name.clone();
+		if index.has_multiple_versions(&pkg.name) {
+			name.push_str(&format!(" v{}", pkg.version));
+		}
+		let names = |usages: &[&Usage<'_>]| {
+			let names = usages.iter().map(|u| u.krate.name.clone()).collect::<BTreeSet<_>>();
+			names.into_iter().collect::<Vec<_>>().join(", ")
+		};
+
+		if global.details() {
+			out::println!(
+				"dependency '{}' has inconsistent default features:{}\n  enabled by: {}\n  disabled by: {}",
+				name,
+				global.code(issue),
+				names(&enabled),
+				names(&disabled)
+			);
+		}
+		let (deviating, others) = if want { (&disabled, &enabled) } else { (&enabled, &disabled) };
+		for usage in deviating.iter() {
+			stats.issues += 1;
+			let path = canonicalize(usage.krate.manifest_path.as_std_path())
+				.map_err(|e| format!("Failed to canonicalize manifest path: {}", e))?;
+			global.annotate(Finding::new(
+				issue,
+				severity,
+				&path,
+				format!(
+					"dependency '{}' has {} default features, but {} {} them",
+					name,
+					if want { "disabled" } else { "enabled" },
+					names(others),
+					if want { "enable" } else { "disable" },
+				),
+			));
+			if fix {
+				to_fix.entry(path).or_default().push((usage.key.clone(), want));
+			}
+		}
+	}
+
+	let mut transaction = FixTransaction::default();
+	for (path, fixes) in to_fix {
+		let mut fixer = AutoFixer::from_manifest(&path)?;
+		for (key, enable) in fixes {
+			let kind = DependencyKind::Normal;
+			let Some(entry) = manifest::get_dependency(fixer.document(), &kind, &key) else {
+				log::info!("Dependency '{}' not found in {}", key, path.display());
+				continue
+			};
+			if entry.workspace {
+				log::info!("Not fixing '{}' since it is inherited from the workspace", key);
+				continue
+			}
+			if enable {
+				manifest::remove_default_features(fixer.document_mut(), &kind, &key)?;
+			} else {
+				manifest::set_default_features(fixer.document_mut(), &kind, &key, false)?;
+			}
+			stats.fixes += 1;
+		}
+		transaction.push(fixer);
+	}
+	global.commit_fixes(transaction)?;
+
+	Ok(stats)
+}
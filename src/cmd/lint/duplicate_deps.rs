@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs, DuplicateResolution},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use std::fs::canonicalize;
+
+/// Find dependencies that are listed as normal and as dev dependency.
+///
+/// The dev entry is redundant if it is a subset of the normal one. The fix removes it, or first
+/// merges its features into the normal entry if it only differs in features.
+#[derive(Debug, clap::Parser)]
+pub struct DuplicateDepsCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+impl DuplicateDepsCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(root)?.get(IssueKind::DuplicateDeps);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members {
+			let mut fixer = AutoFixer::from_manifest(&pkg.manifest_path)?;
+			let duplicates = fixer
+				.duplicate_dependencies()
+				.into_iter()
+				.filter(|dup| !allowed.allows(&pkg.name, &["duplicate-deps", &dup.key]))
+				.collect::<Vec<_>>();
+			if duplicates.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			for dup in duplicates.iter() {
+				issues += 1;
+				let target =
+					dup.target.as_ref().map(|t| format!(" of target '{}'", t)).unwrap_or_default();
+				let reason = match &dup.resolution {
+					DuplicateResolution::Remove => "is already covered by the normal one".into(),
+					DuplicateResolution::MergeFeatures(features) =>
+						format!("only adds the features: {}", features.join(", ")),
+					DuplicateResolution::Conflict(reason) =>
+						format!("cannot be merged since {}", reason),
+				};
+				let msg = format!("dev-dependency '{}'{} {}", dup.key, target, reason);
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::DuplicateDeps.issue()));
+				}
+				global.annotate(Finding::new(
+					IssueKind::DuplicateDeps.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				if self.fixer_args.enable && fixer.resolve_duplicate_dependency(dup)? {
+					fixes += 1;
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint duplicate-deps", &self.cargo_args).flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
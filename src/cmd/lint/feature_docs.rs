@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find features that are not documented.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::Package;
+use std::fs::canonicalize;
+
+/// Comment that the fix inserts above undocumented features.
+pub const FEATURE_DOC_TEMPLATE: &str = "TODO: Document what this feature does.";
+
+/// Find features that are not documented.
+///
+/// A feature is documented by a comment directly above its key or by an entry in the
+/// `[package.metadata.features]` table. The `default` feature does not need documentation. The fix
+/// inserts a TODO comment.
+#[derive(Debug, clap::Parser)]
+pub struct FeatureDocsCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+impl FeatureDocsCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::FeatureDocs);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let mut fixer = AutoFixer::from_manifest(&pkg.manifest_path)?;
+			let found = Self::undocumented(pkg, &fixer)
+				.into_iter()
+				.filter(|f| !allowed.allows(&pkg.name, &["feature-docs", f]))
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			for feature in found.iter() {
+				issues += 1;
+				let msg = format!("feature '{}' is not documented", feature);
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::FeatureDocs.issue()));
+				}
+				global.annotate(Finding::new(
+					IssueKind::FeatureDocs.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				if self.fixer_args.enable {
+					fixer.document_feature(feature, FEATURE_DOC_TEMPLATE)?;
+					fixes += 1;
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint feature-docs", &self.cargo_args).flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+
+	/// All features of `pkg` that have neither a comment nor a metadata entry.
+	///
+	/// The comments are read from the manifest of `fixer` since the metadata does not have them.
+	fn undocumented(pkg: &Package, fixer: &AutoFixer) -> Vec<String> {
+		let documented = pkg.metadata.get("features");
+
+		pkg.features
+			.keys()
+			.filter(|f| f.as_str() != "default")
+			.filter(|f| documented.and_then(|d| d.get(f.as_str())).is_none())
+			.filter(|f| fixer.feature_doc(f).map_or(false, |doc| doc.is_empty()))
+			.cloned()
+			.collect()
+	}
+}
@@ -0,0 +1,223 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find git dependencies that violate the policy of the workspace.
+
+use super::{error_stats, path_deps::load_lockfile};
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs, GitDependency},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		git_deps::{GitDepsConfig, GitDepsPolicy},
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	log, out,
+};
+use std::{collections::BTreeSet, fs::canonicalize};
+use toml_edit::DocumentMut;
+
+/// Find git dependencies that violate the policy of the workspace.
+///
+/// The policy is read from the `git-deps` section of the config file and defaults to
+/// `require-rev`. The fix pins git dependencies to the commit from the lockfile.
+#[derive(Debug, clap::Parser)]
+pub struct GitDepsCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+
+	/// Use this policy instead of the one from the config file.
+	#[clap(long, value_enum)]
+	policy: Option<GitDepsPolicy>,
+}
+
+impl GitDepsCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::GitDeps);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let config = GitDepsConfig::from_workspace(&root)?;
+		let policy = self.policy.unwrap_or(config.policy);
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let fixable = policy == GitDepsPolicy::RequireRev;
+		let lockfile =
+			(self.fixer_args.enable && fixable).then(|| load_lockfile(&root)).transpose()?;
+
+		// The root manifest can contain `workspace.dependencies` with git URLs.
+		let manifests = meta
+			.packages
+			.iter()
+			.filter(|p| meta.workspace_members.contains(&p.id))
+			.map(|p| p.manifest_path.clone().into_std_path_buf())
+			.chain(std::iter::once(root.join("Cargo.toml")))
+			.filter_map(|p| canonicalize(p).ok())
+			.collect::<BTreeSet<_>>();
+
+		let (mut issues, mut fixes) = (0, 0);
+		for manifest in manifests.iter() {
+			let mut fixer = AutoFixer::from_manifest(manifest)?;
+			let krate = fixer.package_name().unwrap_or_default();
+			let found = fixer
+				.git_dependencies()
+				.into_iter()
+				.filter(|dep| !config.is_allowed(&dep.package) && !ignored.contains(&dep.package))
+				.filter(|dep| !allowed.allows(&krate, &["git-deps", &dep.key]))
+				.filter_map(|dep| violation(policy, &dep).map(|reason| (dep, reason)))
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			if global.details() {
+				match fixer.package_name() {
+					Some(name) => out::println!("crate '{}'", name),
+					None => out::println!("workspace root"),
+				}
+			}
+			for (dep, reason) in found.iter() {
+				issues += 1;
+				let msg = format!("dependency '{}' {}: {}", dep.key, reason, dep.git);
+				if global.details() {
+					out::println!("  {}{}", msg, global.code(IssueKind::GitDeps.issue()));
+				}
+				global.annotate(Finding::new(IssueKind::GitDeps.issue(), severity, manifest, msg));
+
+				let Some(lockfile) = lockfile.as_ref() else { continue };
+				match locked_commit(lockfile, dep) {
+					Some(rev) =>
+						if fixer.pin_git_dependency(&dep.key, &rev)? {
+							fixes += 1;
+						},
+					None => {
+						log::warn!(
+							"Cannot fix '{}': no unique commit of '{}' from {} in the lockfile",
+							dep.key,
+							dep.package,
+							dep.git
+						);
+					},
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint git-deps", &self.cargo_args).flag("--fix");
+		let fix_cmd = fixable.then_some(&fix_cmd);
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, fix_cmd, global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
+
+/// Why `dep` violates the `policy`, if it does.
+fn violation(policy: GitDepsPolicy, dep: &GitDependency) -> Option<String> {
+	match policy {
+		GitDepsPolicy::Forbid => Some("is a git dependency".into()),
+		GitDepsPolicy::RequireRev if dep.rev.is_none() => Some(match (&dep.branch, &dep.tag) {
+			(Some(branch), _) => format!("follows branch '{}' instead of a pinned 'rev'", branch),
+			(None, Some(tag)) => format!("uses tag '{}' instead of a pinned 'rev'", tag),
+			(None, None) => "is not pinned to a 'rev'".into(),
+		}),
+		GitDepsPolicy::RequireWorkspace if !dep.workspace =>
+			Some("is not declared in the workspace dependencies".into()),
+		_ => None,
+	}
+}
+
+/// The commit that the lockfile resolved a git dependency to.
+///
+/// Returns `None` if the lockfile contains no or multiple commits for it.
+pub(crate) fn locked_commit(lockfile: &DocumentMut, dep: &GitDependency) -> Option<String> {
+	let reference = match (&dep.branch, &dep.tag) {
+		(Some(branch), _) => Some(format!("branch={}", branch)),
+		(None, Some(tag)) => Some(format!("tag={}", tag)),
+		(None, None) => None,
+	};
+	let mut commits = lockfile
+		.get("package")?
+		.as_array_of_tables()?
+		.iter()
+		.filter(|p| p.get("name").and_then(|n| n.as_str()) == Some(dep.package.as_str()))
+		.filter_map(|p| p.get("source")?.as_str()?.strip_prefix("git+"))
+		.filter_map(|source| {
+			let (url, commit) = source.split_once('#')?;
+			let (url, query) = url.split_once('?').map_or((url, None), |(u, q)| (u, Some(q)));
+			(same_repo(url, &dep.git) && query == reference.as_deref()).then(|| commit.to_string())
+		})
+		.collect::<BTreeSet<_>>();
+
+	if commits.len() == 1 {
+		commits.pop_first()
+	} else {
+		None
+	}
+}
+
+/// Whether two git URLs point to the same repository.
+fn same_repo(a: &str, b: &str) -> bool {
+	let normalize = |url: &str| url.trim_end_matches('/').trim_end_matches(".git").to_string();
+	normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn dep(branch: Option<&str>) -> GitDependency {
+		GitDependency {
+			key: "b".into(),
+			package: "b".into(),
+			git: "https://github.com/example/b.git".into(),
+			rev: None,
+			branch: branch.map(Into::into),
+			tag: None,
+			workspace: false,
+		}
+	}
+
+	#[test]
+	fn locked_commit_works() {
+		let lockfile: DocumentMut = r#"
+[[package]]
+name = "b"
+version = "2.0.0"
+source = "git+https://github.com/example/b#1111111"
+
+[[package]]
+name = "b"
+version = "2.1.0"
+source = "git+https://github.com/example/b?branch=dev#2222222"
+
+[[package]]
+name = "b"
+version = "2.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#
+		.parse()
+		.unwrap();
+
+		assert_eq!(locked_commit(&lockfile, &dep(None)), Some("1111111".into()));
+		assert_eq!(locked_commit(&lockfile, &dep(Some("dev"))), Some("2222222".into()));
+		assert_eq!(locked_commit(&lockfile, &dep(Some("main"))), None);
+	}
+}
@@ -2,16 +2,32 @@
 // SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
 
 use crate::{
-	cmd::{lint::AutoFixer, resolve_dep, CargoArgs, GlobalArgs},
+	cmd::{
+		lint::{error_stats, AutoFixer},
+		output::Finding,
+		CargoArgs, FixCommand, GlobalArgs, PackageIndex,
+	},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
 	grammar::plural,
-	log,
+	log, out,
 };
+use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{DependencyKind, Package};
 use std::{
 	collections::{btree_map::Entry, BTreeMap},
+	fmt::Write,
 	fs::canonicalize,
 };
 
+/// The attribute that makes a crate no-std unless its `std` feature is enabled.
+pub const NO_STD_CFG_ATTR: &str = "#![cfg_attr(not(feature = \"std\"), no_std)]";
+/// The attribute that makes a crate no-std unconditionally.
+pub const NO_STD_ATTR: &str = "#![no_std]";
+
 #[derive(Debug, clap::Parser)]
 pub struct NoStdCmd {
 	#[clap(subcommand)]
@@ -23,6 +39,8 @@ pub enum NoStdSubCmd {
 	/// Default features of no-std dependencies are disabled if the crate itself supports no-std.
 	#[clap(name = "default-features-of-nostd-dependencies-disabled")]
 	DefaultFeaturesDisabled(DefaultFeaturesDisabledCmd),
+	/// The no-std attributes in the `lib.rs` match the features and dependencies of the crate.
+	AttrCheck(AttrCheckCmd),
 }
 
 #[derive(Debug, clap::Parser)]
@@ -36,27 +54,170 @@ pub struct DefaultFeaturesDisabledCmd {
 	fix: bool,
 }
 
+/// Check the no-std attributes in the `lib.rs` of all workspace members.
+///
+/// Crates with a `std` feature should only be no-std when it is disabled, and crates that are
+/// unconditionally `#![no_std]` should not enable the default features of their dependencies.
+#[derive(Debug, clap::Parser)]
+pub struct AttrCheckCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// Whether to fix the issues.
+	#[clap(long, short)]
+	fix: bool,
+}
+
 impl NoStdCmd {
 	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		match &self.sub {
 			NoStdSubCmd::DefaultFeaturesDisabled(cmd) => cmd.run(global),
+			NoStdSubCmd::AttrCheck(cmd) => cmd.run(global),
+		}
+	}
+
+	pub(crate) fn writes_manifests(&self) -> bool {
+		match &self.sub {
+			NoStdSubCmd::DefaultFeaturesDisabled(cmd) => cmd.fix,
+			NoStdSubCmd::AttrCheck(cmd) => cmd.fix,
+		}
+	}
+}
+
+impl AttrCheckCmd {
+	pub(crate) fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severities = SeverityTable::from_workspace(&root)?;
+		let attr_severity = severities.get(IssueKind::NostdCfgAttr);
+		let deps_severity = severities.get(IssueKind::NostdDefaultFeatures);
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut errors, mut warnings, mut fixes) = (0, 0, 0);
+		let mut count = |severity: Severity| match severity {
+			Severity::Warn => warnings += 1,
+			_ => errors += 1,
+		};
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let Some((lib_rs, content)) = read_lib_rs(pkg)? else { continue };
+			let details = g.details();
+			let mut printed = false;
+			let mut header = || {
+				if details && !std::mem::replace(&mut printed, true) {
+					out::println!("crate '{}'", pkg.name);
+				}
+			};
+
+			let missing_attr = pkg.features.contains_key("std") &&
+				!content.contains(NO_STD_CFG_ATTR) &&
+				!content.contains(NO_STD_ATTR) &&
+				attr_severity != Severity::Allow &&
+				!allowed.allows(&pkg.name, &["no-std", "cfg-attr"]);
+			if missing_attr {
+				header();
+				count(attr_severity);
+				let msg = format!(
+					"has a 'std' feature but its lib.rs does not contain '{}'",
+					NO_STD_CFG_ATTR
+				);
+				if details {
+					out::println!("  {}{}", msg, g.code(IssueKind::NostdCfgAttr.issue()));
+				}
+				g.annotate(Finding::new(
+					IssueKind::NostdCfgAttr.issue(),
+					attr_severity,
+					lib_rs.as_std_path(),
+					format!("crate '{}' {}", pkg.name, msg),
+				));
+				if self.fix {
+					std::fs::write(&lib_rs, insert_cfg_attr(&content))
+						.map_err(|e| format!("Failed to write {}: {}", lib_rs, e))?;
+					fixes += 1;
+				}
+			}
+
+			if !content.contains(NO_STD_ATTR) || deps_severity == Severity::Allow {
+				continue
+			}
+			let mut fixer =
+				if self.fix { Some(AutoFixer::from_manifest(&pkg.manifest_path)?) } else { None };
+			for dep in pkg.dependencies.iter() {
+				let name = dep.rename.as_ref().unwrap_or(&dep.name);
+				if dep.kind != DependencyKind::Normal ||
+					!dep.uses_default_features ||
+					ignored.contains(&dep.name) ||
+					allowed.allows(&pkg.name, &["no-std", name])
+				{
+					continue
+				}
+				header();
+				count(deps_severity);
+				let msg =
+					format!("is '{}' but enables the default features of '{}'", NO_STD_ATTR, name);
+				if details {
+					out::println!("  {}{}", msg, g.code(IssueKind::NostdDefaultFeatures.issue()));
+				}
+				g.annotate(Finding::new(
+					IssueKind::NostdDefaultFeatures.issue(),
+					deps_severity,
+					pkg.manifest_path.as_std_path(),
+					format!("crate '{}' {}", pkg.name, msg),
+				));
+
+				if let Some(fixer) = fixer.as_mut() {
+					fixer.disable_default_features(name)?;
+					fixes += 1;
+				}
+			}
+			if let Some(mut fixer) = fixer.filter(AutoFixer::modified) {
+				g.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let fix_cmd = FixCommand::new("lint no-std attr-check", &self.cargo_args).flag("--fix");
+		if let Some(e) = error_stats(errors, warnings, fixes, self.fix, Some(&fix_cmd), g) {
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			g.exit(g.error_code());
 		}
+		Ok(())
 	}
 }
 
 impl DefaultFeaturesDisabledCmd {
 	pub(crate) fn run(&self, g: &GlobalArgs) -> Result<(), String> {
 		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let severities = SeverityTable::from_workspace(meta.workspace_root.as_std_path())?;
+		let severity = severities.get(IssueKind::NostdDefaultFeatures);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
 		let pkgs = &meta.packages;
+		let index = PackageIndex::new(&meta);
 		let mut cache = BTreeMap::new();
+		let mut crate_cfg = CrateCfgHeuristic {
+			severity: severities.get(IssueKind::NostdCrateCfg),
+			allowed: &allowed,
+			errors: 0,
+			warnings: 0,
+		};
 		let mut autofixer = BTreeMap::new();
 		let mut issues = 0;
 		// Dir that we are allowed to write to.
 		let allowed_dir = canonicalize(meta.workspace_root.as_std_path()).unwrap();
 
-		for lhs in pkgs.iter() {
+		for lhs in pkgs.iter().filter(|p| !ignored.contains(&p.name)) {
 			// check if lhs supports no-std builds
-			if !Self::supports_nostd(g, lhs, &mut cache)? {
+			if !Self::supports_nostd(g, lhs, &mut cache, &mut crate_cfg)? {
 				continue;
 			}
 
@@ -65,9 +226,14 @@ impl DefaultFeaturesDisabledCmd {
 					continue;
 				}
 
-				let Some(rhs) = resolve_dep(lhs, dep, &meta) else { continue };
+				let Some(rhs) = index.resolve_dep(lhs, dep) else { continue };
+				if ignored.contains(&rhs.pkg.name) ||
+					allowed.allows(&lhs.name, &["no-std", &rhs.name()])
+				{
+					continue
+				}
 
-				if !Self::supports_nostd(g, &rhs.pkg, &mut cache)? {
+				if !Self::supports_nostd(g, &rhs.pkg, &mut cache, &mut crate_cfg)? {
 					continue;
 				}
 
@@ -75,10 +241,20 @@ impl DefaultFeaturesDisabledCmd {
 					continue;
 				}
 
-				println!(
+				let msg = format!(
 					"Default features not disabled for dependency: {} -> {}",
-					lhs.name, rhs.pkg.name
+					lhs.name,
+					rhs.display_name()
 				);
+				if g.details() {
+					out::println!("{}{}", msg, g.code(IssueKind::NostdDefaultFeatures.issue()));
+				}
+				g.annotate(Finding::new(
+					IssueKind::NostdDefaultFeatures.issue(),
+					severity,
+					lhs.manifest_path.as_std_path(),
+					msg,
+				));
 
 				let fixer = match autofixer.entry(lhs.manifest_path.clone()) {
 					Entry::Occupied(e) => e.into_mut(),
@@ -97,17 +273,58 @@ impl DefaultFeaturesDisabledCmd {
 				issues += 1;
 			}
 		}
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
 
-		let s = plural(autofixer.len());
-		print!("Found {} issue{} in {} crate{s} ", issues, plural(issues), autofixer.len());
 		if self.fix {
 			for (_, fixer) in autofixer.iter_mut() {
-				fixer.save()?;
+				g.save_fixer(fixer)?;
+			}
+		}
+		let fix_cmd = FixCommand::new(
+			"lint no-std default-features-of-nostd-dependencies-disabled",
+			&self.cargo_args,
+		)
+		.flag("--fix");
+		let (mut errors, mut warnings) =
+			if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		errors += crate_cfg.errors;
+		warnings += crate_cfg.warnings;
+		let fixes = if self.fix { issues } else { 0 };
+		if g.summary_only() {
+			let stats = error_stats(errors, warnings, fixes, self.fix, Some(&fix_cmd), g);
+			out::println!("{}", stats.unwrap_or_default());
+		} else {
+			g.write_stats(errors, fixes, warnings);
+			let s = plural(autofixer.len());
+			out::print!(
+				"Found {} issue{} in {} crate{s} ",
+				issues,
+				plural(issues),
+				autofixer.len()
+			);
+			if self.fix {
+				out::println!("and fixed all of them.");
+			} else {
+				let hint = g.fix_hint(|| format!("Re-run with `{}` to apply fixes.", fix_cmd));
+				out::println!(
+					"and fixed none.{}",
+					hint.map(|h| format!(" {}", h)).unwrap_or_default()
+				);
+			}
+			let heuristic = crate_cfg.errors + crate_cfg.warnings;
+			if heuristic > 0 {
+				out::println!(
+					"Found {} crate{} that may unexpectedly pull in libstd.",
+					heuristic,
+					plural(heuristic)
+				);
 			}
-			println!("and fixed all of them.");
+		}
+
+		// The heuristic cannot be fixed automatically.
+		if (self.fix || severity == Severity::Warn) && crate_cfg.errors == 0 {
 			Ok(())
 		} else {
-			println!("and fixed none. Re-run with --fix to apply fixes.");
 			Err("Several issues were not fixed.".to_string())
 		}
 	}
@@ -116,34 +333,18 @@ impl DefaultFeaturesDisabledCmd {
 		g: &GlobalArgs,
 		krate: &Package,
 		cache: &mut BTreeMap<String, bool>,
+		crate_cfg: &mut CrateCfgHeuristic<'_>,
 	) -> Result<bool, String> {
 		log::debug!("Checking if crate supports no-std: {}", krate.name);
 		if let Some(res) = cache.get(krate.manifest_path.as_str()) {
 			return Ok(*res)
 		}
 
-		// try to find the lib.rs
-		let krate_root = krate
-			.manifest_path
-			.parent()
-			.ok_or_else(|| format!("Could not find parent of manifest: {}", krate.manifest_path))?;
-		let lib_rs = krate_root.join("src/lib.rs");
+		let Some((lib_rs, content)) = read_lib_rs(krate)? else { return Ok(false) };
 
-		if !lib_rs.exists() {
-			return Ok(false)
-		}
-		let content = std::fs::read_to_string(&lib_rs)
-			.map_err(|e| format!("Could not read lib.rs: {}", e))?;
-
-		let ret = if content.contains("#![cfg_attr(not(feature = \"std\"), no_std)]") ||
-			content.contains("#![no_std]")
-		{
+		let ret = if content.contains(NO_STD_CFG_ATTR) || content.contains(NO_STD_ATTR) {
 			if content.contains("\n#![cfg(") {
-				println!(
-					"{}: Crate may unexpectedly pull in libstd: {}",
-					g.yellow("WARN"),
-					krate.name
-				);
+				crate_cfg.report(g, krate, &lib_rs);
 			}
 			log::debug!("Crate supports no-std: {} (path={})", krate.name, krate.manifest_path);
 			true
@@ -155,3 +356,72 @@ impl DefaultFeaturesDisabledCmd {
 		Ok(ret)
 	}
 }
+
+/// Reports no-std crates with a crate level `#![cfg]`, since they may pull in libstd anyway.
+struct CrateCfgHeuristic<'a> {
+	severity: Severity,
+	allowed: &'a AllowedFindings,
+	errors: usize,
+	warnings: usize,
+}
+
+impl CrateCfgHeuristic<'_> {
+	fn report(&mut self, g: &GlobalArgs, krate: &Package, lib_rs: &Utf8Path) {
+		if self.severity == Severity::Allow ||
+			self.allowed.allows(&krate.name, &["no-std", "crate-cfg"])
+		{
+			return
+		}
+		let label = if self.severity == Severity::Warn {
+			self.warnings += 1;
+			g.warning("WARN")
+		} else {
+			self.errors += 1;
+			g.error("ERROR")
+		};
+
+		let issue = IssueKind::NostdCrateCfg.issue();
+		let msg = format!("Crate may unexpectedly pull in libstd: {}", krate.name);
+		if g.details() {
+			out::println!("{}: {}{}", label, msg, g.code(issue));
+		}
+		g.annotate(Finding::new(issue, self.severity, lib_rs.as_std_path(), msg));
+	}
+}
+
+/// Path and content of the `src/lib.rs` of a crate, if it has one.
+fn read_lib_rs(krate: &Package) -> Result<Option<(Utf8PathBuf, String)>, String> {
+	let krate_root = krate
+		.manifest_path
+		.parent()
+		.ok_or_else(|| format!("Could not find parent of manifest: {}", krate.manifest_path))?;
+	let lib_rs = krate_root.join("src/lib.rs");
+
+	if !lib_rs.exists() {
+		return Ok(None)
+	}
+	let content =
+		std::fs::read_to_string(&lib_rs).map_err(|e| format!("Could not read lib.rs: {}", e))?;
+	Ok(Some((lib_rs, content)))
+}
+
+/// Insert the [`NO_STD_CFG_ATTR`] after the leading comments of a `lib.rs`.
+pub(crate) fn insert_cfg_attr(content: &str) -> String {
+	let lines = content.lines().collect::<Vec<_>>();
+	let pos = lines
+		.iter()
+		.position(|l| !l.trim().is_empty() && !l.trim_start().starts_with("//"))
+		.unwrap_or(lines.len());
+	// Other inner attributes can directly follow, but items need some space.
+	let spacing = if lines.get(pos).map_or(true, |l| l.starts_with("#![")) { "" } else { "\n" };
+
+	let mut out = String::new();
+	for line in lines[..pos].iter() {
+		let _ = writeln!(out, "{}", line);
+	}
+	let _ = write!(out, "{}\n{}", NO_STD_CFG_ATTR, spacing);
+	for line in lines[pos..].iter() {
+		let _ = writeln!(out, "{}", line);
+	}
+	out
+}
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find optional dependencies that cannot be enabled by a feature of the same name.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		optional_deps::{OptionalDepsConfig, OptionalDepsStyle},
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::Package;
+use clap::ValueEnum;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs::canonicalize,
+};
+
+/// Find optional dependencies without a feature of the same name that enables them.
+///
+/// With the `explicit` style, every optional dependency `foo` needs a passthrough feature
+/// `foo = ["dep:foo"]`. The `implicit` style also accepts the implicit feature that cargo creates
+/// when no `dep:foo` entry exists. The style is read from the `optional-deps` section of the config
+/// file. The fix adds `dep:foo` to the feature `foo` and creates it if needed.
+#[derive(Debug, clap::Parser)]
+pub struct OptionalDepsCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+
+	/// Use this style instead of the one from the config file.
+	#[clap(long, value_enum)]
+	style: Option<OptionalDepsStyle>,
+}
+
+/// Why an optional dependency cannot be enabled by a passthrough feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Finding {
+	/// The dependency only has the implicit feature that cargo creates.
+	Implicit { dependency: String },
+	/// A `dep:` entry removed the implicit feature and there is no explicit one.
+	Missing { dependency: String },
+	/// The feature of the same name does not enable the dependency.
+	NotEnabled { dependency: String },
+}
+
+impl OptionalDepsCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::OptionalDeps);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let style = self.style.unwrap_or(OptionalDepsConfig::from_workspace(&root)?.style);
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let mut fixer = AutoFixer::from_manifest(&pkg.manifest_path)?;
+			let found = findings(style, pkg, &fixer.features())
+				.into_iter()
+				.filter(|f| !ignored.contains(f.dependency()))
+				.filter(|f| !allowed.allows(&pkg.name, &["optional-deps", f.dependency()]))
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			for finding in found.iter() {
+				issues += 1;
+				let msg = match finding {
+					Finding::Implicit { dependency } => format!(
+						"optional dependency '{}' relies on the implicit feature '{}'",
+						dependency, dependency
+					),
+					Finding::Missing { dependency } => format!(
+						"optional dependency '{}' has no feature '{}'",
+						dependency, dependency
+					),
+					Finding::NotEnabled { dependency } =>
+						format!("feature '{}' does not enable 'dep:{}'", dependency, dependency),
+				};
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::OptionalDeps.issue()));
+				}
+				global.annotate(output::Finding::new(
+					IssueKind::OptionalDeps.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				if self.fixer_args.enable {
+					let dependency = finding.dependency();
+					fixer.add_to_feature(dependency, &format!("dep:{}", dependency))?;
+					fixes += 1;
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let mut fix_cmd = FixCommand::new("lint optional-deps", &self.cargo_args);
+		if let Some(style) = self.style.and_then(|s| s.to_possible_value()) {
+			fix_cmd = fix_cmd.arg("--style", style.get_name());
+		}
+		let fix_cmd = fix_cmd.flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
+
+impl Finding {
+	/// The name under which the optional dependency is imported.
+	fn dependency(&self) -> &str {
+		match self {
+			Self::Implicit { dependency } |
+			Self::Missing { dependency } |
+			Self::NotEnabled { dependency } => dependency,
+		}
+	}
+}
+
+/// All optional dependencies of `pkg` that violate `style`.
+///
+/// `features` are the features as written in the manifest, since the metadata also contains the
+/// implicit ones.
+fn findings(
+	style: OptionalDepsStyle,
+	pkg: &Package,
+	features: &BTreeMap<String, Vec<String>>,
+) -> Vec<Finding> {
+	let optional = pkg
+		.dependencies
+		.iter()
+		.filter(|dep| dep.optional)
+		.map(|dep| dep.rename.clone().unwrap_or_else(|| dep.name.clone()))
+		.collect::<BTreeSet<_>>();
+
+	optional
+		.into_iter()
+		.filter_map(|dependency| {
+			let entry = format!("dep:{}", dependency);
+			match features.get(&dependency) {
+				Some(values) if values.contains(&entry) => None,
+				Some(_) => Some(Finding::NotEnabled { dependency }),
+				None if features.values().flatten().any(|v| *v == entry) =>
+					Some(Finding::Missing { dependency }),
+				None if style == OptionalDepsStyle::Explicit =>
+					Some(Finding::Implicit { dependency }),
+				None => None,
+			}
+		})
+		.collect()
+}
@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find path dependencies that point outside of the workspace.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		path_deps::PathDepsConfig,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	log, out,
+};
+use semver::Version;
+use std::{
+	collections::BTreeSet,
+	fs::canonicalize,
+	path::{Path, PathBuf},
+};
+use toml_edit::DocumentMut;
+
+/// Source IDs of crates.io in the lockfile.
+const CRATES_IO_SOURCES: [&str; 2] =
+	["registry+https://github.com/rust-lang/crates.io-index", "sparse+https://index.crates.io/"];
+
+/// Find dependencies whose `path` points outside of the workspace.
+///
+/// These make builds depend on the directory layout of the machine and are therefore often not
+/// reproducible. The fix replaces the `path` with the crates.io version from the lockfile.
+#[derive(Debug, clap::Parser)]
+pub struct NoPathDepsOutsideWorkspaceCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+impl NoPathDepsOutsideWorkspaceCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity =
+			SeverityTable::from_workspace(&root)?.get(IssueKind::PathDepsOutsideWorkspace);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let config = PathDepsConfig::from_workspace(&root)?;
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let lockfile = self.fixer_args.enable.then(|| load_lockfile(&root)).transpose()?;
+
+		// The root manifest can contain `workspace.dependencies` with paths.
+		let manifests = meta
+			.packages
+			.iter()
+			.filter(|p| meta.workspace_members.contains(&p.id))
+			.map(|p| p.manifest_path.clone().into_std_path_buf())
+			.chain(std::iter::once(root.join("Cargo.toml")))
+			.filter_map(|p| canonicalize(p).ok())
+			.collect::<BTreeSet<_>>();
+
+		let (mut issues, mut fixes) = (0, 0);
+		for manifest in manifests.iter() {
+			let mut fixer = AutoFixer::from_manifest(manifest)?;
+			let dir = manifest.parent().unwrap();
+			let krate = fixer.package_name().unwrap_or_default();
+			let outside = fixer
+				.path_dependencies()
+				.into_iter()
+				.filter(|dep| !resolve(dir, &dep.path).starts_with(&root))
+				.filter(|dep| {
+					let is_allowed =
+						config.is_allowed(&dep.package) || ignored.contains(&dep.package);
+					if is_allowed {
+						log::debug!("Path dependency '{}' is allowed by the config", dep.package);
+					}
+					!is_allowed
+				})
+				.filter(|dep| {
+					!allowed.allows(&krate, &["no-path-deps-outside-workspace", &dep.key])
+				})
+				.collect::<Vec<_>>();
+			if outside.is_empty() {
+				continue
+			}
+
+			if global.details() {
+				match fixer.package_name() {
+					Some(name) => out::println!("crate '{}'", name),
+					None => out::println!("workspace root"),
+				}
+			}
+			for dep in outside.iter() {
+				issues += 1;
+				let msg = format!(
+					"dependency '{}' has a path outside of the workspace: {}",
+					dep.key, dep.path
+				);
+				if global.details() {
+					out::println!(
+						"  {}{}",
+						msg,
+						global.code(IssueKind::PathDepsOutsideWorkspace.issue())
+					);
+				}
+				global.annotate(Finding::new(
+					IssueKind::PathDepsOutsideWorkspace.issue(),
+					severity,
+					manifest,
+					msg,
+				));
+
+				let Some(lockfile) = lockfile.as_ref() else { continue };
+				match crates_io_version(lockfile, &dep.package) {
+					Some(version) =>
+						if fixer.replace_path_with_version(&dep.key, &version.to_string())? {
+							fixes += 1;
+						},
+					None => {
+						log::warn!(
+							"Cannot fix '{}': crate '{}' from crates.io is not in the lockfile",
+							dep.key,
+							dep.package
+						);
+					},
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd =
+			FixCommand::new("lint no-path-deps-outside-workspace", &self.cargo_args).flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
+
+/// Resolve the `path` of a dependency relative to the directory of its manifest.
+fn resolve(dir: &Path, path: &str) -> PathBuf {
+	let path = dir.join(path);
+	canonicalize(&path).unwrap_or(path)
+}
+
+/// Load the lockfile of the workspace. A missing lockfile is treated as empty.
+pub(crate) fn load_lockfile(root: &Path) -> Result<DocumentMut, String> {
+	let path = root.join("Cargo.lock");
+	if !path.exists() {
+		log::warn!("No lockfile found at {:?}", path);
+		return Ok(DocumentMut::new())
+	}
+	let content = std::fs::read_to_string(&path)
+		.map_err(|e| format!("Failed to read lockfile {:?}: {}", path, e))?;
+
+	content
+		.parse()
+		.map_err(|e| format!("Failed to parse lockfile {:?}: {}", path, e))
+}
+
+/// The highest version of a crates.io crate in the lockfile.
+pub(crate) fn crates_io_version(lockfile: &DocumentMut, name: &str) -> Option<Version> {
+	lockfile
+		.get("package")?
+		.as_array_of_tables()?
+		.iter()
+		.filter(|p| p.get("name").and_then(|n| n.as_str()) == Some(name))
+		.filter(|p| {
+			p.get("source")
+				.and_then(|s| s.as_str())
+				.map_or(false, |s| CRATES_IO_SOURCES.contains(&s))
+		})
+		.filter_map(|p| p.get("version")?.as_str()?.parse().ok())
+		.max()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn crates_io_version_works() {
+		let lockfile: DocumentMut = r#"
+[[package]]
+name = "a"
+version = "0.1.0"
+
+[[package]]
+name = "a"
+version = "1.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "a"
+version = "1.10.0"
+source = "sparse+https://index.crates.io/"
+
+[[package]]
+name = "b"
+version = "2.0.0"
+source = "git+https://github.com/example/b#abc"
+"#
+		.parse()
+		.unwrap();
+
+		assert_eq!(crates_io_version(&lockfile, "a"), Some(Version::new(1, 10, 0)));
+		assert_eq!(crates_io_version(&lockfile, "b"), None);
+		assert_eq!(crates_io_version(&lockfile, "c"), None);
+	}
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use crate::{
+	cmd::{
+		lint::{build_feature_dag, error_stats, without_ignored, EnabledFeaturesArgs},
+		CargoArgs, GlobalArgs, PackageIndex,
+	},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		rules::{rules_from_workspace, Rule, RuleKind},
+	},
+	grammar::plural,
+	graph::CrateAndFeature,
+	log, out,
+};
+use std::collections::BTreeMap;
+
+/// Check all forbidden feature implications of the `rules` config section at once.
+#[derive(Debug, clap::Parser)]
+pub struct RulesCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// Rule to check instead of the ones in the config file, for example
+	/// `std!->runtime-benchmarks`.
+	#[clap(long = "rule")]
+	rules: Vec<Rule>,
+
+	/// Delimiter for rendering dependency paths.
+	#[clap(long, default_value = " -> ")]
+	path_delimiter: String,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	enabled_features: EnabledFeaturesArgs,
+}
+
+impl RulesCmd {
+	/// What this lint enforces, for `zepter run --describe`.
+	pub(crate) fn describe(&self) -> Vec<String> {
+		let mut lines = match self.rules.as_slice() {
+			[] => vec!["No rule of the `rules` config section is violated.".to_string()],
+			[rule] => vec![rule.describe()],
+			rules => std::iter::once("These feature rules hold:".to_string())
+				.chain(rules.iter().map(Rule::describe))
+				.collect(),
+		};
+		lines.extend(self.enabled_features.describe());
+		lines
+	}
+
+	pub(crate) fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let rules = if self.rules.is_empty() {
+			rules_from_workspace(meta.workspace_root.as_std_path())?
+		} else {
+			self.rules.clone()
+		};
+		if rules.is_empty() {
+			return Err("No rules found. Add them to the `rules` section of the config file or pass them with `--rule`.".into())
+		}
+
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let index = PackageIndex::new(&meta);
+		let dag = without_ignored(&meta, build_feature_dag(&index, &meta.packages), &ignored);
+		let dag = self.enabled_features.prune(&index, dag)?;
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+		let names = meta
+			.packages
+			.iter()
+			.map(|p| (p.id.to_string(), p.name.as_str()))
+			.collect::<BTreeMap<_, _>>();
+		let name = |id: &str| {
+			let id = id.trim_end_matches('?');
+			names.get(id).copied().unwrap_or(id).to_string()
+		};
+		let delimiter = self.path_delimiter.replace("\\n", "\n").replace("\\t", "\t");
+		let mut violated = 0;
+
+		for rule in rules.iter() {
+			log::info!("Checking rule '{}'", rule);
+			let mut violations = Vec::new();
+
+			for node @ CrateAndFeature(id, feature) in dag.lhs_nodes() {
+				if feature != &rule.precondition {
+					continue
+				}
+
+				match rule.kind {
+					RuleKind::NeverEnables => {
+						let Some(enabled) = dag.edges.get(node) else { continue };
+						for CrateAndFeature(dep, _) in
+							enabled.iter().filter(|CrateAndFeature(_, f)| f == &rule.stays_disabled)
+						{
+							let finding = [
+								"never-enables",
+								&rule.precondition,
+								&rule.stays_disabled,
+								&name(dep),
+							];
+							if allowed.allows(&name(id), &finding) {
+								continue
+							}
+							violations.push(format!(
+								"crate '{}' enables '{}/{}'",
+								name(id),
+								name(dep),
+								rule.stays_disabled
+							));
+						}
+					},
+					RuleKind::NeverImplies => {
+						let finding = ["never-implies", &rule.precondition, &rule.stays_disabled];
+						if allowed.allows(&name(id), &finding) {
+							continue
+						}
+						let Some(path) = dag.reachable_predicate(node, |CrateAndFeature(_, f)| {
+							f == &rule.stays_disabled
+						}) else {
+							continue
+						};
+
+						let mut out = Vec::new();
+						path.for_each(|CrateAndFeature(id, feature)| {
+							out.push(format!("{}/{}", name(id), feature));
+						});
+						violations.push(format!("via path: {}", out.join(&delimiter)));
+						// One path is enough to show that the rule is broken.
+						break
+					},
+				}
+			}
+
+			if violations.is_empty() {
+				continue
+			}
+			violated += 1;
+			if !g.summary_only() {
+				out::println!("Rule '{}' is violated:{}", rule, g.code(rule.kind.issue()));
+				for violation in violations {
+					out::println!("  {}", violation);
+				}
+			}
+		}
+
+		if g.summary_only() {
+			out::println!("{}", error_stats(violated, 0, 0, false, None, g).unwrap_or_default());
+		} else if violated == 0 {
+			out::println!("All {} rule{} hold.", rules.len(), plural(rules.len()));
+		}
+
+		if violated == 0 {
+			Ok(())
+		} else {
+			Err(format!("Found {} violated rule{}.", violated, plural(violated)))
+		}
+	}
+}
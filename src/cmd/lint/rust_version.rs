@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Check the `rust-version` of all workspace members.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs, RustVersion},
+	cmd::{output, CargoArgs, FixCommand, GlobalArgs, PackageIndex},
+	config::{
+		allow::AllowedFindings,
+		semver::Semver,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::{DependencyKind, Package};
+use std::fs::canonicalize;
+
+/// Check the `rust-version` of all workspace members.
+///
+/// It must be set, should be inherited from `[workspace.package]` if the workspace has one and
+/// must not be lower than the `rust-version` of any normal or build dependency. The fix sets
+/// `rust-version.workspace = true`.
+#[derive(Debug, clap::Parser)]
+pub struct RustVersionCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+/// Something that is wrong with the `rust-version` of a crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Finding {
+	/// The crate has no `rust-version`.
+	Missing,
+	/// The crate sets a different version than the workspace.
+	Inconsistent { version: Semver, workspace: Semver },
+	/// A dependency requires a newer version than the crate.
+	TooLow { version: Semver, required: Semver, dependency: String },
+}
+
+impl RustVersionCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::RustVersion);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let index = PackageIndex::new(&meta);
+		let workspace = AutoFixer::from_manifest(root.join("Cargo.toml"))?
+			.workspace_rust_version()
+			.map(|v| parse_version(&v))
+			.transpose()?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixes) = (0, 0);
+		for pkg in members {
+			let mut fixer = AutoFixer::from_manifest(&pkg.manifest_path)?;
+			let findings = findings(&index, pkg, fixer.rust_version(), workspace)?
+				.into_iter()
+				.filter(|f| !allowed.allows(&pkg.name, &allow_key(f)))
+				.collect::<Vec<_>>();
+			if findings.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			for finding in findings.iter() {
+				issues += 1;
+				let msg = match finding {
+					Finding::Missing => "'rust-version' is missing".into(),
+					Finding::Inconsistent { version, workspace } => format!(
+						"'rust-version' {} differs from the workspace default {}",
+						version, workspace
+					),
+					Finding::TooLow { version, required, dependency } => format!(
+						"'rust-version' {} is lower than {} of dependency '{}'",
+						version, required, dependency
+					),
+				};
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::RustVersion.issue()));
+				}
+				global.annotate(output::Finding::new(
+					IssueKind::RustVersion.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				let fixable = workspace.is_some() && !matches!(finding, Finding::TooLow { .. });
+				if self.fixer_args.enable && fixable && fixer.inherit_rust_version()? {
+					fixes += 1;
+				}
+			}
+			if fixer.modified() {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint rust-version", &self.cargo_args).flag("--fix");
+		let fix_cmd = workspace.is_some().then_some(&fix_cmd);
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, fix_cmd, global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+}
+
+/// All findings of a workspace member.
+///
+/// `manifest` is the `rust-version` as written in the manifest and `workspace` the one of the
+/// workspace, if any.
+fn findings(
+	index: &PackageIndex<'_>,
+	pkg: &Package,
+	manifest: Option<RustVersion>,
+	workspace: Option<Semver>,
+) -> Result<Vec<Finding>, String> {
+	let Some(version) = pkg.rust_version.as_ref() else { return Ok(vec![Finding::Missing]) };
+	let version = parse_version(&version.to_string())?;
+	let mut found = Vec::new();
+
+	if let (Some(RustVersion::Version(_)), Some(workspace)) = (manifest, workspace) {
+		if version != workspace {
+			found.push(Finding::Inconsistent { version, workspace });
+		}
+	}
+
+	let required = pkg
+		.dependencies
+		.iter()
+		.filter(|dep| dep.kind != DependencyKind::Development)
+		.filter_map(|dep| index.resolve_dep(pkg, dep))
+		.filter_map(|dep| {
+			let required = parse_version(&dep.pkg.rust_version.as_ref()?.to_string()).ok()?;
+			Some((required, dep.display_name()))
+		})
+		.max();
+	if let Some((required, dependency)) = required {
+		if required > version {
+			found.push(Finding::TooLow { version, required, dependency });
+		}
+	}
+
+	Ok(found)
+}
+
+/// The parts of a finding that `package.metadata.zepter` can allow.
+fn allow_key(finding: &Finding) -> Vec<&str> {
+	match finding {
+		Finding::Missing => vec!["rust-version", "missing"],
+		Finding::Inconsistent { .. } => vec!["rust-version", "inconsistent"],
+		Finding::TooLow { dependency, .. } => vec!["rust-version", "dependency", dependency],
+	}
+}
+
+fn parse_version(version: &str) -> Result<Semver, String> {
+	Semver::try_from(version).map_err(|()| format!("Invalid rust-version '{}'", version))
+}
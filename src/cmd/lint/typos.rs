@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find likely typos in feature names.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs, PackageIndex},
+	config::{allow::AllowedFindings, ignore::IgnoredDependencies, severity::Severity},
+	issues, log, out,
+};
+use cargo_metadata::Package;
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fs::canonicalize,
+};
+
+/// Find feature references that are likely typos.
+///
+/// This detects references like `dep/featur` where `dep` has a feature `feature`, and feature
+/// names that only differ in case or `-`/`_` from the spelling that is used by most crates.
+#[derive(Debug, clap::Parser)]
+pub struct FeatureTyposCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// Maximal edit distance for a feature to be considered a typo of another.
+	#[clap(long, default_value_t = 2)]
+	max_distance: usize,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+/// A feature reference that does not exist but is close to one that does.
+struct Typo {
+	/// The feature that contains the reference.
+	feature: String,
+	/// The reference as written in the manifest.
+	value: String,
+	/// The closest existing references.
+	suggestions: Vec<String>,
+}
+
+impl FeatureTyposCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let ignored = IgnoredDependencies::from_workspace(meta.workspace_root.as_std_path())?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+		let index = PackageIndex::new(&meta);
+		let members = meta
+			.packages
+			.iter()
+			.filter(|p| meta.workspace_members.contains(&p.id) && !ignored.contains(&p.name))
+			.collect::<Vec<_>>();
+		// Dir that we are allowed to write to.
+		let allowed_dir = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let canonical = canonical_spellings(&members);
+
+		let (mut errors, mut warnings, mut fixes) = (0, 0, 0);
+		for pkg in members.iter() {
+			let typos = self
+				.typos(pkg, &index, &ignored)
+				.into_iter()
+				.filter(|t| !allowed.allows(&pkg.name, &["feature-typos", &t.feature, &t.value]))
+				.collect::<Vec<_>>();
+			let spellings = pkg
+				.features
+				.keys()
+				.filter_map(|f| canonical.get(f).map(|c| (f, c)))
+				.collect::<Vec<_>>();
+			if typos.is_empty() && spellings.is_empty() {
+				continue
+			}
+
+			let mut fixer = if self.fixer_args.enable {
+				let krate_path = canonicalize(pkg.manifest_path.as_std_path()).unwrap();
+				if !krate_path.starts_with(&allowed_dir) {
+					return Err(format!("Cannot write to path: {}", krate_path.display()))
+				}
+				Some(AutoFixer::from_manifest(&krate_path)?)
+			} else {
+				None
+			};
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			for typo in typos.iter() {
+				errors += 1;
+				let suggestions = typo
+					.suggestions
+					.iter()
+					.map(|s| format!("'{}'", s))
+					.collect::<Vec<_>>()
+					.join(", ");
+				let one_of = if typo.suggestions.len() > 1 { "one of " } else { "" };
+				if details {
+					out::println!(
+						"  feature '{}'\n    unknown feature '{}', did you mean {}{}?{}",
+						typo.feature,
+						typo.value,
+						one_of,
+						suggestions,
+						global.code(&issues::FEATURE_TYPO)
+					);
+				}
+				global.annotate(Finding::new(
+					&issues::FEATURE_TYPO,
+					Severity::Deny,
+					pkg.manifest_path.as_std_path(),
+					format!(
+						"feature '{}' enables unknown feature '{}', did you mean {}{}?",
+						typo.feature, typo.value, one_of, suggestions
+					),
+				));
+
+				if let (Some(fixer), [suggestion]) = (fixer.as_mut(), typo.suggestions.as_slice()) {
+					fixer.replace_in_feature(&typo.feature, &typo.value, suggestion)?;
+					fixes += 1;
+				}
+			}
+			for (feature, canonical) in spellings {
+				warnings += 1;
+				let msg = format!(
+					"feature '{}' only differs in case or separators from '{}'",
+					feature, canonical
+				);
+				if details {
+					out::println!("  {}{}", msg, global.code(&issues::FEATURE_SPELLING));
+				}
+				global.annotate(Finding::new(
+					&issues::FEATURE_SPELLING,
+					Severity::Warn,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+			}
+
+			if let Some(fixer) = fixer.as_mut() {
+				if fixer.modified() {
+					global.save_fixer(fixer)?;
+				}
+			}
+		}
+
+		let mut fix_cmd = FixCommand::new("lint feature-typos", &self.cargo_args);
+		if self.max_distance != 2 {
+			fix_cmd = fix_cmd.arg("--max-distance", self.max_distance.to_string());
+		}
+		let fix_cmd = fix_cmd.flag("--fix");
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, Some(&fix_cmd), global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+
+	/// All references of a crate that likely contain a typo.
+	fn typos(
+		&self,
+		pkg: &Package,
+		index: &PackageIndex<'_>,
+		ignored: &IgnoredDependencies,
+	) -> Vec<Typo> {
+		let mut typos = Vec::new();
+
+		for (feature, values) in pkg.features.iter() {
+			for value in values.iter() {
+				if value.starts_with("dep:") {
+					continue
+				}
+
+				let suggestions = if let Some((dep, dep_feature)) = value.split_once('/') {
+					let (dep, weak) = match dep.strip_suffix('?') {
+						Some(dep) => (dep, "?"),
+						None => (dep, ""),
+					};
+					let Some(rhs) = pkg
+						.dependencies
+						.iter()
+						.filter(|d| d.rename.as_ref().unwrap_or(&d.name) == dep)
+						.find_map(|d| index.resolve_dep(pkg, d))
+					else {
+						log::debug!("Could not resolve dependency '{}' of '{}'", dep, pkg.name);
+						continue
+					};
+					if rhs.pkg.features.contains_key(dep_feature) || ignored.contains(&rhs.pkg.name)
+					{
+						continue
+					}
+
+					closest(dep_feature, rhs.pkg.features.keys(), self.max_distance)
+						.into_iter()
+						.map(|s| format!("{}{}/{}", dep, weak, s))
+						.collect::<Vec<_>>()
+				} else {
+					let is_optional_dep = pkg
+						.dependencies
+						.iter()
+						.any(|d| d.optional && d.rename.as_ref().unwrap_or(&d.name) == value);
+					if pkg.features.contains_key(value) || is_optional_dep {
+						continue
+					}
+
+					closest(value, pkg.features.keys(), self.max_distance)
+				};
+
+				if !suggestions.is_empty() {
+					typos.push(Typo {
+						feature: feature.clone(),
+						value: value.clone(),
+						suggestions,
+					});
+				}
+			}
+		}
+
+		typos
+	}
+}
+
+/// Maps each non-canonical feature name to its canonical spelling.
+///
+/// Feature names are grouped by their lowercase form with `_` replaced by `-`. The spelling that is
+/// used by the most crates of the group is canonical. Ties are broken in favour of the normalized
+/// form and then alphabetically.
+fn canonical_spellings(pkgs: &[&Package]) -> BTreeMap<String, String> {
+	let mut groups = BTreeMap::<String, BTreeMap<&String, usize>>::new();
+	for pkg in pkgs.iter() {
+		for feature in pkg.features.keys() {
+			*groups.entry(normalize(feature)).or_default().entry(feature).or_default() += 1;
+		}
+	}
+
+	let mut canonical = BTreeMap::new();
+	for (normalized, spellings) in groups.into_iter().filter(|(_, s)| s.len() > 1) {
+		let (best, _) = spellings
+			.iter()
+			.max_by_key(|(s, count)| (**count, **s == &normalized, core::cmp::Reverse(*s)))
+			.expect("Groups are never empty");
+
+		for spelling in spellings.keys().filter(|s| *s != best) {
+			canonical.insert((*spelling).clone(), (*best).clone());
+		}
+	}
+	canonical
+}
+
+fn normalize(feature: &str) -> String {
+	feature.to_lowercase().replace('_', "-")
+}
+
+/// Find the candidates that are closest to `name` while not being further away than `max`.
+///
+/// Candidates that only differ in case or separators are always preferred.
+pub(crate) fn closest<'a, I: IntoIterator<Item = &'a String>>(
+	name: &str,
+	candidates: I,
+	max: usize,
+) -> Vec<String> {
+	let mut best = BTreeMap::<usize, BTreeSet<String>>::new();
+	for candidate in candidates.into_iter().filter(|c| *c != name) {
+		let distance = if normalize(candidate) == normalize(name) {
+			0
+		} else {
+			edit_distance(name, candidate)
+		};
+
+		if distance <= max {
+			best.entry(distance).or_default().insert(candidate.clone());
+		}
+	}
+
+	best.into_values().next().map(|c| c.into_iter().collect()).unwrap_or_default()
+}
+
+/// The Damerau-Levenshtein distance (optimal string alignment) between two strings.
+///
+/// Counts insertions, deletions, substitutions and swaps of adjacent characters.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+	let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+	let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+	for (i, row) in d.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	for (j, cell) in d[0].iter_mut().enumerate() {
+		*cell = j;
+	}
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+			}
+		}
+	}
+
+	d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn edit_distance_works() {
+		assert_eq!(edit_distance("std", "std"), 0);
+		assert_eq!(edit_distance("std", "sdt"), 1);
+		assert_eq!(edit_distance("std", "st"), 1);
+		assert_eq!(edit_distance("try-runtime", "try-runtim"), 1);
+		assert_eq!(edit_distance("std", "no-std"), 3);
+		assert_eq!(edit_distance("", "abc"), 3);
+	}
+
+	#[test]
+	fn closest_prefers_spelling_variants() {
+		let candidates = ["std", "Std", "stx", "runtime_benchmarks"].map(String::from);
+
+		assert_eq!(closest("STD", &candidates, 2), vec!["Std", "std"]);
+		assert_eq!(closest("sdt", &candidates, 2), vec!["std"]);
+		assert_eq!(closest("runtime-benchmarks", &candidates, 2), vec!["runtime_benchmarks"]);
+		assert!(closest("foo", &candidates, 2).is_empty());
+	}
+}
@@ -0,0 +1,211 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Find weak feature references to dependencies that are always enabled.
+
+use super::error_stats;
+use crate::{
+	autofix::{AutoFixer, AutoFixerArgs},
+	cmd::{output::Finding, CargoArgs, FixCommand, GlobalArgs},
+	config::{
+		allow::AllowedFindings,
+		ignore::IgnoredDependencies,
+		severity::{IssueKind, Severity, SeverityTable},
+	},
+	out,
+};
+use cargo_metadata::Package;
+use std::{collections::BTreeSet, fs::canonicalize};
+
+/// Find weak feature references like `dep?/std` whose dependency is always enabled.
+///
+/// The `?` only enables the feature if something else enables the optional dependency. This is
+/// misleading if the same feature enables the dependency anyway or if the `default` feature of
+/// the crate does. The fix drops the `?` in the first case. Dependencies that are enabled by
+/// `default` are only reported, since `default-features = false` still disables them.
+#[derive(Debug, clap::Parser)]
+pub struct WeakFeaturesCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	pub(crate) fixer_args: AutoFixerArgs,
+}
+
+/// A weak feature entry whose dependency is always enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WeakFeature {
+	/// The feature that contains the entry.
+	feature: String,
+	/// The entry as written in the manifest, like `dep?/std`.
+	entry: String,
+	/// The name under which the dependency is imported.
+	dependency: String,
+	/// Why the dependency is always enabled.
+	reason: Reason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reason {
+	/// The feature itself enables the optional dependency.
+	EnabledByFeature,
+	/// The `default` feature of the crate enables the optional dependency.
+	EnabledByDefault,
+}
+
+impl WeakFeaturesCmd {
+	pub(crate) fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let root = canonicalize(meta.workspace_root.as_std_path()).unwrap();
+		let severity = SeverityTable::from_workspace(&root)?.get(IssueKind::WeakFeatures);
+		if severity == Severity::Allow {
+			return Ok(())
+		}
+		let ignored = IgnoredDependencies::from_workspace(&root)?;
+		let allowed = AllowedFindings::from_metadata(&meta)?;
+
+		let mut members = meta.workspace_packages();
+		members.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let (mut issues, mut fixable, mut fixes) = (0, 0, 0);
+		for pkg in members.into_iter().filter(|p| !ignored.contains(&p.name)) {
+			let found = Self::weak_features(pkg)
+				.into_iter()
+				.filter(|f| !ignored.contains(&f.dependency))
+				.filter(|f| {
+					!allowed.allows(&pkg.name, &["weak-features", &f.feature, &f.dependency])
+				})
+				.collect::<Vec<_>>();
+			if found.is_empty() {
+				continue
+			}
+
+			let details = global.details();
+			if details {
+				out::println!("crate '{}'", pkg.name);
+			}
+			let mut fixer = if self.fixer_args.enable {
+				Some(AutoFixer::from_manifest(&pkg.manifest_path)?)
+			} else {
+				None
+			};
+			for f in found.iter() {
+				issues += 1;
+				let msg = match f.reason {
+					Reason::EnabledByFeature => format!(
+						"feature '{}' enables '{}' but also enables '{}'",
+						f.feature, f.entry, f.dependency
+					),
+					Reason::EnabledByDefault => format!(
+						"feature '{}' enables '{}' but '{}' is enabled by 'default'",
+						f.feature, f.entry, f.dependency
+					),
+				};
+				if details {
+					out::println!("  {}{}", msg, global.code(IssueKind::WeakFeatures.issue()));
+				}
+				global.annotate(Finding::new(
+					IssueKind::WeakFeatures.issue(),
+					severity,
+					pkg.manifest_path.as_std_path(),
+					msg,
+				));
+
+				if f.reason != Reason::EnabledByFeature {
+					continue
+				}
+				fixable += 1;
+				if let Some(fixer) = fixer.as_mut() {
+					let strong = f.entry.replacen("?/", "/", 1);
+					if pkg.features[&f.feature].contains(&strong) {
+						fixer.remove_from_feature(&f.feature, &f.entry)?;
+					} else {
+						fixer.replace_in_feature(&f.feature, &f.entry, &strong)?;
+					}
+					fixes += 1;
+				}
+			}
+			if let Some(mut fixer) = fixer.filter(AutoFixer::modified) {
+				global.save_fixer(&mut fixer)?;
+			}
+		}
+
+		let (errors, warnings) = if severity == Severity::Warn { (0, issues) } else { (issues, 0) };
+		let fix_cmd = FixCommand::new("lint weak-features", &self.cargo_args).flag("--fix");
+		let fix_cmd = (fixable > 0).then_some(&fix_cmd);
+		if let Some(e) =
+			error_stats(errors, warnings, fixes, self.fixer_args.enable, fix_cmd, global)
+		{
+			out::println!("{}", e);
+		}
+
+		if errors > fixes {
+			global.exit(global.error_code());
+		}
+		Ok(())
+	}
+
+	/// All weak feature entries of `pkg` whose dependency is always enabled.
+	///
+	/// Cargo only accepts weak entries for optional dependencies, so these are the ones that the
+	/// feature itself or `default` enables.
+	fn weak_features(pkg: &Package) -> Vec<WeakFeature> {
+		let by_default = enabled_by(pkg, "default");
+		let mut found = Vec::new();
+
+		for (feature, entries) in pkg.features.iter() {
+			let by_feature = enabled_by(pkg, feature);
+			for entry in entries {
+				let Some((dep, _)) = entry.split_once("?/") else { continue };
+
+				let reason = if by_feature.contains(dep) {
+					Reason::EnabledByFeature
+				} else if by_default.contains(dep) {
+					Reason::EnabledByDefault
+				} else {
+					continue
+				};
+				found.push(WeakFeature {
+					feature: feature.clone(),
+					entry: entry.clone(),
+					dependency: dep.into(),
+					reason,
+				});
+			}
+		}
+		found
+	}
+}
+
+/// The optional dependencies that `feature` of `pkg` enables, directly or through other features
+/// of the crate.
+fn enabled_by<'a>(pkg: &'a Package, feature: &'a str) -> BTreeSet<&'a str> {
+	let mut enabled = BTreeSet::new();
+	let mut visited = BTreeSet::new();
+	let mut todo = vec![feature];
+
+	while let Some(feature) = todo.pop() {
+		if !visited.insert(feature) {
+			continue
+		}
+		for entry in pkg.features.get(feature).into_iter().flatten() {
+			if let Some(dep) = entry.strip_prefix("dep:") {
+				enabled.insert(dep);
+			} else if let Some((dep, _)) = entry.split_once('/') {
+				// Weak entries like `dep?/std` do not enable the dependency.
+				if !dep.ends_with('?') {
+					enabled.insert(dep);
+					todo.push(dep);
+				}
+			} else if pkg.features.contains_key(entry) {
+				todo.push(entry);
+			} else {
+				// The implicit feature of an optional dependency.
+				enabled.insert(entry);
+			}
+		}
+	}
+	enabled
+}
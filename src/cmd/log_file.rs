@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Copy of the log records in a file for the global `--log-file` arg.
+//!
+//! The logger of the binary passes every record to [`write`] in addition to printing it. Nothing is
+//! written until [`open`] was called.
+
+use serde_json::json;
+use std::{
+	fs::{File, OpenOptions},
+	io::Write,
+	path::Path,
+	sync::Mutex,
+};
+
+/// How the records are written into the log file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+	/// Timestamp, level and message like on stderr.
+	#[default]
+	Text,
+	/// One JSON object per line.
+	Json,
+}
+
+struct LogFile {
+	file: File,
+	format: LogFormat,
+	/// The sub-command that is running, like `lint propagate-feature`.
+	command: String,
+}
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Start writing all records to `path`.
+///
+/// The file is appended to, since the steps of a workflow are separate processes that share it.
+pub fn open(path: &Path, format: LogFormat, command: String) -> Result<(), String> {
+	let file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+	*LOG_FILE.lock().unwrap() = Some(LogFile { file, format, command });
+	Ok(())
+}
+
+/// Write a record to the log file, if one was opened.
+///
+/// Errors are ignored since logging must never fail a command.
+pub fn write(timestamp: &str, level: &str, target: &str, message: &str) {
+	let Ok(mut guard) = LOG_FILE.lock() else { return };
+	let Some(log) = guard.as_mut() else { return };
+
+	let line = format_record(log.format, &log.command, timestamp, level, target, message);
+	let _ = log.file.write_all(line.as_bytes());
+}
+
+fn format_record(
+	format: LogFormat,
+	command: &str,
+	timestamp: &str,
+	level: &str,
+	target: &str,
+	message: &str,
+) -> String {
+	match format {
+		LogFormat::Text => format!("{} [{}] {}\n", timestamp, level, message),
+		LogFormat::Json => format!(
+			"{}\n",
+			json!({
+				"timestamp": timestamp,
+				"level": level,
+				"target": target,
+				"command": command,
+				"pid": std::process::id(),
+				"message": message,
+			})
+		),
+	}
+}
+
+/// The sub-command of this process for the log records, like `lint propagate-feature`.
+///
+/// These are the leading arguments up to the first flag.
+pub(crate) fn command_context() -> String {
+	std::env::args()
+		.skip(1)
+		.skip_while(|a| a == "zepter")
+		.take_while(|a| !a.starts_with('-'))
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn format_record_works() {
+		let text = format_record(LogFormat::Text, "run", "2024-01-01T00:00:00Z", "INFO", "z", "hi");
+		assert_eq!(text, "2024-01-01T00:00:00Z [INFO] hi\n");
+
+		let line = format_record(LogFormat::Json, "run check", "t", "WARN", "zepter::cmd", "a\"b");
+		let value: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+		assert_eq!(value["command"], "run check");
+		assert_eq!(value["level"], "WARN");
+		assert_eq!(value["target"], "zepter::cmd");
+		assert_eq!(value["message"], "a\"b");
+		assert!(line.ends_with("}\n"));
+	}
+}
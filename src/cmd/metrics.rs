@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Metrics of a run for the global `--metrics-file` arg.
+//!
+//! Every command records its issues via [`record_stats`] and the packages of its metadata via
+//! [`record_packages`]. The file is written by [`write`] once the process ends. Workflows pass a
+//! file to each of their steps and collect them with [`record_steps`], so that the metrics of a
+//! workflow contain one entry per step.
+
+use crate::log;
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Mutex, time::Instant};
+
+/// How the metrics are written into the metrics file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MetricsFormat {
+	/// A single JSON object.
+	#[default]
+	Json,
+	/// The Prometheus text format with one gauge per metric.
+	Prometheus,
+}
+
+/// Metrics of a single command, like `lint propagate-feature`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandMetrics {
+	pub command: String,
+	pub issues: usize,
+	pub fixed: usize,
+	pub warnings: usize,
+	/// Number of packages in the metadata, including dependencies.
+	pub packages: usize,
+	pub workspace_members: usize,
+	pub duration_seconds: f64,
+}
+
+/// Content of the metrics file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+	pub issues: usize,
+	pub fixed: usize,
+	pub warnings: usize,
+	pub duration_seconds: f64,
+	/// This process or the steps of the workflow that it ran.
+	pub commands: Vec<CommandMetrics>,
+}
+
+struct State {
+	/// When the command started.
+	start: Option<Instant>,
+	/// The metrics of this process.
+	own: CommandMetrics,
+	/// The metrics of the workflow steps that this process ran.
+	steps: Vec<CommandMetrics>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+	start: None,
+	own: CommandMetrics {
+		command: String::new(),
+		issues: 0,
+		fixed: 0,
+		warnings: 0,
+		packages: 0,
+		workspace_members: 0,
+		duration_seconds: 0.0,
+	},
+	steps: Vec::new(),
+});
+
+fn lock() -> std::sync::MutexGuard<'static, State> {
+	STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start the clock of the `command`, like `lint propagate-feature`.
+pub fn init(command: String) {
+	let mut state = lock();
+	state.start = Some(Instant::now());
+	state.own.command = command;
+}
+
+/// Add the issues of a lint or formatter.
+pub fn record_stats(errors: usize, fixes: usize, warnings: usize) {
+	let mut state = lock();
+	state.own.issues += errors;
+	state.own.fixed += fixes;
+	state.own.warnings += warnings;
+}
+
+/// Record the size of the metadata that was loaded.
+///
+/// Commands that load multiple workspaces report the largest one.
+pub fn record_packages(packages: usize, workspace_members: usize) {
+	let mut state = lock();
+	state.own.packages = state.own.packages.max(packages);
+	state.own.workspace_members = state.own.workspace_members.max(workspace_members);
+}
+
+/// Add the commands from the metrics file of a workflow step.
+///
+/// A step that did not write one, for example since it crashed, is skipped.
+pub fn record_steps(path: &Path) {
+	let Ok(content) = std::fs::read_to_string(path) else { return };
+	match serde_json::from_str::<Metrics>(&content) {
+		Ok(metrics) => lock().steps.extend(metrics.commands),
+		Err(e) => {
+			log::warn!("Failed to parse metrics file {}: {}", path.display(), e);
+		},
+	}
+}
+
+/// Write the metrics of this process to `path`. Only writes once per process.
+pub fn write(path: &Path, format: MetricsFormat) -> Result<(), String> {
+	let mut state = lock();
+	let Some(start) = state.start.take() else { return Ok(()) };
+	state.own.duration_seconds = start.elapsed().as_secs_f64();
+
+	// A workflow only reports its steps since it does not lint anything itself.
+	let commands =
+		if state.steps.is_empty() { vec![state.own.clone()] } else { state.steps.clone() };
+	let metrics = Metrics::new(commands, state.own.duration_seconds);
+
+	std::fs::write(path, metrics.format(format))
+		.map_err(|e| format!("Failed to write metrics file {}: {}", path.display(), e))
+}
+
+impl Metrics {
+	/// Sum up the issues of all `commands`.
+	pub fn new(commands: Vec<CommandMetrics>, duration_seconds: f64) -> Self {
+		Self {
+			issues: commands.iter().map(|c| c.issues).sum(),
+			fixed: commands.iter().map(|c| c.fixed).sum(),
+			warnings: commands.iter().map(|c| c.warnings).sum(),
+			duration_seconds,
+			commands,
+		}
+	}
+
+	pub fn format(&self, format: MetricsFormat) -> String {
+		match format {
+			MetricsFormat::Json => format!(
+				"{}\n",
+				serde_json::to_string_pretty(self).expect("Metrics are serializable")
+			),
+			MetricsFormat::Prometheus => self.prometheus(),
+		}
+	}
+
+	/// One gauge per metric with the position and name of the command as labels.
+	fn prometheus(&self) -> String {
+		type Getter = fn(&CommandMetrics) -> f64;
+		let gauges: [(&str, &str, Getter); 6] = [
+			("issues", "Issues that were found.", |c| c.issues as f64),
+			("fixed", "Issues that were fixed.", |c| c.fixed as f64),
+			("warnings", "Issues that were only reported as warning.", |c| c.warnings as f64),
+			("packages", "Packages in the metadata, including dependencies.", |c| {
+				c.packages as f64
+			}),
+			("workspace_members", "Packages in the workspace.", |c| c.workspace_members as f64),
+			("duration_seconds", "How long the command took.", |c| c.duration_seconds),
+		];
+
+		let mut out = String::new();
+		for (name, help, get) in gauges {
+			out.push_str(&format!("# HELP zepter_{} {}\n", name, help));
+			out.push_str(&format!("# TYPE zepter_{} gauge\n", name));
+			for (i, command) in self.commands.iter().enumerate() {
+				out.push_str(&format!(
+					"zepter_{}{{step=\"{}\",command=\"{}\"}} {}\n",
+					name,
+					i + 1,
+					escape_label(&command.command),
+					get(command)
+				));
+			}
+		}
+		out
+	}
+}
+
+/// Escape a Prometheus label value.
+fn escape_label(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn metrics() -> Metrics {
+		let lint = CommandMetrics {
+			command: "lint propagate-feature".into(),
+			issues: 2,
+			fixed: 1,
+			packages: 10,
+			workspace_members: 3,
+			duration_seconds: 0.5,
+			..Default::default()
+		};
+		let fmt = CommandMetrics { command: "format features".into(), warnings: 1, ..lint.clone() };
+		Metrics::new(vec![lint, fmt], 1.25)
+	}
+
+	#[test]
+	fn json_roundtrips() {
+		let metrics = metrics();
+		assert_eq!((metrics.issues, metrics.fixed, metrics.warnings), (4, 2, 1));
+
+		let json = metrics.format(MetricsFormat::Json);
+		assert_eq!(serde_json::from_str::<Metrics>(&json).unwrap(), metrics);
+	}
+
+	#[test]
+	fn prometheus_works() {
+		let text = metrics().format(MetricsFormat::Prometheus);
+
+		assert!(text.starts_with(
+			"# HELP zepter_issues Issues that were found.\n# TYPE zepter_issues gauge\nzepter_issues{step=\"1\",command=\"lint propagate-feature\"} 2\nzepter_issues{step=\"2\",command=\"format features\"} 2\n"
+		));
+		assert!(
+			text.contains("zepter_duration_seconds{step=\"2\",command=\"format features\"} 0.5\n")
+		);
+		assert_eq!(escape_label("a\"b\\"), "a\\\"b\\\\");
+	}
+}
@@ -4,16 +4,45 @@
 //! Sub-command definition and implementation.
 
 pub mod debug;
+pub mod explain;
 pub mod fmt;
+pub mod init;
 pub mod lint;
+pub mod log_file;
+pub mod metrics;
+pub mod output;
+pub mod progress;
 pub mod run;
+pub mod serve;
+pub mod timing;
 pub mod trace;
 pub mod transpose;
+pub mod update;
 
-use crate::{log, ErrToStr};
+use crate::{
+	autofix::{write_manifest, AutoFixer, FixTransaction},
+	config::theme::{Role, Theme},
+	dag::Dag,
+	grammar::{enumerate, plural},
+	issues::Issue,
+	log, out, CrateId, ErrToStr,
+};
+use log_file::LogFormat;
+use metrics::MetricsFormat;
+use output::{Finding, OutputFormat};
 
-use cargo_metadata::{Dependency, Metadata, MetadataCommand, Package, Resolve};
-use std::{fs::canonicalize, path::Path};
+use cargo_metadata::{
+	semver::VersionReq, Dependency, DependencyKind, Metadata, MetadataCommand, Node, NodeDep,
+	Package, PackageId,
+};
+use std::{
+	cell::{Cell, OnceCell, RefCell},
+	collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+	ffi::OsStr,
+	fs::canonicalize,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicBool, Ordering},
+};
 
 /// See out how Rust dependencies and features are enabled.
 #[derive(Debug, clap::Parser)]
@@ -42,9 +71,12 @@ pub struct GlobalArgs {
 	#[clap(long = "log", global = true, default_value = "info", ignore_case = true)]
 	level: String,
 
-	/// Use ANSI terminal colors.
-	#[clap(long, global = true, default_value_t = false)]
-	color: bool,
+	/// Whether to use ANSI terminal colors.
+	///
+	/// Respects `CARGO_TERM_COLOR` when set to `auto`. Passing `--color` without a value is the
+	/// same as `--color=always`.
+	#[clap(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorMode::Auto, num_args(0..=1), require_equals = true, default_missing_value = "always", verbatim_doc_comment)]
+	color: ColorMode,
 
 	/// Try to exit with code zero if the intended check failed.
 	///
@@ -53,127 +85,587 @@ pub struct GlobalArgs {
 	#[clap(long, global = true, verbatim_doc_comment)]
 	exit_code_zero: bool,
 
-	/// Dont print any hints on how to fix the error.
+	/// How to hint at fixing the found issues.
 	///
-	/// This is mostly used internally when dispatching, workflows since they come with their
-	/// hints.
-	#[clap(long, global = true, value_enum, verbatim_doc_comment, default_value_t = FixHint::On)]
+	/// `on` prints a command that fixes them and `off` prints nothing. Any other value is printed
+	/// as custom hint. This is mostly used internally when dispatching workflows since they come
+	/// with their own hints.
+	#[clap(long, global = true, value_name = "HINT", default_value = "on", verbatim_doc_comment)]
 	fix_hint: FixHint,
+
+	/// Only print a single summary line like `issues=12 fixed=12 warnings=0`.
+	///
+	/// Suppresses the per-crate details of lints and formatting. Meant for CI wrappers that
+	/// parse the output.
+	#[clap(long, global = true, verbatim_doc_comment)]
+	summary_only: bool,
+
+	/// Refuse to apply fixes when the manifests have uncommitted changes.
+	///
+	/// Checks `git status` of the manifests that a fix modifies before writing them. This
+	/// avoids mixing fixes with manual edits.
+	#[clap(long, global = true, verbatim_doc_comment)]
+	git_diff_safety: bool,
+
+	/// Apply fixes even if `--git-diff-safety` found uncommitted changes.
+	#[clap(long, global = true)]
+	allow_dirty: bool,
+
+	/// Verify the workspace with cargo after applying fixes.
+	///
+	/// Restores all modified manifests if the verification fails. Passing `--verify-with-cargo`
+	/// without a value is the same as `--verify-with-cargo=metadata`.
+	#[clap(long, global = true, value_enum, value_name = "MODE", num_args(0..=1), require_equals = true, default_missing_value = "metadata", verbatim_doc_comment)]
+	verify_with_cargo: Option<VerifyMode>,
+
+	/// Print the stable code of every finding, like `[Z0001]`.
+	///
+	/// Run `zepter explain CODE` for a description of the issue and how to fix it.
+	#[clap(long, global = true, verbatim_doc_comment)]
+	show_codes: bool,
+
+	/// Print how long each phase of the command took, like loading the metadata.
+	///
+	/// The breakdown is printed to stderr after the command finished. Helps to tell whether
+	/// Zepter or cargo is slow.
+	#[clap(long, global = true, verbatim_doc_comment)]
+	verbose: bool,
+
+	/// How to print the findings of lints and formatting.
+	///
+	/// `github` prints them as GitHub Actions annotations of the offending files instead of the
+	/// per-crate details. The summary line is printed either way. `tree` merges the paths of
+	/// `trace` into a tree and is the same as `human` otherwise.
+	#[clap(long, global = true, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Human, verbatim_doc_comment)]
+	format: OutputFormat,
+
+	/// Append the log records to this file in addition to stderr.
+	///
+	/// Workflows pass it on to their steps, so that a whole run ends up in one file. Uses the
+	/// same level as `--log`.
+	#[clap(long, global = true, value_name = "PATH", verbatim_doc_comment)]
+	log_file: Option<PathBuf>,
+
+	/// Format of the records in the `--log-file`.
+	///
+	/// `json` writes one object per line with the timestamp, level, target, command and
+	/// message.
+	#[clap(long, global = true, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text, verbatim_doc_comment)]
+	log_format: LogFormat,
+
+	/// Write metrics like the number of issues, packages and the duration into this file.
+	///
+	/// Workflows write one entry per step. The file is overwritten once the command finished.
+	#[clap(long, global = true, value_name = "PATH", verbatim_doc_comment)]
+	metrics_file: Option<PathBuf>,
+
+	/// Format of the `--metrics-file`.
+	///
+	/// `prometheus` writes one gauge per metric with the step and command as labels.
+	#[clap(long, global = true, value_enum, value_name = "FORMAT", default_value_t = MetricsFormat::Json, verbatim_doc_comment)]
+	metrics_format: MetricsFormat,
+
+	/// Write the number of issues into this file. Used internally by workflows.
+	#[clap(long, global = true, value_name = "PATH", hide = true)]
+	stats_file: Option<PathBuf>,
+
+	/// Issues, fixes and warnings of this process that were written to the `--stats-file`.
+	#[clap(skip)]
+	stats: Cell<(usize, usize, usize)>,
+
+	/// Original content of all manifests that were modified by fixes.
+	#[clap(skip)]
+	originals: RefCell<BTreeMap<PathBuf, String>>,
+
+	/// The color theme, resolved on first use.
+	#[clap(skip)]
+	theme: OnceCell<Theme>,
 }
 
 /// Sub-commands of the [Root](Command) command.
 #[derive(Debug, clap::Subcommand)]
 enum SubCommand {
 	Trace(trace::TraceCmd),
-	Lint(lint::LintCmd),
+	Lint(Box<lint::LintCmd>),
 	#[clap(alias = "fmt", alias = "f")]
 	Format(fmt::FormatCmd),
 	Run(run::RunCmd),
+	Init(init::InitCmd),
 	#[clap(hide = true)]
 	Transpose(transpose::TransposeCmd),
 	Debug(debug::DebugCmd),
+	Explain(explain::ExplainCmd),
+	Serve(serve::ServeCmd),
+	#[clap(name = "self")]
+	SelfCmd(update::SelfCmd),
+}
+
+/// When to use ANSI terminal colors.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ColorMode {
+	/// Use colors if `CARGO_TERM_COLOR` says so or stdout is a terminal.
+	Auto,
+	/// Always use colors.
+	Always,
+	/// Never use colors.
+	Never,
+}
+
+impl ColorMode {
+	/// Resolve `Auto` to either `Always` or `Never`.
+	pub fn resolve(self) -> Self {
+		use std::io::IsTerminal;
+
+		if self != Self::Auto {
+			return self
+		}
+		match std::env::var("CARGO_TERM_COLOR").ok().as_deref() {
+			Some("always") => Self::Always,
+			Some("never") => Self::Never,
+			_ if std::io::stdout().is_terminal() => Self::Always,
+			_ => Self::Never,
+		}
+	}
+}
+
+/// How to verify the workspace after applying fixes.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum VerifyMode {
+	/// Check that `cargo metadata` can still resolve the workspace.
+	Metadata,
+	/// Run `cargo check -q` for every modified manifest.
+	Check,
 }
 
 /// A hint on how to fix the error.
-#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FixHint {
 	/// Prints some hint that is (hopefully) helpful.
 	On,
 	/// Prints no hint at all.
 	Off,
-	// NOTE: We could have a `Custom` here one day
+	/// Prints this text instead of the default hint.
+	Custom(String),
+}
+
+impl std::str::FromStr for FixHint {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"on" => Ok(Self::On),
+			"off" => Ok(Self::Off),
+			"" => Err("The hint cannot be empty".into()),
+			custom => Ok(Self::Custom(custom.into())),
+		}
+	}
+}
+
+/// A copy-pastable command line that fixes the reported issues.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixCommand(Vec<String>);
+
+impl FixCommand {
+	/// Start a command like `zepter lint propagate-feature` in the same workspace.
+	pub fn new(subcommand: &str, cargo_args: &CargoArgs) -> Self {
+		let mut cmd = Self(vec!["zepter".into(), subcommand.into()]);
+		for path in cargo_args.manifest_path.iter() {
+			cmd = cmd.arg("--manifest-path", path.display().to_string());
+		}
+		if cargo_args.workspace {
+			cmd = cmd.flag("--workspace");
+		}
+		cmd
+	}
+
+	/// Append a flag without value.
+	pub fn flag(mut self, flag: &str) -> Self {
+		self.0.push(flag.into());
+		self
+	}
+
+	/// Append a flag with a value that is quoted if needed.
+	pub fn arg<V: AsRef<str>>(mut self, flag: &str, value: V) -> Self {
+		let value = value.as_ref();
+		self.0.push(flag.into());
+		if value.is_empty() || value.contains(|c: char| c.is_whitespace() || "'\"*?$`".contains(c))
+		{
+			self.0.push(format!("'{}'", value.replace('\'', "'\\''")));
+		} else {
+			self.0.push(value.into());
+		}
+		self
+	}
+}
+
+impl std::fmt::Display for FixCommand {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.0.join(" "))
+	}
 }
 
 impl Command {
 	pub fn run(&self) -> Result<(), String> {
-		self.global.setup_logging();
+		self.global.setup_logging()?;
+		progress::init(!self.global.quiet);
+		timing::init(self.global.verbose);
+		VERBOSE.store(self.global.verbose, Ordering::Relaxed);
 
-		match self.subcommand.as_ref() {
+		let res = match self.subcommand.as_ref() {
 			Some(SubCommand::Transpose(cmd)) => cmd.run(&self.global),
 
-			Some(SubCommand::Trace(cmd)) => {
-				cmd.run(&self.global);
-				Ok(())
-			},
+			Some(SubCommand::Trace(cmd)) => cmd.run(&self.global),
 			Some(SubCommand::Lint(cmd)) => cmd.run(&self.global),
 			Some(SubCommand::Format(cmd)) => {
 				cmd.run(&self.global);
 				Ok(())
 			},
-			Some(SubCommand::Run(cmd)) => {
-				cmd.run(&self.global);
-				Ok(())
-			},
-			Some(SubCommand::Debug(cmd)) => {
-				cmd.run(&self.global);
-				Ok(())
-			},
-			None => {
-				run::RunCmd::default().run(&self.global);
-				Ok(())
-			},
+			Some(SubCommand::Run(cmd)) => cmd.run(&self.global),
+			Some(SubCommand::Init(cmd)) => cmd.run(&self.global),
+			Some(SubCommand::Debug(cmd)) => cmd.run(&self.global),
+			Some(SubCommand::Explain(cmd)) => cmd.run(&self.global),
+			Some(SubCommand::Serve(cmd)) => cmd.run(&self.global),
+			Some(SubCommand::SelfCmd(cmd)) => cmd.run(&self.global),
+			None => run::RunCmd::default().run(&self.global),
+		}
+		.and_then(|()| self.global.verify_fixes());
+		self.global.write_metrics();
+		timing::report();
+		res
+	}
+
+	/// Describe what this command enforces, for `zepter run --describe`.
+	///
+	/// The first line is a summary and the others are details. Returns `None` for commands that
+	/// do not have a specific description.
+	pub(crate) fn describe(&self) -> Option<Vec<String>> {
+		match self.subcommand.as_ref()? {
+			SubCommand::Lint(cmd) => cmd.describe(),
+			SubCommand::Format(cmd) => Some(cmd.describe()),
+			_ => None,
+		}
+	}
+
+	/// Whether this command modifies manifests, like with `--fix`.
+	pub(crate) fn writes_manifests(&self) -> bool {
+		match self.subcommand.as_ref() {
+			Some(SubCommand::Lint(cmd)) => cmd.writes_manifests(),
+			Some(SubCommand::Format(cmd)) => cmd.writes_manifests(),
+			Some(SubCommand::Transpose(cmd)) => cmd.writes_manifests(),
+			// The steps of the workflow could modify them.
+			Some(SubCommand::Run(_)) | None => true,
+			_ => false,
 		}
 	}
 }
 
 impl GlobalArgs {
-	pub fn setup_logging(&self) {
+	pub fn setup_logging(&self) -> Result<(), String> {
 		#[cfg(feature = "logging")]
 		if self.quiet {
 			::log::set_max_level(::log::LevelFilter::Error);
 		} else {
 			::log::set_max_level(self.level);
 		}
+		if let Some(path) = &self.log_file {
+			log_file::open(path, self.log_format, log_file::command_context())?;
+		}
+		if self.metrics_file.is_some() {
+			metrics::init(log_file::command_context());
+		}
+		Ok(())
+	}
+
+	/// The `--log-file` and its format, if any.
+	pub fn log_file(&self) -> Option<(&Path, LogFormat)> {
+		self.log_file.as_deref().map(|path| (path, self.log_format))
+	}
+
+	/// The `--metrics-file`, if any.
+	pub fn metrics_file(&self) -> Option<&Path> {
+		self.metrics_file.as_deref()
+	}
+
+	/// Write the `--metrics-file`, if any.
+	fn write_metrics(&self) {
+		let Some(path) = self.metrics_file.as_ref() else { return };
+		if let Err(e) = metrics::write(path, self.metrics_format) {
+			log::warn!("{}", e);
+		}
 	}
 
 	pub fn warn_unstable(&self) {
 		log::warn!("Unstable feature - do not rely on this!");
 	}
-	pub fn show_hints(&self) -> bool {
+
+	/// The hint on how to fix the found issues, if one should be printed.
+	///
+	/// Falls back to `default` unless hints are disabled or a custom one was passed.
+	pub fn fix_hint(&self, default: impl FnOnce() -> String) -> Option<String> {
 		// We use exahausive match to make sure to not forget a new variant in the future.
-		match self.fix_hint {
-			FixHint::On => true,
-			FixHint::Off => false,
+		match &self.fix_hint {
+			FixHint::On => Some(default()),
+			FixHint::Off => None,
+			FixHint::Custom(hint) => Some(hint.clone()),
 		}
 	}
 
-	pub fn error_code(&self) -> i32 {
-		if self.exit_code_zero {
-			0
-		} else {
-			1
+	/// Whether the timing of the phases is printed.
+	pub fn verbose(&self) -> bool {
+		self.verbose
+	}
+
+	/// Whether only the machine readable summary should be printed.
+	pub fn summary_only(&self) -> bool {
+		self.summary_only
+	}
+
+	/// The output format of the findings.
+	pub fn format(&self) -> OutputFormat {
+		self.format
+	}
+
+	/// Whether the human readable details of the findings should be printed.
+	pub fn details(&self) -> bool {
+		!self.summary_only && self.format != OutputFormat::Github
+	}
+
+	/// Print `finding` in the machine readable `--format`, if any.
+	pub fn annotate(&self, mut finding: Finding) {
+		if self.summary_only {
+			return
+		}
+		match self.format {
+			OutputFormat::Human | OutputFormat::Tree => (),
+			OutputFormat::Github => {
+				if let Some(issue) = finding.issue {
+					finding.message.push_str(&self.code(issue));
+				}
+				out::println!("{}", finding.to_github());
+			},
 		}
 	}
 
-	pub fn red(&self, s: &str) -> String {
-		if !self.color {
-			s.to_string()
-		} else {
-			format!("\x1b[31m{}\x1b[0m", s)
+	/// Error if `--git-diff-safety` is set and any of `manifests` has uncommitted changes.
+	///
+	/// Called for the manifests of a fix right before they are written.
+	pub fn check_git_clean<'a>(
+		&self,
+		manifests: impl IntoIterator<Item = &'a Path>,
+	) -> Result<(), String> {
+		if !self.git_diff_safety || self.allow_dirty {
+			return Ok(())
 		}
+		let manifests = manifests
+			.into_iter()
+			.map(|p| {
+				canonicalize(p).map_err(|e| format!("Failed to canonicalize {}: {e}", p.display()))
+			})
+			.collect::<Result<BTreeSet<_>, _>>()?;
+		let Some(first) = manifests.first().and_then(|p| p.parent()) else { return Ok(()) };
+
+		// Porcelain paths are relative to the root of the repository.
+		let top = git_output(first, ["rev-parse", "--show-toplevel"])?;
+		let top = canonicalize(String::from_utf8_lossy(&top).trim())
+			.map_err(|e| format!("Failed to locate the git repository: {e}"))?;
+		// Relative paths since git does not like symlinks in absolute ones.
+		let relative = manifests
+			.iter()
+			.filter_map(|p| p.strip_prefix(&top).ok())
+			.collect::<BTreeSet<_>>();
+		if relative.is_empty() {
+			return Ok(())
+		}
+		let args = ["status", "--porcelain", "-z", "--"].map(OsStr::new);
+		let stdout =
+			git_output(&top, args.into_iter().chain(relative.iter().map(|p| p.as_os_str())))?;
+
+		// Entries are `XY PATH` and terminated by NUL. Paths are not quoted in this format. Renames
+		// and copies are followed by their original path as another entry.
+		let path = |raw: &[u8]| PathBuf::from(String::from_utf8_lossy(raw).as_ref());
+		let mut changed = BTreeSet::new();
+		let mut entries = stdout.split(|b| *b == 0).filter(|e| e.len() > 3);
+		while let Some(entry) = entries.next() {
+			let (status, raw) = entry.split_at(3);
+			changed.insert(path(raw));
+			if status.iter().any(|s| matches!(s, b'R' | b'C')) {
+				changed.extend(entries.next().map(path));
+			}
+		}
+		let dirty = relative
+			.into_iter()
+			.filter(|p| changed.contains(*p))
+			.map(|p| p.display().to_string())
+			.collect::<Vec<_>>();
+		if dirty.is_empty() {
+			return Ok(())
+		}
+		Err(format!(
+			"{} manifest{} in the working directory contain{} changes that were not yet committed into git:\n\n{}\n\nTo proceed despite this, pass the `--allow-dirty` flag",
+			dirty.len(),
+			plural(dirty.len()),
+			if dirty.len() == 1 { "s" } else { "" },
+			dirty.join("\n"),
+		))
 	}
 
-	pub fn yellow(&self, s: &str) -> String {
-		if !self.color {
-			s.to_string()
-		} else {
-			format!("\x1b[33m{}\x1b[0m", s)
+	/// Write the fixes of `fixer` to disk.
+	///
+	/// Refuses dirty manifests with `--git-diff-safety`. Remembers the original manifest so that
+	/// it can be restored by `--verify-with-cargo`.
+	pub fn save_fixer(&self, fixer: &mut AutoFixer) -> Result<(), String> {
+		self.check_git_clean(fixer.manifest.as_deref())?;
+		self.write_fixer(fixer)
+	}
+
+	fn write_fixer(&self, fixer: &mut AutoFixer) -> Result<(), String> {
+		let _phase = timing::Phase::start("saving");
+		if let Some(path) = fixer.manifest.as_ref() {
+			self.originals
+				.borrow_mut()
+				.entry(path.clone())
+				.or_insert_with(|| fixer.original().into());
+		}
+		fixer.save().map_err(Into::into)
+	}
+
+	/// Write all fixes of `transaction` or none of them.
+	///
+	/// See [`FixTransaction::commit`]. All manifests are checked by `--git-diff-safety` before
+	/// the first one is written. They can be restored by `--verify-with-cargo` like the ones of
+	/// [`Self::save_fixer`].
+	pub fn commit_fixes(&self, transaction: FixTransaction) -> Result<(), String> {
+		if transaction.is_empty() {
+			return Ok(())
 		}
+		self.check_git_clean(transaction.manifests())?;
+		let n = transaction.commit(|fixer| self.write_fixer(fixer))?;
+		log::debug!("Applied the fixes to {} manifest{}", n, plural(n));
+		Ok(())
 	}
 
-	pub fn green(&self, s: &str) -> String {
-		if !self.color {
-			s.to_string()
+	/// Verify that the workspace still works with all saved fixes.
+	///
+	/// Does nothing unless `--verify-with-cargo` was passed. Restores the original manifests if
+	/// the verification fails.
+	pub fn verify_fixes(&self) -> Result<(), String> {
+		let Some(mode) = self.verify_with_cargo else { return Ok(()) };
+		let originals = std::mem::take(&mut *self.originals.borrow_mut());
+		let Some(first) = originals.keys().next() else { return Ok(()) };
+
+		let progress = progress::Progress::spinner("Verifying fixes with cargo");
+		let phase = timing::Phase::start("verifying");
+		let res = match mode {
+			VerifyMode::Metadata => MetadataCommand::new()
+				.manifest_path(first)
+				.exec()
+				.map(|_| ())
+				.map_err(|e| e.to_string()),
+			VerifyMode::Check => originals.keys().try_for_each(|path| cargo_check(path)),
+		};
+		drop((phase, progress));
+		let Err(err) = res else {
+			log::debug!(
+				"Verified {} modified manifest{}",
+				originals.len(),
+				plural(originals.len())
+			);
+			return Ok(())
+		};
+
+		for (path, original) in originals.iter() {
+			write_manifest(path, original).map_err(|e| {
+				format!("Verification with cargo failed and restoring failed: {}", e)
+			})?;
+		}
+		Err(format!(
+			"Verification with cargo failed. Restored {} modified manifest{}:\n{}",
+			originals.len(),
+			plural(originals.len()),
+			err.trim()
+		))
+	}
+
+	/// The code of `issue` to append to a finding, if `--show-codes` is set.
+	pub fn code(&self, issue: &Issue) -> String {
+		if self.show_codes {
+			format!(" [{}]", issue.code)
 		} else {
-			format!("\x1b[32m{}\x1b[0m", s)
+			String::new()
 		}
 	}
 
-	pub fn bold(&self, s: &str) -> String {
-		if !self.color {
-			s.to_string()
+	/// Add the number of issues to the `--stats-file` and the metrics, if any.
+	///
+	/// Lints that run multiple times per process, like once per feature, report the total.
+	pub fn write_stats(&self, errors: usize, fixes: usize, warnings: usize) {
+		metrics::record_stats(errors, fixes, warnings);
+		let Some(path) = self.stats_file.as_ref() else { return };
+		let (e, f, w) = self.stats.get();
+		let (errors, fixes, warnings) = (e + errors, f + fixes, w + warnings);
+		self.stats.set((errors, fixes, warnings));
+		let stats = format!("issues={} fixed={} warnings={}", errors, fixes, warnings);
+
+		if let Err(e) = std::fs::write(path, stats) {
+			log::warn!("Failed to write stats file {}: {}", path.display(), e);
+		}
+	}
+
+	/// Exit the process with `code` after verifying the saved fixes.
+	///
+	/// Only ends the request when `zepter serve` runs the command.
+	pub fn exit(&self, code: i32) -> ! {
+		let res = self.verify_fixes();
+		self.write_metrics();
+		timing::report();
+		if let Err(err) = res {
+			out::eprintln!("{}", err);
+			serve::exit(1)
+		}
+		serve::exit(code)
+	}
+
+	pub fn error_code(&self) -> i32 {
+		if self.exit_code_zero {
+			0
 		} else {
-			format!("\x1b[1m{}\x1b[0m", s)
+			1
+		}
+	}
+
+	/// Whether ANSI terminal colors should be used for the output.
+	pub fn colored(&self) -> bool {
+		self.color.resolve() == ColorMode::Always
+	}
+
+	/// The color theme from `ZEPTER_THEME` or the config file in the current directory.
+	pub fn theme(&self) -> Theme {
+		*self
+			.theme
+			.get_or_init(|| Theme::resolve(std::env::current_dir().unwrap_or_default()))
+	}
+
+	pub fn error(&self, s: &str) -> String {
+		self.style(Role::Error, s)
+	}
+
+	pub fn warning(&self, s: &str) -> String {
+		self.style(Role::Warning, s)
+	}
+
+	pub fn success(&self, s: &str) -> String {
+		self.style(Role::Success, s)
+	}
+
+	pub fn emphasis(&self, s: &str) -> String {
+		self.style(Role::Emphasis, s)
+	}
+
+	/// Wrap `s` into the ANSI escape sequence of `role` if colors are enabled.
+	fn style(&self, role: Role, s: &str) -> String {
+		match self.theme().style(role) {
+			Some(code) if self.colored() => format!("\x1b[{}m{}\x1b[0m", code, s),
+			_ => s.to_string(),
 		}
 	}
 }
@@ -185,7 +677,7 @@ pub struct CargoArgs {
 	///
 	/// For directories it appends a `Cargo.toml`.
 	#[arg(long, global = true)]
-	pub manifest_path: Option<std::path::PathBuf>,
+	pub manifest_path: Vec<std::path::PathBuf>,
 
 	/// Whether to only consider workspace crates.
 	#[clap(long, global = true)]
@@ -197,12 +689,235 @@ pub struct CargoArgs {
 
 	/// Whether to use all the locked dependencies from the `Cargo.lock`.
 	///
-	/// Otherwise it may update some dependencies. For CI usage its a good idea to use it.
-	#[clap(long, global = true)]
-	pub locked: bool,
+	/// Otherwise it may update some dependencies. For CI usage its a good idea to use it. Passing
+	/// `--locked` without a value is the same as `--locked=always`.
+	#[clap(long, global = true, value_enum, value_name = "WHEN", default_value_t = LockedMode::Never, num_args(0..=1), require_equals = true, default_missing_value = "always", verbatim_doc_comment)]
+	pub locked: LockedMode,
 
 	#[clap(long, global = true)]
 	pub all_features: bool,
+
+	/// How to treat dependencies that could not be resolved.
+	///
+	/// They are skipped by the analysis. Dev-dependencies of crates outside of the workspace are
+	/// never resolved by cargo and therefore not reported.
+	#[clap(long, global = true, value_enum, value_name = "MODE", default_value_t = UnresolvedDepsMode::Ignore)]
+	pub unresolved_deps: UnresolvedDepsMode,
+}
+
+/// How to treat dependencies that could not be resolved.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum UnresolvedDepsMode {
+	/// Skip them silently.
+	Ignore,
+	/// Print a warning for each of them.
+	Warn,
+	/// Fail with a list of them.
+	Error,
+}
+
+/// Which details of a crate are shown next to its name in the output.
+#[derive(Debug, Clone, Default, clap::Parser, PartialEq)]
+pub struct DisplayArgs {
+	/// Show the version of the crates in the output.
+	#[clap(long)]
+	pub show_version: bool,
+
+	/// Show the source location of crates in the output.
+	#[clap(long)]
+	pub show_source: bool,
+
+	/// Show the manifest path of crates in the output.
+	#[clap(long)]
+	pub show_path: bool,
+}
+
+impl DisplayArgs {
+	/// Also show the version if `show` is set, like for crates that exist in multiple versions.
+	pub fn with_version(&self, show: bool) -> Self {
+		Self { show_version: self.show_version || show, ..self.clone() }
+	}
+
+	/// The requested details of `krate` to append to its name, like ` v1.0.0 (local)`.
+	pub fn details(&self, krate: &Package) -> String {
+		let mut out = String::new();
+		if self.show_version {
+			out.push_str(&format!(" v{}", krate.version));
+		}
+		if self.show_source {
+			match krate.source.as_ref() {
+				Some(source) => out.push_str(&format!(" ({})", source.repr)),
+				None => out.push_str(" (local)"),
+			}
+		}
+		if self.show_path {
+			out.push_str(&format!(" ({})", krate.manifest_path));
+		}
+		out
+	}
+}
+
+/// Selection of packages by name, like the `-p` and `--exclude` args of cargo.
+#[derive(Debug, Clone, Default, clap::Parser, PartialEq)]
+pub struct PackageSpec {
+	/// Only consider these packages.
+	///
+	/// Can be passed multiple times and supports glob patterns like `pallet-*`.
+	#[clap(long = "packages", short = 'p', alias = "package", value_delimiter = ',', num_args(0..), verbatim_doc_comment)]
+	pub packages: Vec<String>,
+
+	/// Do not consider these packages.
+	///
+	/// Supports glob patterns like `pallet-*`.
+	#[clap(long, value_delimiter = ',', verbatim_doc_comment)]
+	pub exclude: Vec<String>,
+
+	/// Only consider packages with files that changed since this git ref.
+	///
+	/// Uncommitted and untracked files count as changed. Meant for pre-commit hooks, like
+	/// `--since origin/master`.
+	#[clap(long, value_name = "REF", verbatim_doc_comment)]
+	pub since: Option<String>,
+}
+
+impl PackageSpec {
+	/// Which packages are considered, for `zepter run --describe`.
+	pub fn describe(&self) -> Vec<String> {
+		let mut lines = Vec::new();
+		if !self.packages.is_empty() {
+			lines
+				.push(format!("Only packages matching {} are checked.", enumerate(&self.packages)));
+		}
+		if !self.exclude.is_empty() {
+			lines.push(format!("Packages matching {} are not checked.", enumerate(&self.exclude)));
+		}
+		if let Some(since) = &self.since {
+			lines.push(format!("Only packages that changed since `{}` are checked.", since));
+		}
+		lines
+	}
+
+	/// Select all packages of the metadata that match the spec.
+	///
+	/// Errors if a pattern of `--packages` does not match any package.
+	pub fn select<'a>(&self, meta: &'a Metadata) -> Result<Vec<&'a Package>, String> {
+		let pkgs = meta.packages.iter().collect::<Vec<_>>();
+		let include = self.packages.iter().map(|p| glob_regex(p)).collect::<Result<Vec<_>, _>>()?;
+		let exclude = self.exclude.iter().map(|p| glob_regex(p)).collect::<Result<Vec<_>, _>>()?;
+
+		for (pattern, regex) in self.packages.iter().zip(include.iter()) {
+			if !pkgs.iter().any(|p| regex.is_match(&p.name)) {
+				return Err(format!("Package pattern '{}' does not match any package", pattern))
+			}
+		}
+		for (pattern, regex) in self.exclude.iter().zip(exclude.iter()) {
+			if !pkgs.iter().any(|p| regex.is_match(&p.name)) {
+				log::warn!("Excluded package pattern '{}' does not match any package", pattern);
+			}
+		}
+		let touched = match self.since.as_ref() {
+			Some(since) => {
+				let files = changed_files(meta.workspace_root.as_std_path(), since)?;
+				Some(touched_packages(&pkgs, &files))
+			},
+			None => None,
+		};
+
+		Ok(pkgs
+			.iter()
+			.filter(|p| include.is_empty() || include.iter().any(|r| r.is_match(&p.name)))
+			.filter(|p| !exclude.iter().any(|r| r.is_match(&p.name)))
+			.filter(|p| touched.as_ref().map_or(true, |t| t.contains(&p.id)))
+			.copied()
+			.collect())
+	}
+}
+
+/// The stdout of git with `args`, run in `dir`.
+fn git_output<I, S>(dir: &Path, args: I) -> Result<Vec<u8>, String>
+where
+	I: IntoIterator<Item = S>,
+	S: AsRef<OsStr>,
+{
+	let output = std::process::Command::new("git")
+		.current_dir(dir)
+		.args(args)
+		.output()
+		.map_err(|e| format!("Failed to run git: {e}"))?;
+	if !output.status.success() {
+		return Err(format!("Failed to run git: {}", String::from_utf8_lossy(&output.stderr).trim()))
+	}
+	Ok(output.stdout)
+}
+
+/// All files below `root` that differ from the git ref `since`, including untracked ones.
+fn changed_files(root: &Path, since: &str) -> Result<Vec<PathBuf>, String> {
+	let git = |args: &[&str]| -> Result<Vec<PathBuf>, String> {
+		let output = std::process::Command::new("git")
+			.current_dir(root)
+			.args(args)
+			.output()
+			.map_err(|e| format!("Failed to run git {}: {e}", args[0]))?;
+		if !output.status.success() {
+			return Err(format!(
+				"Failed to run git {}: {}",
+				args[0],
+				String::from_utf8_lossy(&output.stderr).trim()
+			))
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).lines().map(|l| root.join(l)).collect())
+	};
+
+	let mut files = git(&["diff", "--name-only", "--relative", since, "--"])?;
+	files.extend(git(&["ls-files", "--others", "--exclude-standard"])?);
+	log::debug!("Found {} file{} changed since '{}'", files.len(), plural(files.len()), since);
+	Ok(files)
+}
+
+/// The IDs of all packages that contain one of the `files`.
+///
+/// A file of a nested package only counts for the innermost one.
+fn touched_packages<'a>(pkgs: &[&'a Package], files: &[PathBuf]) -> BTreeSet<&'a PackageId> {
+	let dirs = pkgs
+		.iter()
+		.filter_map(|p| Some((p.manifest_path.parent()?.as_std_path(), &p.id)))
+		.collect::<Vec<_>>();
+
+	files
+		.iter()
+		.filter_map(|file| {
+			dirs.iter()
+				.filter(|(dir, _)| file.starts_with(dir))
+				.max_by_key(|(dir, _)| dir.components().count())
+				.map(|(_, id)| *id)
+		})
+		.collect()
+}
+
+/// Convert a glob pattern with `*` and `?` wildcards into an anchored regex.
+pub(crate) fn glob_regex(pattern: &str) -> Result<regex::Regex, String> {
+	let mut re = String::from("^");
+	for c in pattern.chars() {
+		match c {
+			'*' => re.push_str(".*"),
+			'?' => re.push('.'),
+			c => re.push_str(&regex::escape(&c.to_string())),
+		}
+	}
+	re.push('$');
+
+	regex::Regex::new(&re).map_err(|e| format!("Invalid package pattern '{}': {}", pattern, e))
+}
+
+/// When to pass `--locked` to cargo.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum LockedMode {
+	/// Allow cargo to update the lockfile.
+	Never,
+	/// Fail if the lockfile needs to be updated.
+	Always,
+	/// Like `always`, but retry without `--locked` if the lockfile needs to be updated.
+	Auto,
 }
 
 impl CargoArgs {
@@ -212,13 +927,54 @@ impl CargoArgs {
 	}
 
 	/// Load the metadata of the rust project.
+	///
+	/// Errors if `--manifest-path` was passed multiple times. Use [`Self::load_merged_metadata`]
+	/// for commands that support it.
 	pub fn load_metadata(&self) -> Result<Metadata, String> {
-		let err = match self.load_metadata_unsorted() {
+		let meta = match self.manifest_path.as_slice() {
+			[] => self.load_metadata_from(None),
+			[path] => self.load_metadata_from(Some(path.as_path())),
+			_ => Err("Only `trace` accepts `--manifest-path` multiple times.".into()),
+		}?;
+		metrics::record_packages(meta.packages.len(), meta.workspace_members.len());
+		Ok(meta)
+	}
+
+	/// Load the metadata of every `--manifest-path` and merge them into one.
+	///
+	/// This allows to follow path dependencies between sibling workspaces. Also returns the name
+	/// of the workspace that every package came from, but only if there are multiple.
+	pub fn load_merged_metadata(&self) -> Result<(Metadata, BTreeMap<String, String>), String> {
+		if self.manifest_path.len() < 2 {
+			return Ok((self.load_metadata()?, BTreeMap::new()))
+		}
+		let metas = self
+			.manifest_path
+			.iter()
+			.map(|path| self.load_metadata_from(Some(path.as_path())))
+			.collect::<Result<Vec<_>, _>>()?;
+		let (meta, workspaces) = merge_metadata(metas);
+		metrics::record_packages(meta.packages.len(), meta.workspace_members.len());
+		Ok((meta, workspaces))
+	}
+
+	fn load_metadata_from(&self, manifest_path: Option<&Path>) -> Result<Metadata, String> {
+		if let Some(cached) = serve::cached_metadata(manifest_path) {
+			return cached
+		}
+		let _progress = progress::Progress::spinner("Loading metadata");
+		let _phase = timing::Phase::start("cargo metadata");
+		let err = match self.load_metadata_unsorted(manifest_path, self.locked != LockedMode::Never)
+		{
 			Ok(meta) => return Ok(meta),
 			Err(err) => err,
 		};
 
 		if check_for_locked_error(&err) {
+			if self.locked == LockedMode::Auto {
+				log::warn!("The Cargo.lock file needs to be updated. Retrying without --locked.");
+				return self.load_metadata_unsorted(manifest_path, false)
+			}
 			Err("\nThe Cargo.lock file needs to be updated first since --locked is present.\n"
 				.to_string())
 		} else {
@@ -226,14 +982,18 @@ impl CargoArgs {
 		}
 	}
 
-	fn load_metadata_unsorted(&self) -> Result<Metadata, String> {
+	fn load_metadata_unsorted(
+		&self,
+		manifest_path: Option<&Path>,
+		locked: bool,
+	) -> Result<Metadata, String> {
 		let mut cmd = MetadataCommand::new();
 
-		if let Some(ref manifest_path) = self.manifest_path {
+		if let Some(manifest_path) = manifest_path {
 			let manifest_path = if manifest_path.is_dir() {
 				manifest_path.join("Cargo.toml")
 			} else {
-				manifest_path.clone()
+				manifest_path.to_path_buf()
 			};
 			cmd.manifest_path(&manifest_path);
 		}
@@ -243,66 +1003,483 @@ impl CargoArgs {
 		if self.workspace {
 			cmd.no_deps();
 		}
+		let mut options = Vec::new();
 		if self.offline {
-			cmd.other_options(vec!["--offline".to_string()]);
+			options.push("--offline".to_string());
 		}
-		if self.locked {
-			cmd.other_options(vec!["--locked".to_string()]);
+		if locked {
+			options.push("--locked".to_string());
 		}
+		cmd.other_options(options);
 
 		cmd.exec().map_err(|e| format!("Failed to load metadata: {e}"))
 	}
 }
 
+/// Merge the metadata of multiple workspaces into the first one.
+///
+/// Packages and resolve nodes that appear in multiple of them are only kept once. Returns the
+/// name of the workspace that every package came from; members belong to their workspace and
+/// other packages to the first workspace that contains them.
+pub fn merge_metadata(metas: Vec<Metadata>) -> (Metadata, BTreeMap<String, String>) {
+	let label = |meta: &Metadata| {
+		meta.workspace_root
+			.file_name()
+			.unwrap_or(meta.workspace_root.as_str())
+			.to_string()
+	};
+	let mut origins = BTreeMap::new();
+	for meta in metas.iter() {
+		for id in meta.workspace_members.iter() {
+			origins.entry(id.repr.clone()).or_insert_with(|| label(meta));
+		}
+	}
+	for meta in metas.iter() {
+		for pkg in meta.packages.iter() {
+			origins.entry(pkg.id.repr.clone()).or_insert_with(|| label(meta));
+		}
+	}
+
+	let mut metas = metas.into_iter();
+	let mut merged = metas.next().expect("Needs at least one metadata");
+	for meta in metas {
+		let known = merged.packages.iter().map(|p| p.id.clone()).collect::<HashSet<_>>();
+		merged
+			.packages
+			.extend(meta.packages.into_iter().filter(|p| !known.contains(&p.id)));
+		for id in meta.workspace_members {
+			if !merged.workspace_members.contains(&id) {
+				merged.workspace_members.push(id);
+			}
+		}
+		if let (Some(into), Some(from)) = (merged.resolve.as_mut(), meta.resolve) {
+			let known = into.nodes.iter().map(|n| n.id.clone()).collect::<HashSet<_>>();
+			into.nodes.extend(from.nodes.into_iter().filter(|n| !known.contains(&n.id)));
+		}
+	}
+
+	(merged, origins)
+}
+
 fn check_for_locked_error(err: &str) -> bool {
 	err.contains("needs to be updated but --locked was passed to prevent this")
 }
 
-/// Resolve the dependency `dep` of `pkg` within the metadata.
+/// Whether `--verbose` was passed. Set once by [`Command::run`].
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Whether notes for the global `--verbose` flag should be printed.
+pub(crate) fn verbose() -> bool {
+	VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Lookup table for the packages of the metadata.
 ///
-/// This checks whether the dependency is a workspace or external crate and resolves it accordingly.
-pub(crate) fn resolve_dep(
-	pkg: &Package,
-	dep: &Dependency,
-	meta: &Metadata,
-) -> Option<RenamedPackage> {
-	match meta.resolve.as_ref() {
-		Some(resolve) => resolve_dep_from_graph(pkg, dep, (meta, resolve)),
-		None => resolve_dep_from_workspace(dep, meta),
+/// Searching through `meta.packages` is linear, so doing it in a loop is quadratic on big
+/// workspaces. Build this once per run and pass it around instead.
+pub struct PackageIndex<'a> {
+	meta: &'a Metadata,
+	by_id: HashMap<&'a str, &'a Package>,
+	by_name: HashMap<&'a str, Vec<&'a Package>>,
+	nodes: HashMap<&'a str, &'a Node>,
+	workspace: HashSet<&'a str>,
+	/// Crates that are patched or replaced. Only needed without a resolve graph.
+	patched: HashSet<String>,
+	/// Dependencies that [`Self::resolve_dep`] could not resolve so far.
+	unresolved: RefCell<BTreeSet<UnresolvedDep>>,
+	/// Path packages that are treated as if they were published, see
+	/// [`Self::assume_published`].
+	published: HashSet<&'a str>,
+}
+
+/// A dependency that could not be resolved within the metadata.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UnresolvedDep {
+	/// Name of the crate that declares the dependency.
+	pub krate: String,
+	/// The name under which the dependency is imported.
+	pub dependency: String,
+	pub reason: UnresolvedReason,
+}
+
+/// Why a dependency could not be resolved.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UnresolvedReason {
+	/// The dependency is only used on a target that cargo did not resolve.
+	TargetGated(String),
+	/// The dependency is optional and no feature enables it.
+	OptionalDisabled,
+	/// The resolve graph does not contain the crate or the dependency.
+	NotInResolveGraph,
+	/// The dependency has no library target, like `artifact = "bin"` dependencies. It is part of
+	/// the crate DAG, but has no features that could be checked.
+	Artifact,
+}
+
+impl std::fmt::Display for UnresolvedReason {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::TargetGated(target) => write!(f, "only used on target '{}'", target),
+			Self::OptionalDisabled => write!(f, "optional and not enabled"),
+			Self::NotInResolveGraph => write!(f, "not in the resolve graph"),
+			Self::Artifact => write!(f, "artifact dependency without a library"),
+		}
 	}
 }
 
-/// Resolve the dependency `dep` within the workspace.
-///
-/// Errors if `dep` is not a workspace member.
-pub(crate) fn resolve_dep_from_workspace(
-	dep: &Dependency,
-	meta: &Metadata,
-) -> Option<RenamedPackage> {
-	for work in meta.workspace_packages() {
-		if work.name == dep.name {
-			let pkg = meta.packages.iter().find(|pkg| pkg.id == work.id).cloned();
-			return pkg.map(|pkg| RenamedPackage::new(pkg, dep.rename.clone(), dep.optional))
+impl std::fmt::Display for UnresolvedDep {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "'{}' of '{}': {}", self.dependency, self.krate, self.reason)
+	}
+}
+
+impl<'a> PackageIndex<'a> {
+	pub fn new(meta: &'a Metadata) -> Self {
+		let mut by_id = HashMap::with_capacity(meta.packages.len());
+		let mut by_name = HashMap::<_, Vec<_>>::new();
+
+		for pkg in meta.packages.iter() {
+			by_id.insert(pkg.id.repr.as_str(), pkg);
+			by_name.entry(pkg.name.as_str()).or_default().push(pkg);
+		}
+		let nodes = meta
+			.resolve
+			.iter()
+			.flat_map(|r| r.nodes.iter())
+			.map(|node| (node.id.repr.as_str(), node))
+			.collect();
+		let workspace = meta.workspace_members.iter().map(|id| id.repr.as_str()).collect();
+		// The resolve graph already contains the patched packages.
+		let patched = match meta.resolve {
+			Some(_) => HashSet::new(),
+			None => patched_crates(meta.workspace_root.as_std_path()),
+		};
+
+		Self {
+			meta,
+			by_id,
+			by_name,
+			nodes,
+			workspace,
+			patched,
+			unresolved: Default::default(),
+			published: Default::default(),
 		}
 	}
-	None
+
+	/// Treat the path packages called `names`, or all of them with `all`, as if they were
+	/// published.
+	///
+	/// Cargo removes their dev-dependencies without a version when publishing, so these no longer
+	/// resolve. Errors if a name is not a path package.
+	pub fn assume_published(mut self, names: &[String], all: bool) -> Result<Self, String> {
+		for name in names.iter() {
+			let pkgs = self.by_name(name);
+			if pkgs.is_empty() {
+				return Err(format!("Crate '{}' of `--assume-published` not found", name))
+			}
+			if pkgs.iter().all(|pkg| pkg.source.is_some()) {
+				return Err(format!(
+					"Crate '{}' of `--assume-published` is not a path dependency",
+					name
+				))
+			}
+		}
+		self.published = self
+			.meta
+			.packages
+			.iter()
+			.filter(|pkg| pkg.source.is_none() && (all || names.contains(&pkg.name)))
+			.map(|pkg| pkg.id.repr.as_str())
+			.collect();
+		Ok(self)
+	}
+
+	/// Whether the path package `pkg` is treated as if it was published.
+	pub fn is_assumed_published(&self, pkg: &Package) -> bool {
+		self.published.contains(pkg.id.repr.as_str())
+	}
+
+	/// Whether cargo removes `dep` from the manifest of `pkg` when publishing it.
+	///
+	/// This is the case for dev-dependencies that only have a path, since they could not be found
+	/// by the users of the published crate.
+	fn stripped_on_publish(&self, pkg: &Package, dep: &Dependency) -> bool {
+		dep.kind == DependencyKind::Development &&
+			dep.path.is_some() &&
+			dep.req == VersionReq::STAR &&
+			self.is_assumed_published(pkg)
+	}
+
+	pub fn meta(&self) -> &'a Metadata {
+		self.meta
+	}
+
+	/// Find a package by its ID. A trailing `?` of optional dependencies is ignored.
+	pub fn get(&self, id: &str) -> Option<&'a Package> {
+		self.by_id.get(id.trim_end_matches('?')).copied()
+	}
+
+	/// All packages with this name. There can be multiple in different versions.
+	pub fn by_name(&self, name: &str) -> &[&'a Package] {
+		self.by_name.get(name).map_or(&[], Vec::as_slice)
+	}
+
+	/// Build a dependency graph over the crate ids, only following the dependencies for which
+	/// `follow` returns true.
+	pub fn crate_dag(&self, follow: impl Fn(&Dependency) -> bool) -> Dag<CrateId> {
+		let mut dag = Dag::new();
+
+		for pkg in self.meta.packages.iter() {
+			let id = pkg.id.to_string();
+			dag.add_node(id.clone());
+
+			for dep in pkg.dependencies.iter().filter(|d| follow(d)) {
+				if let Some(dep) = self.resolve_dep(pkg, dep) {
+					dag.add_edge(id.clone(), dep.pkg.id.to_string());
+				} else if let Some(artifact) = self.artifact_dep(pkg, dep) {
+					dag.add_edge(id.clone(), artifact.id.to_string());
+				}
+			}
+		}
+
+		dag
+	}
+
+	/// Resolve the dependency `dep` of `pkg` within the metadata.
+	///
+	/// This checks whether the dependency is a workspace or external crate and resolves it
+	/// accordingly. Dependencies that cargo removes when publishing do not resolve for crates that
+	/// are [assumed to be published](Self::assume_published).
+	pub fn resolve_dep(&self, pkg: &Package, dep: &Dependency) -> Option<RenamedPackage> {
+		if self.stripped_on_publish(pkg, dep) {
+			return None
+		}
+		let resolved = match self.meta.resolve {
+			Some(_) => self.resolve_dep_from_graph(pkg, dep),
+			None => self.resolve_dep_from_workspace(dep),
+		};
+		let Some(resolved) = resolved else {
+			self.record_unresolved(pkg, dep);
+			return None
+		};
+		if is_patched(dep, resolved) {
+			log::debug!(
+				"Dependency '{}' of '{}' is patched to {}",
+				dep.name,
+				pkg.name,
+				resolved.source.as_ref().map_or("a path", |s| s.repr.as_str())
+			);
+		}
+
+		Some(RenamedPackage::new(resolved.clone(), dep.rename.clone(), dep.optional))
+	}
+
+	/// Remember that `dep` of `pkg` could not be resolved for [`Self::report_unresolved`].
+	///
+	/// Without a resolve graph only workspace members can be resolved, which is expected. Cargo
+	/// does not resolve dev-dependencies of crates outside of the workspace either.
+	fn record_unresolved(&self, pkg: &Package, dep: &Dependency) {
+		if self.meta.resolve.is_none() ||
+			(dep.kind == DependencyKind::Development &&
+				!self.workspace.contains(pkg.id.repr.as_str()))
+		{
+			return
+		}
+		let reason = if self.artifact_dep(pkg, dep).is_some() {
+			UnresolvedReason::Artifact
+		} else if let Some(target) = dep.target.as_ref() {
+			UnresolvedReason::TargetGated(target.to_string())
+		} else if dep.optional {
+			UnresolvedReason::OptionalDisabled
+		} else {
+			UnresolvedReason::NotInResolveGraph
+		};
+
+		self.unresolved.borrow_mut().insert(UnresolvedDep {
+			krate: pkg.name.clone(),
+			dependency: dep.rename.clone().unwrap_or_else(|| dep.name.clone()),
+			reason,
+		});
+	}
+
+	/// Report all dependencies that could not be resolved so far according to `mode`.
+	///
+	/// Errors in [`UnresolvedDepsMode::Error`] if there are any. Artifact dependencies are expected
+	/// to have no features and are only listed with `--verbose`.
+	pub fn report_unresolved(&self, mode: UnresolvedDepsMode) -> Result<(), String> {
+		let (artifacts, unresolved): (Vec<_>, Vec<_>) = self
+			.unresolved
+			.borrow()
+			.iter()
+			.cloned()
+			.partition(|dep| dep.reason == UnresolvedReason::Artifact);
+		if verbose() && !artifacts.is_empty() {
+			out::eprintln!(
+				"Skipped the features of {} artifact dependenc{}:\n  {}",
+				artifacts.len(),
+				if artifacts.len() == 1 { "y" } else { "ies" },
+				artifacts
+					.iter()
+					.map(|dep| format!("'{}' of '{}'", dep.dependency, dep.krate))
+					.collect::<Vec<_>>()
+					.join("\n  ")
+			);
+		}
+		match mode {
+			UnresolvedDepsMode::Ignore => Ok(()),
+			UnresolvedDepsMode::Warn => {
+				for dep in unresolved.iter() {
+					log::warn!("Could not resolve dependency {}", dep);
+				}
+				Ok(())
+			},
+			UnresolvedDepsMode::Error if unresolved.is_empty() => Ok(()),
+			UnresolvedDepsMode::Error => Err(format!(
+				"Could not resolve {} dependenc{}:\n  {}",
+				unresolved.len(),
+				if unresolved.len() == 1 { "y" } else { "ies" },
+				unresolved.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n  ")
+			)),
+		}
+	}
+
+	/// Resolve the dependency `dep` within the workspace.
+	///
+	/// Errors if `dep` is not a workspace member. Registry and git dependencies only resolve to a
+	/// workspace member if they are patched.
+	fn resolve_dep_from_workspace(&self, dep: &Dependency) -> Option<&'a Package> {
+		if dep.source.is_some() && !self.patched.contains(&dep.name) {
+			return None
+		}
+		self.by_name(&dep.name)
+			.iter()
+			.find(|pkg| self.workspace.contains(pkg.id.repr.as_str()))
+			.copied()
+	}
+
+	/// Resolve the dependency `dep` of `pkg` within the resolve graph.
+	///
+	/// The resolve graph should only be used for external crates. I did not try what happens for
+	/// workspace members - better don't do it.
+	fn resolve_dep_from_graph(&self, pkg: &Package, dep: &Dependency) -> Option<&'a Package> {
+		let dep_name = dep.rename.clone().unwrap_or(dep.name.clone()).replace('-', "_");
+		let resolved_pkg = self.nodes.get(pkg.id.repr.as_str())?;
+		let named = resolved_pkg
+			.deps
+			.iter()
+			.filter(|node| node.name == dep_name)
+			.collect::<Vec<_>>();
+		// The same name can resolve to multiple versions, like on different targets. Each of them
+		// has its own features, so pick the one of this dependency.
+		let resolved_dep_id = named
+			.iter()
+			.find(|node| self.is_resolved_from(node, dep))
+			.or_else(|| named.first())
+			.copied()
+			.or_else(|| {
+				// The node name is the one of the library target, which can differ from the
+				// package.
+				resolved_pkg.deps.iter().filter(|_| dep.rename.is_none()).find(|node| {
+					self.get(&node.pkg.repr)
+						.map_or(false, |p| p.name == dep.name && dep.req.matches(&p.version))
+				})
+			})?;
+
+		self.get(&resolved_dep_id.pkg.repr)
+	}
+
+	/// Whether the resolved `node` fits the version, kind and target of `dep`.
+	///
+	/// Old Cargo versions do not report the kinds, in which case only the version is checked.
+	fn is_resolved_from(&self, node: &NodeDep, dep: &Dependency) -> bool {
+		let Some(pkg) = self.get(&node.pkg.repr) else { return false };
+		dep.req.matches(&pkg.version) &&
+			(node.dep_kinds.is_empty() ||
+				node.dep_kinds.iter().any(|k| k.kind == dep.kind && k.target == dep.target))
+	}
+
+	/// Whether multiple versions of the crate `name` are in the metadata.
+	///
+	/// Findings about such crates should show the version, since the name alone is ambiguous.
+	pub fn has_multiple_versions(&self, name: &str) -> bool {
+		self.by_name(name).len() > 1
+	}
+
+	/// The package of `dep` if cargo resolved it, but as dependency without a library target.
+	///
+	/// This is the case for `artifact = "bin"` dependencies and crates without a library. Cargo
+	/// leaves them out of the `deps` of a resolve node, so they are only found by package ID.
+	pub fn artifact_dep(&self, pkg: &Package, dep: &Dependency) -> Option<&'a Package> {
+		let node = self.nodes.get(pkg.id.repr.as_str())?;
+		node.dependencies
+			.iter()
+			.filter_map(|id| self.get(&id.repr))
+			.find(|p| p.name == dep.name && dep.req.matches(&p.version) && !has_lib(p))
+	}
+}
+
+/// Whether `pkg` has a library target that other crates can link against.
+fn has_lib(pkg: &Package) -> bool {
+	pkg.targets
+		.iter()
+		.flat_map(|t| t.kind.iter())
+		.any(|kind| matches!(kind.as_str(), "lib" | "rlib" | "dylib" | "proc-macro"))
+}
+
+/// Whether cargo uses a different source for `dep` than the one that is declared.
+///
+/// This is the case for `[patch]` and `[replace]` sections in the workspace manifest or in
+/// `.cargo/config.toml`.
+fn is_patched(dep: &Dependency, resolved: &Package) -> bool {
+	let Some(declared) = dep.source.as_ref() else { return false };
+	// Git sources of packages carry the commit as fragment.
+	let strip = |s: &str| s.split('#').next().unwrap_or_default().to_string();
+	resolved.source.as_ref().map_or(true, |s| strip(&s.repr) != strip(declared))
 }
 
-/// Resolve the dependency `dep` of `pkg` within the resolve graph.
+/// Names of all crates that are patched or replaced by the workspace at `root`.
 ///
-/// The resolve graph should only be used for external crates. I did not try what happens for
-/// workspace members - better don't do it.
-pub(crate) fn resolve_dep_from_graph(
-	pkg: &Package,
-	dep: &Dependency,
-	(meta, resolve): (&Metadata, &Resolve),
-) -> Option<RenamedPackage> {
-	let dep_name = dep.rename.clone().unwrap_or(dep.name.clone()).replace('-', "_");
-	let resolved_pkg = resolve.nodes.iter().find(|node| node.id == pkg.id)?;
-	let resolved_dep_id = resolved_pkg.deps.iter().find(|node| node.name == dep_name)?;
-	let resolve_dep = meta.packages.iter().find(|pkg| pkg.id == resolved_dep_id.pkg)?;
+/// Looks at the workspace manifest and all `.cargo/config.toml` files from `root` upwards.
+fn patched_crates(root: &Path) -> HashSet<String> {
+	let mut files = vec![root.join("Cargo.toml")];
+	for dir in root.ancestors() {
+		files.push(dir.join(".cargo").join("config.toml"));
+		files.push(dir.join(".cargo").join("config"));
+	}
 
-	Some(RenamedPackage::new(resolve_dep.clone(), dep.rename.clone(), dep.optional))
+	let mut patched = HashSet::new();
+	for file in files {
+		let Ok(content) = std::fs::read_to_string(&file) else { continue };
+		match patched_in(&content) {
+			Ok(names) => patched.extend(names),
+			Err(err) => {
+				log::warn!("Could not parse {} for patches: {}", file.display(), err);
+			},
+		}
+	}
+	patched
+}
+
+/// Names of the crates in the `[patch]` and `[replace]` sections of a manifest or cargo config.
+pub(crate) fn patched_in(content: &str) -> Result<BTreeSet<String>, String> {
+	let doc = content.parse::<toml_edit::DocumentMut>().map_err(|e| e.to_string())?;
+	let mut patched = BTreeSet::new();
+
+	let patches = doc.get("patch").and_then(|p| p.as_table_like());
+	for (_source, deps) in patches.iter().flat_map(|p| p.iter()) {
+		for (name, dep) in deps.as_table_like().iter().flat_map(|d| d.iter()) {
+			// Renamed patches refer to the crate with `package`.
+			let name = dep.get("package").and_then(|p| p.as_str()).unwrap_or(name);
+			patched.insert(name.to_string());
+		}
+	}
+	// Replacements are specified as `name:version`.
+	let replace = doc.get("replace").and_then(|r| r.as_table_like());
+	for (spec, _) in replace.iter().flat_map(|r| r.iter()) {
+		patched.insert(spec.split(':').next().unwrap_or(spec).to_string());
+	}
+	Ok(patched)
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -325,12 +1502,23 @@ impl RenamedPackage {
 		self.pkg.name.clone()
 	}
 
+	/// The name under which the package is imported, followed by the original one if it differs.
+	///
+	/// All command output should use this when printing a dependency, like `b (renamed from B)`.
 	pub fn display_name(&self) -> String {
 		match &self.rename {
 			Some(rename) => format!("{} (renamed from {})", rename, self.pkg.name),
 			None => self.pkg.name.clone(),
 		}
 	}
+
+	/// Like [`Self::display_name`] but for a `feature` of the package, like `b/F (renamed from B)`.
+	pub fn display_feature(&self, feature: &str) -> String {
+		match &self.rename {
+			Some(rename) => format!("{}/{} (renamed from {})", rename, feature, self.pkg.name),
+			None => format!("{}/{}", self.pkg.name, feature),
+		}
+	}
 }
 
 impl Ord for RenamedPackage {
@@ -380,3 +1568,19 @@ pub(crate) fn check_can_modify<P: AsRef<Path>>(root: P, modify: P) -> Result<boo
 		Ok(true)
 	}
 }
+
+/// Run `cargo check -q` for the package of `manifest`.
+fn cargo_check(manifest: &Path) -> Result<(), String> {
+	let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+	let output = std::process::Command::new(cargo)
+		.args(["check", "-q", "--manifest-path"])
+		.arg(manifest)
+		.output()
+		.map_err(|e| format!("Failed to run cargo check: {e}"))?;
+
+	if output.status.success() {
+		Ok(())
+	} else {
+		Err(String::from_utf8_lossy(&output.stderr).into())
+	}
+}
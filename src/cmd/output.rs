@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Machine readable output of findings for the global `--format` arg.
+//!
+//! Lints describe every finding as [`Finding`] and print it with [`GlobalArgs::annotate`]. The
+//! human readable output is only printed with `--format human`.
+//!
+//! [`GlobalArgs::annotate`]: super::GlobalArgs::annotate
+
+use crate::{config::severity::Severity, issues::Issue};
+use std::{
+	fs::canonicalize,
+	path::{Path, PathBuf},
+};
+
+/// How findings are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+	/// Human readable text that is grouped by crate.
+	#[default]
+	Human,
+	/// GitHub Actions workflow commands that annotate the files in pull requests.
+	Github,
+	/// Like `human`, but `trace` merges its paths into a tree like `cargo tree`.
+	Tree,
+}
+
+/// A single finding of a lint in a file of the workspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+	/// The kind of the finding. Formatting findings have none.
+	pub issue: Option<&'static Issue>,
+	/// Whether the finding fails the lint or is only a warning.
+	pub severity: Severity,
+	/// The file that has to be changed to resolve the finding, normally a manifest.
+	pub file: PathBuf,
+	/// One line description of the finding.
+	pub message: String,
+}
+
+impl Finding {
+	pub fn new<P: Into<PathBuf>>(
+		issue: &'static Issue,
+		severity: Severity,
+		file: P,
+		message: impl Into<String>,
+	) -> Self {
+		Self { issue: Some(issue), severity, file: file.into(), message: message.into() }
+	}
+
+	/// Format as GitHub Actions workflow command like `::error
+	/// file=A/Cargo.toml,title=zepter::msg`.
+	///
+	/// The file path is made relative to the repository, since GitHub ignores absolute paths.
+	pub fn to_github(&self) -> String {
+		let level = match self.severity {
+			Severity::Warn => "warning",
+			Severity::Allow | Severity::Deny => "error",
+		};
+		let file = relative_to_repo(&self.file);
+
+		format!(
+			"::{} file={},title=zepter::{}",
+			level,
+			escape_property(&file.display().to_string()),
+			escape_data(&self.message)
+		)
+	}
+}
+
+/// The path relative to `GITHUB_WORKSPACE` or the current directory, if it is inside of them.
+fn relative_to_repo(path: &Path) -> PathBuf {
+	let path = canonicalize(path).unwrap_or_else(|_| path.into());
+	let bases =
+		[std::env::var_os("GITHUB_WORKSPACE").map(PathBuf::from), std::env::current_dir().ok()];
+
+	bases
+		.into_iter()
+		.flatten()
+		.filter_map(|base| canonicalize(base).ok())
+		.find_map(|base| path.strip_prefix(base).ok().map(Path::to_path_buf))
+		.unwrap_or(path)
+}
+
+/// Escape the message of a workflow command.
+fn escape_data(s: &str) -> String {
+	s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape a property of a workflow command, like the `file`.
+fn escape_property(s: &str) -> String {
+	escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::issues;
+
+	#[test]
+	fn to_github_works() {
+		let finding =
+			Finding::new(&issues::GIT_DEPS, Severity::Warn, "/no/such/A/Cargo.toml", "100% bad");
+		assert_eq!(
+			finding.to_github(),
+			"::warning file=/no/such/A/Cargo.toml,title=zepter::100%25 bad"
+		);
+
+		let finding = Finding { severity: Severity::Deny, message: "a\nb".into(), ..finding };
+		assert!(finding.to_github().starts_with("::error file="));
+		assert!(finding.to_github().ends_with("::a%0Ab"));
+	}
+
+	#[test]
+	fn escape_property_works() {
+		assert_eq!(escape_property("C:\\a,b%"), "C%3A\\a%2Cb%25");
+	}
+}
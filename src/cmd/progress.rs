@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Spinners and progress bars for long running steps.
+//!
+//! They are drawn to stderr and only when it is a terminal, so they never end up in CI logs or in
+//! the output that other tools parse. Without the `progress` feature all of this is a no-op.
+
+use crate::out;
+use std::{
+	fmt::Display,
+	io::IsTerminal,
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether progress should be drawn at all. Set once by [`init`].
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable progress reporting for this process, unless stderr is not a terminal.
+pub fn init(enabled: bool) {
+	ENABLED.store(enabled && std::io::stderr().is_terminal(), Ordering::Relaxed);
+}
+
+/// A spinner or progress bar that is cleared once it is dropped.
+#[derive(Default)]
+pub struct Progress {
+	#[cfg(feature = "progress")]
+	bar: Option<indicatif::ProgressBar>,
+}
+
+impl Progress {
+	/// A spinner for a step of unknown length.
+	pub fn spinner(msg: impl Into<String>) -> Self {
+		#[cfg(feature = "progress")]
+		if ENABLED.load(Ordering::Relaxed) {
+			let bar = indicatif::ProgressBar::new_spinner().with_message(msg.into());
+			bar.enable_steady_tick(std::time::Duration::from_millis(100));
+			return Self { bar: Some(bar) }
+		}
+		let _ = msg;
+		Self::default()
+	}
+
+	/// A progress bar that counts up to `len`.
+	pub fn bar(len: usize, msg: impl Into<String>) -> Self {
+		#[cfg(feature = "progress")]
+		if ENABLED.load(Ordering::Relaxed) {
+			let style = indicatif::ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len}")
+				.expect("Template is valid")
+				.progress_chars("=> ");
+			let bar = indicatif::ProgressBar::new(len as u64)
+				.with_style(style)
+				.with_message(msg.into());
+			return Self { bar: Some(bar) }
+		}
+		let _ = (len, msg);
+		Self::default()
+	}
+
+	/// Advance the bar by one.
+	pub fn inc(&self) {
+		#[cfg(feature = "progress")]
+		if let Some(bar) = self.bar.as_ref() {
+			bar.inc(1);
+		}
+	}
+
+	/// Print a line to stdout without garbling the progress on stderr.
+	pub fn println(&self, line: impl Display) {
+		#[cfg(feature = "progress")]
+		if let Some(bar) = self.bar.as_ref() {
+			return bar.suspend(|| out::println!("{}", line))
+		}
+		out::println!("{}", line);
+	}
+}
+
+impl Drop for Progress {
+	fn drop(&mut self) {
+		#[cfg(feature = "progress")]
+		if let Some(bar) = self.bar.take() {
+			bar.finish_and_clear();
+		}
+	}
+}
@@ -1,11 +1,28 @@
 // SPDX-License-Identifier: GPL-3.0-only
 // SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
 
-use super::GlobalArgs;
+use super::{Command, GlobalArgs};
 use crate::{
-	config::{workflow::WORKFLOW_DEFAULT_NAME, ConfigArgs},
+	config::{
+		git_deps::GitDepsConfig,
+		ignore::IgnoredDependencies,
+		only_enables::OnlyEnablesConfig,
+		path_deps::PathDepsConfig,
+		rules::{rules_from_config, Rule},
+		severity::{Severity, SeverityTable},
+		workflow::{expand_env_vars, WorkflowFile, WORKFLOW_DEFAULT_NAME},
+		ConfigArgs,
+	},
+	grammar::{enumerate, plural},
 	log,
 };
+use clap::{CommandFactory, Parser};
+use std::{
+	collections::BTreeMap,
+	fmt::Write,
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime},
+};
 
 #[derive(Default, Debug, clap::Parser)]
 pub struct RunCmd {
@@ -20,26 +37,408 @@ pub struct RunArgs {
 
 	#[clap(name = "WORKFLOW", index = 1)]
 	pub workflow: Option<String>,
+
+	/// Only run these steps of the workflow.
+	///
+	/// Steps are selected by their position starting at 1, or by their `name`.
+	#[clap(long = "step", value_name = "STEP", value_delimiter = ',')]
+	pub steps: Vec<String>,
+
+	/// Stop at the first failing step instead of running all steps and printing a summary.
+	#[clap(long)]
+	pub fail_fast: bool,
+
+	/// Re-run the workflow whenever a manifest or the config file changes.
+	#[clap(long)]
+	pub watch: bool,
+
+	/// Time in milliseconds that the files must be stable before re-running.
+	#[clap(long, value_name = "MS", default_value_t = 500, requires = "watch")]
+	pub watch_debounce: u64,
+
+	/// Print what the workflow enforces instead of running it.
+	///
+	/// The description is Markdown and can be pasted into the contribution guidelines of a
+	/// project.
+	#[clap(long, conflicts_with = "watch", verbatim_doc_comment)]
+	pub describe: bool,
+
+	/// Do not query crates.io for a newer release of Zepter.
+	///
+	/// This is the default if `CARGO_NET_OFFLINE` is set.
+	#[clap(long, verbatim_doc_comment)]
+	pub offline: bool,
 }
 
 impl RunCmd {
-	pub fn run(&self, g: &GlobalArgs) {
-		let config = self.args.config.load().expect("Invalid config file");
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		if self.args.watch {
+			self.watch(g);
+		}
+
+		let config = self
+			.args
+			.config
+			.load(self.workflow_name())
+			.map_err(|e| format!("Invalid config file: {}", e))?;
+		if self.args.describe {
+			print!("{}", self.describe(&config)?);
+			return Ok(())
+		}
+		let res = self.run_workflow(&config, g);
+		super::update::notify_if_outdated(g, config.update_check(), self.args.offline);
+		if res.is_err() {
+			g.exit(1);
+		}
+		Ok(())
+	}
 
-		let name = self.args.workflow.as_deref().unwrap_or(WORKFLOW_DEFAULT_NAME);
+	fn workflow_name(&self) -> &str {
+		self.args.workflow.as_deref().unwrap_or(WORKFLOW_DEFAULT_NAME)
+	}
+
+	fn run_workflow(&self, config: &WorkflowFile, g: &GlobalArgs) -> Result<(), ()> {
+		let name = self.workflow_name();
 		let Some(workflow) = config.workflow(name) else {
 			panic!("Workflow '{}' not found", name);
 		};
 
 		log::info!("Running workflow '{}'", name);
-		if let Err(err) = workflow.run(g) {
+		let res = workflow.select(&self.args.steps).and_then(|workflow| {
+			if workflow.has_matrix() {
+				self.args
+					.config
+					.workspace_packages()
+					.and_then(|packages| workflow.expand(&packages))
+					.and_then(|workflow| workflow.run(g, config.path(), self.args.fail_fast))
+			} else {
+				workflow.run(g, config.path(), self.args.fail_fast)
+			}
+		});
+		if let Err(err) = res {
 			println!("Error: {}", err);
 
 			if let Some(help) = config.fmt_help() {
 				println!("\n{}", help);
 			}
 
-			std::process::exit(1);
+			return Err(())
+		}
+
+		Ok(())
+	}
+
+	/// Describe the steps of the workflow and the policy of the config file.
+	fn describe(&self, config: &WorkflowFile) -> Result<String, String> {
+		let name = self.workflow_name();
+		let workflow = config
+			.workflow(name)
+			.ok_or_else(|| format!("Workflow '{}' not found", name))?
+			.select(&self.args.steps)?;
+		let env = |name: &str| std::env::var(name).ok();
+
+		let mut out = format!("Workflow `{}` enforces:\n\n", name);
+		for (i, step) in workflow.steps.iter().enumerate() {
+			let args = step
+				.args
+				.iter()
+				.map(|a| expand_env_vars(a, env))
+				.collect::<Result<Vec<_>, _>>()?;
+			let mut lines = describe_step(&args);
+			if !step.foreach_package.is_empty() {
+				lines.push(format!(
+					"Runs once for every package matching {}.",
+					enumerate(&step.foreach_package)
+				));
+			}
+			if step.parallel {
+				lines.push("Runs concurrently with the adjacent `parallel` steps.".into());
+			}
+
+			let _ = writeln!(out, "{}. {}", i + 1, lines[0]);
+			for line in lines.iter().skip(1) {
+				let _ = writeln!(out, "   - {}", line);
+			}
+		}
+
+		for (title, lines) in describe_policy(config.path())? {
+			let _ = writeln!(out, "\n{}:\n", title);
+			for line in lines {
+				let _ = writeln!(out, "- {}", line);
+			}
+		}
+		Ok(out)
+	}
+
+	/// Run the workflow and then re-run it on every change. Never returns.
+	fn watch(&self, g: &GlobalArgs) -> ! {
+		let root = self.args.config.locate_workspace().expect("Locate workspace");
+		let name = self.workflow_name();
+		let debounce = Duration::from_millis(self.args.watch_debounce);
+		let mut watcher = ManifestWatcher::new(&root, self.args.config.locate_config().ok());
+
+		loop {
+			let start = Instant::now();
+			let res = match self.args.config.load(name) {
+				Ok(config) => self.run_workflow(&config, g),
+				Err(err) => {
+					println!("{}", g.error(&format!("Invalid config file: {}", err)));
+					Err(())
+				},
+			};
+			let status = match res {
+				Ok(()) => g.success("passed"),
+				Err(()) => g.error("failed"),
+			};
+			println!("Workflow '{}' {} after {:.2?}.", name, status, start.elapsed());
+			// Fixes of the workflow itself must not trigger the next run.
+			watcher.rescan();
+
+			log::info!("Watching {} file{} for changes…", watcher.len(), plural(watcher.len()));
+			let changed = watcher.wait_for_changes(debounce);
+			let changed = changed
+				.iter()
+				.map(|p| p.strip_prefix(&root).unwrap_or(p).display().to_string())
+				.collect::<Vec<_>>();
+			println!(
+				"\nDetected {} changed file{}: {}",
+				changed.len(),
+				plural(changed.len()),
+				changed.join(", ")
+			);
 		}
 	}
 }
+
+/// Describe a single workflow step by parsing its arguments.
+///
+/// Falls back to the help text of the sub-command if it has no specific description.
+fn describe_step(args: &[String]) -> Vec<String> {
+	let argv = std::iter::once("zepter").chain(args.iter().map(String::as_str));
+	match Command::try_parse_from(argv) {
+		Ok(cmd) =>
+			if let Some(lines) = cmd.describe() {
+				return lines
+			},
+		Err(err) => {
+			log::debug!("Failed to parse step '{}': {}", args.join(" "), err);
+		},
+	}
+
+	let mut cmd = Command::command();
+	let mut about = None;
+	for arg in args.iter().take_while(|a| !a.starts_with('-')) {
+		let Some(sub) = cmd.find_subcommand(arg).cloned() else { break };
+		// Clap strips the trailing period of single sentences.
+		about = sub.get_about().map(|a| format!("{}.", a.to_string().trim_end_matches('.')));
+		cmd = sub;
+	}
+	vec![about.unwrap_or_else(|| format!("Runs `zepter {}`.", args.join(" ")))]
+}
+
+/// Describe the sections of the config file that apply to all lints.
+///
+/// Returns a title and the lines of each non-empty section.
+fn describe_policy(config: Option<&Path>) -> Result<Vec<(&'static str, Vec<String>)>, String> {
+	let mut sections = Vec::new();
+
+	let rules = rules_from_config(config)?;
+	if !rules.is_empty() {
+		sections
+			.push(("Forbidden feature implications", rules.iter().map(Rule::describe).collect()));
+	}
+
+	let mut exempted = IgnoredDependencies::from_config(config)?
+		.iter()
+		.map(|krate| format!("`{}` is ignored by all lints.", krate))
+		.collect::<Vec<_>>();
+	exempted.extend(
+		PathDepsConfig::from_config(config)?
+			.allow
+			.iter()
+			.map(|krate| format!("`{}` may be a path dependency outside of the workspace.", krate)),
+	);
+	exempted.extend(
+		GitDepsConfig::from_config(config)?
+			.allow
+			.iter()
+			.map(|krate| format!("`{}` may be a git dependency regardless of the policy.", krate)),
+	);
+	exempted.extend(
+		OnlyEnablesConfig::from_config(config)?
+			.allow
+			.iter()
+			.map(|exemption| format!("`{}` is exempt from `lint only-enables`.", exemption)),
+	);
+	if !exempted.is_empty() {
+		sections.push(("Exempted crates", exempted));
+	}
+
+	let relaxed = SeverityTable::from_config(config)?
+		.iter()
+		.filter_map(|(kind, severity)| {
+			let effect = match severity {
+				Severity::Allow => "is not reported",
+				Severity::Warn => "is only a warning",
+				Severity::Deny => return None,
+			};
+			let issue = kind.issue();
+			Some(format!("{} ({}) {}.", issue.code, issue.title, effect))
+		})
+		.collect::<Vec<_>>();
+	if !relaxed.is_empty() {
+		sections.push(("Relaxed issues", relaxed));
+	}
+
+	Ok(sections)
+}
+
+/// Polls all manifests of a workspace for modifications.
+///
+/// This does not use any OS-specific file watching API to keep the dependencies minimal.
+pub(crate) struct ManifestWatcher {
+	root: PathBuf,
+	/// Files that are watched in addition to the manifests.
+	extra: Option<PathBuf>,
+	snapshot: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl ManifestWatcher {
+	pub(crate) fn new(root: &Path, extra: Option<PathBuf>) -> Self {
+		let mut watcher = Self { root: root.to_path_buf(), extra, snapshot: Default::default() };
+		watcher.snapshot = watcher.scan();
+		watcher
+	}
+
+	/// Number of watched files.
+	pub(crate) fn len(&self) -> usize {
+		self.snapshot.len()
+	}
+
+	/// Take a new snapshot and forget about all changes since the last one.
+	pub(crate) fn rescan(&mut self) {
+		self.snapshot = self.scan();
+	}
+
+	/// The files that changed since the last call, without waiting for them to be stable.
+	pub(crate) fn poll(&mut self) -> Vec<PathBuf> {
+		let current = self.scan();
+		let changed = changed_files(&self.snapshot, &current);
+		self.snapshot = current;
+		changed
+	}
+
+	/// Block until some files changed and then return them.
+	///
+	/// Only returns once the files did not change for at least `debounce`.
+	fn wait_for_changes(&mut self, debounce: Duration) -> Vec<PathBuf> {
+		let mut latest = self.snapshot.clone();
+
+		loop {
+			std::thread::sleep(debounce);
+			let current = self.scan();
+
+			if current == latest && current != self.snapshot {
+				let changed = changed_files(&self.snapshot, &current);
+				self.snapshot = current;
+				return changed
+			}
+			latest = current;
+		}
+	}
+
+	fn scan(&self) -> BTreeMap<PathBuf, SystemTime> {
+		let mut found = BTreeMap::new();
+		Self::scan_dir(&self.root, &mut found);
+
+		if let Some(extra) = &self.extra {
+			if let Ok(modified) = extra.metadata().and_then(|m| m.modified()) {
+				found.insert(extra.clone(), modified);
+			}
+		}
+		found
+	}
+
+	fn scan_dir(dir: &Path, found: &mut BTreeMap<PathBuf, SystemTime>) {
+		let Ok(entries) = std::fs::read_dir(dir) else { return };
+
+		for entry in entries.filter_map(Result::ok) {
+			let path = entry.path();
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+
+			if path.is_dir() {
+				if name == "target" || name.starts_with('.') {
+					continue
+				}
+				Self::scan_dir(&path, found);
+			} else if name == "Cargo.toml" {
+				if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+					found.insert(path, modified);
+				}
+			}
+		}
+	}
+}
+
+/// The files that were added, removed or modified between two snapshots.
+fn changed_files(
+	old: &BTreeMap<PathBuf, SystemTime>,
+	new: &BTreeMap<PathBuf, SystemTime>,
+) -> Vec<PathBuf> {
+	let mut changed = Vec::new();
+
+	for (path, modified) in new.iter() {
+		if old.get(path) != Some(modified) {
+			changed.push(path.clone());
+		}
+	}
+	for path in old.keys() {
+		if !new.contains_key(path) {
+			changed.push(path.clone());
+		}
+	}
+
+	changed.sort();
+	changed
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn changed_files_works() {
+		let t0 = SystemTime::UNIX_EPOCH;
+		let t1 = t0 + Duration::from_secs(1);
+		let old = BTreeMap::from([("a".into(), t0), ("b".into(), t0), ("c".into(), t0)]);
+		let new = BTreeMap::from([("a".into(), t0), ("b".into(), t1), ("d".into(), t0)]);
+
+		assert_eq!(
+			changed_files(&old, &new),
+			vec![PathBuf::from("b"), PathBuf::from("c"), PathBuf::from("d")]
+		);
+		assert!(changed_files(&new, &new).is_empty());
+	}
+
+	#[test]
+	fn rescan_forgets_changes() {
+		let dir = tempfile::tempdir().unwrap();
+		let manifest = dir.path().join("Cargo.toml");
+		std::fs::write(&manifest, "[features]\n").unwrap();
+		let mut watcher = ManifestWatcher::new(dir.path(), None);
+
+		let later = SystemTime::now() + Duration::from_secs(10);
+		std::fs::File::options()
+			.write(true)
+			.open(&manifest)
+			.unwrap()
+			.set_modified(later)
+			.unwrap();
+		watcher.rescan();
+		assert!(watcher.poll().is_empty());
+
+		std::fs::write(&manifest, "[features]\nstd = []\n").unwrap();
+		assert_eq!(watcher.poll(), vec![manifest]);
+	}
+}
@@ -0,0 +1,363 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Long running server that keeps the metadata of a workspace warm for editor integrations.
+//!
+//! Requests are JSON-RPC 2.0 messages with one message per line on stdin. Every response is
+//! written as a single line to stdout, logs go to stderr. The `lint`, `format` and `trace` methods
+//! run the sub-command of the same name in this process. Its output is captured through the
+//! [`out`](crate::out) macros and exiting only ends the request.
+//!
+//! The metadata and the feature graph that was built from it are kept between requests. Both are
+//! reloaded once a manifest of the workspace changed.
+
+use super::{
+	lint::{build_uncached_feature_dag, DagBuildError},
+	run::ManifestWatcher,
+	CargoArgs, Command, GlobalArgs, PackageIndex, UnresolvedDep,
+};
+use crate::{dag::Dag, grammar::plural, graph::CrateAndFeature, log};
+use cargo_metadata::{Metadata, Package};
+use clap::Parser;
+use serde_json::{json, Value};
+use std::{
+	collections::BTreeSet,
+	fmt::Write,
+	io::BufRead,
+	panic::AssertUnwindSafe,
+	path::{Path, PathBuf},
+	sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// The workspace that is served right now, if any.
+static SERVED: Mutex<Option<Served>> = Mutex::new(None);
+
+/// The output of the request that is handled right now, if any.
+static CAPTURED: Mutex<Option<Captured>> = Mutex::new(None);
+
+/// The metadata of the served workspace and the feature graph of all its packages.
+struct Served {
+	meta: Metadata,
+	dag: Dag<CrateAndFeature>,
+	malformed: Vec<DagBuildError>,
+	/// The dependencies that could not be resolved while building the `dag`.
+	unresolved: BTreeSet<UnresolvedDep>,
+}
+
+#[derive(Default)]
+struct Captured {
+	stdout: String,
+	stderr: String,
+}
+
+/// Unwinds a request that called [`exit`] back to the server.
+struct Exit(i32);
+
+/// The JSON-RPC error codes that the server uses.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Serve lint, format and trace requests as JSON-RPC over stdio.
+///
+/// Keeps the metadata and the feature graph of the workspace loaded between requests and reloads
+/// them when a manifest changes.
+#[derive(Debug, clap::Parser)]
+pub struct ServeCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+}
+
+/// The state of a running server.
+struct Server {
+	root: PathBuf,
+	/// The `cargo_args` of the command with absolute manifest paths.
+	cargo_args: CargoArgs,
+	watcher: ManifestWatcher,
+	packages: usize,
+}
+
+/// A single JSON-RPC request. Requests without `id` are notifications and get no response.
+#[derive(serde::Deserialize)]
+struct Request {
+	id: Option<Value>,
+	method: String,
+	#[serde(default)]
+	params: Value,
+}
+
+/// The params of the `lint`, `format` and `trace` methods.
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CommandParams {
+	/// Arguments that are passed after the sub-command, like `propagate-feature --features=std`.
+	#[serde(default)]
+	args: Vec<String>,
+}
+
+/// An error that is sent back as JSON-RPC error object.
+struct RpcError {
+	code: i64,
+	message: String,
+}
+
+impl RpcError {
+	fn new(code: i64, message: impl Into<String>) -> Self {
+		Self { code, message: message.into() }
+	}
+}
+
+impl ServeCmd {
+	pub fn run(&self, _global: &GlobalArgs) -> Result<(), String> {
+		let meta = self.cargo_args.load_metadata()?;
+		let root = meta.workspace_root.clone().into_std_path_buf();
+		let mut cargo_args = self.cargo_args.clone();
+		for path in cargo_args.manifest_path.iter_mut() {
+			*path = path
+				.canonicalize()
+				.map_err(|e| format!("Failed to find {}: {}", path.display(), e))?;
+		}
+		// Requests run in the workspace root, so that their relative paths resolve in there.
+		std::env::set_current_dir(&root)
+			.map_err(|e| format!("Failed to change into {}: {}", root.display(), e))?;
+		// The output of the requests is not shown in a terminal.
+		std::env::set_var("CARGO_TERM_COLOR", "never");
+
+		let mut server =
+			Server { watcher: ManifestWatcher::new(&root, None), root, cargo_args, packages: 0 };
+		server.store(meta);
+		log::info!(
+			"Serving {} with {} package{}",
+			server.root.display(),
+			server.packages,
+			plural(server.packages)
+		);
+
+		let res = server.serve();
+		*served() = None;
+		res
+	}
+}
+
+impl Server {
+	/// Answer requests until stdin is closed or `shutdown` was requested.
+	fn serve(&mut self) -> Result<(), String> {
+		let stdin = std::io::stdin();
+
+		for line in stdin.lock().lines() {
+			let line = line.map_err(|e| format!("Failed to read from stdin: {}", e))?;
+			if line.trim().is_empty() {
+				continue
+			}
+			let request = serde_json::from_str::<Value>(&line)
+				.map_err(|e| RpcError::new(PARSE_ERROR, e.to_string()))
+				.and_then(|value| {
+					serde_json::from_value::<Request>(value)
+						.map_err(|e| RpcError::new(INVALID_REQUEST, e.to_string()))
+				});
+			let request = match request {
+				Ok(request) => request,
+				Err(err) => {
+					respond(None, Err(err));
+					continue
+				},
+			};
+
+			let result = self.handle(&request);
+			if request.id.is_some() {
+				respond(request.id.clone(), result);
+			}
+			if request.method == "shutdown" {
+				return Ok(())
+			}
+		}
+		Ok(())
+	}
+
+	fn handle(&mut self, request: &Request) -> Result<Value, RpcError> {
+		match request.method.as_str() {
+			"lint" | "format" | "trace" => {
+				let params = match &request.params {
+					Value::Null => CommandParams::default(),
+					params => serde_json::from_value(params.clone())
+						.map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?,
+				};
+				self.refresh().map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+				Ok(run_command(&request.method, &params.args))
+			},
+			"reload" => {
+				self.reload().map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+				Ok(json!({ "packages": self.packages }))
+			},
+			"shutdown" => Ok(Value::Null),
+			method => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+		}
+	}
+
+	/// Reload the metadata if a manifest changed since the last request.
+	fn refresh(&mut self) -> Result<(), String> {
+		let changed = self.watcher.poll();
+		if changed.is_empty() {
+			return Ok(())
+		}
+		log::info!(
+			"Reloading the metadata since {} manifest{} changed",
+			changed.len(),
+			plural(changed.len())
+		);
+		self.reload()
+	}
+
+	fn reload(&mut self) -> Result<(), String> {
+		// Otherwise the cached metadata would be loaded again.
+		*served() = None;
+		let meta = self.cargo_args.load_metadata()?;
+		// Also catches manifests that were added or removed by the reload itself.
+		self.watcher = ManifestWatcher::new(&self.root, None);
+		self.store(meta);
+		Ok(())
+	}
+
+	/// Build the feature graph of the metadata and keep both for the requests.
+	fn store(&mut self, meta: Metadata) {
+		let index = PackageIndex::new(&meta);
+		let (dag, malformed) = build_uncached_feature_dag(&index, &meta.packages);
+		let unresolved = index.unresolved.take();
+		self.packages = meta.packages.len();
+		*served() = Some(Served { meta, dag, malformed, unresolved });
+	}
+}
+
+/// Run `zepter <subcommand> <args>` in this process and return its exit code and output.
+fn run_command(subcommand: &str, args: &[String]) -> Value {
+	let argv = ["zepter", subcommand].into_iter().map(String::from).chain(args.iter().cloned());
+	let cmd = match Command::try_parse_from(argv) {
+		Ok(cmd) => cmd,
+		// Like `--help` or a missing argument.
+		Err(err) => {
+			let (stdout, stderr) = match err.use_stderr() {
+				true => (String::new(), err.to_string()),
+				false => (err.to_string(), String::new()),
+			};
+			return json!({ "code": err.exit_code(), "stdout": stdout, "stderr": stderr })
+		},
+	};
+	log::debug!("Running command 'zepter {} {}'", subcommand, args.join(" "));
+
+	#[cfg(feature = "logging")]
+	let level = ::log::max_level();
+	*captured() = Some(Captured::default());
+	let res = std::panic::catch_unwind(AssertUnwindSafe(|| cmd.run()));
+	let Captured { stdout, mut stderr } = captured().take().unwrap_or_default();
+	#[cfg(feature = "logging")]
+	::log::set_max_level(level);
+
+	let code = match res {
+		Ok(Ok(())) => 0,
+		Ok(Err(err)) => {
+			stderr.push_str(&format!("{}\n", err));
+			1
+		},
+		Err(payload) => match payload.downcast::<Exit>() {
+			Ok(exit) => exit.0,
+			// The panic hook already logged it, but the client should also see it.
+			Err(payload) => {
+				let msg = payload
+					.downcast_ref::<&str>()
+					.map(ToString::to_string)
+					.or_else(|| payload.downcast_ref::<String>().cloned())
+					.unwrap_or_default();
+				stderr.push_str(&format!("The command panicked: {}\n", msg));
+				101
+			},
+		},
+	};
+
+	json!({ "code": code, "stdout": stdout, "stderr": stderr })
+}
+
+/// Write the response to a request as a single line.
+fn respond(id: Option<Value>, result: Result<Value, RpcError>) {
+	let id = id.unwrap_or(Value::Null);
+	let response = match result {
+		Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+		Err(err) => json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"error": { "code": err.code, "message": err.message },
+		}),
+	};
+	println!("{}", response);
+}
+
+/// The metadata of the served workspace, if this process serves one.
+///
+/// Only used when no explicit manifest path was passed, since the requests run in the root of the
+/// served workspace.
+pub(crate) fn cached_metadata(manifest_path: Option<&Path>) -> Option<Result<Metadata, String>> {
+	if manifest_path.is_some() {
+		return None
+	}
+	served().as_ref().map(|served| Ok(served.meta.clone()))
+}
+
+/// The feature graph of the served workspace, if `pkgs` are all of its packages.
+///
+/// Replays the unresolved dependencies into the `index`, as if the graph was built with it. Not
+/// used when some crates are [assumed to be
+/// published](PackageIndex::assume_published), since that changes how their dependencies
+/// resolve.
+pub(crate) fn cached_feature_dag(
+	index: &PackageIndex<'_>,
+	pkgs: &[Package],
+) -> Option<(Dag<CrateAndFeature>, Vec<DagBuildError>)> {
+	let served = served();
+	let served = served.as_ref()?;
+	if !index.published.is_empty() || served.meta.packages != pkgs {
+		return None
+	}
+	index.unresolved.borrow_mut().extend(served.unresolved.iter().cloned());
+	Some((served.dag.clone(), served.malformed.clone()))
+}
+
+/// Exit the process with `code`, or only end the request that is handled right now.
+pub(crate) fn exit(code: i32) -> ! {
+	if captured().is_some() {
+		std::panic::resume_unwind(Box::new(Exit(code)))
+	}
+	std::process::exit(code)
+}
+
+/// Write to stdout or into the response of the request that is handled right now.
+#[doc(hidden)]
+pub fn write_stdout(args: std::fmt::Arguments<'_>) {
+	match captured().as_mut() {
+		Some(captured) => {
+			let _ = captured.stdout.write_fmt(args);
+		},
+		None => std::print!("{}", args),
+	}
+}
+
+/// Write to stderr or into the response of the request that is handled right now.
+#[doc(hidden)]
+pub fn write_stderr(args: std::fmt::Arguments<'_>) {
+	match captured().as_mut() {
+		Some(captured) => {
+			let _ = captured.stderr.write_fmt(args);
+		},
+		None => std::eprint!("{}", args),
+	}
+}
+
+fn served() -> MutexGuard<'static, Option<Served>> {
+	// A panicking request does not leave the state half-written.
+	SERVED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn captured() -> MutexGuard<'static, Option<Captured>> {
+	CAPTURED.lock().unwrap_or_else(PoisonError::into_inner)
+}
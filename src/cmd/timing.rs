@@ -0,0 +1,140 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Timing of the phases of a command for the global `--verbose` flag.
+//!
+//! Phases are measured with a [`Phase`] guard and summed up by name. A phase that starts while
+//! another one is running pauses the outer one, so the times never overlap and the rest of the
+//! total is reported as `other`. Without `--verbose` all of this is a no-op.
+
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+/// Whether phases should be measured at all. Set once by [`init`].
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static STATE: Mutex<State> =
+	Mutex::new(State { start: None, totals: Vec::new(), running: Vec::new() });
+
+struct State {
+	/// When the command started.
+	start: Option<Instant>,
+	/// Total time of every phase in the order in which they first ran.
+	totals: Vec<(&'static str, Duration)>,
+	/// Stack of running phases and since when they run without being paused.
+	running: Vec<(&'static str, Instant)>,
+}
+
+impl State {
+	fn add(&mut self, name: &'static str, elapsed: Duration) {
+		match self.totals.iter_mut().find(|(n, _)| *n == name) {
+			Some((_, total)) => *total += elapsed,
+			None => self.totals.push((name, elapsed)),
+		}
+	}
+}
+
+/// Enable the timing for this process and start the clock of the total.
+pub fn init(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+	if enabled {
+		lock().start = Some(Instant::now());
+	}
+}
+
+fn lock() -> std::sync::MutexGuard<'static, State> {
+	STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Measures a phase until it is dropped.
+#[must_use = "The phase ends when this is dropped"]
+pub struct Phase {
+	enabled: bool,
+}
+
+impl Phase {
+	/// Start a phase like `cargo metadata` and pause the currently running one.
+	pub fn start(name: &'static str) -> Self {
+		let enabled = ENABLED.load(Ordering::Relaxed);
+		if enabled {
+			let now = Instant::now();
+			let mut state = lock();
+			if let Some((outer, since)) = state.running.last().copied() {
+				state.add(outer, now - since);
+			}
+			state.running.push((name, now));
+		}
+		Self { enabled }
+	}
+}
+
+impl Drop for Phase {
+	fn drop(&mut self) {
+		if !self.enabled {
+			return
+		}
+		let now = Instant::now();
+		let mut state = lock();
+		if let Some((name, since)) = state.running.pop() {
+			state.add(name, now - since);
+		}
+		// Resume the outer phase.
+		if let Some((_, since)) = state.running.last_mut() {
+			*since = now;
+		}
+	}
+}
+
+/// Print the time of every phase to stderr. Only prints once per process.
+///
+/// Phases that are still running, for example because the command exits early, are counted up
+/// to now.
+pub fn report() {
+	if !ENABLED.swap(false, Ordering::Relaxed) {
+		return
+	}
+	let now = Instant::now();
+	let mut state = lock();
+	let Some(start) = state.start else { return };
+	while let Some((name, since)) = state.running.pop() {
+		state.add(name, now - since);
+	}
+	eprint!("{}", format_report(&state.totals, now - start));
+}
+
+/// Format the phases as table with the rest of `total` as `other`.
+fn format_report(phases: &[(&str, Duration)], total: Duration) -> String {
+	let measured = phases.iter().map(|(_, d)| *d).sum::<Duration>();
+	let mut rows = phases.to_vec();
+	rows.push(("other", total.saturating_sub(measured)));
+	rows.push(("total", total));
+
+	let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+	let mut out = String::from("Timing:\n");
+	for (name, duration) in rows {
+		out.push_str(&format!("  {:<width$}  {:.2?}\n", name, duration));
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn format_report_works() {
+		let ms = Duration::from_millis;
+		let report = format_report(&[("cargo metadata", ms(300)), ("analysis", ms(50))], ms(400));
+
+		assert_eq!(
+			report,
+			"Timing:\n  cargo metadata  300.00ms\n  analysis        50.00ms\n  other           50.00ms\n  total           400.00ms\n"
+		);
+		assert!(format_report(&[], ms(1)).ends_with("  other  1.00ms\n  total  1.00ms\n"));
+	}
+}
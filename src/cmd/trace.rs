@@ -4,8 +4,7 @@
 //! Trace the dependency path from one crate to another.
 
 use super::*;
-use crate::{dag::Dag, log, CrateId};
-use cargo_metadata::{Metadata, Package};
+use crate::{dag::Dag, graph::CrateAndFeature, log, out, CrateId};
 use clap::Parser;
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -16,13 +15,9 @@ pub struct TraceCmd {
 	#[clap(flatten)]
 	cargo_args: super::CargoArgs,
 
-	/// Show the source location of crates in the output.
-	#[clap(long)]
-	show_source: bool,
-
-	/// Show the version of the crates in the output.
-	#[clap(long)]
-	show_version: bool,
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	display: DisplayArgs,
 
 	/// Delimiter for rendering dependency paths.
 	#[clap(long, default_value = " -> ")]
@@ -42,12 +37,26 @@ pub struct TraceCmd {
 	/// The dependency crate to end at.
 	#[clap(index(2))]
 	to: String,
+
+	/// Feature of the root crate to start from.
+	///
+	/// Traces the feature activation path instead of the dependency path.
+	#[clap(long, requires = "to_feature")]
+	from_feature: Option<String>,
+
+	/// Feature of the dependency crate to end at.
+	#[clap(long, requires = "from_feature")]
+	to_feature: Option<String>,
 }
 
+/// A path of crate IDs and optionally the feature that is enabled at each hop.
+type TracePath = Vec<(CrateId, Option<String>)>;
+
 impl TraceCmd {
-	pub fn run(&self, _global: &GlobalArgs) {
-		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
-		let (dag, index) = Self::build_dag(meta).expect("Builds dependency graph");
+	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
+		let (meta, origins) = self.cargo_args.load_merged_metadata()?;
+		let index = PackageIndex::new(&meta);
+		let renames = lint::dependency_renames(&index, &meta.packages);
 		let lookup = |id: &str| {
 			index
 				.get(id)
@@ -55,23 +64,102 @@ impl TraceCmd {
 		};
 
 		let froms = index
+			.by_name(&self.from)
 			.iter()
-			.filter(|(_id, krate)| krate.name == self.from)
-			.map(|(id, _)| id)
+			.map(|krate| krate.id.to_string())
 			.collect::<Vec<_>>();
 		if froms.is_empty() {
 			panic!("Could not find crate {} in the left dependency graph", self.from);
 		}
 
 		let tos = index
+			.by_name(&self.to)
 			.iter()
-			.filter(|(_id, krate)| krate.name == self.to)
-			.map(|(id, _)| id)
+			.map(|krate| krate.id.to_string())
 			.collect::<Vec<_>>();
 		if tos.is_empty() {
 			panic!("Could not find crate {} in the right dependency graph", self.to);
 		}
 
+		let paths = if let (Some(from_feature), Some(to_feature)) =
+			(&self.from_feature, &self.to_feature)
+		{
+			let dag = lint::build_feature_dag(&index, &meta.packages);
+			index.report_unresolved(self.cargo_args.unresolved_deps)?;
+			let froms = froms
+				.into_iter()
+				.map(|id| CrateAndFeature(id, from_feature.clone()))
+				.collect::<Vec<_>>();
+			let tos = tos
+				.into_iter()
+				.map(|id| CrateAndFeature(id, to_feature.clone()))
+				.collect::<Vec<_>>();
+
+			Self::all_paths(&dag, &froms, &tos, |CrateAndFeature(id, feature)| {
+				(id.clone(), Some(feature.clone()))
+			})
+		} else {
+			let dag = index.crate_dag(|_| true);
+			index.report_unresolved(self.cargo_args.unresolved_deps)?;
+			Self::all_paths(&dag, &froms, &tos, |id| (id.clone(), None))
+		};
+		if paths.is_empty() {
+			panic!("No path found");
+		}
+		log::info!("Found {} distinct paths", paths.len());
+		// Unescape the delimiter - the ghetto way.
+		let delimiter = self.path_delimiter.replace("\\n", "\n").replace("\\t", "\t");
+
+		let label = |prev: Option<&CrateId>, id: &CrateId, feature: &Option<String>| {
+			let krate = lookup(id);
+			let rename = prev.and_then(|prev| renames.get(&(prev.clone(), id.clone())));
+			let mut out = rename.unwrap_or(&krate.name).clone();
+			if let Some(feature) = feature {
+				out.push_str(&format!("/{}", feature));
+			}
+			if rename.is_some() {
+				out.push_str(&format!(" (renamed from {})", krate.name));
+			}
+			if let Some(origin) = origins.get(id) {
+				out.push_str(&format!(" [{}]", origin));
+			}
+			out.push_str(&self.display.details(krate));
+			out
+		};
+		let labeled = paths.iter().map(|path| {
+			let mut prev: Option<&CrateId> = None;
+			path.iter()
+				.map(|(id, feature)| {
+					let node = ((id.clone(), feature.clone()), label(prev, id, feature));
+					prev = Some(id);
+					node
+				})
+				.collect::<Vec<_>>()
+		});
+
+		// Paths that share a prefix are only printed once in the tree.
+		if global.format() == OutputFormat::Tree {
+			let mut tree = TraceTree::default();
+			for path in labeled {
+				tree.insert(path);
+			}
+			out::print!("{}", tree.render());
+		} else {
+			for path in labeled {
+				let out = path.into_iter().map(|(_, label)| label).collect::<Vec<_>>();
+				out::println!("{}", out.join(&delimiter));
+			}
+		}
+		Ok(())
+	}
+
+	/// Find a path between every pair of `froms` and `tos` and translate its nodes with `f`.
+	fn all_paths<T: Ord + Clone>(
+		dag: &Dag<T>,
+		froms: &[T],
+		tos: &[T],
+		f: impl Fn(&T) -> (CrateId, Option<String>),
+	) -> BTreeSet<TracePath> {
 		log::info!(
 			"No version or features specified: Checking all {} possibly distinct paths",
 			froms.len() * tos.len()
@@ -81,63 +169,71 @@ impl TraceCmd {
 		for from in froms.iter() {
 			for to in tos.iter() {
 				if let Some(path) = dag.any_path(from, to) {
-					paths.insert(path);
+					paths.insert(path.0.iter().map(|node| f(node.as_ref())).collect());
 				}
 			}
 		}
-		if paths.is_empty() {
-			panic!("No path found");
-		}
-		log::info!("Found {} distinct paths", paths.len());
-		// Unescape the delimiter - the ghetto way.
-		let delimiter = self.path_delimiter.replace("\\n", "\n").replace("\\t", "\t");
 
-		for path in paths {
-			let mut out = String::new();
-			let mut is_first = true;
+		paths
+	}
+}
 
-			path.for_each(|id| {
-				let krate = lookup(id);
-				if !is_first {
-					out.push_str(&delimiter);
-				}
-				is_first = false;
-				out.push_str(&krate.name);
-				if self.show_version {
-					out.push_str(&format!(" v{}", krate.version));
-				}
-				if self.show_source {
-					if let Some(source) = krate.source.as_ref() {
-						out.push_str(&format!(" ({})", source.repr));
-					} else {
-						out.push_str(" (local)");
-					}
-				}
-			});
+/// Paths that are merged by their common prefixes, like the output of `cargo tree`.
+#[derive(Debug, Default)]
+struct TraceTree {
+	/// The label and sub-tree of every node by its crate and feature.
+	children: BTreeMap<(CrateId, Option<String>), (String, TraceTree)>,
+}
 
-			println!("{out}");
+impl TraceTree {
+	/// Insert a path of nodes and their labels.
+	fn insert(&mut self, path: impl IntoIterator<Item = ((CrateId, Option<String>), String)>) {
+		let mut tree = self;
+		for (node, label) in path {
+			tree = &mut tree.children.entry(node).or_insert_with(|| (label, Self::default())).1;
 		}
 	}
 
-	/// Build a dependency graph over the crates ids and return an index of all crates.
-	fn build_dag(meta: Metadata) -> Result<(Dag<CrateId>, BTreeMap<CrateId, Package>), String> {
-		let mut dag = Dag::new();
-		let mut index = BTreeMap::new();
-
-		for pkg in meta.packages.clone() {
-			let id = pkg.id.to_string();
-			dag.add_node(id.clone());
-			index.insert(pkg.id.to_string(), pkg.clone());
-
-			for dep in pkg.dependencies.iter() {
-				if let Some(dep) = resolve_dep(&pkg, dep, &meta) {
-					let dep = dep.pkg; // TODO account for renaming
-					let did = dep.id.to_string();
-					dag.add_edge(id.clone(), did);
-				}
-			}
+	/// Render every root on its own line and its sub-tree below it.
+	fn render(&self) -> String {
+		let mut out = String::new();
+		for (label, tree) in self.children.values() {
+			out.push_str(&format!("{}\n", label));
+			tree.render_children("", &mut out);
+		}
+		out
+	}
+
+	fn render_children(&self, prefix: &str, out: &mut String) {
+		let len = self.children.len();
+		for (i, (label, tree)) in self.children.values().enumerate() {
+			let last = i + 1 == len;
+			let (branch, indent) =
+				if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+			out.push_str(&format!("{}{}{}\n", prefix, branch, label));
+			tree.render_children(&format!("{}{}", prefix, indent), out);
 		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn path(nodes: &[&str]) -> Vec<((CrateId, Option<String>), String)> {
+		nodes.iter().map(|&n| ((n.to_string(), None), n.to_string())).collect()
+	}
+
+	#[test]
+	fn trace_tree_merges_prefixes() {
+		let mut tree = TraceTree::default();
+		tree.insert(path(&["A", "B", "D"]));
+		tree.insert(path(&["A", "C", "D"]));
+		tree.insert(path(&["A", "B", "E", "D"]));
 
-		Ok((dag, index))
+		assert_eq!(
+			tree.render(),
+			"A\n├── B\n│   ├── D\n│   └── E\n│       └── D\n└── C\n    └── D\n"
+		);
 	}
 }
@@ -4,6 +4,7 @@
 use crate::{
 	cmd::{
 		check_can_modify,
+		lint::DepKind,
 		transpose::{AutoFixer, Dep, Op, SourceLocationSelector, Version, VersionReq},
 		CargoArgs, GlobalArgs,
 	},
@@ -12,7 +13,7 @@ use crate::{
 };
 use cargo_metadata::Package;
 use itertools::Itertools;
-use std::collections::{BTreeMap, BTreeMap as Map, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeMap as Map, BTreeSet};
 
 /// Lift up a dependency to the workspace and reference it from all packages.
 #[derive(Debug, clap::Parser)]
@@ -26,12 +27,16 @@ pub struct LiftToWorkspaceCmd {
 
 	/// Instead of dry-running, actually modify the files.
 	#[clap(long)]
-	fix: bool,
+	pub(crate) fix: bool,
 
 	/// How to determine which version to use for the whole workspace.
 	#[clap(long, alias = "version-resolver", value_enum, default_value_t = VersionSelectorMode::Unambiguous, requires_if("exact", "exact_version"))]
 	version_selector: VersionSelectorMode,
 
+	/// How to reconcile the `features` of a dependency between the packages.
+	#[clap(long, value_enum, default_value_t = FeatureSelectorMode::None)]
+	feature_selector: FeatureSelectorMode,
+
 	/// Do not try to modify this package.
 	#[clap(long)]
 	skip_package: Option<String>,
@@ -47,6 +52,13 @@ pub struct LiftToWorkspaceCmd {
 	/// Ignore errors and continue with the next dependency.
 	#[clap(long)]
 	ignore_errors: bool,
+
+	/// Only lift dependencies of these kinds: `normal`, `dev` or `build`.
+	///
+	/// The other dependency tables are neither considered for the version and features nor
+	/// modified. Can be comma separated. All kinds are lifted by default.
+	#[clap(long, value_name = "KIND", value_delimiter = ',', verbatim_doc_comment)]
+	kind: Vec<DepKind>,
 }
 
 /// How to determine which version to use for the whole workspace.
@@ -62,6 +74,21 @@ pub enum VersionSelectorMode {
 	Highest,
 }
 
+/// How to reconcile the `features` of a dependency between the packages.
+#[derive(Debug, Clone, PartialEq, clap::ValueEnum)]
+pub enum FeatureSelectorMode {
+	/// Keep the features in the packages and do not add any to the workspace.
+	None,
+	/// Put the features that all packages use into the workspace.
+	///
+	/// The packages only keep the features that they use in addition to these.
+	Intersection,
+	/// Put the features that any package uses into the workspace.
+	///
+	/// This enables more features for packages that used fewer.
+	Union,
+}
+
 impl LiftToWorkspaceCmd {
 	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
 		g.warn_unstable();
@@ -80,7 +107,7 @@ impl LiftToWorkspaceCmd {
 		}
 
 		for pkg in meta.packages.iter() {
-			for dep in pkg.dependencies.iter() {
+			for dep in pkg.dependencies.iter().filter(|d| self.has_kind(d)) {
 				if !regex_lookup.values().any(|r| r.is_match(&dep.name)) &&
 					!self.dependencies.contains(&dep.name)
 				{
@@ -111,7 +138,13 @@ impl LiftToWorkspaceCmd {
 			}
 		}
 
-		self.try_apply_changes(&mut fixers)
+		self.try_apply_changes(&mut fixers, g)
+	}
+
+	/// Whether `dep` is of one of the kinds that `--kind` selected.
+	fn has_kind(&self, dep: &Dep) -> bool {
+		self.kind.is_empty() ||
+			DepKind::try_from(dep.kind).map_or(false, |kind| self.kind.contains(&kind))
 	}
 
 	fn validate_args(&self) -> Result<(), String> {
@@ -124,6 +157,7 @@ impl LiftToWorkspaceCmd {
 	fn try_apply_changes(
 		&self,
 		fixers: &mut Map<String, (Option<Package>, AutoFixer)>,
+		g: &GlobalArgs,
 	) -> Result<(), String> {
 		let mut modified = 0;
 		for (_pkg, fixer) in fixers.values_mut() {
@@ -133,7 +167,7 @@ impl LiftToWorkspaceCmd {
 
 			modified += 1;
 			if self.fix {
-				fixer.save()?;
+				g.save_fixer(fixer)?;
 			} else if let Some(_pkg) = _pkg {
 				log::debug!("Would modify {:?}", _pkg.name);
 			} else {
@@ -160,18 +194,23 @@ impl LiftToWorkspaceCmd {
 	) -> Result<(), String> {
 		let maybe_rename = self.detect_rename(g, name, meta)?;
 		let source_location = self.detect_source_location(meta, name)?;
-		let by_version = Self::build_version_index(meta, name);
-		let versions = by_version.keys().collect::<Vec<_>>();
+		let by_version = self.build_version_index(meta, name);
+		let versions = by_version.values().map(|deps| &deps[0].1.req).collect::<Vec<_>>();
 		let best_version = self.find_best_version(g, name, &versions, &by_version)?;
 
 		let mut all_use_default_features = true;
+		let mut member_features = Vec::<BTreeSet<&str>>::new();
 		for (pkg, dep) in by_version.values().flatten() {
 			if !check_can_modify(&meta.workspace_root, &pkg.manifest_path)? {
 				continue
 			}
 
 			all_use_default_features &= dep.uses_default_features;
+			if self.skip_package.as_ref() != Some(&pkg.name) {
+				member_features.push(dep.features.iter().map(String::as_str).collect());
+			}
 		}
+		let workspace_features = self.select_features(&member_features);
 
 		// We default in the workspace to enabling them if all packages use them but otherwise turn
 		// them off.
@@ -205,6 +244,7 @@ impl LiftToWorkspaceCmd {
 			&dep,
 			maybe_rename.as_deref(),
 			workspace_default_features_enabled,
+			workspace_features.as_deref(),
 			location.as_deref(),
 		)?;
 
@@ -227,17 +267,36 @@ impl LiftToWorkspaceCmd {
 			if let Some(rename) = &maybe_rename {
 				assert_eq!(rename, dep_name);
 			}
-			let ref location = source_location;
+			let location = &source_location;
+
+			// The package only needs to list the features that the workspace does not enable.
+			let features = workspace_features.as_ref().map(|workspace| {
+				let gained = workspace.iter().filter(|f| !dep.features.contains(f)).join(", ");
+				if !gained.is_empty() {
+					log::warn!(
+						"Lifting '{}' enables additional features in '{}': {}",
+						name,
+						pkg.name,
+						gained
+					);
+				}
+				dep.features
+					.iter()
+					.filter(|f| !workspace.contains(f))
+					.cloned()
+					.collect::<Vec<_>>()
+			});
 
 			if dep.uses_default_features != workspace_default_features_enabled {
 				fixer.lift_dependency(
 					dep_name,
 					&dep.kind,
 					Some(dep.uses_default_features),
+					features.as_deref(),
 					location,
 				)?;
 			} else {
-				fixer.lift_dependency(dep_name, &dep.kind, None, location)?;
+				fixer.lift_dependency(dep_name, &dep.kind, None, features.as_deref(), location)?;
 			}
 		}
 
@@ -247,7 +306,7 @@ impl LiftToWorkspaceCmd {
 			let v = format!("{} {}", name, &best_version);
 			log::info!(
 				"Selected {} for lift up in {} crate{}.",
-				g.bold(&v),
+				g.emphasis(&v),
 				total_changes,
 				plural(total_changes)
 			);
@@ -256,19 +315,40 @@ impl LiftToWorkspaceCmd {
 		Ok(())
 	}
 
+	/// The features to put into the workspace dependency, or `None` to leave them in the packages.
+	fn select_features(&self, members: &[BTreeSet<&str>]) -> Option<Vec<String>> {
+		let mut members = members.iter();
+		let first = members.next().cloned().unwrap_or_default();
+
+		let selected = match self.feature_selector {
+			FeatureSelectorMode::None => return None,
+			FeatureSelectorMode::Intersection =>
+				members.fold(first, |acc, m| acc.intersection(m).copied().collect()),
+			FeatureSelectorMode::Union =>
+				members.fold(first, |acc, m| acc.union(m).copied().collect()),
+		};
+		Some(selected.into_iter().map(Into::into).collect())
+	}
+
 	/// Index what versions of a crate are used in the workspace.
+	///
+	/// Keyed by the version requirement string to iterate them in a stable order.
 	fn build_version_index(
+		&self,
 		meta: &cargo_metadata::Metadata,
 		name: &str,
-	) -> HashMap<VersionReq, Vec<(Package, Dep)>> {
-		let mut by_version = HashMap::<VersionReq, Vec<(Package, Dep)>>::new();
+	) -> BTreeMap<String, Vec<(Package, Dep)>> {
+		let mut by_version = BTreeMap::<String, Vec<(Package, Dep)>>::new();
 		for pkg in meta.packages.iter() {
-			for dep in pkg.dependencies.iter() {
+			for dep in pkg.dependencies.iter().filter(|d| self.has_kind(d)) {
 				if dep.name != name {
 					continue
 				}
 
-				by_version.entry(dep.req.clone()).or_default().push((pkg.clone(), dep.clone()));
+				by_version
+					.entry(dep.req.to_string())
+					.or_default()
+					.push((pkg.clone(), dep.clone()));
 			}
 		}
 		by_version
@@ -291,7 +371,7 @@ impl LiftToWorkspaceCmd {
 				}
 			}
 
-			for dep in pkg.dependencies.iter() {
+			for dep in pkg.dependencies.iter().filter(|d| self.has_kind(d)) {
 				if dep.name == name {
 					if dep.path.is_some() {
 						local = true;
@@ -335,7 +415,7 @@ impl LiftToWorkspaceCmd {
 				}
 			}
 
-			for dep in pkg.dependencies.iter() {
+			for dep in pkg.dependencies.iter().filter(|d| self.has_kind(d)) {
 				if dep.name == name {
 					if let Some(rename) = &dep.rename {
 						if name == rename {
@@ -366,7 +446,7 @@ impl LiftToWorkspaceCmd {
 				err += &format!(
 					"{: >3} time{s}: {} from {}",
 					pkgs.len(),
-					g.bold(rename),
+					g.emphasis(rename),
 					pkgs.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
 				);
 				if pkgs.len() > 3 {
@@ -378,7 +458,7 @@ impl LiftToWorkspaceCmd {
 			err += &format!(
 				"{: >3} time{s}: {} from {}",
 				unnrenamed.len(),
-				g.bold("no alias"),
+				g.emphasis("no alias"),
 				unnrenamed.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
 			);
 			if unnrenamed.len() > 3 {
@@ -388,7 +468,7 @@ impl LiftToWorkspaceCmd {
 			let renames_count = renames.values().map(|pkgs| pkgs.len()).sum::<usize>();
 			Err(format!(
 				"Dependency '{}' is used {} time{} with and {} time{} without an alias:\n\n{err}\n\nThis cannot be fixed automatically since it would break your code and configs.",
-				g.bold(name),
+				g.emphasis(name),
 				renames_count,
 				plural(renames_count),
 				unnrenamed.len(),
@@ -405,7 +485,7 @@ impl LiftToWorkspaceCmd {
 				err += &format!(
 					"{: >3} time{s}: {} from {}",
 					pkgs.len(),
-					g.bold(rename),
+					g.emphasis(rename),
 					pkgs.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
 				);
 				if pkgs.len() > 3 {
@@ -416,7 +496,7 @@ impl LiftToWorkspaceCmd {
 
 			return Err(format!(
 				"Dependency '{}' is used with {} conflicting aliases:\n\n{}\nThis cannot be fixed automatically since it would break your code and configs.",
-				g.bold(name),
+				g.emphasis(name),
 				renames.len(),
 				err
 			))
@@ -428,26 +508,24 @@ impl LiftToWorkspaceCmd {
 		g: &GlobalArgs,
 		name: &str,
 		versions: &[&VersionReq],
-		by_version: &HashMap<VersionReq, Vec<(Package, Dep)>>,
+		by_version: &BTreeMap<String, Vec<(Package, Dep)>>,
 	) -> Result<String, String> {
 		let found = match self.version_selector {
 			VersionSelectorMode::Exact => self.exact_version.clone().expect("Checked by clippy"),
-			VersionSelectorMode::Highest => try_find_latest(by_version.keys())?,
+			VersionSelectorMode::Highest => try_find_latest(versions.iter().copied())?,
 			VersionSelectorMode::Unambiguous => {
 				if versions.len() > 1 {
 					let str_width = versions.iter().map(|v| v.to_string().len()).max().unwrap();
 					let mut err = String::new();
 					// iter by descending frequency
-					for (version, pkgs) in by_version
-						.iter()
-						.sorted_by_key(|(v, pkgs)| (pkgs.len(), v.to_string()))
-						.rev()
+					for (version, pkgs) in
+						by_version.iter().sorted_by_key(|(v, pkgs)| (pkgs.len(), v.as_str())).rev()
 					{
 						let ddd = if pkgs.len() > 3 { ", …" } else { "" };
 						let s = plural_or(pkgs.len(), " ");
 						err.push_str(&format!(
 							"  {: <width$}: {: >3} time{s} ({}{ddd})\n",
-							version.to_string(),
+							version,
 							pkgs.len(),
 							pkgs.iter()
 								.map(|(c, _)| c.name.as_str())
@@ -458,7 +536,7 @@ impl LiftToWorkspaceCmd {
 						));
 					}
 
-					let version_hint = match try_find_latest(by_version.keys()) {
+					let version_hint = match try_find_latest(versions.iter().copied()) {
 						Ok(latest) => latest,
 						Err(_e) => {
 							log::warn!("Could not find determine latest common version: {}", _e);
@@ -471,7 +549,7 @@ impl LiftToWorkspaceCmd {
 						"\nFound {} different versions of '{}' in the workspace:\n\n{err}\nHint: {}\n",
 						versions.len(),
 						name,
-						g.bold(&hint),
+						g.emphasis(&hint),
 					))
 				} else {
 					versions.first().unwrap().to_string()
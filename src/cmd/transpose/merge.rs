@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use crate::{
+	cmd::{check_can_modify, transpose::AutoFixer, CargoArgs, GlobalArgs, PackageIndex},
+	grammar::plural,
+	log,
+};
+use std::collections::BTreeMap;
+
+/// Merge a feature into another one in all manifests of the workspace.
+///
+/// The entries of the feature are added to the other one, or it is renamed if the crate does not
+/// have the other feature yet. All references like `dep/from` are updated to `dep/into`.
+#[derive(Debug, clap::Parser)]
+pub struct MergeCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// The feature that is merged and removed.
+	#[clap(long)]
+	from: String,
+
+	/// The feature that is merged into.
+	#[clap(long)]
+	into: String,
+
+	/// Instead of dry-running, actually modify the files.
+	#[clap(long)]
+	pub(crate) fix: bool,
+}
+
+impl MergeCmd {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		g.warn_unstable();
+		if self.from == self.into {
+			return Err("The features must be different".into())
+		}
+
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		self.check_conflicts(&meta)?;
+
+		let mut fixers = BTreeMap::new();
+		for pkg in meta.workspace_packages() {
+			let manifest = &pkg.manifest_path;
+			if !check_can_modify(&meta.workspace_root, manifest)? {
+				continue
+			}
+
+			let mut fixer = AutoFixer::from_manifest(manifest)?;
+			let merge = fixer
+				.merge_feature(&self.from, &self.into)
+				.map_err(|e| format!("Failed to merge in {}: {}", manifest, e))?;
+			if !fixer.modified() {
+				continue
+			}
+
+			println!("crate '{}'", pkg.name);
+			if merge.renamed {
+				println!("  renamed feature '{}' to '{}'", self.from, self.into);
+			} else if merge.merged {
+				println!("  merged feature '{}' into '{}'", self.from, self.into);
+			}
+			if merge.references > 0 {
+				let s = plural(merge.references);
+				println!("  updated {} reference{s} to '{}'", merge.references, self.from);
+			}
+			fixers.insert(manifest.clone(), fixer);
+		}
+
+		if fixers.is_empty() {
+			return Err(format!("Feature '{}' is not used in the workspace", self.from))
+		}
+
+		let s = plural(fixers.len());
+		if !self.fix {
+			return Err(format!(
+				"Held back modifications to {} file{s}. Re-run with --fix to apply.",
+				fixers.len()
+			))
+		}
+		for fixer in fixers.values_mut() {
+			g.save_fixer(fixer)?;
+		}
+		log::info!("Modified {} manifest{s}.", fixers.len());
+		Ok(())
+	}
+
+	/// Find references to crates that are not modified and do not have the target feature.
+	fn check_conflicts(&self, meta: &cargo_metadata::Metadata) -> Result<(), String> {
+		let index = PackageIndex::new(meta);
+		let mut conflicts = Vec::new();
+
+		for pkg in meta.workspace_packages() {
+			for (feature, entries) in pkg.features.iter() {
+				for entry in entries {
+					let Some((dep, dep_feature)) = entry.split_once('/') else { continue };
+					if dep_feature != self.from {
+						continue
+					}
+					let dep = dep.trim_end_matches('?');
+					let Some(dep) = pkg
+						.dependencies
+						.iter()
+						.find(|d| d.rename.as_ref().unwrap_or(&d.name) == dep)
+						.and_then(|d| index.resolve_dep(pkg, d))
+					else {
+						continue
+					};
+
+					let modified = meta.workspace_members.contains(&dep.pkg.id) &&
+						check_can_modify(&meta.workspace_root, &dep.pkg.manifest_path)?;
+					if !modified && !dep.pkg.features.contains_key(&self.into) {
+						conflicts.push(format!(
+							"crate '{}' feature '{}' enables '{}', but '{}' has no feature '{}'",
+							pkg.name, feature, entry, dep.pkg.name, self.into
+						));
+					}
+				}
+			}
+		}
+
+		if conflicts.is_empty() {
+			return Ok(())
+		}
+		conflicts.sort();
+		Err(format!(
+			"Cannot merge since {} reference{} to crates outside of the workspace would break:\n  {}",
+			conflicts.len(),
+			plural(conflicts.len()),
+			conflicts.join("\n  ")
+		))
+	}
+}
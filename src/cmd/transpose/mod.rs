@@ -2,11 +2,17 @@
 // SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
 
 mod lift_to_workspace;
+mod merge;
+mod rename;
 
 use super::GlobalArgs;
 use crate::{
 	autofix::*,
-	cmd::{resolve_dep, transpose::lift_to_workspace::LiftToWorkspaceCmd},
+	cmd::{
+		transpose::{lift_to_workspace::LiftToWorkspaceCmd, merge::MergeCmd, rename::RenameCmd},
+		PackageIndex,
+	},
+	grammar::plural,
 };
 
 use cargo_metadata::{Dependency as Dep, DependencyKind};
@@ -32,9 +38,20 @@ impl TransposeCmd {
 	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		match &self.subcommand {
 			TransposeSubCmd::Dependency(cmd) => cmd.run(global),
-			TransposeSubCmd::Features(cmd) => {
-				cmd.run(global);
-				Ok(())
+			TransposeSubCmd::Features(cmd) => cmd.run(global),
+		}
+	}
+
+	/// Whether the command modifies manifests instead of only printing what it would do.
+	pub(crate) fn writes_manifests(&self) -> bool {
+		match &self.subcommand {
+			TransposeSubCmd::Dependency(cmd) => match &cmd.subcommand {
+				DependencySubCmd::LiftToWorkspace(cmd) => cmd.fix,
+				DependencySubCmd::Rename(cmd) => cmd.fix,
+			},
+			TransposeSubCmd::Features(cmd) => match &cmd.subcommand {
+				FeaturesSubCmd::StripDevOnly(cmd) => !cmd.dry_run,
+				FeaturesSubCmd::Merge(cmd) => cmd.fix,
 			},
 		}
 	}
@@ -59,6 +76,7 @@ impl DependencyCmd {
 	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		match &self.subcommand {
 			DependencySubCmd::LiftToWorkspace(cmd) => cmd.run(global),
+			DependencySubCmd::Rename(cmd) => cmd.run(global),
 		}
 	}
 }
@@ -70,9 +88,10 @@ pub struct FeaturesCmd {
 }
 
 impl FeaturesCmd {
-	pub fn run(&self, global: &GlobalArgs) {
+	pub fn run(&self, global: &GlobalArgs) -> Result<(), String> {
 		match &self.subcommand {
 			FeaturesSubCmd::StripDevOnly(cmd) => cmd.run(global),
+			FeaturesSubCmd::Merge(cmd) => cmd.run(global),
 		}
 	}
 }
@@ -81,12 +100,15 @@ impl FeaturesCmd {
 pub enum DependencySubCmd {
 	#[clap(alias = "lift", alias = "l")]
 	LiftToWorkspace(LiftToWorkspaceCmd),
+	Rename(RenameCmd),
 }
 
 #[derive(Debug, clap::Subcommand)]
 pub enum FeaturesSubCmd {
 	/// Strip out dev dependencies.
 	StripDevOnly(StripDevDepsCmd),
+	/// Merge a feature into another one across the workspace.
+	Merge(MergeCmd),
 }
 
 #[derive(Debug, clap::Parser)]
@@ -95,41 +117,38 @@ pub struct StripDevDepsCmd {
 	#[clap(flatten)]
 	cargo_args: super::CargoArgs,
 
-	/// Only consider these packages.
-	#[clap(long, short = 'p', value_delimiter = ',', verbatim_doc_comment)]
-	packages: Option<Vec<String>>,
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	packages: super::PackageSpec,
+
+	/// Only print the entries that would be removed instead of modifying the files.
+	#[clap(long)]
+	dry_run: bool,
 }
 
 impl StripDevDepsCmd {
-	pub fn run(&self, g: &GlobalArgs) {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
 		g.warn_unstable();
-		let meta = self.cargo_args.load_metadata().expect("Loads metadata");
+		let meta = self.cargo_args.load_metadata()?;
 
+		let index = PackageIndex::new(&meta);
 		let kind = DependencyKind::Development;
 		// Allowed dir that we can write to.
 		let allowed_dir = canonicalize(meta.workspace_root.as_std_path()).unwrap();
 
-		for name in self.packages.iter().flatten() {
-			if !meta.packages.iter().any(|p| p.name == *name) {
-				eprintln!("Could not find package named '{}'", g.red(name));
-				std::process::exit(1);
-			}
-		}
+		let pkgs = self.packages.select(&meta).unwrap_or_else(|err| {
+			eprintln!("{}", g.error(&err));
+			std::process::exit(1);
+		});
 
 		let mut fixers = Map::new();
-		for pkg in meta.packages.iter() {
-			if let Some(packages) = &self.packages {
-				if !packages.contains(&pkg.name) {
-					continue
-				}
-			}
-
+		for pkg in pkgs {
 			// Are we allowed to modify this file path?
 			let krate_path = canonicalize(pkg.manifest_path.clone().into_std_path_buf()).unwrap();
 			if !krate_path.starts_with(&allowed_dir) {
 				continue
 			}
-			let mut fixer = AutoFixer::from_manifest(&krate_path).unwrap();
+			let mut fixer = AutoFixer::from_manifest(&krate_path)?;
 
 			// Find all dependencies that are only used as dev dependencies in this package.
 			let devs = pkg.dependencies.iter().filter(|d| d.kind == kind);
@@ -139,24 +158,45 @@ impl StripDevDepsCmd {
 				})
 				.collect::<Vec<_>>();
 
+			let mut patterns = Vec::new();
 			for dep in only_dev.iter() {
 				// Account for renamed crates:
-				let Some(dep) = resolve_dep(pkg, dep, &meta) else {
-					eprintln!("Could not resolve dependency '{}'", g.red(&dep.name));
-					std::process::exit(1);
-				};
+				let Some(dep) = index.resolve_dep(pkg, dep) else { continue };
 
-				fixer.remove_feature(&format!("{}/", dep.name()));
-				fixer.remove_feature(&format!("{}?/", dep.name()));
+				patterns.push(format!("{}/", dep.name()));
+				patterns.push(format!("{}?/", dep.name()));
+			}
+			let patterns = patterns.iter().map(String::as_str).collect::<Vec<_>>();
+			let removed = fixer.remove_from_features(&patterns, EntryMatch::Prefix);
+
+			if !removed.is_empty() {
+				let verb = if self.dry_run { "would remove" } else { "removed" };
+				println!("crate '{}'", pkg.name);
+				for RemovedEntry { feature, entry } in removed {
+					println!("  {} '{}' from '{}'", verb, entry, feature);
+				}
 			}
-
 			if fixer.modified() {
 				fixers.insert(pkg.name.clone(), fixer);
 			}
 		}
 
+		index.report_unresolved(self.cargo_args.unresolved_deps)?;
+
+		if self.dry_run {
+			if !fixers.is_empty() {
+				let s = plural(fixers.len());
+				println!(
+					"Held back modifications to {} file{s}. Re-run without --dry-run to apply.",
+					fixers.len()
+				);
+			}
+			return Ok(())
+		}
+		g.check_git_clean(fixers.values().filter_map(|f| f.manifest.as_deref()))?;
 		for fixer in fixers.values_mut() {
-			fixer.save().unwrap();
+			g.save_fixer(fixer)?;
 		}
+		Ok(())
 	}
 }
@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+use crate::{
+	cmd::{check_can_modify, transpose::AutoFixer, CargoArgs, GlobalArgs},
+	grammar::plural,
+	log,
+};
+use std::collections::BTreeMap;
+
+/// Rename a dependency in all manifests of the workspace.
+///
+/// This changes the dependency key and keeps the original crate through the `package` field. All
+/// references in the `[features]` of the manifests are updated accordingly. Rust code that uses the
+/// old name is not modified.
+#[derive(Debug, clap::Parser)]
+pub struct RenameCmd {
+	#[allow(missing_docs)]
+	#[clap(flatten)]
+	cargo_args: CargoArgs,
+
+	/// The current name of the dependency as used in the manifests.
+	#[clap(long)]
+	from: String,
+
+	/// The new name of the dependency.
+	#[clap(long)]
+	to: String,
+
+	/// Instead of dry-running, actually modify the files.
+	#[clap(long)]
+	pub(crate) fix: bool,
+}
+
+impl RenameCmd {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		g.warn_unstable();
+		if self.from == self.to {
+			return Err("The old and new name must be different".into())
+		}
+
+		let meta = self.cargo_args.clone().with_workspace(true).load_metadata()?;
+		let mut manifests =
+			meta.packages.iter().map(|p| p.manifest_path.clone()).collect::<Vec<_>>();
+		manifests.push(meta.workspace_root.join("Cargo.toml"));
+		manifests.sort();
+		manifests.dedup();
+
+		let mut fixers = BTreeMap::new();
+		for manifest in manifests {
+			if !manifest.exists() || !check_can_modify(&meta.workspace_root, &manifest)? {
+				continue
+			}
+
+			let mut fixer = AutoFixer::from_manifest(&manifest)?;
+			if fixer
+				.rename_dependency(&self.from, &self.to)
+				.map_err(|e| format!("Failed to rename in {}: {}", manifest, e))?
+			{
+				log::debug!("Renaming '{}' to '{}' in {}", self.from, self.to, manifest);
+				fixers.insert(manifest, fixer);
+			}
+		}
+
+		if fixers.is_empty() {
+			return Err(format!("Dependency '{}' is not used in the workspace", self.from))
+		}
+
+		let s = plural(fixers.len());
+		if !self.fix {
+			return Err(format!(
+				"Held back modifications to {} file{s}. Re-run with --fix to apply.",
+				fixers.len()
+			))
+		}
+		for fixer in fixers.values_mut() {
+			g.save_fixer(fixer)?;
+		}
+		log::info!("Modified {} manifest{s}.", fixers.len());
+		Ok(())
+	}
+}
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Check whether a newer version of Zepter was released.
+
+use super::GlobalArgs;
+use crate::{
+	config::{semver::Semver, workflow::UpdateCheck},
+	log,
+};
+use std::{
+	io::IsTerminal,
+	path::PathBuf,
+	process::{Command, Stdio},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How long a cached release version is used before crates.io is queried again.
+///
+/// Failed queries are cached as well, so that an unreachable crates.io does not slow down every
+/// run.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long to wait for crates.io before giving up.
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Manage the Zepter binary itself.
+#[derive(Debug, clap::Parser)]
+pub struct SelfCmd {
+	#[clap(subcommand)]
+	subcommand: SelfSubCmd,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum SelfSubCmd {
+	/// Check whether a newer version of Zepter was released on crates.io.
+	UpdateCheck(UpdateCheckCmd),
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct UpdateCheckCmd {
+	/// Use the cached release version instead of querying crates.io.
+	///
+	/// This is the default if `CARGO_NET_OFFLINE` is set.
+	#[clap(long, verbatim_doc_comment)]
+	offline: bool,
+}
+
+impl SelfCmd {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		match &self.subcommand {
+			SelfSubCmd::UpdateCheck(cmd) => cmd.run(g),
+		}
+	}
+}
+
+impl UpdateCheckCmd {
+	pub fn run(&self, g: &GlobalArgs) -> Result<(), String> {
+		let latest = if self.offline || net_offline() {
+			read_cache().and_then(|(_, v)| v).ok_or_else(|| {
+				"No cached release version. Run `zepter self update-check` without `--offline` first."
+					.to_string()
+			})?
+		} else {
+			let latest = fetch_latest();
+			write_cache(latest.as_ref().ok());
+			latest?
+		};
+
+		match upgrade_notice(g, &running(), &latest) {
+			Some(notice) => println!("{}", notice),
+			None => println!("Zepter {} is up to date.", running()),
+		}
+		Ok(())
+	}
+}
+
+/// Print an upgrade notice if `mode` allows it and a newer version was released.
+///
+/// Never fails and uses the cached version if it is recent enough. Network errors are only logged
+/// since the check must not get in the way of the actual command. Nothing is queried if `offline`
+/// or `CARGO_NET_OFFLINE` is set.
+pub fn notify_if_outdated(g: &GlobalArgs, mode: UpdateCheck, offline: bool) {
+	let enabled = match mode {
+		UpdateCheck::Never => false,
+		UpdateCheck::Always => true,
+		UpdateCheck::Auto => std::env::var_os("CI").is_none() && std::io::stdout().is_terminal(),
+	};
+	if !enabled {
+		return
+	}
+
+	let cached = read_cache();
+	let latest = match cached {
+		Some((checked, latest)) if checked.elapsed().map_or(false, |age| age < CACHE_TTL) => latest,
+		_ if offline || net_offline() => cached.and_then(|(_, latest)| latest),
+		_ => {
+			let latest = fetch_latest();
+			write_cache(latest.as_ref().ok());
+			if let Err(err) = &latest {
+				log::debug!("Skipping the update check: {}", err);
+			}
+			latest.ok()
+		},
+	};
+	let Some(latest) = latest else { return };
+	if let Some(notice) = upgrade_notice(g, &running(), &latest) {
+		println!("\n{}", notice);
+	}
+}
+
+/// The notice to print if `latest` is newer than `running`.
+fn upgrade_notice(g: &GlobalArgs, running: &Semver, latest: &Semver) -> Option<String> {
+	(latest > running).then(|| {
+		format!(
+			"{}: Zepter {} is available, you are running {}. Update with `cargo install zepter -f --locked`.",
+			g.warning("NOTE"),
+			latest,
+			running
+		)
+	})
+}
+
+/// The version of the running binary.
+fn running() -> Semver {
+	Semver::try_from(clap::crate_version!()).expect("Crate version is valid semver")
+}
+
+/// Whether cargo was told to not access the network through `CARGO_NET_OFFLINE`.
+fn net_offline() -> bool {
+	matches!(std::env::var("CARGO_NET_OFFLINE").as_deref(), Ok("true" | "1"))
+}
+
+/// Query the latest release from crates.io through `cargo search`.
+///
+/// Gives up after [`SEARCH_TIMEOUT`].
+fn fetch_latest() -> Result<Semver, String> {
+	let mut child = Command::new("cargo")
+		.args(["search", "zepter", "--limit", "1", "--color", "never"])
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.map_err(|e| format!("Failed to run cargo search: {e}"))?;
+	let start = Instant::now();
+	while child
+		.try_wait()
+		.map_err(|e| format!("Failed to run cargo search: {e}"))?
+		.is_none()
+	{
+		if start.elapsed() > SEARCH_TIMEOUT {
+			let _ = child.kill();
+			let _ = child.wait();
+			return Err(format!("cargo search did not finish within {:?}", SEARCH_TIMEOUT))
+		}
+		std::thread::sleep(Duration::from_millis(20));
+	}
+	let output = child
+		.wait_with_output()
+		.map_err(|e| format!("Failed to run cargo search: {e}"))?;
+	if !output.status.success() {
+		return Err(format!(
+			"Failed to run cargo search: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		))
+	}
+	parse_search(&String::from_utf8_lossy(&output.stdout))
+		.ok_or_else(|| "Could not find zepter in the output of cargo search".into())
+}
+
+/// Find the version of zepter in the output of `cargo search`.
+///
+/// The lines look like `zepter = "1.5.1"    # Analyze, Fix and Format features...`.
+fn parse_search(output: &str) -> Option<Semver> {
+	output.lines().find_map(|line| {
+		let version = line.strip_prefix("zepter = \"")?.split('"').next()?;
+		Semver::try_from(version).ok()
+	})
+}
+
+/// The file that caches the latest release version.
+///
+/// Lives in `$XDG_CACHE_HOME/zepter` or `$HOME/.cache/zepter`.
+fn cache_path() -> Option<PathBuf> {
+	let dir = std::env::var_os("XDG_CACHE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+	Some(dir.join("zepter").join("latest-version"))
+}
+
+/// When the latest version was fetched and what it was. `None` if fetching it failed.
+fn read_cache() -> Option<(SystemTime, Option<Semver>)> {
+	parse_cache(&std::fs::read_to_string(cache_path()?).ok()?)
+}
+
+/// Parse the cache content of the form `<unix seconds> <version>` or `<unix seconds>` for a failed
+/// check.
+fn parse_cache(content: &str) -> Option<(SystemTime, Option<Semver>)> {
+	let (secs, version) = match content.trim().split_once(' ') {
+		Some((secs, version)) => (secs, Some(Semver::try_from(version).ok()?)),
+		None => (content.trim(), None),
+	};
+	let checked = UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?);
+	Some((checked, version))
+}
+
+/// Remember the latest version or that it could not be fetched.
+///
+/// Failing to do so only means that it is fetched again next time.
+fn write_cache(latest: Option<&Semver>) {
+	let Some(path) = cache_path() else { return };
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+	let content = match latest {
+		Some(latest) => format!("{} {}\n", now, latest),
+		None => format!("{}\n", now),
+	};
+	let res = path
+		.parent()
+		.map_or(Ok(()), std::fs::create_dir_all)
+		.and_then(|()| std::fs::write(&path, content));
+	if let Err(err) = res {
+		log::debug!("Could not write the update check cache {}: {}", path.display(), err);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_search_works() {
+		let output = "zepter = \"1.5.1\"    # Analyze, Fix and Format features in your Rust workspace.\n... and 2 crates more (use --limit N to see more)\n";
+		assert_eq!(parse_search(output), Some((1, 5, 1).into()));
+		assert_eq!(parse_search("zepter-fork = \"9.0.0\"    # A fork\n"), None);
+		assert_eq!(parse_search(""), None);
+	}
+
+	#[test]
+	fn parse_cache_works() {
+		let (checked, version) = parse_cache("1700000000 1.6.0\n").unwrap();
+		assert_eq!(checked, UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+		assert_eq!(version, Some((1, 6, 0).into()));
+		assert_eq!(parse_cache("1700000000\n"), Some((checked, None)));
+		assert!(parse_cache("1.6.0").is_none());
+		assert!(parse_cache("now 1.6.0").is_none());
+	}
+}
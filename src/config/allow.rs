@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Findings that crate owners allow locally in their manifest.
+//!
+//! They are configured in the `package.metadata.zepter` table of a workspace member:
+//!
+//! ```toml
+//! [package.metadata.zepter]
+//! allow = ["propagate-feature:std:serde", "no-std:log"]
+//! ```
+//!
+//! An entry consists of the lint name and the parts of a finding, separated by `:`. It allows
+//! all findings of the crate that start with these parts. A `*` matches any single part.
+
+use cargo_metadata::Metadata;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Names of the lints that can be allowed.
+pub const LINTS: [&str; 16] = [
+	"propagate-feature",
+	"never-enables",
+	"never-implies",
+	"only-enables",
+	"no-std",
+	"feature-typos",
+	"no-path-deps-outside-workspace",
+	"duplicate-deps",
+	"build-dep-features",
+	"feature-docs",
+	"git-deps",
+	"rust-version",
+	"weak-features",
+	"default-feature",
+	"optional-deps",
+	"default-features-conflict",
+];
+
+/// Allowed findings of all workspace members by crate name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AllowedFindings(BTreeMap<String, Vec<Vec<String>>>);
+
+/// The `package.metadata.zepter` table of a manifest.
+#[derive(Deserialize)]
+struct ManifestMetadata {
+	#[serde(default)]
+	allow: Vec<String>,
+}
+
+impl AllowedFindings {
+	/// Collect the allowed findings from the manifest metadata of all workspace members.
+	pub fn from_metadata(meta: &Metadata) -> Result<Self, String> {
+		let mut allowed = BTreeMap::new();
+
+		for pkg in meta.workspace_packages() {
+			let Some(section) = pkg.metadata.get("zepter") else { continue };
+			let section = ManifestMetadata::deserialize(section.clone()).map_err(|e| {
+				format!("Invalid `package.metadata.zepter` of crate '{}': {}", pkg.name, e)
+			})?;
+
+			let mut entries = Vec::new();
+			for entry in section.allow {
+				let parts = entry.split(':').map(|p| p.trim().to_string()).collect::<Vec<_>>();
+				if !LINTS.contains(&parts[0].as_str()) {
+					return Err(format!(
+						"Crate '{}' allows unknown lint '{}'. Expected one of: {}",
+						pkg.name,
+						parts[0],
+						LINTS.join(", ")
+					))
+				}
+				entries.push(parts);
+			}
+			allowed.insert(pkg.name.clone(), entries);
+		}
+
+		Ok(Self(allowed))
+	}
+
+	/// Whether the crate allows a finding like `["propagate-feature", "std", "serde"]`.
+	pub fn allows(&self, krate: &str, finding: &[&str]) -> bool {
+		let Some(entries) = self.0.get(krate) else { return false };
+
+		entries.iter().any(|entry| {
+			entry.len() <= finding.len() &&
+				entry.iter().zip(finding.iter()).all(|(a, f)| a == "*" || a == f)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn allows_works() {
+		let allowed = AllowedFindings(BTreeMap::from([(
+			"A".into(),
+			vec![
+				vec!["propagate-feature".into(), "std".into(), "serde".into()],
+				vec!["no-std".into()],
+				vec!["feature-typos".into(), "*".into(), "sdt".into()],
+			],
+		)]));
+
+		assert!(allowed.allows("A", &["propagate-feature", "std", "serde"]));
+		assert!(!allowed.allows("A", &["propagate-feature", "std", "log"]));
+		assert!(!allowed.allows("A", &["propagate-feature", "std"]));
+		assert!(allowed.allows("A", &["no-std", "log"]));
+		assert!(allowed.allows("A", &["feature-typos", "default", "sdt"]));
+		assert!(!allowed.allows("B", &["no-std", "log"]));
+	}
+}
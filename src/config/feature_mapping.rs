@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Features of third-party crates that have a different name than the propagated one.
+//!
+//! They are configured in the `feature-mapping` section of the config file as crate, propagated
+//! feature and the name of the equivalent feature of that crate:
+//!
+//! ```yaml
+//! feature-mapping:
+//!   some-crate:
+//!     runtime-benchmarks: bench
+//!     std: alloc
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// The equivalent name of a feature per crate.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FeatureMapping(BTreeMap<String, BTreeMap<String, String>>);
+
+/// The part of the config file that contains the feature mapping.
+#[derive(Default, Deserialize)]
+struct MappingFile {
+	#[serde(default, rename = "feature-mapping", alias = "feature_mapping")]
+	feature_mapping: FeatureMapping,
+}
+
+impl FeatureMapping {
+	/// Load the feature mapping from the config file of a workspace.
+	///
+	/// Returns an empty mapping if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		let path = workspace_config(root);
+		Ok(load_section::<MappingFile>(path.as_deref(), "feature-mapping")?.feature_mapping)
+	}
+
+	/// Map `feature` of `krate` to `to`. Overrides the config file.
+	pub fn insert(&mut self, krate: &str, feature: &str, to: &str) {
+		self.0.entry(krate.into()).or_default().insert(feature.into(), to.into());
+	}
+
+	/// The name that `krate` uses for `feature`, which is the feature itself if it is not mapped.
+	pub fn get<'a>(&'a self, krate: &str, feature: &'a str) -> &'a str {
+		self.0.get(krate).and_then(|m| m.get(feature)).map_or(feature, String::as_str)
+	}
+
+	/// All mappings as `(crate, feature, to)` in alphabetical order.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+		self.0.iter().flat_map(|(krate, mapping)| {
+			mapping
+				.iter()
+				.map(move |(from, to)| (krate.as_str(), from.as_str(), to.as_str()))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(yaml: &str) -> FeatureMapping {
+		serde_yaml::from_str::<MappingFile>(yaml).unwrap().feature_mapping
+	}
+
+	#[test]
+	fn feature_mapping_works() {
+		let mut mapping = parse("feature-mapping:\n  x:\n    runtime-benchmarks: bench\n");
+		assert_eq!(mapping.get("x", "runtime-benchmarks"), "bench");
+		assert_eq!(mapping.get("x", "std"), "std");
+		assert_eq!(mapping.get("y", "runtime-benchmarks"), "runtime-benchmarks");
+
+		mapping.insert("x", "std", "alloc");
+		assert_eq!(mapping.get("x", "std"), "alloc");
+		assert_eq!(
+			mapping.iter().collect::<Vec<_>>(),
+			vec![("x", "runtime-benchmarks", "bench"), ("x", "std", "alloc")]
+		);
+		assert_eq!(parse("version: 1\n"), FeatureMapping::default());
+	}
+}
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Policy for git dependencies.
+//!
+//! It is configured in the `git-deps` section of the config file:
+//!
+//! ```yaml
+//! git-deps:
+//!   policy: require-rev
+//!   allow:
+//!     - my-fork
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path};
+
+/// Which git dependencies are accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitDepsPolicy {
+	/// Git dependencies are not allowed at all.
+	Forbid,
+	/// Git dependencies must be pinned to a commit with `rev`.
+	#[default]
+	RequireRev,
+	/// Git dependencies must be declared in `[workspace.dependencies]`.
+	RequireWorkspace,
+}
+
+/// Config of the `lint git-deps` command.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct GitDepsConfig {
+	/// The policy that all git dependencies must follow.
+	#[serde(default)]
+	pub policy: GitDepsPolicy,
+	/// Names of crates that may be used as git dependency regardless of the policy.
+	#[serde(default)]
+	pub allow: BTreeSet<String>,
+}
+
+/// The part of the config file that contains the git dependency config.
+#[derive(Default, Deserialize)]
+struct GitDepsFile {
+	#[serde(default, rename = "git-deps")]
+	git_deps: GitDepsConfig,
+}
+
+impl GitDepsConfig {
+	/// Load the config from the config file of a workspace.
+	///
+	/// Returns the default config if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		Self::from_config(workspace_config(root).as_deref())
+	}
+
+	/// Load the config from a config file, or the default config if there is none.
+	pub fn from_config(path: Option<&Path>) -> Result<Self, String> {
+		Ok(load_section::<GitDepsFile>(path, "git-deps")?.git_deps)
+	}
+
+	/// Whether the crate may be used as git dependency regardless of the policy.
+	pub fn is_allowed(&self, krate: &str) -> bool {
+		self.allow.contains(krate)
+	}
+}
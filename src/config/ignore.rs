@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Dependencies that are ignored by all lints.
+//!
+//! They are configured in the `ignore-dependencies` section of the config file:
+//!
+//! ```yaml
+//! ignore-dependencies:
+//!   - wasm-bindgen
+//!   - getrandom
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path};
+
+/// Names of crates that no lint reports issues about.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct IgnoredDependencies(BTreeSet<String>);
+
+/// The part of the config file that contains the ignored dependencies.
+#[derive(Default, Deserialize)]
+struct IgnoreFile {
+	#[serde(default, rename = "ignore-dependencies", alias = "ignore_dependencies")]
+	ignore_dependencies: IgnoredDependencies,
+}
+
+impl IgnoredDependencies {
+	/// Load the ignored dependencies from the config file of a workspace.
+	///
+	/// Returns an empty set if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		Self::from_config(workspace_config(root).as_deref())
+	}
+
+	/// Load the ignored dependencies from a config file, or an empty set if there is none.
+	pub fn from_config(path: Option<&Path>) -> Result<Self, String> {
+		Ok(load_section::<IgnoreFile>(path, "ignore-dependencies")?.ignore_dependencies)
+	}
+
+	/// Whether issues that involve this crate should not be reported.
+	pub fn contains(&self, krate: &str) -> bool {
+		self.0.contains(krate)
+	}
+
+	/// All ignored crates in alphabetical order.
+	pub fn iter(&self) -> impl Iterator<Item = &String> {
+		self.0.iter()
+	}
+}
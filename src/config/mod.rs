@@ -1,11 +1,22 @@
 // SPDX-License-Identifier: GPL-3.0-only
 // SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
 
+pub mod allow;
+pub mod feature_mapping;
+pub mod git_deps;
+pub mod ignore;
+pub mod only_enables;
+pub mod optional_deps;
+pub mod path_deps;
+pub mod rules;
 pub mod semver;
+pub mod severity;
+pub mod theme;
 pub mod workflow;
 
-use crate::{config::workflow::WorkflowFile, log, ErrToStr};
+use crate::{config::workflow::WorkflowFile, log, out, ErrToStr};
 
+use serde::de::DeserializeOwned;
 use std::{
 	fs::canonicalize,
 	path::{Path, PathBuf},
@@ -42,6 +53,9 @@ impl Default for Toggle {
 
 pub const WELL_KNOWN_CFG_PATHS: &[&str] = &["zepter.yaml", ".zepter.yaml"];
 
+/// The config file that `run` passes to its steps, since they do not get its `--config`.
+pub const CONFIG_ENV: &str = "ZEPTER_CONFIG";
+
 /// Search for `zepter.yaml`, `zepter`, `.zepter.yaml` or `.zepter` in the folders:
 /// - `./`
 /// - `./.cargo/`
@@ -71,20 +85,64 @@ pub fn search_config<P: AsRef<Path>>(workspace: P) -> Result<PathBuf, Vec<PathBu
 	Err(searched)
 }
 
+/// The config file of a workspace.
+///
+/// This is the one from [`CONFIG_ENV`] if set, or otherwise the one that [`search_config`] finds.
+pub fn workspace_config<P: AsRef<Path>>(root: P) -> Option<PathBuf> {
+	match std::env::var_os(CONFIG_ENV) {
+		Some(path) if !path.is_empty() => Some(path.into()),
+		_ => search_config(root).ok(),
+	}
+}
+
+/// Parse a config file into `T`, which only declares the key of a single section.
+///
+/// All other keys are ignored since they belong to other sections. Returns the default if there is
+/// no config file.
+pub(crate) fn load_section<T: DeserializeOwned + Default>(
+	path: Option<&Path>,
+	section: &str,
+) -> Result<T, String> {
+	let Some(path) = path else { return Ok(T::default()) };
+	log::debug!("Loading {} from {}", section, path.display());
+
+	let content = std::fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read config file {:?}: {}", path, e))?;
+	serde_yaml::from_str(&content)
+		.map_err(|e| format!("Failed to parse {} in {:?}: {}", section, path, e))
+}
+
 impl ConfigArgs {
-	pub fn load(&self) -> Result<WorkflowFile, String> {
+	/// Load the config file and check that it can run the given workflow.
+	pub fn load(&self, workflow: &str) -> Result<WorkflowFile, String> {
 		let path = self.locate_config()?;
 		log::debug!("Using config file: {path:?}");
-		let cfg = WorkflowFile::from_path(path)?;
+		let cfg = WorkflowFile::from_path(&path)?;
 
 		if self.check_cfg_compatibility == Toggle::On {
-			cfg.check_cfg_compatibility()?;
+			cfg.check_cfg_compatibility(workflow).map_err(|e| self.upgrade_hint(e, &path))?;
 		}
 
 		Ok(cfg)
 	}
 
-	fn locate_config(&self) -> Result<PathBuf, String> {
+	/// Explain which binary and config file are involved and how to resolve a version mismatch.
+	fn upgrade_hint(&self, err: String, path: &Path) -> String {
+		let binary =
+			std::env::current_exe().map_or_else(|_| "zepter".into(), |p| p.display().to_string());
+		let origin =
+			if self.config.is_some() { "passed with `--config`" } else { "found in the workspace" };
+
+		format!(
+			"{}\n  Binary: {}\n  Config: {} ({})\nPlease update Zepter with `cargo install zepter -f --locked` or ignore this check with `--check-cfg-compatibility=off`.",
+			err,
+			binary,
+			path.display(),
+			origin
+		)
+	}
+
+	pub(crate) fn locate_config(&self) -> Result<PathBuf, String> {
 		if let Some(path) = &self.config {
 			let path = canonicalize(path).err_to_str()?;
 
@@ -99,9 +157,9 @@ impl ConfigArgs {
 			match search_config(root) {
 				Ok(cfg) => Ok(cfg),
 				Err(searched) => {
-					println!("Failed to find config file in any of these locations:");
+					out::println!("Failed to find config file in any of these locations:");
 					for path in searched {
-						println!(" - {}", path.display());
+						out::println!(" - {}", path.display());
 					}
 					Err("Could not find a config file".into())
 				},
@@ -109,7 +167,19 @@ impl ConfigArgs {
 		}
 	}
 
-	fn locate_workspace(&self) -> Result<PathBuf, String> {
+	/// Names of all workspace members.
+	pub(crate) fn workspace_packages(&self) -> Result<Vec<String>, String> {
+		let manifest = self.locate_workspace()?.join("Cargo.toml");
+		let meta = cargo_metadata::MetadataCommand::new()
+			.manifest_path(&manifest)
+			.no_deps()
+			.exec()
+			.map_err(|e| format!("Failed to load metadata of {}: {}", manifest.display(), e))?;
+
+		Ok(meta.workspace_packages().into_iter().map(|p| p.name.clone()).collect())
+	}
+
+	pub(crate) fn locate_workspace(&self) -> Result<PathBuf, String> {
 		let mut cmd = std::process::Command::new("cargo");
 		cmd.arg("locate-project").args([
 			"--message-format",
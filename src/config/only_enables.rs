@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Crates and features that are exempt from `lint only-enables`.
+//!
+//! They are configured in the `only-enables` section of the config file:
+//!
+//! ```yaml
+//! only-enables:
+//!   allow:
+//!     - test-utils
+//!     - mock/runtime-benchmarks
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeSet, fmt, path::Path, str::FromStr};
+
+/// Config of the `lint only-enables` command.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OnlyEnablesConfig {
+	/// Exemptions in the form `crate` or `crate/feature`.
+	#[serde(default)]
+	pub allow: BTreeSet<String>,
+}
+
+/// The part of the config file that contains the only-enables config.
+#[derive(Default, Deserialize)]
+struct OnlyEnablesFile {
+	#[serde(default, rename = "only-enables")]
+	only_enables: OnlyEnablesConfig,
+}
+
+impl OnlyEnablesConfig {
+	/// Load the config from the config file of a workspace.
+	///
+	/// Returns the default config if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		Self::from_config(workspace_config(root).as_deref())
+	}
+
+	/// Load the config from a config file, or the default config if there is none.
+	pub fn from_config(path: Option<&Path>) -> Result<Self, String> {
+		Ok(load_section::<OnlyEnablesFile>(path, "only-enables")?.only_enables)
+	}
+
+	/// Parse all exemptions of the config.
+	pub fn exemptions(&self) -> Result<Vec<Exemption>, String> {
+		self.allow.iter().map(|e| e.parse()).collect()
+	}
+}
+
+/// A crate or a single feature of it that may enable the feature of its dependencies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exemption {
+	pub krate: String,
+	/// Only exempt this feature of the crate, or all if `None`.
+	pub feature: Option<String>,
+}
+
+impl Exemption {
+	/// Whether `feature` of `krate` is exempt.
+	pub fn matches(&self, krate: &str, feature: &str) -> bool {
+		self.krate == krate && self.feature.as_ref().map_or(true, |f| f == feature)
+	}
+}
+
+impl FromStr for Exemption {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (krate, feature) = match s.split_once('/') {
+			Some((krate, feature)) => (krate, Some(feature)),
+			None => (s, None),
+		};
+		if krate.is_empty() || feature.map_or(false, |f| f.is_empty() || f.contains('/')) {
+			return Err(format!("Invalid exemption '{}', expected `crate` or `crate/feature`", s))
+		}
+		Ok(Self { krate: krate.into(), feature: feature.map(Into::into) })
+	}
+}
+
+impl fmt::Display for Exemption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.feature {
+			Some(feature) => write!(f, "{}/{}", self.krate, feature),
+			None => write!(f, "{}", self.krate),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn exemption_parses() {
+		let krate = "mock".parse::<Exemption>().unwrap();
+		assert!(krate.matches("mock", "std") && krate.matches("mock", "runtime-benchmarks"));
+		assert!(!krate.matches("other", "std"));
+
+		let feature = "mock/std".parse::<Exemption>().unwrap();
+		assert!(feature.matches("mock", "std") && !feature.matches("mock", "runtime-benchmarks"));
+		assert_eq!(feature.to_string(), "mock/std");
+
+		for invalid in ["", "/std", "mock/", "mock/std/x"] {
+			assert!(invalid.parse::<Exemption>().is_err(), "{}", invalid);
+		}
+	}
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Style of the features that enable optional dependencies.
+//!
+//! It is configured in the `optional-deps` section of the config file:
+//!
+//! ```yaml
+//! optional-deps:
+//!   style: implicit
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::path::Path;
+
+/// How optional dependencies are exposed as features.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptionalDepsStyle {
+	/// Every optional dependency `foo` has a feature `foo = ["dep:foo"]`.
+	#[default]
+	Explicit,
+	/// The implicit feature of an optional dependency is enough, as long as it is not disabled
+	/// by a `dep:foo` entry.
+	Implicit,
+}
+
+/// Config of the `lint optional-deps` command.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct OptionalDepsConfig {
+	/// The style that all optional dependencies must follow.
+	#[serde(default)]
+	pub style: OptionalDepsStyle,
+}
+
+/// The part of the config file that contains the optional dependency config.
+#[derive(Default, Deserialize)]
+struct OptionalDepsFile {
+	#[serde(default, rename = "optional-deps")]
+	optional_deps: OptionalDepsConfig,
+}
+
+impl OptionalDepsConfig {
+	/// Load the config from the config file of a workspace.
+	///
+	/// Returns the default config if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		let path = workspace_config(root);
+		Ok(load_section::<OptionalDepsFile>(path.as_deref(), "optional-deps")?.optional_deps)
+	}
+}
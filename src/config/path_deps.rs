@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Allowlist of path dependencies that may point outside of the workspace.
+//!
+//! They are configured in the `path-deps` section of the config file:
+//!
+//! ```yaml
+//! path-deps:
+//!   allow:
+//!     - my-local-fork
+//! ```
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeSet, path::Path};
+
+/// Config of the `lint no-path-deps-outside-workspace` command.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PathDepsConfig {
+	/// Names of crates that may be used as path dependency from outside the workspace.
+	#[serde(default)]
+	pub allow: BTreeSet<String>,
+}
+
+/// The part of the config file that contains the path dependency config.
+#[derive(Default, Deserialize)]
+struct PathDepsFile {
+	#[serde(default, rename = "path-deps")]
+	path_deps: PathDepsConfig,
+}
+
+impl PathDepsConfig {
+	/// Load the config from the config file of a workspace.
+	///
+	/// Returns the default config if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		Self::from_config(workspace_config(root).as_deref())
+	}
+
+	/// Load the config from a config file, or the default config if there is none.
+	pub fn from_config(path: Option<&Path>) -> Result<Self, String> {
+		Ok(load_section::<PathDepsFile>(path, "path-deps")?.path_deps)
+	}
+
+	/// Whether the crate may be used as path dependency from outside the workspace.
+	pub fn is_allowed(&self, krate: &str) -> bool {
+		self.allow.contains(krate)
+	}
+}
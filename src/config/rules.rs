@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Forbidden feature implications that are checked by `lint rules`.
+//!
+//! They are configured in the `rules` section of the config file:
+//!
+//! ```yaml
+//! rules:
+//!   - std !-> runtime-benchmarks
+//!   - try-runtime !> std
+//! ```
+//!
+//! `A !-> B` means that feature `A` never implies `B`, not even transitively. `A !> B` means
+//! that feature `A` never directly enables `B` on the crate itself or one of its dependencies.
+
+use crate::config::{load_section, workspace_config};
+use serde::{Deserialize, Deserializer};
+use std::{fmt, path::Path, str::FromStr};
+
+/// A single forbidden implication between two features.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+	/// The feature that is enabled.
+	pub precondition: String,
+	/// The feature that must stay disabled.
+	pub stays_disabled: String,
+	/// Whether transitive implications are forbidden as well.
+	pub kind: RuleKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+	/// Like `lint never-enables`.
+	NeverEnables,
+	/// Like `lint never-implies`.
+	NeverImplies,
+}
+
+/// The part of the config file that contains the rules.
+#[derive(Default, Deserialize)]
+struct RulesFile {
+	#[serde(default)]
+	rules: Vec<Rule>,
+}
+
+/// Load the rules from the config file of a workspace.
+///
+/// Returns no rules if there is no config file.
+pub fn rules_from_workspace<P: AsRef<Path>>(root: P) -> Result<Vec<Rule>, String> {
+	rules_from_config(workspace_config(root).as_deref())
+}
+
+/// Load the rules from a config file, or no rules if there is none.
+pub fn rules_from_config(path: Option<&Path>) -> Result<Vec<Rule>, String> {
+	Ok(load_section::<RulesFile>(path, "rules")?.rules)
+}
+
+impl Rule {
+	/// Plain English description of the rule like "Feature `std` never implies
+	/// `runtime-benchmarks`".
+	pub fn describe(&self) -> String {
+		match self.kind {
+			RuleKind::NeverEnables => format!(
+				"Feature `{}` never directly enables `{}`.",
+				self.precondition, self.stays_disabled
+			),
+			RuleKind::NeverImplies => format!(
+				"Feature `{}` never implies `{}`, not even transitively.",
+				self.precondition, self.stays_disabled
+			),
+		}
+	}
+}
+
+impl FromStr for Rule {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (kind, (lhs, rhs)) = if let Some(split) = s.split_once("!->") {
+			(RuleKind::NeverImplies, split)
+		} else if let Some(split) = s.split_once("!>") {
+			(RuleKind::NeverEnables, split)
+		} else {
+			return Err(format!("Rule '{}' must have the form 'A !-> B' or 'A !> B'", s))
+		};
+		let (precondition, stays_disabled) = (lhs.trim(), rhs.trim());
+
+		if precondition.is_empty() || stays_disabled.is_empty() {
+			return Err(format!("Rule '{}' is missing a feature", s))
+		}
+		Ok(Self { precondition: precondition.into(), stays_disabled: stays_disabled.into(), kind })
+	}
+}
+
+impl<'de> Deserialize<'de> for Rule {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		s.parse().map_err(serde::de::Error::custom)
+	}
+}
+
+impl fmt::Display for Rule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let op = match self.kind {
+			RuleKind::NeverEnables => "!>",
+			RuleKind::NeverImplies => "!->",
+		};
+		write!(f, "{} {} {}", self.precondition, op, self.stays_disabled)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rule_parse_works() {
+		let rule: Rule = "std !-> runtime-benchmarks".parse().unwrap();
+		assert_eq!(rule.kind, RuleKind::NeverImplies);
+		assert_eq!(rule.precondition, "std");
+		assert_eq!(rule.stays_disabled, "runtime-benchmarks");
+		assert_eq!(rule.to_string(), "std !-> runtime-benchmarks");
+
+		let rule: Rule = "try-runtime!>std".parse().unwrap();
+		assert_eq!(rule.kind, RuleKind::NeverEnables);
+		assert_eq!(rule.to_string(), "try-runtime !> std");
+
+		assert!("std -> runtime-benchmarks".parse::<Rule>().is_err());
+		assert!("std !-> ".parse::<Rule>().is_err());
+	}
+}
@@ -5,7 +5,10 @@
 //!
 //! Used to lock in config format and binary version.
 
-use serde::{de, Deserialize, Deserializer};
+use serde::{
+	de::{self, Visitor},
+	Deserialize, Deserializer,
+};
 use std::fmt::{self, Display, Formatter};
 
 /// A semantic version.
@@ -45,14 +48,44 @@ impl Display for Semver {
 	}
 }
 
-impl Semver {
+impl<'de> Deserialize<'de> for Semver {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Self::from_serde(deserializer)
+	}
+}
+
+struct SemverVisitor;
+
+impl<'de> Visitor<'de> for SemverVisitor {
+	type Value = Semver;
+
+	fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str("a version like `1.2.3`")
+	}
+
 	#[allow(clippy::map_err_ignore)]
+	fn visit_str<E: de::Error>(self, v: &str) -> Result<Semver, E> {
+		Semver::try_from(v).map_err(|_| E::custom("Invalid semver"))
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<Semver, E> {
+		self.visit_str(&v.to_string())
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<Semver, E> {
+		self.visit_str(&v.to_string())
+	}
+}
+
+impl Semver {
+	/// Deserialize from a string like `1.2.3` or a number like `1` or `1.2`.
+	///
+	/// Numbers are accepted since YAML does not parse `format: 1` as string.
 	pub fn from_serde<'de, D>(deserializer: D) -> Result<Self, D::Error>
 	where
 		D: Deserializer<'de>,
 	{
-		let s = String::deserialize(deserializer)?;
-		Self::try_from(s.as_str()).map_err(|_| de::Error::custom("Invalid semver"))
+		deserializer.deserialize_any(SemverVisitor)
 	}
 
 	/// Whether `self` is newer or equal to `other`.
@@ -98,6 +131,13 @@ mod test {
 
 		let embedding = serde_json::from_str::<Embedding>(s).unwrap();
 		assert_eq!(embedding.version, Semver::from((1, 2, 3)));
+
+		let value = serde_yaml::from_str::<serde_yaml::Value>("version: 1").unwrap();
+		let embedding = serde_yaml::from_value::<Embedding>(value).unwrap();
+		assert_eq!(embedding.version, Semver::from((1, 0, 0)));
+
+		let embedding = serde_yaml::from_str::<Embedding>("version: 1.2").unwrap();
+		assert_eq!(embedding.version, Semver::from((1, 2, 0)));
 	}
 
 	#[test]
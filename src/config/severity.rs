@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Severity levels of lint issues, similar to the lint levels of rustc.
+//!
+//! They are configured in the `severity` section of the config file:
+//!
+//! ```yaml
+//! severity:
+//!   left-side-missing: warn
+//!   nostd-default-features: allow
+//! ```
+//!
+//! The issue codes like `Z0002` can be used instead of the names.
+
+use crate::config::{load_section, workspace_config};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+
+/// A kind of issue that a lint can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IssueKind {
+	/// A crate does not propagate a feature to one of its dependencies.
+	#[serde(alias = "Z0001")]
+	PropagateMissing,
+	/// A crate does not have a feature that one of its dependencies has.
+	#[serde(alias = "Z0002")]
+	LeftSideMissing,
+	/// A no-std crate does not disable the default features of a no-std dependency.
+	#[serde(alias = "Z0102")]
+	NostdDefaultFeatures,
+	/// A dependency has a `path` that points outside of the workspace.
+	#[serde(alias = "Z0201")]
+	PathDepsOutsideWorkspace,
+	/// A dependency is listed as normal and as dev dependency.
+	#[serde(alias = "Z0202")]
+	DuplicateDeps,
+	/// A feature enables a feature of a build-dependency.
+	#[serde(alias = "Z0203")]
+	BuildDepFeatures,
+	/// A feature is not documented.
+	#[serde(alias = "Z0303")]
+	FeatureDocs,
+	/// A crate has a `std` feature but is not no-std when it is disabled.
+	#[serde(alias = "Z0101")]
+	NostdCfgAttr,
+	/// A git dependency violates the policy of the `git-deps` config section.
+	#[serde(alias = "Z0204")]
+	GitDeps,
+	/// A crate has a missing, inconsistent or too low `rust-version`.
+	#[serde(alias = "Z0205")]
+	RustVersion,
+	/// A no-std crate has a crate level `#![cfg]` that may pull in the standard library.
+	#[serde(alias = "Z0103")]
+	NostdCrateCfg,
+	/// A weak feature references a dependency that is always enabled.
+	#[serde(alias = "Z0304")]
+	WeakFeatures,
+	/// A crate does not declare an explicit `default` feature.
+	#[serde(alias = "Z0104")]
+	DefaultFeature,
+	/// An optional dependency has no feature of the same name that enables it.
+	#[serde(alias = "Z0305")]
+	OptionalDeps,
+	/// A dependency has `default-features = false` but enables the `default` feature.
+	#[serde(alias = "Z0206")]
+	DefaultFeaturesConflict,
+	/// Workspace members disagree on the default features of a dependency.
+	#[serde(alias = "Z0207")]
+	DefaultFeaturesConsistency,
+}
+
+impl IssueKind {
+	/// The severity if the kind is not configured.
+	///
+	/// Heuristic issues only warn since they can be false positives.
+	pub fn default_severity(self) -> Severity {
+		match self {
+			Self::NostdCrateCfg => Severity::Warn,
+			_ => Severity::Deny,
+		}
+	}
+}
+
+/// How to treat an issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+	/// Do not report the issue.
+	Allow,
+	/// Report the issue, but do not fail.
+	Warn,
+	/// Report the issue and fail.
+	#[default]
+	Deny,
+}
+
+/// Maps issue kinds to their severity. Kinds that are not configured use their
+/// [default](IssueKind::default_severity).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct SeverityTable(BTreeMap<IssueKind, Severity>);
+
+/// The part of the config file that contains the severities.
+#[derive(Default, Deserialize)]
+struct SeverityFile {
+	#[serde(default)]
+	severity: SeverityTable,
+}
+
+impl SeverityTable {
+	/// Load the table from the config file of a workspace.
+	///
+	/// Returns the default table if there is no config file.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+		Self::from_config(workspace_config(root).as_deref())
+	}
+
+	/// Load the table from a config file, or the default table if there is none.
+	pub fn from_config(path: Option<&Path>) -> Result<Self, String> {
+		Ok(load_section::<SeverityFile>(path, "severity")?.severity)
+	}
+
+	pub fn get(&self, kind: IssueKind) -> Severity {
+		self.0.get(&kind).copied().unwrap_or_else(|| kind.default_severity())
+	}
+
+	/// All issue kinds that are explicitly configured.
+	pub fn iter(&self) -> impl Iterator<Item = (IssueKind, Severity)> + '_ {
+		self.0.iter().map(|(kind, severity)| (*kind, *severity))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn severity_table_parses() {
+		let file: SeverityFile = serde_yaml::from_str(
+			"version: {}\nseverity:\n  left-side-missing: warn\n  propagate-missing: allow\n",
+		)
+		.unwrap();
+
+		assert_eq!(file.severity.get(IssueKind::LeftSideMissing), Severity::Warn);
+		assert_eq!(file.severity.get(IssueKind::PropagateMissing), Severity::Allow);
+		assert_eq!(file.severity.get(IssueKind::NostdDefaultFeatures), Severity::Deny);
+		assert_eq!(file.severity.get(IssueKind::NostdCrateCfg), Severity::Warn);
+
+		let file: SeverityFile = serde_yaml::from_str("severity:\n  Z0102: warn\n").unwrap();
+		assert_eq!(file.severity.get(IssueKind::NostdDefaultFeatures), Severity::Warn);
+
+		let file: SeverityFile = serde_yaml::from_str("version: {}\n").unwrap();
+		assert_eq!(file.severity, SeverityTable::default());
+		assert!(serde_yaml::from_str::<SeverityFile>("severity:\n  unknown: warn\n").is_err());
+	}
+}
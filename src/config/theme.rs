@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Color themes of the human readable output.
+
+use crate::{
+	config::{load_section, workspace_config},
+	log,
+};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Environment variable that selects the theme. Takes precedence over the config file.
+pub const THEME_ENV: &str = "ZEPTER_THEME";
+
+/// How the human readable output is styled when colors are enabled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+	/// Red errors, yellow warnings and green fixes.
+	#[default]
+	Default,
+	/// Bold magenta errors, bold yellow warnings and bold blue fixes. Does not rely on telling
+	/// red and green apart.
+	HighContrast,
+	/// No styling at all, even if colors are enabled.
+	Plain,
+}
+
+/// What a piece of styled output means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+	Error,
+	Warning,
+	Success,
+	Emphasis,
+}
+
+/// The part of the config file that contains the theme.
+#[derive(Default, Deserialize)]
+struct ThemeFile {
+	theme: Option<Theme>,
+}
+
+impl Theme {
+	/// The theme from `ZEPTER_THEME` or the config file in `dir`.
+	///
+	/// Invalid values are reported as warning and fall back to the default theme, since the theme
+	/// should never fail a command.
+	pub fn resolve<P: AsRef<Path>>(dir: P) -> Self {
+		Self::from_env()
+			.and_then(|theme| match theme {
+				Some(theme) => Ok(Some(theme)),
+				None => Self::from_workspace(dir),
+			})
+			.unwrap_or_else(|e| {
+				log::warn!("{}", e);
+				None
+			})
+			.unwrap_or_default()
+	}
+
+	/// The theme from the `ZEPTER_THEME` environment variable, if set.
+	pub fn from_env() -> Result<Option<Self>, String> {
+		let Ok(value) = std::env::var(THEME_ENV) else { return Ok(None) };
+		Self::parse(&value).map(Some)
+	}
+
+	/// The theme from the config file of a workspace, if it sets one.
+	pub fn from_workspace<P: AsRef<Path>>(root: P) -> Result<Option<Self>, String> {
+		let path = workspace_config(root);
+		Ok(load_section::<ThemeFile>(path.as_deref(), "theme")?.theme)
+	}
+
+	pub fn parse(value: &str) -> Result<Self, String> {
+		match value.trim() {
+			"default" => Ok(Self::Default),
+			"high-contrast" => Ok(Self::HighContrast),
+			"plain" => Ok(Self::Plain),
+			_ => Err(format!(
+				"Unknown theme '{}' in {}. Expected one of: default, high-contrast, plain",
+				value, THEME_ENV
+			)),
+		}
+	}
+
+	/// The ANSI style of `role`, if it is styled at all.
+	pub fn style(self, role: Role) -> Option<&'static str> {
+		match (self, role) {
+			(Self::Plain, _) => None,
+			(Self::Default, Role::Error) => Some("31"),
+			(Self::Default, Role::Warning) => Some("33"),
+			(Self::Default, Role::Success) => Some("32"),
+			(Self::HighContrast, Role::Error) => Some("1;35"),
+			(Self::HighContrast, Role::Warning) => Some("1;33"),
+			(Self::HighContrast, Role::Success) => Some("1;34"),
+			(_, Role::Emphasis) => Some("1"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn theme_parses() {
+		let file: ThemeFile = serde_yaml::from_str("version: {}\ntheme: high-contrast\n").unwrap();
+		assert_eq!(file.theme, Some(Theme::HighContrast));
+		let file: ThemeFile = serde_yaml::from_str("version: {}\n").unwrap();
+		assert_eq!(file.theme, None);
+
+		assert_eq!(Theme::parse("plain"), Ok(Theme::Plain));
+		assert!(Theme::parse("dark").unwrap_err().starts_with("Unknown theme 'dark'"));
+	}
+
+	#[test]
+	fn theme_styles() {
+		assert_eq!(Theme::Default.style(Role::Error), Some("31"));
+		assert_eq!(Theme::HighContrast.style(Role::Success), Some("1;34"));
+		assert_eq!(Theme::HighContrast.style(Role::Emphasis), Some("1"));
+		assert_eq!(Theme::Plain.style(Role::Emphasis), None);
+	}
+}
@@ -3,9 +3,22 @@
 
 //! Loads config and workflow files.
 
-use crate::{cmd::GlobalArgs, config::semver::Semver, log};
+use crate::{
+	cmd::{glob_regex, log_file::LogFormat, metrics, output::OutputFormat, Command, GlobalArgs},
+	config::{semver::Semver, CONFIG_ENV},
+	log,
+};
+use clap::Parser;
 use serde::Deserialize;
-use std::{collections::BTreeMap as Map, str::FromStr};
+use std::{
+	collections::BTreeMap as Map,
+	io::Write,
+	ops::Range,
+	path::{Path, PathBuf},
+	process::{ExitStatus, Stdio},
+	str::FromStr,
+	time::{Duration, Instant},
+};
 
 pub type WorkflowName = String;
 
@@ -15,8 +28,11 @@ pub const WORKFLOW_DEFAULT_NAME: &str = "default";
 #[derive(Deserialize)]
 pub struct WorkflowFile {
 	version: Version,
-	workflows: Map<WorkflowName, Workflow>,
+	workflows: Map<WorkflowName, WorkflowDef>,
 	help: Option<WorkflowHelp>,
+	/// Where the file was loaded from, if it was loaded from a file.
+	#[serde(skip)]
+	path: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
@@ -26,13 +42,225 @@ pub struct Version {
 
 	#[serde(deserialize_with = "Semver::from_serde")]
 	binary: Semver,
+
+	/// Minimum binary version of specific workflows, in addition to `binary`.
+	#[serde(default)]
+	workflows: Map<WorkflowName, Semver>,
+
+	/// Whether to print a notice after a run if a newer binary was released.
+	#[serde(default, rename = "update-check")]
+	update_check: UpdateCheck,
+}
+
+/// When to check crates.io for a newer release of Zepter.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateCheck {
+	/// Only for interactive runs outside of CI.
+	#[default]
+	Auto,
+	/// After every run.
+	Always,
+	/// Never.
+	Never,
 }
 
+/// The steps of a workflow and the commands that run around their fixes.
 #[derive(Deserialize, Clone)]
-pub struct Workflow(pub Vec<WorkflowStep>);
+#[serde(from = "WorkflowRepr")]
+pub struct Workflow {
+	pub steps: Vec<WorkflowStep>,
+	pub hooks: FixHooks,
+}
+
+/// Shell commands that only run if a step of the workflow modified a manifest.
+#[derive(Clone, Default)]
+pub struct FixHooks {
+	/// Run before the next step, once the preceding steps modified manifests.
+	pub before_fix: Vec<String>,
+	/// Run after the last step if any step modified manifests.
+	pub after_fix: Vec<String>,
+}
+
+/// A workflow is either a plain list of steps or a map with steps and fix hooks:
+///
+/// ```yaml
+/// default:
+///   steps:
+///     - [ 'format', 'features', '--fix' ]
+///   before-fix: [ 'cargo update -w --offline' ]
+///   after-fix: [ 'taplo fmt' ]
+/// ```
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WorkflowRepr {
+	Steps(Vec<WorkflowStep>),
+	Map(HookedWorkflow),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct HookedWorkflow {
+	steps: Vec<WorkflowStep>,
+	#[serde(default)]
+	before_fix: Vec<String>,
+	#[serde(default)]
+	after_fix: Vec<String>,
+}
 
+impl From<WorkflowRepr> for Workflow {
+	fn from(repr: WorkflowRepr) -> Self {
+		match repr {
+			WorkflowRepr::Steps(steps) => Self { steps, hooks: Default::default() },
+			WorkflowRepr::Map(HookedWorkflow { steps, before_fix, after_fix }) =>
+				Self { steps, hooks: FixHooks { before_fix, after_fix } },
+		}
+	}
+}
+
+/// A workflow as written in the config file.
+///
+/// Extensions are replaced by the steps that they resolve to in [`WorkflowFile::into_resolved`].
 #[derive(Deserialize, Clone)]
-pub struct WorkflowStep(pub Vec<String>);
+#[serde(untagged)]
+enum WorkflowDef {
+	Steps(Workflow),
+	Extends(WorkflowExtension),
+}
+
+/// A workflow that inherits the steps of another one:
+///
+/// ```yaml
+/// fix:
+///   extends: check
+///   append-args: [ '--fix' ]
+///   steps:
+///     - name: format
+///       run: [ 'format', 'features', '--fix' ]
+/// ```
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct WorkflowExtension {
+	/// Name of the workflow whose steps are inherited.
+	extends: WorkflowName,
+	/// Arguments that are appended to every inherited step.
+	#[serde(default)]
+	append_args: Vec<String>,
+	/// Steps that replace the inherited step with the same `name` or are appended otherwise.
+	#[serde(default)]
+	steps: Vec<WorkflowStep>,
+	/// Replaces the inherited `before-fix` hooks.
+	before_fix: Option<Vec<String>>,
+	/// Replaces the inherited `after-fix` hooks.
+	after_fix: Option<Vec<String>>,
+}
+
+impl WorkflowExtension {
+	/// Apply the extension to the steps of the extended workflow.
+	fn apply(&self, base: &Workflow) -> Workflow {
+		let mut steps = base.steps.clone();
+		for step in steps.iter_mut() {
+			step.args.extend(self.append_args.iter().cloned());
+		}
+
+		for step in self.steps.iter() {
+			let inherited = step
+				.name
+				.as_ref()
+				.and_then(|n| steps.iter().position(|s| s.name.as_ref() == Some(n)));
+			match inherited {
+				Some(i) => steps[i] = step.clone(),
+				None => steps.push(step.clone()),
+			}
+		}
+		let hooks = FixHooks {
+			before_fix: self.before_fix.clone().unwrap_or_else(|| base.hooks.before_fix.clone()),
+			after_fix: self.after_fix.clone().unwrap_or_else(|| base.hooks.after_fix.clone()),
+		};
+		Workflow { steps, hooks }
+	}
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(from = "WorkflowStepRepr")]
+pub struct WorkflowStep {
+	/// Name to select the step with `zepter run --step`.
+	pub name: Option<String>,
+	/// Arguments that are passed to Zepter.
+	pub args: Vec<String>,
+	/// Run the step once for every workspace package that matches one of these patterns.
+	pub foreach_package: Vec<String>,
+	/// Hint that is printed instead of the default one when the step finds issues.
+	pub hint: Option<String>,
+	/// Run the step concurrently with the adjacent steps that are also `parallel`.
+	pub parallel: bool,
+}
+
+/// A step is either a plain list of arguments or a map with a name, package matrix, hint or
+/// `parallel` flag.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WorkflowStepRepr {
+	Args(Vec<String>),
+	#[serde(rename_all = "kebab-case")]
+	Map {
+		name: Option<String>,
+		#[serde(default)]
+		foreach_package: Vec<String>,
+		#[serde(alias = "args")]
+		run: Vec<String>,
+		hint: Option<String>,
+		#[serde(default)]
+		parallel: bool,
+	},
+}
+
+impl From<WorkflowStepRepr> for WorkflowStep {
+	fn from(repr: WorkflowStepRepr) -> Self {
+		match repr {
+			WorkflowStepRepr::Args(args) =>
+				Self { name: None, args, foreach_package: Vec::new(), hint: None, parallel: false },
+			WorkflowStepRepr::Map { name, foreach_package, run, hint, parallel } =>
+				Self { name, args: run, foreach_package, hint, parallel },
+		}
+	}
+}
+
+/// Replace every `${VAR}` in `arg` with the value of the environment variable `VAR`.
+///
+/// `${VAR:-default}` falls back to `default` if the variable is unset or empty. Variables are
+/// looked up with `env`.
+pub fn expand_env_vars(arg: &str, env: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+	let mut expanded = String::new();
+	let mut rest = arg;
+
+	while let Some(start) = rest.find("${") {
+		expanded.push_str(&rest[..start]);
+		let Some(len) = rest[start..].find('}') else {
+			return Err(format!("Missing closing '}}' of variable in '{}'", arg))
+		};
+		let var = &rest[start + 2..start + len];
+		let (name, default) = match var.split_once(":-") {
+			Some((name, default)) => (name, Some(default)),
+			None => (var, None),
+		};
+
+		match (env(name), default) {
+			(Some(value), Some(default)) if value.is_empty() => expanded.push_str(default),
+			(Some(value), _) => expanded.push_str(&value),
+			(None, Some(default)) => expanded.push_str(default),
+			(None, None) =>
+				return Err(format!("Environment variable '{}' is not set in '{}'", name, arg)),
+		}
+		rest = &rest[start + len + 1..];
+	}
+
+	expanded.push_str(rest);
+	Ok(expanded)
+}
+
+/// Placeholder in the arguments and hint of a `foreach-package` step.
+pub const PACKAGE_PLACEHOLDER: &str = "{package}";
 
 #[derive(Deserialize, Clone)]
 pub struct WorkflowHelp {
@@ -41,72 +269,469 @@ pub struct WorkflowHelp {
 }
 
 impl Workflow {
-	pub fn run(self, _g: &GlobalArgs) -> Result<(), String> {
-		for (_i, step) in self.0.iter().enumerate() {
-			let mut args = step.0.clone();
-			// No default hint since the workflows can provide their own.
-			args.push("--fix-hint=off".into());
-			let cmd = std::env::args().next().unwrap_or("zepter".into());
-
-			log::debug!("Running command '{} {}'", cmd, args.join(" "));
+	/// Whether any step needs to be expanded with [`Self::expand`].
+	pub fn has_matrix(&self) -> bool {
+		self.steps.iter().any(|step| !step.foreach_package.is_empty())
+	}
 
-			let status = std::process::Command::new(&cmd)
-				.args(args.clone())
-				.status()
-				.map_err(|e| format!("Failed to run command '{}': {}", cmd, e))?;
+	/// The ranges of steps that run one after another.
+	///
+	/// Adjacent steps with `parallel: true` form one group, all others are a group on their own.
+	fn groups(&self) -> Vec<Range<usize>> {
+		let mut groups = Vec::<Range<usize>>::new();
+		for (i, step) in self.steps.iter().enumerate() {
+			match groups.last_mut() {
+				Some(last) if step.parallel && self.steps[last.start].parallel => last.end = i + 1,
+				_ => groups.push(i..i + 1),
+			}
+		}
+		groups
+	}
 
-			let first_two_args = args
-				.iter()
-				.rev()
-				.skip(1)
-				.rev()
-				.take(2)
-				.map(String::as_str)
-				.collect::<Vec<_>>()
-				.join(" ");
+	/// Only keep the steps that are selected by their 1-based position or name.
+	///
+	/// Keeps all steps if `selectors` is empty.
+	pub fn select(self, selectors: &[String]) -> Result<Self, String> {
+		if selectors.is_empty() {
+			return Ok(self)
+		}
+		let len = self.steps.len();
+		let names = self.steps.iter().filter_map(|s| s.name.as_deref()).collect::<Vec<_>>();
 
-			if !status.success() {
+		for selector in selectors.iter() {
+			let found = match selector.parse::<usize>() {
+				Ok(index) => (1..=len).contains(&index),
+				Err(_) => names.contains(&selector.as_str()),
+			};
+			if !found {
+				let names = match names.as_slice() {
+					[] => String::new(),
+					names => format!(" or one of: {}", names.join(", ")),
+				};
 				return Err(format!(
-					"Command '{}' failed with exit code {}",
-					first_two_args,
-					status.code().unwrap_or(1)
+					"Step '{}' not found. Expected a number from 1 to {}{}",
+					selector, len, names
 				))
 			}
+		}
+
+		let steps = self
+			.steps
+			.into_iter()
+			.enumerate()
+			.filter(|(i, step)| {
+				selectors.iter().any(|s| {
+					s.parse::<usize>().map_or(false, |index| index == i + 1) ||
+						step.name.as_ref() == Some(s)
+				})
+			})
+			.map(|(_, step)| step)
+			.collect();
+		Ok(Self { steps, hooks: self.hooks })
+	}
+
+	/// Replace every `foreach-package` step with one step per matching package.
+	///
+	/// The package name is substituted for `{package}`, or passed with `-p` if the arguments have
+	/// no placeholder.
+	pub fn expand(self, packages: &[String]) -> Result<Self, String> {
+		let mut steps = Vec::new();
+
+		for step in self.steps {
+			if step.foreach_package.is_empty() {
+				steps.push(step);
+				continue
+			}
+			let mut matched = Vec::new();
+			for pattern in step.foreach_package.iter() {
+				let regex = glob_regex(pattern)?;
+				let before = matched.len();
+				matched.extend(packages.iter().filter(|p| regex.is_match(p)));
+
+				if matched.len() == before {
+					return Err(format!(
+						"Pattern '{}' does not match any workspace package",
+						pattern
+					))
+				}
+			}
+			matched.sort();
+			matched.dedup();
+
+			let templated = step.args.iter().any(|a| a.contains(PACKAGE_PLACEHOLDER));
+			for package in matched {
+				let mut args = step
+					.args
+					.iter()
+					.map(|a| a.replace(PACKAGE_PLACEHOLDER, package))
+					.collect::<Vec<_>>();
+				if !templated {
+					args.extend(["-p".into(), package.clone()]);
+				}
+				let hint = step.hint.as_ref().map(|h| h.replace(PACKAGE_PLACEHOLDER, package));
+				steps.push(WorkflowStep {
+					name: step.name.clone(),
+					args,
+					foreach_package: Vec::new(),
+					hint,
+					parallel: step.parallel,
+				});
+			}
+		}
+
+		Ok(Self { steps, hooks: self.hooks })
+	}
+
+	/// Run all steps and print a summary of them if any failed.
+	///
+	/// Continues after a failing step unless `fail_fast` is set. The fix hooks only run if a step
+	/// reported that it fixed something. The steps use the `config` file instead of searching for
+	/// one in the workspace.
+	pub fn run(self, g: &GlobalArgs, config: Option<&Path>, fail_fast: bool) -> Result<(), String> {
+		let env = |name: &str| std::env::var(name).ok();
+		let mut reports = Vec::new();
+		let mut unhooked_fixes = false;
+
+		for group in self.groups() {
+			let commands = group
+				.clone()
+				.map(|i| StepCommand::new(i, &self.steps[i], g, env))
+				.collect::<Result<Vec<_>, _>>()?;
+			// Fixes of concurrent steps could overwrite each other.
+			let fixing = commands.len() > 1 && commands.iter().any(StepCommand::writes_manifests);
+			if fixing {
+				log::info!(
+					"Running steps {} to {} one after another since they modify manifests",
+					group.start + 1,
+					group.end
+				);
+			}
+
+			if commands.len() > 1 && !fixing {
+				if unhooked_fixes {
+					run_hooks("before-fix", &self.hooks.before_fix)?;
+					unhooked_fixes = false;
+				}
+				let start = Instant::now();
+				let children = commands
+					.iter()
+					.map(|c| {
+						c.command(g, config)
+							.stdout(Stdio::piped())
+							.stderr(Stdio::piped())
+							.spawn()
+							.map_err(|e| {
+								format!("Failed to run command '{}': {}", program().display(), e)
+							})
+					})
+					.collect::<Result<Vec<_>, _>>()?;
+				let outputs = std::thread::scope(|s| {
+					let handles = children
+						.into_iter()
+						.map(|child| s.spawn(move || (child.wait_with_output(), start.elapsed())))
+						.collect::<Vec<_>>();
+					handles
+						.into_iter()
+						.map(|h| h.join().expect("Step panicked"))
+						.collect::<Vec<_>>()
+				});
+
+				// Print the outputs in the order of the steps and not in the order they finished.
+				for (command, (output, duration)) in commands.into_iter().zip(outputs) {
+					let output = output.map_err(|e| {
+						format!("Failed to run command '{}': {}", program().display(), e)
+					})?;
+					let _ = std::io::stdout().write_all(&output.stdout);
+					let _ = std::io::stderr().write_all(&output.stderr);
+					let report =
+						command.report(output.status, duration, fail_fast, self.steps.len())?;
+					unhooked_fixes |= report.fixed > 0;
+					reports.push(report);
+				}
+				continue
+			}
+
+			for command in commands {
+				if unhooked_fixes {
+					run_hooks("before-fix", &self.hooks.before_fix)?;
+					unhooked_fixes = false;
+				}
+				let start = Instant::now();
+				let status = command.command(g, config).status().map_err(|e| {
+					format!("Failed to run command '{}': {}", program().display(), e)
+				})?;
+				let report =
+					command.report(status, start.elapsed(), fail_fast, self.steps.len())?;
+				unhooked_fixes |= report.fixed > 0;
+				reports.push(report);
+			}
+		}
+
+		let modified = reports.iter().filter(|r| r.fixed > 0).count();
+		if modified > 0 {
+			run_hooks("after-fix", &self.hooks.after_fix)?;
+		}
+		let modified = (modified > 0)
+			.then(|| format!("Manifests were modified by {} of {} steps", modified, reports.len()));
+
+		let failed = reports.iter().filter(|r| r.code.is_some()).count();
+		if failed == 0 {
+			if let Some(modified) = modified {
+				log::info!("{}", modified);
+			}
+			return Ok(())
+		}
+
+		println!("Workflow summary:");
+		for (i, report) in reports.iter().enumerate() {
+			let status = match report.code {
+				None => g.success("ok"),
+				Some(code) => g.error(&format!("failed with exit code {}", code)),
+			};
+			let stats = report.stats.as_deref().map(|s| format!(", {}", s)).unwrap_or_default();
+			println!(
+				"  {}/{} {}: {}{} in {:.2?}",
+				i + 1,
+				reports.len(),
+				report.label,
+				status,
+				stats,
+				report.duration
+			);
+		}
+		if let Some(modified) = modified {
+			println!("{}.", modified);
+		}
+		println!("Re-run the failed steps with:");
+		for report in reports.iter().filter(|r| r.code.is_some()) {
+			println!("  zepter {}", report.command.join(" "));
+		}
+
+		Err(format!("{} of {} steps failed", failed, reports.len()))
+	}
+}
+
+/// A step with the arguments that it is run with.
+struct StepCommand {
+	/// Position of the step in the workflow, starting at 0.
+	index: usize,
+	/// All arguments of the step without the ones that were added by the workflow runner.
+	command: Vec<String>,
+	/// The arguments that the step is run with.
+	args: Vec<String>,
+	/// Where the step writes its `issues=… fixed=… warnings=…` line.
+	stats_file: PathBuf,
+	/// Where the step writes its metrics if the workflow has a `--metrics-file`.
+	metrics_file: Option<PathBuf>,
+}
+
+impl StepCommand {
+	fn new(
+		index: usize,
+		step: &WorkflowStep,
+		g: &GlobalArgs,
+		env: impl Fn(&str) -> Option<String> + Copy,
+	) -> Result<Self, String> {
+		let command = step
+			.args
+			.iter()
+			.map(|a| expand_env_vars(a, env))
+			.collect::<Result<Vec<_>, _>>()?;
+		let mut args = command.clone();
+		// No default hint since the workflows can provide their own.
+		match &step.hint {
+			Some(hint) => args.push(format!("--fix-hint={}", expand_env_vars(hint, env)?)),
+			None => args.push("--fix-hint=off".into()),
+		}
+		let stats_file =
+			std::env::temp_dir().join(format!("zepter-stats-{}-{}", std::process::id(), index));
+		args.push(format!("--stats-file={}", stats_file.display()));
+		if g.verbose() {
+			args.push("--verbose".into());
+		}
+		if g.format() == OutputFormat::Github {
+			args.push("--format=github".into());
+		}
+		if let Some((path, format)) = g.log_file() {
+			args.push(format!("--log-file={}", path.display()));
+			if format == LogFormat::Json {
+				args.push("--log-format=json".into());
+			}
+		}
+		// Every step gets its own file since parallel steps would overwrite each other.
+		let metrics_file = g.metrics_file().map(|_| {
+			let path = std::env::temp_dir().join(format!(
+				"zepter-metrics-{}-{}",
+				std::process::id(),
+				index
+			));
+			args.push(format!("--metrics-file={}", path.display()));
+			args.push("--metrics-format=json".into());
+			path
+		});
+
+		Ok(Self { index, command, args, stats_file, metrics_file })
+	}
+
+	/// Whether the step modifies manifests, like with `--fix`.
+	///
+	/// Steps that cannot be parsed do not, since they only print an error, the help or the version.
+	fn writes_manifests(&self) -> bool {
+		let argv = std::iter::once("zepter").chain(self.command.iter().map(String::as_str));
+		Command::try_parse_from(argv).map_or(false, |cmd| cmd.writes_manifests())
+	}
+
+	/// The process that runs the step.
+	fn command(&self, g: &GlobalArgs, config: Option<&Path>) -> std::process::Command {
+		let cmd = program();
+		log::debug!("Running command '{} {}'", cmd.display(), self.args.join(" "));
+
+		let mut command = std::process::Command::new(&cmd);
+		command
+			.args(self.args.clone())
+			// Sub-processes cannot detect whether our stdout is a terminal.
+			.env("CARGO_TERM_COLOR", if g.colored() { "always" } else { "never" });
+		if let Some(config) = config {
+			command.env(CONFIG_ENV, config);
+		}
+		command
+	}
+
+	/// The outcome of the step once it exited with `status`.
+	///
+	/// Errors if the step failed and the workflow should stop, which is the case with `fail_fast`
+	/// or if it is the only one of the `steps`.
+	fn report(
+		self,
+		status: ExitStatus,
+		duration: Duration,
+		fail_fast: bool,
+		steps: usize,
+	) -> Result<StepReport, String> {
+		let stats = std::fs::read_to_string(&self.stats_file).ok();
+		let _ = std::fs::remove_file(&self.stats_file);
+		if let Some(path) = &self.metrics_file {
+			metrics::record_steps(path);
+			let _ = std::fs::remove_file(path);
+		}
+
+		let first_two_args =
+			self.command.iter().take(2).map(String::as_str).collect::<Vec<_>>().join(" ");
 
-			log::info!("{}/{} {:<}", _i + 1, self.0.len(), first_two_args);
+		let code = (!status.success()).then(|| status.code().unwrap_or(1));
+		match code {
+			Some(code) if fail_fast || steps == 1 =>
+				return Err(format!("Command '{}' failed with exit code {}", first_two_args, code)),
+			Some(_) => (),
+			None => {
+				log::info!("{}/{} {:<}", self.index + 1, steps, first_two_args);
+			},
 		}
+		let fixed = stats.as_deref().and_then(fixed_count).unwrap_or_default();
+		Ok(StepReport {
+			label: first_two_args,
+			command: self.command,
+			code,
+			stats,
+			fixed,
+			duration,
+		})
+	}
+}
+
+/// The binary that is running, to run the steps with the same version.
+///
+/// Not the first argument, since that is only the name if it was found in the `PATH`.
+fn program() -> PathBuf {
+	std::env::current_exe().unwrap_or_else(|_| "zepter".into())
+}
 
-		Ok(())
+/// Outcome of a single workflow step.
+struct StepReport {
+	/// The first two arguments of the step, like `lint propagate-feature`.
+	label: String,
+	/// All arguments of the step without the ones that were added by the workflow runner.
+	command: Vec<String>,
+	/// The exit code if the step failed.
+	code: Option<i32>,
+	/// The `issues=… fixed=… warnings=…` line written by the step.
+	stats: Option<String>,
+	/// Number of issues that the step fixed according to its `stats`.
+	fixed: usize,
+	duration: Duration,
+}
+
+/// The number of fixes in the `issues=… fixed=… warnings=…` line of a step.
+fn fixed_count(stats: &str) -> Option<usize> {
+	stats.split_whitespace().find_map(|kv| kv.strip_prefix("fixed="))?.parse().ok()
+}
+
+/// Run the shell commands of a fix hook in order and stop at the first one that fails.
+fn run_hooks(hook: &str, commands: &[String]) -> Result<(), String> {
+	for command in commands.iter() {
+		log::info!("Running {} hook '{}'", hook, command);
+		let status = std::process::Command::new("sh")
+			.args(["-c", command])
+			.status()
+			.map_err(|e| format!("Failed to run {} hook '{}': {}", hook, command, e))?;
+		if !status.success() {
+			return Err(format!(
+				"Hook '{}' failed with exit code {}",
+				command,
+				status.code().unwrap_or(1)
+			))
+		}
 	}
+	Ok(())
 }
 
 impl FromStr for WorkflowFile {
 	type Err = String;
 
 	fn from_str(content: &str) -> Result<Self, Self::Err> {
-		let parsed = serde_yaml::from_str::<WorkflowFile>(content)
+		// Merge keys like `<<: *anchor` are not applied by serde.
+		let mut value = serde_yaml::from_str::<serde_yaml::Value>(content)
+			.map_err(|e| format!("yaml parsing: {}", e))?;
+		value.apply_merge().map_err(|e| format!("yaml parsing: {}", e))?;
+		let parsed = serde_yaml::from_value::<WorkflowFile>(value)
 			.map_err(|e| format!("yaml parsing: {}", e))?;
 
 		if parsed.version.format != (1, 0, 0).into() {
 			return Err("Can only parse workflow files with version '1'".into())
 		}
+		if let Some(name) =
+			parsed.version.workflows.keys().find(|n| !parsed.workflows.contains_key(*n))
+		{
+			return Err(format!("Version requirement for unknown workflow '{}'", name))
+		}
 
 		parsed.into_resolved()
 	}
 }
 
 impl WorkflowFile {
+	/// The steps of a workflow. Only returns workflows that are resolved.
 	pub fn workflow<S: AsRef<str>>(&self, name: S) -> Option<Workflow> {
-		self.workflows.get(name.as_ref()).cloned()
+		match self.workflows.get(name.as_ref())? {
+			WorkflowDef::Steps(workflow) => Some(workflow.clone()),
+			WorkflowDef::Extends(_) => None,
+		}
 	}
 
 	/// Load a workflow file from the given path.
-	pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+	pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, String> {
 		let path = path.as_ref();
 		let content = std::fs::read_to_string(path)
 			.map_err(|e| format!("Failed to read config file {:?}: {}", path, e))?;
 
-		content.parse()
+		let mut file: Self = content.parse()?;
+		file.path = Some(path.into());
+		Ok(file)
+	}
+
+	/// The path that the file was loaded from with [`Self::from_path`].
+	pub fn path(&self) -> Option<&Path> {
+		self.path.as_deref()
 	}
 
 	/// Format the user-provided help message.
@@ -126,32 +751,79 @@ impl WorkflowFile {
 		format!("{}{}", text, links).into()
 	}
 
-	/// Iteratively resolve all references in the workflow file.
+	/// Iteratively resolve all extensions and references in the workflow file.
 	pub fn into_resolved(mut self) -> Result<Self, String> {
+		self.resolve_extensions()?;
 		while self.resolve_once()? {}
 		Ok(self)
 	}
 
+	/// Replace every workflow that `extends` another one by its steps.
+	///
+	/// Workflows can extend workflows that are extensions themselves, but not in a cycle.
+	fn resolve_extensions(&mut self) -> Result<(), String> {
+		loop {
+			let pending = self
+				.workflows
+				.iter()
+				.filter_map(|(name, wf)| match wf {
+					WorkflowDef::Extends(ext) => Some((name.clone(), ext.clone())),
+					WorkflowDef::Steps(_) => None,
+				})
+				.collect::<Vec<_>>();
+			if pending.is_empty() {
+				return Ok(())
+			}
+
+			let mut progress = false;
+			for (name, ext) in pending.iter() {
+				match self.workflows.get(&ext.extends) {
+					Some(WorkflowDef::Steps(base)) => {
+						let resolved = ext.apply(base);
+						self.workflows.insert(name.clone(), WorkflowDef::Steps(resolved));
+						progress = true;
+					},
+					Some(WorkflowDef::Extends(_)) => (),
+					None =>
+						return Err(format!(
+							"Workflow '{}' extends unknown workflow '{}'",
+							name, ext.extends
+						)),
+				}
+			}
+			if !progress {
+				let names = pending.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+				return Err(format!("Workflows extend each other in a cycle: {}", names.join(", ")))
+			}
+		}
+	}
+
 	/// Do one iterative resolve step and return whether something changed.
 	pub fn resolve_once(&mut self) -> Result<bool, String> {
 		let wfs = self.workflows.clone();
 
 		for wf in self.workflows.values_mut() {
-			for step in wf.0.iter_mut() {
-				for (i, orig_line) in step.0.iter_mut().enumerate() {
-					if let Some(line) = orig_line.strip_prefix('$') {
+			let WorkflowDef::Steps(wf) = wf else { continue };
+			for step in wf.steps.iter_mut() {
+				for (i, orig_line) in step.args.iter_mut().enumerate() {
+					// `${VAR}` is an environment variable and not a reference.
+					if let Some(line) = orig_line.strip_prefix('$').filter(|l| !l.starts_with('{'))
+					{
 						let (vname, index) = line.split_once('.').expect("Expecting $name.index");
 						let index: u32 = index.parse().map_err(|e| {
 							format!("Failed to parse index '{}' in line '{}': {}", index, line, e)
 						})?;
 
-						let value = wfs.get(vname).ok_or_else(|| {
-							format!("Failed to find workflow '{}' in line '{}'", vname, line)
-						})?;
+						let Some(WorkflowDef::Steps(value)) = wfs.get(vname) else {
+							return Err(format!(
+								"Failed to find workflow '{}' in line '{}'",
+								vname, line
+							))
+						};
 
-						step.0.remove(i);
-						for line in value.0[index as usize].0.iter().rev() {
-							step.0.insert(i, line.clone());
+						step.args.remove(i);
+						for line in value.steps[index as usize].args.iter().rev() {
+							step.args.insert(i, line.clone());
 						}
 
 						return Ok(true)
@@ -163,18 +835,42 @@ impl WorkflowFile {
 		Ok(false)
 	}
 
+	/// The minimum binary version that is needed to run a workflow.
+	///
+	/// This is the global `binary` version or the one of the workflow, whichever is newer.
+	pub fn required_binary(&self, workflow: &str) -> Semver {
+		match self.version.workflows.get(workflow) {
+			Some(v) if !self.version.binary.is_newer_or_equal(v) => *v,
+			_ => self.version.binary,
+		}
+	}
+
+	/// When to check for a newer release of the binary.
+	pub fn update_check(&self) -> UpdateCheck {
+		self.version.update_check
+	}
+
 	/// Whether the config file is compatible with the current version of the running binary.
-	pub fn check_cfg_compatibility(&self) -> Result<(), String> {
+	pub fn check_cfg_compatibility(&self, workflow: &str) -> Result<(), String> {
 		let current_version =
 			Semver::try_from(clap::crate_version!()).expect("Crate version is valid semver");
-		let file_version = self.version.binary;
+		self.check_compatibility_with(workflow, &current_version)
+	}
+
+	fn check_compatibility_with(&self, workflow: &str, current: &Semver) -> Result<(), String> {
+		let required = self.required_binary(workflow);
 
-		if current_version.is_newer_or_equal(&file_version) {
+		if current.is_newer_or_equal(&required) {
 			Ok(())
+		} else if required != self.version.binary {
+			Err(format!(
+				"Workflow '{}' requires at least version {}, but the current version is {}.",
+				workflow, required, current
+			))
 		} else {
 			Err(format!(
-				"Config file version is too new. The file requires at least version {}, but the current version is {}. Please update Zepter or ignore this check with `--check-cfg-compatibility=off`.",
-				file_version, current_version
+				"Config file version is too new. The file requires at least version {}, but the current version is {}.",
+				required, current
 			))
 		}
 	}
@@ -189,7 +885,251 @@ mod tests {
 		let cfg = WorkflowFile::from_path("presets/polkadot.yaml").unwrap();
 		// Sanity checky only
 		assert_eq!(cfg.workflows.len(), 2);
-		assert_eq!(cfg.workflow("check").unwrap().0.len(), 2);
-		assert_eq!(cfg.workflow("default").unwrap().0.len(), 2);
+		assert_eq!(cfg.workflow("check").unwrap().steps.len(), 2);
+		assert_eq!(cfg.workflow("default").unwrap().steps.len(), 2);
+
+		let cfg = WorkflowFile::from_path("presets/default.yaml").unwrap();
+		assert_eq!(cfg.workflow("check").unwrap().steps.len(), 2);
+		assert_eq!(cfg.workflow("default").unwrap().steps.len(), 2);
+	}
+
+	#[test]
+	fn workflow_binary_version_works() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\n  workflows:\n    new: 1.2.0\n    old: 0.9.0\nworkflows:\n  new: []\n  old: []\n  default: []\n"
+			.parse()
+			.unwrap();
+		assert_eq!(cfg.required_binary("new"), (1, 2, 0).into());
+		assert_eq!(cfg.required_binary("old"), (1, 0, 0).into());
+		assert_eq!(cfg.required_binary("default"), (1, 0, 0).into());
+
+		let current = (1, 1, 0).into();
+		assert!(cfg.check_compatibility_with("default", &current).is_ok());
+		assert!(cfg.check_compatibility_with("old", &current).is_ok());
+		assert_eq!(
+			cfg.check_compatibility_with("new", &current),
+			Err(
+				"Workflow 'new' requires at least version 1.2.0, but the current version is 1.1.0."
+					.into()
+			)
+		);
+
+		let unknown = "version:\n  format: 1\n  binary: 1.0.0\n  workflows:\n    other: 1.2.0\nworkflows: {}\n";
+		assert_eq!(
+			unknown.parse::<WorkflowFile>().err(),
+			Some("Version requirement for unknown workflow 'other'".into())
+		);
+	}
+
+	#[test]
+	fn workflow_update_check_works() {
+		let cfg: WorkflowFile =
+			"version:\n  format: 1\n  binary: 1.0.0\nworkflows: {}\n".parse().unwrap();
+		assert_eq!(cfg.update_check(), UpdateCheck::Auto);
+
+		let cfg: WorkflowFile =
+			"version:\n  format: 1\n  binary: 1.0.0\n  update-check: never\nworkflows: {}\n"
+				.parse()
+				.unwrap();
+		assert_eq!(cfg.update_check(), UpdateCheck::Never);
+	}
+
+	#[test]
+	fn workflow_expand_works() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  default:\n    - ['format', 'features']\n    - foreach-package: ['pallet-*', 'frame']\n      run: ['lint', 'propagate-feature']\n    - foreach-package: ['pallet-b']\n      run: ['lint', 'why-enabled', '--package={package}']\n      hint: ask the owners of {package}\n"
+			.parse()
+			.unwrap();
+		let packages = ["frame", "pallet-a", "pallet-b", "other"].map(String::from);
+		let wf = cfg.workflow("default").unwrap();
+		assert!(wf.has_matrix());
+
+		let wf = wf.expand(&packages).unwrap();
+		assert!(!wf.has_matrix());
+		let steps = wf.steps.iter().map(|s| s.args.join(" ")).collect::<Vec<_>>();
+		assert_eq!(
+			steps,
+			vec![
+				"format features",
+				"lint propagate-feature -p frame",
+				"lint propagate-feature -p pallet-a",
+				"lint propagate-feature -p pallet-b",
+				"lint why-enabled --package=pallet-b",
+			]
+		);
+		assert_eq!(wf.steps[4].hint.as_deref(), Some("ask the owners of pallet-b"));
+		assert_eq!(wf.steps[1].hint, None);
+
+		let wf = cfg.workflow("default").unwrap();
+		assert_eq!(
+			wf.expand(&["frame".into()]).err(),
+			Some("Pattern 'pallet-*' does not match any workspace package".into())
+		);
+	}
+
+	#[test]
+	fn workflow_select_works() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  default:\n    - ['format', 'features']\n    - name: lint\n      run: ['lint', 'propagate-feature']\n    - ['lint', 'no-std']\n"
+			.parse()
+			.unwrap();
+		let select = |selectors: &[&str]| {
+			let selectors = selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+			cfg.workflow("default")
+				.unwrap()
+				.select(&selectors)
+				.map(|wf| wf.steps.iter().map(|s| s.args.join(" ")).collect::<Vec<_>>())
+		};
+
+		assert_eq!(select(&[]).unwrap().len(), 3);
+		assert_eq!(select(&["3"]).unwrap(), vec!["lint no-std"]);
+		assert_eq!(
+			select(&["lint", "1"]).unwrap(),
+			vec!["format features", "lint propagate-feature"]
+		);
+		assert_eq!(
+			select(&["4"]).err(),
+			Some("Step '4' not found. Expected a number from 1 to 3 or one of: lint".into())
+		);
+		assert_eq!(
+			select(&["0"]).err(),
+			Some("Step '0' not found. Expected a number from 1 to 3 or one of: lint".into())
+		);
+	}
+
+	#[test]
+	fn workflow_extends_works() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  check:\n    - name: lint\n      run: ['lint', 'propagate-feature']\n    - ['format', 'features']\n  default:\n    extends: check\n    append-args: ['--fix']\n  strict:\n    extends: default\n    steps:\n      - name: lint\n        run: ['lint', 'propagate-feature', '--strict']\n      - ['lint', 'no-std']\n"
+			.parse()
+			.unwrap();
+		let steps = |name: &str| {
+			cfg.workflow(name)
+				.unwrap()
+				.steps
+				.iter()
+				.map(|s| s.args.join(" "))
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(steps("check"), vec!["lint propagate-feature", "format features"]);
+		assert_eq!(steps("default"), vec!["lint propagate-feature --fix", "format features --fix"]);
+		assert_eq!(
+			steps("strict"),
+			vec!["lint propagate-feature --strict", "format features --fix", "lint no-std"]
+		);
+		assert_eq!(cfg.workflow("default").unwrap().steps[0].name.as_deref(), Some("lint"));
+
+		let unknown =
+			"version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  default:\n    extends: check\n";
+		assert_eq!(
+			unknown.parse::<WorkflowFile>().err(),
+			Some("Workflow 'default' extends unknown workflow 'check'".into())
+		);
+		let cycle = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  a:\n    extends: b\n  b:\n    extends: a\n";
+		assert_eq!(
+			cycle.parse::<WorkflowFile>().err(),
+			Some("Workflows extend each other in a cycle: a, b".into())
+		);
+	}
+
+	#[test]
+	fn workflow_fix_hooks_work() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  check:\n    steps:\n      - ['format', 'features']\n    before-fix: ['cargo update -w --offline']\n    after-fix: ['taplo fmt']\n  default:\n    extends: check\n    append-args: ['--fix']\n  plain:\n    extends: check\n    after-fix: []\n"
+			.parse()
+			.unwrap();
+		let hooks = |name: &str| {
+			let hooks = cfg.workflow(name).unwrap().hooks;
+			(hooks.before_fix, hooks.after_fix)
+		};
+
+		assert_eq!(cfg.workflow("check").unwrap().steps.len(), 1);
+		assert_eq!(
+			hooks("default"),
+			(vec!["cargo update -w --offline".into()], vec!["taplo fmt".into()])
+		);
+		assert_eq!(hooks("plain"), (vec!["cargo update -w --offline".into()], vec![]));
+
+		assert_eq!(fixed_count("issues=3 fixed=2 warnings=0"), Some(2));
+		assert_eq!(fixed_count("issues=3"), None);
+	}
+
+	#[test]
+	fn workflow_parallel_groups_work() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  check:\n    - run: ['a']\n      parallel: true\n    - run: ['b']\n      parallel: true\n    - ['c']\n    - run: ['d']\n      parallel: true\n"
+			.parse()
+			.unwrap();
+		let check = cfg.workflow("check").unwrap();
+
+		assert_eq!(check.steps.iter().filter(|s| s.parallel).count(), 3);
+		assert_eq!(check.groups(), vec![0..2, 2..3, 3..4]);
+	}
+
+	#[test]
+	fn workflow_yaml_anchors_work() {
+		let cfg: WorkflowFile = "version:\n  format: 1\n  binary: 1.0.0\nworkflows:\n  check:\n    - &lint\n      name: lint\n      run: ['lint', 'propagate-feature']\n      hint: run zepter\n  default:\n    - <<: *lint\n      run: ['lint', 'propagate-feature', '--fix']\n    - *lint\n"
+			.parse()
+			.unwrap();
+		let wf = cfg.workflow("default").unwrap();
+
+		assert_eq!(wf.steps.len(), 2);
+		assert_eq!(wf.steps[0].args.join(" "), "lint propagate-feature --fix");
+		assert_eq!(wf.steps[0].hint.as_deref(), Some("run zepter"));
+		assert_eq!(wf.steps[1].args.join(" "), "lint propagate-feature");
+	}
+
+	#[test]
+	fn expand_env_vars_works() {
+		let env = |name: &str| match name {
+			"MANIFEST" => Some("a/Cargo.toml".to_string()),
+			"EMPTY" => Some(String::new()),
+			_ => None,
+		};
+
+		assert_eq!(expand_env_vars("--offline", env).unwrap(), "--offline");
+		assert_eq!(
+			expand_env_vars("--manifest-path=${MANIFEST}", env).unwrap(),
+			"--manifest-path=a/Cargo.toml"
+		);
+		assert_eq!(expand_env_vars("${UNSET:-Cargo.toml}", env).unwrap(), "Cargo.toml");
+		assert_eq!(expand_env_vars("${EMPTY:-std}", env).unwrap(), "std");
+		assert_eq!(expand_env_vars("${EMPTY}", env).unwrap(), "");
+		assert_eq!(expand_env_vars("${MANIFEST}:${UNSET:-}", env).unwrap(), "a/Cargo.toml:");
+		assert_eq!(
+			expand_env_vars("--features=${UNSET}", env).err(),
+			Some("Environment variable 'UNSET' is not set in '--features=${UNSET}'".into())
+		);
+		assert_eq!(
+			expand_env_vars("${MANIFEST", env).err(),
+			Some("Missing closing '}' of variable in '${MANIFEST'".into())
+		);
+	}
+
+	#[test]
+	fn step_writes_manifests_works() {
+		let writes = |step: &str| {
+			StepCommand {
+				index: 0,
+				command: step.split_whitespace().map(Into::into).collect(),
+				args: Vec::new(),
+				stats_file: PathBuf::new(),
+				metrics_file: None,
+			}
+			.writes_manifests()
+		};
+
+		assert!(writes("lint propagate-feature --features std --fix"));
+		assert!(writes("lint no-std attr-check -f"));
+		assert!(writes("format features --fix"));
+		assert!(writes("transpose features strip-dev-only"));
+		assert!(writes("transpose dependency lift-to-workspace serde --fix"));
+		assert!(writes("run check"));
+
+		assert!(!writes("lint unknown --fix"));
+		assert!(!writes("--version"));
+		assert!(!writes("lint propagate-feature --features std"));
+		assert!(!writes("lint propagate-feature --features std --fix-hint=off"));
+		assert!(!writes("format features --check"));
+		assert!(!writes("transpose features strip-dev-only --dry-run"));
+		assert!(!writes("transpose dependency lift-to-workspace serde"));
+		assert!(!writes(
+			"lint never-implies --precondition std --stays-disabled runtime-benchmarks"
+		));
 	}
 }
@@ -10,13 +10,15 @@
 use core::fmt::{Display, Formatter};
 use std::{
 	borrow::{Cow, ToOwned},
+	cell::RefCell,
 	collections::{BTreeMap, BTreeSet},
+	rc::Rc,
 };
 
 /// Represents *Directed Acyclic Graph* through its edge relation.
 ///
 /// A "node" in that sense is anything on the left- or right-hand side of this relation.
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Dag<T: Ord> {
 	/// Dependant -> Dependency
 	/// eg: Polkadot -> Substrate or Me -> Rust
@@ -142,10 +144,55 @@ where
 		self.edges.entry(node).or_default();
 	}
 
+	/// Disconnect two nodes. Returns whether they were connected.
+	///
+	/// Both nodes stay in the Dag, even if they have no edges anymore.
+	pub fn remove_edge(&mut self, from: &T, to: &T) -> bool {
+		self.edges.get_mut(from).map_or(false, |v| v.remove(to))
+	}
+
+	/// Remove a node together with all of its incoming and outgoing edges.
+	///
+	/// Returns whether the node was part of the Dag.
+	pub fn remove_node(&mut self, node: &T) -> bool {
+		let mut found = self.edges.remove(node).is_some();
+		for v in self.edges.values_mut() {
+			found |= v.remove(node);
+		}
+		found
+	}
+
+	/// The number of outgoing edges of a node.
 	pub fn degree(&self, node: &T) -> usize {
 		self.edges.get(node).map_or(0, |v| v.len())
 	}
 
+	/// The number of incoming edges of a node.
+	///
+	/// This is `O(n)` in the number of LHS nodes. Use [`Self::in_degrees`] for bulk queries.
+	pub fn in_degree(&self, node: &T) -> usize {
+		self.edges.values().filter(|v| v.contains(node)).count()
+	}
+
+	/// The out-degree of all LHS nodes.
+	pub fn out_degrees(&self) -> BTreeMap<&T, usize> {
+		self.edges.iter().map(|(k, v)| (k, v.len())).collect()
+	}
+
+	/// The in-degree of all RHS nodes.
+	pub fn in_degrees(&self) -> BTreeMap<&T, usize> {
+		let mut degrees = BTreeMap::new();
+		for to in self.rhs_iter() {
+			*degrees.entry(to).or_default() += 1;
+		}
+		degrees
+	}
+
+	/// Statistics about the out-degree of all LHS nodes.
+	pub fn degree_stats(&self) -> Option<DegreeStats> {
+		DegreeStats::from_degrees(self.edges.values().map(|v| v.len()))
+	}
+
 	/// Whether `from` is directly adjacent to `to`.
 	///
 	/// *Directly* means with via an edge.
@@ -158,6 +205,20 @@ where
 		self.any_path(from, to).is_some()
 	}
 
+	/// All nodes that are reachable from any of the `from` nodes, including themselves.
+	pub fn reachable_from(&self, from: impl IntoIterator<Item = T>) -> BTreeSet<T> {
+		let mut visited = BTreeSet::new();
+		let mut stack = from.into_iter().collect::<Vec<_>>();
+
+		while let Some(node) = stack.pop() {
+			if let Some(next) = self.edges.get(&node) {
+				stack.extend(next.iter().filter(|n| !visited.contains(*n)).cloned());
+			}
+			visited.insert(node);
+		}
+		visited
+	}
+
 	/// Whether `from` appears on the lhs of the edge relation.
 	///
 	/// Aka: Whether `self` has any dependencies nodes.
@@ -193,6 +254,23 @@ where
 		Self { edges }
 	}
 
+	/// The `Dag` with only the edges that fulfill `pred`.
+	pub fn sub_edges(&self, pred: impl Fn(&T, &T) -> bool) -> Self {
+		let mut edges = BTreeMap::<T, BTreeSet<T>>::new();
+		for (k, v) in self.edges.iter() {
+			let v = v.iter().filter(|v| pred(k, v)).cloned().collect::<BTreeSet<_>>();
+			if !v.is_empty() {
+				edges.insert(k.clone(), v);
+			}
+		}
+		Self { edges }
+	}
+
+	/// Cache for repeated reachability queries on `self`.
+	pub fn reachability(&self) -> Reachability<'_, T> {
+		Reachability::new(self)
+	}
+
 	/// Get get a ref to the a LHS node.
 	pub fn lhs_node(&self, from: &T) -> Option<&T> {
 		self.edges.get_key_value(from).map(|(k, _)| k)
@@ -345,6 +423,158 @@ where
 	}
 }
 
+/// Memoized reachability of a [Dag] for many queries.
+///
+/// The descendants of a node are computed on its first query and cached as bitset. Later queries
+/// from the same node are a lookup, and the first query of another node re-uses the cached
+/// descendants of all nodes that it passes.
+pub struct Reachability<'a, T: Ord> {
+	ids: BTreeMap<&'a T, usize>,
+	nodes: Vec<&'a T>,
+	edges: Vec<Vec<usize>>,
+	/// The descendants of every node that was queried, including the node itself.
+	descendants: RefCell<Vec<Option<Rc<BitSet>>>>,
+}
+
+impl<'a, T: Ord> Reachability<'a, T> {
+	pub fn new(dag: &'a Dag<T>) -> Self {
+		let mut ids = BTreeMap::new();
+		for node in dag.edges.keys().chain(dag.edges.values().flatten()) {
+			let id = ids.len();
+			ids.entry(node).or_insert(id);
+		}
+		let mut nodes = ids.iter().map(|(node, id)| (*id, *node)).collect::<Vec<_>>();
+		nodes.sort_by_key(|(id, _)| *id);
+		let nodes = nodes.into_iter().map(|(_, node)| node).collect::<Vec<_>>();
+
+		let mut edges = vec![Vec::new(); nodes.len()];
+		for (from, tos) in dag.edges.iter() {
+			edges[ids[from]] = tos.iter().map(|to| ids[to]).collect();
+		}
+		let descendants = RefCell::new(vec![None; nodes.len()]);
+
+		Self { ids, nodes, edges, descendants }
+	}
+
+	/// Whether `to` is reachable from `from`. Same as [`Dag::reachable`].
+	pub fn reachable(&self, from: &T, to: &T) -> bool {
+		if from == to {
+			return true
+		}
+		let (Some(from), Some(to)) = (self.ids.get(from), self.ids.get(to)) else { return false };
+		self.descendants(*from).contains(*to)
+	}
+
+	/// Whether any node that fulfills `pred` is reachable from `from`, including itself.
+	pub fn any_reachable(&self, from: &T, pred: impl Fn(&T) -> bool) -> bool {
+		let Some(from_id) = self.ids.get(from) else { return pred(from) };
+		self.descendants(*from_id).iter().any(|id| pred(self.nodes[id]))
+	}
+
+	/// The number of nodes whose descendants are cached.
+	pub fn num_cached(&self) -> usize {
+		self.descendants.borrow().iter().filter(|d| d.is_some()).count()
+	}
+
+	fn descendants(&self, from: usize) -> Rc<BitSet> {
+		if let Some(known) = &self.descendants.borrow()[from] {
+			return known.clone()
+		}
+		let mut seen = BitSet::new(self.nodes.len());
+		let mut stack = vec![from];
+
+		while let Some(node) = stack.pop() {
+			if seen.contains(node) {
+				continue
+			}
+			if let Some(known) = &self.descendants.borrow()[node] {
+				seen.union(known);
+				continue
+			}
+			seen.insert(node);
+			stack.extend(self.edges[node].iter().filter(|n| !seen.contains(**n)));
+		}
+
+		let seen = Rc::new(seen);
+		self.descendants.borrow_mut()[from] = Some(seen.clone());
+		seen
+	}
+}
+
+/// Fixed size set of node ids.
+#[derive(Clone)]
+struct BitSet(Vec<u64>);
+
+impl BitSet {
+	fn new(len: usize) -> Self {
+		Self(vec![0; len.div_ceil(64)])
+	}
+
+	fn insert(&mut self, id: usize) {
+		self.0[id / 64] |= 1 << (id % 64);
+	}
+
+	fn contains(&self, id: usize) -> bool {
+		self.0[id / 64] & (1 << (id % 64)) != 0
+	}
+
+	fn union(&mut self, other: &Self) {
+		for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+			*a |= b;
+		}
+	}
+
+	fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+		self.0.iter().enumerate().flat_map(|(i, &word)| {
+			(0..64)
+				.filter(move |bit| word & (1u64 << bit) != 0)
+				.map(move |bit| i * 64 + bit)
+		})
+	}
+}
+
+/// Summary statistics of node degrees in a [Dag].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DegreeStats {
+	/// The number of nodes that were considered.
+	pub nodes: usize,
+	pub min: usize,
+	pub max: usize,
+	pub mean: f64,
+	pub median: usize,
+}
+
+impl DegreeStats {
+	/// Calculate the statistics of some degrees. Returns `None` if there are none.
+	pub fn from_degrees(degrees: impl IntoIterator<Item = usize>) -> Option<Self> {
+		let mut degrees = degrees.into_iter().collect::<Vec<_>>();
+		if degrees.is_empty() {
+			return None
+		}
+		degrees.sort_unstable();
+
+		let nodes = degrees.len();
+		let sum: usize = degrees.iter().sum();
+		Some(Self {
+			nodes,
+			min: degrees[0],
+			max: degrees[nodes - 1],
+			mean: sum as f64 / nodes as f64,
+			median: degrees[nodes / 2],
+		})
+	}
+}
+
+impl Display for DegreeStats {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"min {}, max {}, mean {:.2}, median {}",
+			self.min, self.max, self.mean, self.median
+		)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -371,4 +601,108 @@ mod tests {
 		let dag2 = dag.clone().into_transitive_hull();
 		assert_eq!(dag.num_edges(), dag2.num_edges());
 	}
+
+	#[test]
+	fn dag_degrees_work() {
+		let mut dag = Dag::<&str>::default();
+		for (from, to) in [("A", "B"), ("A", "C"), ("B", "C"), ("D", "C")] {
+			dag.add_edge(from, to);
+		}
+
+		assert_eq!(dag.degree(&"A"), 2);
+		assert_eq!(dag.in_degree(&"C"), 3);
+		assert_eq!(dag.in_degree(&"A"), 0);
+		assert_eq!(dag.out_degrees(), BTreeMap::from([(&"A", 2), (&"B", 1), (&"D", 1)]));
+		assert_eq!(dag.in_degrees(), BTreeMap::from([(&"B", 1), (&"C", 3)]));
+
+		let stats = dag.degree_stats().unwrap();
+		assert_eq!((stats.nodes, stats.min, stats.max, stats.median), (3, 1, 2, 1));
+		assert_eq!(stats.to_string(), "min 1, max 2, mean 1.33, median 1");
+		assert!(Dag::<&str>::default().degree_stats().is_none());
+	}
+
+	#[test]
+	fn dag_reachable_from_works() {
+		let mut dag = Dag::<&str>::default();
+		for (from, to) in [("A", "B"), ("B", "C"), ("C", "A"), ("D", "E")] {
+			dag.add_edge(from, to);
+		}
+
+		assert_eq!(dag.reachable_from(["B"]), BTreeSet::from(["A", "B", "C"]));
+		assert_eq!(dag.reachable_from(["E", "D"]), BTreeSet::from(["D", "E"]));
+		assert_eq!(dag.reachable_from(["X"]), BTreeSet::from(["X"]));
+		assert!(dag.reachable_from([]).is_empty());
+	}
+
+	#[test]
+	fn reachability_works() {
+		let mut dag = Dag::<&str>::default();
+		for (from, to) in [("A", "B"), ("B", "C"), ("C", "A"), ("C", "D"), ("E", "D")] {
+			dag.add_edge(from, to);
+		}
+		let reach = dag.reachability();
+
+		assert!(reach.reachable(&"A", &"D"));
+		assert!(reach.reachable(&"C", &"B"));
+		assert!(!reach.reachable(&"D", &"A"));
+		assert!(!reach.reachable(&"A", &"E"));
+		assert!(reach.reachable(&"X", &"X"));
+		assert!(!reach.reachable(&"X", &"A"));
+		assert!(reach.any_reachable(&"E", |n| n == &"D"));
+		assert!(!reach.any_reachable(&"D", |n| n == &"E"));
+		// Only `B` was never queried.
+		assert_eq!(reach.num_cached(), 4);
+	}
+
+	#[test]
+	fn reachability_agrees_with_any_path() {
+		use rand::{Rng, SeedableRng};
+
+		let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+		let mut dag = Dag::<usize>::default();
+		for _ in 0..120 {
+			dag.add_edge(rng.gen_range(0..70), rng.gen_range(0..70));
+		}
+		let reach = dag.reachability();
+
+		for from in 0..70 {
+			for to in 0..70 {
+				assert_eq!(reach.reachable(&from, &to), dag.reachable(&from, &to), "{from}->{to}");
+			}
+		}
+	}
+
+	#[test]
+	fn sub_edges_works() {
+		let mut dag = Dag::<&str>::default();
+		for (from, to) in [("A", "B"), ("A", "a"), ("B", "C"), ("b", "c")] {
+			dag.add_edge(from, to);
+		}
+		let sub = dag.sub_edges(|from, to| from.to_lowercase() != to.to_lowercase());
+
+		assert_eq!(sub.num_edges(), 3);
+		assert!(!sub.adjacent(&"A", &"a"));
+		assert!(sub.adjacent(&"b", &"c"));
+	}
+
+	#[test]
+	fn dag_remove_works() {
+		let mut dag = Dag::<&str>::default();
+		for (from, to) in [("A", "B"), ("A", "C"), ("B", "C"), ("C", "D")] {
+			dag.add_edge(from, to);
+		}
+
+		assert!(dag.remove_edge(&"A", &"B"));
+		assert!(!dag.remove_edge(&"A", &"B"));
+		assert!(!dag.remove_edge(&"X", &"B"));
+		assert!(dag.lhs_contains(&"B"));
+		assert_eq!(dag.reachable_from(["A"]), BTreeSet::from(["A", "C", "D"]));
+
+		// Only known as RHS node.
+		assert!(dag.remove_node(&"D"));
+		assert!(dag.remove_node(&"C"));
+		assert!(!dag.remove_node(&"C"));
+		assert_eq!(dag.num_edges(), 0);
+		assert_eq!(dag.reachable_from(["A"]), BTreeSet::from(["A"]));
+	}
 }
@@ -19,3 +19,13 @@ pub(crate) fn plural_or(n: usize, or: &str) -> String {
 		"s".to_string()
 	}
 }
+
+/// Quote and join items like "`a`, `b` and `c`".
+pub(crate) fn enumerate<S: AsRef<str>>(items: &[S]) -> String {
+	let quoted = items.iter().map(|i| format!("`{}`", i.as_ref())).collect::<Vec<_>>();
+	match quoted.split_last() {
+		None => String::new(),
+		Some((last, [])) => last.clone(),
+		Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+	}
+}
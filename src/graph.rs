@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! The feature graph of a workspace for use by other tools.
+//!
+//! The lints build a [`Dag`] of [`CrateAndFeature`] nodes where an edge means that the left
+//! feature enables the right one. [`FeatureGraph`] bundles it with the packages that the nodes
+//! refer to, so that it can be serialized and queried without the metadata:
+//!
+//! ```no_run
+//! use zepter::graph::{CrateAndFeature, FeatureGraph};
+//!
+//! let meta = cargo_metadata::MetadataCommand::new().exec().unwrap();
+//! let graph = FeatureGraph::from_metadata(&meta);
+//!
+//! for node in graph.nodes_of("serde", "std") {
+//!     let (pkg, feature) = graph.resolve(&node).unwrap();
+//!     let enabled_by = graph.dag.inverse_lookup(&node).count();
+//!     println!("{} v{} '{}': enabled by {}", pkg.name, pkg.version, feature, enabled_by);
+//! }
+//! std::fs::write("graph.json", graph.to_json()?).unwrap();
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! # Stability
+//!
+//! Everything that is exported from this module follows semantic versioning, unlike the
+//! internals of [`crate::cmd`] that it re-exports. The serialized [`FeatureGraph`] contains its
+//! [`FORMAT_VERSION`], which is bumped on every incompatible change of the layout.
+//! [`FeatureGraph::from_json`] rejects other versions instead of misreading them.
+//!
+//! The nodes follow these rules, which are also covered by the version:
+//! - The crate is identified by its package ID. Dependencies that cargo did not resolve are only
+//!   identified by their name, since they are a dead end.
+//! - A `?` behind the ID marks a weak reference like `dep?/feature`, see
+//!   [`CrateAndFeature::is_weak`].
+//! - Features that start with `#` are internal helper nodes, see [`CrateAndFeature::is_internal`].
+
+pub use crate::{
+	cmd::{
+		lint::{build_feature_dag, try_build_feature_dag, DagBuildError},
+		PackageIndex,
+	},
+	dag::{Dag, Path, Reachability},
+	CrateId,
+};
+use cargo_metadata::{Metadata, Package};
+use core::{
+	fmt,
+	fmt::{Display, Formatter},
+	str::FromStr,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The version of the serialized [`FeatureGraph`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A feature of a crate. The node type of the feature graph.
+///
+/// Serialized as `ID/feature`. Feature names cannot contain a `/`, so the last one separates
+/// both parts even if the ID contains some.
+#[derive(
+	Clone, Eq, Ord, PartialEq, PartialOrd, Debug, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(into = "String", try_from = "String")]
+pub struct CrateAndFeature(pub String, pub String);
+
+impl CrateAndFeature {
+	/// The ID of the crate without the `?` of a weak reference.
+	pub fn crate_id(&self) -> &str {
+		self.0.trim_end_matches('?')
+	}
+
+	pub fn feature(&self) -> &str {
+		&self.1
+	}
+
+	/// Whether the node is the target of a weak reference like `dep?/feature`.
+	pub fn is_weak(&self) -> bool {
+		self.0.ends_with('?')
+	}
+
+	/// Whether this is a helper node like `#entrypoint` instead of a real feature.
+	pub fn is_internal(&self) -> bool {
+		self.1.starts_with('#')
+	}
+
+	/// The package and feature name of this node. `None` if the crate was not resolved.
+	pub fn resolve<'a>(&'a self, index: &PackageIndex<'a>) -> Option<(&'a Package, &'a str)> {
+		Some((index.get(self.crate_id())?, self.feature()))
+	}
+}
+
+impl Display for CrateAndFeature {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(f, "{}/{}", self.0, self.1)
+	}
+}
+
+impl FromStr for CrateAndFeature {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.rsplit_once('/') {
+			Some((krate, feature)) if !krate.is_empty() && !feature.is_empty() =>
+				Ok(Self(krate.into(), feature.into())),
+			_ => Err(format!("Expected a node like 'CRATE/FEATURE' but got '{}'", s)),
+		}
+	}
+}
+
+impl From<CrateAndFeature> for String {
+	fn from(node: CrateAndFeature) -> Self {
+		node.to_string()
+	}
+}
+
+impl TryFrom<String> for CrateAndFeature {
+	type Error = String;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		s.parse()
+	}
+}
+
+/// The feature graph together with the packages of its nodes.
+///
+/// Owns all of its data, unlike [`PackageIndex`] which borrows the metadata.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FeatureGraph {
+	/// Always [`FORMAT_VERSION`] when created by this version of the crate.
+	pub version: u32,
+	/// Edges from a feature to the features that it enables.
+	pub dag: Dag<CrateAndFeature>,
+	/// All packages of the metadata by their ID.
+	pub packages: BTreeMap<CrateId, Package>,
+	/// IDs of the workspace members.
+	pub workspace_members: BTreeSet<CrateId>,
+}
+
+impl FeatureGraph {
+	/// Build the graph of all packages in the metadata.
+	///
+	/// Malformed feature entries are skipped with a warning, like in the lints.
+	pub fn from_metadata(meta: &Metadata) -> Self {
+		let dag = build_feature_dag(&PackageIndex::new(meta), &meta.packages);
+		Self::from_dag(meta, dag)
+	}
+
+	/// Bundle an already built `dag` with the packages of `meta`.
+	pub fn from_dag(meta: &Metadata, dag: Dag<CrateAndFeature>) -> Self {
+		Self {
+			version: FORMAT_VERSION,
+			dag,
+			packages: meta.packages.iter().map(|p| (p.id.repr.clone(), p.clone())).collect(),
+			workspace_members: meta.workspace_members.iter().map(|id| id.repr.clone()).collect(),
+		}
+	}
+
+	/// The package of a crate ID. Ignores the `?` of weak references.
+	pub fn package(&self, id: &str) -> Option<&Package> {
+		self.packages.get(id.trim_end_matches('?'))
+	}
+
+	/// The package and feature name of a node. `None` if the crate was not resolved.
+	pub fn resolve<'a>(&'a self, node: &'a CrateAndFeature) -> Option<(&'a Package, &'a str)> {
+		Some((self.package(&node.0)?, node.feature()))
+	}
+
+	/// The nodes of `feature` in all versions of the crate `name`.
+	///
+	/// Returns nodes of features that the crate does not have as well, since the graph can still
+	/// contain edges to them.
+	pub fn nodes_of(&self, name: &str, feature: &str) -> Vec<CrateAndFeature> {
+		self.packages
+			.values()
+			.filter(|p| p.name == name)
+			.map(|p| CrateAndFeature(p.id.repr.clone(), feature.into()))
+			.collect()
+	}
+
+	pub fn is_workspace_member(&self, id: &str) -> bool {
+		self.workspace_members.contains(id.trim_end_matches('?'))
+	}
+
+	pub fn to_json(&self) -> Result<String, String> {
+		serde_json::to_string(self).map_err(|e| format!("Failed to serialize graph: {}", e))
+	}
+
+	/// Parse a graph that was written by [`Self::to_json`].
+	///
+	/// Errors if it was written in another [`FORMAT_VERSION`].
+	pub fn from_json(json: &str) -> Result<Self, String> {
+		#[derive(serde::Deserialize)]
+		struct Versioned {
+			version: u32,
+		}
+
+		let Versioned { version } = serde_json::from_str(json)
+			.map_err(|e| format!("Failed to parse graph version: {}", e))?;
+		if version != FORMAT_VERSION {
+			return Err(format!(
+				"Unsupported graph format version {}, expected {}",
+				version, FORMAT_VERSION
+			))
+		}
+		serde_json::from_str(json).map_err(|e| format!("Failed to parse graph: {}", e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::mock::Context;
+
+	/// `A` with the feature `F0` that enables `F0` of its dependency `B`.
+	fn meta() -> (Context, Metadata) {
+		let ctx = Context::new();
+		let meta = ctx
+			.workspace_metadata(
+				"
+- name: A
+  deps:
+  - B
+  features:
+    F0:
+    - [B, F0]
+- name: B
+  features:
+    F0: null
+",
+			)
+			.unwrap();
+		(ctx, meta)
+	}
+
+	#[test]
+	fn crate_and_feature_roundtrips() {
+		let id = "serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)";
+		let node = CrateAndFeature(format!("{}?", id), "std".into());
+
+		assert_eq!(node.to_string().parse::<CrateAndFeature>(), Ok(node.clone()));
+		assert_eq!((node.crate_id(), node.is_weak(), node.is_internal()), (id, true, false));
+		assert!("std".parse::<CrateAndFeature>().is_err());
+		assert!("serde/".parse::<CrateAndFeature>().is_err());
+	}
+
+	#[test]
+	fn graph_json_roundtrips() {
+		let (_ctx, meta) = meta();
+		let graph = FeatureGraph::from_metadata(&meta);
+		let json = graph.to_json().unwrap();
+
+		assert_eq!(FeatureGraph::from_json(&json).unwrap(), graph);
+		let old = json.replacen(&format!("\"version\":{}", FORMAT_VERSION), "\"version\":0", 1);
+		assert!(FeatureGraph::from_json(&old).unwrap_err().contains("version 0"));
+	}
+
+	#[test]
+	fn graph_resolves_nodes() {
+		let (_ctx, meta) = meta();
+		let graph = FeatureGraph::from_metadata(&meta);
+		let index = PackageIndex::new(&meta);
+
+		let [node] = <[_; 1]>::try_from(graph.nodes_of("A", "F0")).unwrap();
+		let (pkg, feature) = graph.resolve(&node).unwrap();
+		assert_eq!((pkg.name.as_str(), feature), ("A", "F0"));
+		assert_eq!(node.resolve(&index).map(|(p, _)| &p.id), Some(&pkg.id));
+		assert!(graph.is_workspace_member(&node.0));
+
+		let enabled = graph.dag.edges.get(&node).unwrap();
+		let enabled = enabled.iter().filter_map(|n| graph.resolve(n)).collect::<Vec<_>>();
+		assert!(enabled.iter().any(|(p, f)| (p.name.as_str(), *f) == ("B", "F0")));
+	}
+}
@@ -0,0 +1,368 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Registry of all issues that the lints can report.
+//!
+//! Every issue has a stable code like `Z0001`. The lints print it next to their findings with
+//! `--show-codes` and `zepter explain` describes it in detail. The first two digits group the
+//! issues by topic: `00` feature propagation, `01` no-std, `02` dependencies, `03` feature names
+//! and `04` feature rules.
+
+use crate::config::{rules::RuleKind, severity::IssueKind};
+
+/// An issue that a lint can report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Issue {
+	/// Stable code like `Z0001`.
+	pub code: &'static str,
+	/// The lint that reports the issue, like `propagate-feature`.
+	pub lint: &'static str,
+	/// One line description.
+	pub title: &'static str,
+	/// Why this is an issue and what the lint checks.
+	pub explanation: &'static str,
+	/// An example of the issue and how it is fixed.
+	pub example: &'static str,
+}
+
+pub const PROPAGATE_MISSING: Issue = Issue {
+	code: "Z0001",
+	lint: "propagate-feature",
+	title: "A feature is not propagated to a dependency",
+	explanation:
+		"A crate has a feature that one of its dependencies also has, but does not enable it on the
+dependency. Enabling the feature on the crate therefore does not enable it on the dependency,
+which is mostly a mistake for features like `std` or `runtime-benchmarks`.",
+	example: "[features]
+std = [
+	\"serde/std\", # Add this line to propagate `std` to `serde`.
+]",
+};
+
+pub const LEFT_SIDE_MISSING: Issue = Issue {
+	code: "Z0002",
+	lint: "propagate-feature",
+	title: "A crate does not have a feature of its dependencies",
+	explanation: "Some dependencies of a crate have a feature that the crate itself does not have. The feature
+can therefore not be propagated to them, which is normally an oversight.",
+	example: "[features]
+# Add the feature and enable it on the dependencies.
+std = [ \"serde/std\" ]",
+};
+
+pub const NOSTD_CFG_ATTR: Issue = Issue {
+	code: "Z0101",
+	lint: "no-std",
+	title: "A crate with a `std` feature is not no-std without it",
+	explanation: "A crate that declares a `std` feature should only link the standard library when the feature is
+enabled. This is done by making the crate `no_std` whenever the feature is disabled.",
+	example: "// Add this line to the top of the `src/lib.rs`:
+#![cfg_attr(not(feature = \"std\"), no_std)]",
+};
+
+pub const NOSTD_DEFAULT_FEATURES: Issue = Issue {
+	code: "Z0102",
+	lint: "no-std",
+	title: "A no-std crate does not disable the default features of a dependency",
+	explanation: "Default features of dependencies commonly include `std`. A no-std crate that does not disable
+them pulls in the standard library through the dependency.",
+	example: "[dependencies]
+serde = { version = \"1.0\", default-features = false }",
+};
+
+pub const NOSTD_CRATE_CFG: Issue = Issue {
+	code: "Z0103",
+	lint: "no-std",
+	title: "A no-std crate has a crate level `#![cfg]` that may pull in the standard library",
+	explanation:
+		"The `lib.rs` of a no-std crate contains a crate level `#![cfg(..)]` attribute. This is a
+heuristic: the attribute often compiles the crate only for specific features or targets, in which
+the standard library may be linked regardless of the no-std attribute. It is reported as warning
+unless its severity is configured otherwise.",
+	example: "#![cfg_attr(not(feature = \"std\"), no_std)]
+// Gate single items instead of the whole crate with `#![cfg(feature = \"std\")]`:
+#[cfg(feature = \"std\")]
+pub mod std_only;",
+};
+
+pub const PATH_DEPS_OUTSIDE_WORKSPACE: Issue = Issue {
+	code: "Z0201",
+	lint: "no-path-deps-outside-workspace",
+	title: "A path dependency points outside of the workspace",
+	explanation: "A dependency with a `path` that leaves the workspace only builds on machines that have the same
+directory layout. It is normally a leftover of local debugging.",
+	example: "[dependencies]
+# Use the published version instead of `path = \"../../other-repo/serde\"`.
+serde = \"1.0\"",
+};
+
+pub const DUPLICATE_DEPS: Issue = Issue {
+	code: "Z0202",
+	lint: "duplicate-deps",
+	title: "A dependency is listed as normal and as dev-dependency",
+	explanation: "Normal dependencies are also available to tests, so the dev-dependency entry is redundant unless
+it enables additional features. Both entries must agree on the version and source.",
+	example: "[dependencies]
+serde = { version = \"1.0\", features = [ \"derive\" ] }
+
+[dev-dependencies]
+# Remove the redundant `serde` entry or only keep it for extra features.",
+};
+
+pub const BUILD_DEP_FEATURES: Issue = Issue {
+	code: "Z0203",
+	lint: "build-dep-features",
+	title: "A feature enables a feature of a build-dependency",
+	explanation: "Features of build-dependencies only affect the build script. Entries like `build-dep/std` in the
+`std` feature are therefore misleading and are not propagated to the normal dependencies.",
+	example: "[features]
+std = [
+	\"serde/std\",
+	# Remove `\"build-helper/std\"`.
+]",
+};
+
+pub const GIT_DEPS: Issue = Issue {
+	code: "Z0204",
+	lint: "git-deps",
+	title: "A git dependency violates the policy of the workspace",
+	explanation:
+		"Git dependencies that follow a branch change whenever the branch moves, which makes builds
+irreproducible. The `git-deps` config section either forbids them, requires a pinned `rev` or
+requires them to be declared once in `[workspace.dependencies]`.",
+	example: "[dependencies]
+# Pin the commit instead of following `branch = \"main\"`.
+serde = { git = \"https://github.com/serde-rs/serde\", rev = \"3f4c7e5\" }",
+};
+
+pub const RUST_VERSION: Issue = Issue {
+	code: "Z0205",
+	lint: "rust-version",
+	title: "A crate has a missing, inconsistent or too low `rust-version`",
+	explanation:
+		"The `rust-version` of a crate is the oldest compiler that it supports. It should be set by
+every workspace member, ideally inherited from `[workspace.package]`, and must not be lower than
+the `rust-version` of any of its dependencies.",
+	example: "[package]
+# Inherit the `rust-version` of `[workspace.package]`.
+rust-version.workspace = true",
+};
+
+pub const DEFAULT_FEATURES_CONFLICT: Issue = Issue {
+	code: "Z0206",
+	lint: "default-features-conflict",
+	title: "A dependency has `default-features = false` but enables the `default` feature",
+	explanation:
+		"Enabling the `default` feature of a dependency enables its default features, even with
+`default-features = false`. This usually happens by manual edits and re-enables the default
+features unnoticed. Either entry should be removed.",
+	example: "[dependencies]
+# Remove `\"default\"` or `default-features = false`.
+serde = { version = \"1.0\", default-features = false, features = [ \"derive\" ] }",
+};
+
+pub const DEFAULT_FEATURES_CONSISTENCY: Issue = Issue {
+	code: "Z0207",
+	lint: "propagate-feature",
+	title: "Workspace members disagree on the default features of a dependency",
+	explanation:
+		"Cargo builds a dependency only once per workspace and enables its default features if any
+member depends on it with them. A member with `default-features = false` therefore still gets them,
+which is not what its author expects. Checked with `--check-default-features-consistency`.",
+	example: "[dependencies]
+# Use the same `default-features` in every member.
+serde = { version = \"1.0\", default-features = false }",
+};
+
+pub const FEATURE_TYPO: Issue = Issue {
+	code: "Z0301",
+	lint: "feature-typos",
+	title: "A feature references a feature that does not exist",
+	explanation:
+		"Cargo silently ignores some references to unknown features of optional dependencies. A
+misspelled reference therefore does not enable anything.",
+	example: "[features]
+# Fix the spelling of `serde/stdd`.
+std = [ \"serde/std\" ]",
+};
+
+pub const FEATURE_SPELLING: Issue = Issue {
+	code: "Z0302",
+	lint: "feature-typos",
+	title: "A feature name only differs in case or separators from a common one",
+	explanation:
+		"Features like `runtime_benchmarks` and `runtime-benchmarks` are easy to confuse. Using the
+spelling of the rest of the workspace avoids enabling the wrong one.",
+	example: "[features]
+# Rename `runtime_benchmarks`.
+runtime-benchmarks = []",
+};
+
+pub const FEATURE_DOCS: Issue = Issue {
+	code: "Z0303",
+	lint: "feature-docs",
+	title: "A feature is not documented",
+	explanation: "Features without documentation are hard to use correctly. A feature is documented by a comment
+directly above it or an entry in `[package.metadata.features]`.",
+	example: "[features]
+# Use the standard library.
+std = []",
+};
+
+pub const DEFAULT_FEATURE: Issue = Issue {
+	code: "Z0104",
+	lint: "default-feature",
+	title: "A crate does not declare an explicit `default` feature",
+	explanation:
+		"Every crate should declare its `default` feature, even if it is empty. This makes it obvious
+which features a dependency gets without `default-features = false`. With `--require-std` the
+`default` feature must also enable `std` in crates that have one.",
+	example: "[features]
+default = [ \"std\" ]
+std = []",
+};
+
+pub const WEAK_FEATURES: Issue = Issue {
+	code: "Z0304",
+	lint: "weak-features",
+	title: "A weak feature references a dependency that is always enabled",
+	explanation:
+		"A weak entry like `dep?/std` only enables the feature if something else enables the optional
+dependency. The `?` is misleading if the same feature or the `default` feature of the crate
+enables the dependency anyway.",
+	example: "[dependencies]
+serde = { version = \"1.0\", default-features = false, optional = true }
+
+[features]
+# Use `serde/std` since `std` enables `serde` anyway.
+std = [ \"dep:serde\", \"serde/std\" ]",
+};
+
+pub const OPTIONAL_DEPS: Issue = Issue {
+	code: "Z0305",
+	lint: "optional-deps",
+	title: "An optional dependency has no feature of the same name that enables it",
+	explanation:
+		"Users enable an optional dependency `foo` with the feature `foo`. Cargo only creates this
+feature implicitly if no `dep:foo` entry exists. The explicit style requires a passthrough feature
+for every optional dependency, the implicit style only if the implicit feature is gone.",
+	example: "[dependencies]
+serde = { version = \"1.0\", optional = true }
+
+[features]
+serde = [ \"dep:serde\" ]",
+};
+
+pub const NEVER_ENABLES: Issue = Issue {
+	code: "Z0401",
+	lint: "never-enables",
+	title: "A feature directly enables a feature that it must not",
+	explanation: "Some features must stay disabled when another one is enabled, like `std` for the
+`runtime-benchmarks` feature. This is checked for the crate itself and its dependencies.",
+	example: "[features]
+runtime-benchmarks = [
+	\"frame/runtime-benchmarks\",
+	# Remove `\"frame/std\"`.
+]",
+};
+
+pub const NEVER_IMPLIES: Issue = Issue {
+	code: "Z0402",
+	lint: "never-implies",
+	title: "A feature transitively implies a feature that it must not",
+	explanation: "Like Z0401, but also follows the feature graph through all dependencies. The printed path shows
+where the feature gets enabled.",
+	example: "# Remove the first edge of the printed path that should not be there.",
+};
+
+pub const ONLY_ENABLES: Issue = Issue {
+	code: "Z0403",
+	lint: "only-enables",
+	title: "A feature of a dependency is enabled by an unexpected feature",
+	explanation:
+		"A feature of a dependency should only be enabled by one specific feature of the crate.",
+	example: "[features]
+# Only `std` may enable `serde/std`.
+std = [ \"serde/std\" ]",
+};
+
+/// All issues ordered by code.
+pub const ISSUES: [&Issue; 21] = [
+	&PROPAGATE_MISSING,
+	&LEFT_SIDE_MISSING,
+	&NOSTD_CFG_ATTR,
+	&NOSTD_DEFAULT_FEATURES,
+	&NOSTD_CRATE_CFG,
+	&DEFAULT_FEATURE,
+	&PATH_DEPS_OUTSIDE_WORKSPACE,
+	&DUPLICATE_DEPS,
+	&BUILD_DEP_FEATURES,
+	&GIT_DEPS,
+	&RUST_VERSION,
+	&DEFAULT_FEATURES_CONFLICT,
+	&DEFAULT_FEATURES_CONSISTENCY,
+	&FEATURE_TYPO,
+	&FEATURE_SPELLING,
+	&FEATURE_DOCS,
+	&WEAK_FEATURES,
+	&OPTIONAL_DEPS,
+	&NEVER_ENABLES,
+	&NEVER_IMPLIES,
+	&ONLY_ENABLES,
+];
+
+/// Find an issue by its code. Ignores the case.
+pub fn find(code: &str) -> Option<&'static Issue> {
+	ISSUES.into_iter().find(|issue| issue.code.eq_ignore_ascii_case(code))
+}
+
+impl IssueKind {
+	/// The issue of this kind.
+	pub fn issue(self) -> &'static Issue {
+		match self {
+			Self::PropagateMissing => &PROPAGATE_MISSING,
+			Self::LeftSideMissing => &LEFT_SIDE_MISSING,
+			Self::NostdDefaultFeatures => &NOSTD_DEFAULT_FEATURES,
+			Self::PathDepsOutsideWorkspace => &PATH_DEPS_OUTSIDE_WORKSPACE,
+			Self::DuplicateDeps => &DUPLICATE_DEPS,
+			Self::BuildDepFeatures => &BUILD_DEP_FEATURES,
+			Self::FeatureDocs => &FEATURE_DOCS,
+			Self::NostdCfgAttr => &NOSTD_CFG_ATTR,
+			Self::GitDeps => &GIT_DEPS,
+			Self::RustVersion => &RUST_VERSION,
+			Self::NostdCrateCfg => &NOSTD_CRATE_CFG,
+			Self::WeakFeatures => &WEAK_FEATURES,
+			Self::DefaultFeature => &DEFAULT_FEATURE,
+			Self::OptionalDeps => &OPTIONAL_DEPS,
+			Self::DefaultFeaturesConflict => &DEFAULT_FEATURES_CONFLICT,
+			Self::DefaultFeaturesConsistency => &DEFAULT_FEATURES_CONSISTENCY,
+		}
+	}
+}
+
+impl RuleKind {
+	/// The issue of a violated rule.
+	pub fn issue(self) -> &'static Issue {
+		match self {
+			Self::NeverEnables => &NEVER_ENABLES,
+			Self::NeverImplies => &NEVER_IMPLIES,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn codes_are_unique_and_sorted() {
+		let codes = ISSUES.iter().map(|i| i.code).collect::<Vec<_>>();
+		let mut sorted = codes.clone();
+		sorted.sort();
+		sorted.dedup();
+
+		assert_eq!(codes, sorted);
+		assert_eq!(find("z0102"), Some(&NOSTD_DEFAULT_FEATURES));
+		assert_eq!(find("Z9999"), None);
+	}
+}
@@ -8,6 +8,9 @@ pub mod cmd;
 pub mod config;
 pub mod dag;
 pub mod grammar;
+pub mod graph;
+pub mod issues;
+pub mod manifest;
 pub mod mock;
 mod tests;
 
@@ -30,6 +33,37 @@ pub mod log {
 	pub use crate::{debug, error, info, trace, warn};
 }
 
+/// Internal use only.
+///
+/// Like the `std` macros, but captured into the response while `zepter serve` runs a request.
+pub mod out {
+	pub use crate::{eprintln, print, println};
+}
+
+#[macro_export]
+macro_rules! print {
+	($($arg:tt)*) => {
+		$crate::cmd::serve::write_stdout(format_args!($($arg)*))
+	};
+}
+
+#[macro_export]
+macro_rules! println {
+	() => {
+		$crate::print!("\n")
+	};
+	($($arg:tt)*) => {
+		$crate::print!("{}\n", format_args!($($arg)*))
+	};
+}
+
+#[macro_export]
+macro_rules! eprintln {
+	($($arg:tt)*) => {
+		$crate::cmd::serve::write_stderr(format_args!("{}\n", format_args!($($arg)*)))
+	};
+}
+
 #[macro_export]
 macro_rules! info {
 	($($arg:tt)*) => {
@@ -90,14 +124,3 @@ impl<R, E: std::fmt::Display> ErrToStr<R> for Result<R, E> {
 		self.map_err(|e| format!("{}", e))
 	}
 }
-
-use cargo_metadata::DependencyKind;
-
-pub(crate) fn kind_to_str(kind: &DependencyKind) -> &'static str {
-	match kind {
-		DependencyKind::Development => "dev-dependencies",
-		DependencyKind::Build => "build-dependencies",
-		DependencyKind::Normal => "dependencies",
-		_ => unreachable!(),
-	}
-}
@@ -38,7 +38,14 @@ fn setup_logging() {
 			let begin = level_style.render();
 			let reset = level_style.render_reset();
 
-			writeln!(buf, "[{begin}{}{reset}] {}", record.level(), record.args())
+			let message = record.args().to_string();
+			zepter::cmd::log_file::write(
+				&buf.timestamp_millis().to_string(),
+				record.level().as_str(),
+				record.target(),
+				&message,
+			);
+			writeln!(buf, "[{begin}{}{reset}] {}", record.level(), message)
 		})
 		.init();
 }
@@ -0,0 +1,358 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Typed edits of the dependency tables of a `Cargo.toml`.
+//!
+//! These are the same edits that the [`AutoFixer`] does and keep the formatting and comments of
+//! the manifest. They work on the document of an [`AutoFixer`], which takes care of loading and
+//! saving it:
+//!
+//! ```no_run
+//! use cargo_metadata::DependencyKind;
+//! use zepter::{autofix::AutoFixer, manifest};
+//!
+//! let mut fixer = AutoFixer::from_manifest("Cargo.toml")?;
+//! manifest::set_default_features(fixer.document_mut(), &DependencyKind::Normal, "log", false)?;
+//! fixer.save()?;
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! Only the top-level dependency tables are edited, not the target specific ones.
+//!
+//! [`AutoFixer`]: crate::autofix::AutoFixer
+
+use cargo_metadata::DependencyKind;
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Key, Table, Value};
+
+/// The keys of all kinds of dependency tables.
+pub const DEPENDENCY_KINDS: [&str; 3] = ["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// The key of the dependency table of `kind`, like `dev-dependencies`.
+///
+/// Panics on [`DependencyKind::Unknown`].
+pub fn kind_to_str(kind: &DependencyKind) -> &'static str {
+	match kind {
+		DependencyKind::Development => "dev-dependencies",
+		DependencyKind::Build => "build-dependencies",
+		DependencyKind::Normal => "dependencies",
+		_ => unreachable!(),
+	}
+}
+
+/// A dependency entry as written in the manifest.
+///
+/// Unset fields are omitted from the manifest. Keys that are not covered, like `registry`, are
+/// ignored when reading and kept when editing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyEntry {
+	/// The version requirement, if any.
+	pub version: Option<String>,
+	/// The local path, if any.
+	pub path: Option<String>,
+	/// The URL of a git repository, if any.
+	pub git: Option<String>,
+	/// Whether it is inherited with `workspace = true`.
+	pub workspace: bool,
+	/// The name of the crate if the dependency is renamed.
+	pub package: Option<String>,
+	/// The `default-features` if set explicitly.
+	pub default_features: Option<bool>,
+	/// The enabled features.
+	pub features: Vec<String>,
+	/// Whether the dependency is optional.
+	pub optional: bool,
+}
+
+impl DependencyEntry {
+	/// Parse a plain version or a table. `None` if the entry has an unexpected type.
+	pub fn from_item(item: &Item) -> Option<Self> {
+		if let Some(version) = item.as_str() {
+			return Some(Self { version: Some(version.into()), ..Default::default() })
+		}
+		let table = item.as_table_like()?;
+		let string = |key: &str| table.get(key).and_then(|v| v.as_str()).map(String::from);
+		let boolean = |key: &str| table.get(key).and_then(|v| v.as_bool());
+
+		Some(Self {
+			version: string("version"),
+			path: string("path"),
+			git: string("git"),
+			workspace: boolean("workspace").unwrap_or_default(),
+			package: string("package"),
+			default_features: boolean("default-features").or_else(|| boolean("default_features")),
+			features: table
+				.get("features")
+				.and_then(|f| f.as_array())
+				.map(|f| f.iter().filter_map(|f| f.as_str().map(Into::into)).collect())
+				.unwrap_or_default(),
+			optional: boolean("optional").unwrap_or_default(),
+		})
+	}
+
+	/// A plain version if nothing else is set, otherwise an inline table.
+	pub fn to_item(&self) -> Item {
+		let simple = Self { version: self.version.clone(), ..Default::default() };
+		if let Some(version) = self.version.as_ref().filter(|_| *self == simple) {
+			return toml_edit::value(version.as_str())
+		}
+
+		let mut table = InlineTable::new();
+		if self.workspace {
+			table.insert("workspace", true.into());
+		}
+		for (key, v) in [
+			("version", &self.version),
+			("path", &self.path),
+			("git", &self.git),
+			("package", &self.package),
+		] {
+			if let Some(v) = v {
+				table.insert(key, v.as_str().into());
+			}
+		}
+		if let Some(default_features) = self.default_features {
+			table.insert("default-features", default_features.into());
+		}
+		if !self.features.is_empty() {
+			let features = self.features.iter().map(String::as_str).collect::<Array>();
+			table.insert("features", Value::Array(features));
+		}
+		if self.optional {
+			table.insert("optional", true.into());
+		}
+		Item::Value(Value::InlineTable(table))
+	}
+}
+
+/// The dependency `name` of the table `kind`, if any.
+///
+/// `name` is the key in the manifest, which differs from the crate name for renamed dependencies.
+pub fn get_dependency(
+	doc: &DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+) -> Option<DependencyEntry> {
+	DependencyEntry::from_item(doc.get(kind_to_str(kind))?.get(name)?)
+}
+
+/// Add the dependency `name` to the table `kind`.
+///
+/// The table is created if needed and the entry is inserted at its alphabetical position if the
+/// table is sorted. Errors if the dependency already exists.
+pub fn add_dependency(
+	doc: &mut DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+	entry: &DependencyEntry,
+) -> Result<(), String> {
+	let kind = kind_to_str(kind);
+	let deps = doc
+		.entry(kind)
+		.or_insert_with(toml_edit::table)
+		.as_table_mut()
+		.ok_or_else(|| format!("'{}' is not a table", kind))?;
+	if deps.contains_key(name) {
+		return Err(format!("Dependency '{}' already exists in '{}'", name, kind))
+	}
+
+	insert_sorted(deps, name, entry.to_item());
+	Ok(())
+}
+
+/// Remove the dependency `name` from the table `kind` and the table itself if it becomes empty.
+///
+/// Returns whether the dependency existed.
+pub fn remove_dependency(doc: &mut DocumentMut, kind: &DependencyKind, name: &str) -> bool {
+	let kind = kind_to_str(kind);
+	let Some(deps) = doc.get_mut(kind).and_then(|d| d.as_table_like_mut()) else { return false };
+	if deps.remove(name).is_none() {
+		return false
+	}
+	if deps.is_empty() {
+		doc.remove(kind);
+	}
+	true
+}
+
+/// Replace the `features` of a dependency. An empty list removes them.
+pub fn set_features(
+	doc: &mut DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+	features: &[String],
+) -> Result<(), String> {
+	let item = dependency_mut(doc, kind, name)?;
+	if features.is_empty() {
+		remove_table_value(item, "features");
+	} else {
+		expand_version(item);
+		let features = features.iter().map(String::as_str).collect::<Array>();
+		set_table_value(item, "features", Value::Array(features));
+	}
+	Ok(())
+}
+
+/// Set `default-features` of a dependency. A plain version is turned into an inline table.
+pub fn set_default_features(
+	doc: &mut DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+	enabled: bool,
+) -> Result<(), String> {
+	let item = dependency_mut(doc, kind, name)?;
+	expand_version(item);
+	if item.as_table_like().is_none() {
+		return Err(format!("Dependency '{}' is neither a version nor a table", name))
+	}
+	set_table_value(item, "default-features", enabled.into());
+	Ok(())
+}
+
+/// Remove `default-features` of a dependency, so that cargo enables its default features.
+pub fn remove_default_features(
+	doc: &mut DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+) -> Result<(), String> {
+	let item = dependency_mut(doc, kind, name)?;
+	remove_table_value(item, "default-features");
+	remove_table_value(item, "default_features");
+	Ok(())
+}
+
+fn dependency_mut<'a>(
+	doc: &'a mut DocumentMut,
+	kind: &DependencyKind,
+	name: &str,
+) -> Result<&'a mut Item, String> {
+	let kind = kind_to_str(kind);
+	doc.get_mut(kind)
+		.and_then(|d| d.as_table_like_mut())
+		.and_then(|d| d.get_mut(name))
+		.ok_or_else(|| format!("Dependency '{}' not found in '{}'", name, kind))
+}
+
+/// Insert or overwrite a value of an inline or expanded table while keeping its formatting.
+pub(crate) fn set_table_value(item: &mut Item, key: &str, mut v: Value) {
+	let Some(table) = item.as_table_like_mut() else { return };
+
+	if let Some(old) = table.get_mut(key) {
+		if let Some(decor) = old.as_value().map(|old| old.decor().clone()) {
+			*v.decor_mut() = decor;
+		}
+		*old = Item::Value(v);
+	} else if let Some(inline) = item.as_inline_table_mut() {
+		if inline.is_empty() {
+			inline.insert(key, v);
+		} else {
+			push_inline_value(inline, key, v);
+		}
+	} else if let Some(table) = item.as_table_like_mut() {
+		table.insert(key, Item::Value(v));
+	}
+}
+
+/// Remove a value from an inline or expanded table while keeping its formatting.
+pub(crate) fn remove_table_value(item: &mut Item, key: &str) {
+	if let Some(inline) = item.as_inline_table_mut() {
+		let was_last = inline.iter().last().map_or(false, |(k, _)| k == key);
+		let Some(removed) = inline.remove(key) else { return };
+		// Keep the whitespace before the closing brace.
+		if let (true, Some(suffix), Some((_, last))) =
+			(was_last, removed.decor().suffix().cloned(), inline.iter_mut().last())
+		{
+			last.decor_mut().set_suffix(suffix);
+		}
+	} else if let Some(table) = item.as_table_like_mut() {
+		table.remove(key);
+	}
+}
+
+/// Replace the entry `old` of an inline or expanded table with `new = v` at the same position.
+pub(crate) fn replace_table_key(item: &mut Item, old: &str, new: &str, mut v: Value) {
+	let position = |order: &[String], k: &Key| {
+		let k = if k.get() == new { old } else { k.get() };
+		order.iter().position(|o| o == k).unwrap_or(usize::MAX)
+	};
+
+	if let Some(inline) = item.as_inline_table_mut() {
+		let order = inline.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+		let Some((key, removed)) = inline.remove_entry(old) else { return };
+		*v.decor_mut() = removed.decor().clone();
+
+		inline.insert_formatted(&Key::new(new).with_leaf_decor(key.leaf_decor().clone()), v);
+		inline.sort_values_by(|a, _, b, _| position(&order, a).cmp(&position(&order, b)));
+	} else if let Some(table) = item.as_table_mut() {
+		let order = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+		let Some((key, removed)) = table.remove_entry(old) else { return };
+		if let Some(removed) = removed.as_value() {
+			*v.decor_mut() = removed.decor().clone();
+		}
+
+		table.insert_formatted(
+			&Key::new(new).with_leaf_decor(key.leaf_decor().clone()),
+			Item::Value(v),
+		);
+		table.sort_values_by(|a, _, b, _| position(&order, a).cmp(&position(&order, b)));
+	}
+}
+
+/// Append a value to an inline table while keeping the whitespace before the closing brace.
+pub(crate) fn push_inline_value(table: &mut InlineTable, key: &str, mut v: Value) {
+	if let Some((_, last)) = table.iter_mut().last() {
+		if let Some(suffix) = last.decor().suffix().cloned() {
+			last.decor_mut().set_suffix("");
+			v.decor_mut().set_suffix(suffix);
+		}
+	}
+	v.decor_mut().set_prefix(" ");
+	table.insert(key, v);
+}
+
+/// Insert `key` at its alphabetical position if the `table` is sorted, or append it otherwise.
+///
+/// The keys after the position are moved together with their formatting and comments.
+pub(crate) fn insert_sorted(table: &mut Table, key: &str, item: Item) {
+	let keys = table.iter().map(|(k, _)| k.to_string()).collect::<Vec<_>>();
+	let sorted = keys.windows(2).all(|w| w[0] <= w[1]);
+	let Some(pos) = keys.iter().position(|k| k.as_str() > key).filter(|_| sorted) else {
+		table.insert(key, item);
+		return
+	};
+
+	let tail = keys[pos..].iter().filter_map(|k| table.remove_entry(k)).collect::<Vec<_>>();
+	table.insert(key, item);
+	for (key, item) in tail {
+		table.insert_formatted(&key, item);
+	}
+}
+
+/// Turn a plain version like `log = "0.4"` into an inline table like `log = { version = "0.4" }`.
+fn expand_version(item: &mut Item) {
+	if let Some(version) = item.as_str() {
+		let mut inline = InlineTable::new();
+		inline.insert("version", version.into());
+		let decor = item.as_value().map(|v| v.decor().clone()).unwrap_or_default();
+		let mut v = Value::InlineTable(inline);
+		*v.decor_mut() = decor;
+		*item = Item::Value(v);
+	}
+}
+
+/// Add features to a dependency entry. A plain version is turned into an inline table.
+pub(crate) fn add_dependency_features(item: &mut Item, features: &[String]) -> Result<(), String> {
+	expand_version(item);
+	let existing = item
+		.as_table_like_mut()
+		.ok_or("Dependency is neither a version nor a table")?
+		.get_mut("features");
+	if let Some(array) = existing.and_then(|f| f.as_array_mut()) {
+		for feature in features {
+			array.push(feature.as_str());
+		}
+	} else {
+		let array = features.iter().map(String::as_str).collect::<Array>();
+		set_table_value(item, "features", Value::Array(array));
+	}
+	Ok(())
+}
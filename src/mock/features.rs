@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Random `[features]` tables for property tests of the [`AutoFixer`](crate::autofix::AutoFixer).
+//!
+//! [`features_table`] generates valid manifests with comments, odd whitespace, trailing commas and
+//! duplicate entries. The entries use a small alphabet, so that duplicates and entries that only
+//! differ by a `?` are common.
+
+use proptest::{collection, option, prelude::*};
+use std::collections::BTreeMap;
+
+/// A single entry of a feature together with its comments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenEntry {
+	/// The entry like `dep?/feature`.
+	pub value: String,
+	/// A comment on its own line above the entry.
+	pub comment: Option<String>,
+	/// A comment behind the entry on the same line.
+	pub trailing: Option<String>,
+}
+
+/// A feature with its entries and how it is laid out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenFeature {
+	pub entries: Vec<GenEntry>,
+	/// Write every entry on its own line. Features with comments are always multi-line.
+	pub multiline: bool,
+	/// Indentation of the entries in multi-line layout.
+	pub indent: String,
+	/// Whitespace around the entries in single-line layout.
+	pub padding: String,
+	pub trailing_comma: bool,
+}
+
+/// A `[features]` table with unique feature names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenFeatures {
+	pub features: BTreeMap<String, GenFeature>,
+}
+
+impl GenFeatures {
+	/// Render the table as manifest of a crate called `gen`.
+	pub fn to_manifest(&self) -> String {
+		let mut out =
+			String::from("[package]\nname = \"gen\"\nversion = \"0.1.0\"\n\n[features]\n");
+		for (name, feature) in self.features.iter() {
+			out.push_str(&format!("{} = {}\n", name, feature.to_toml()));
+		}
+		out
+	}
+
+	/// The entries of every feature in the order that they were generated.
+	pub fn entries(&self) -> BTreeMap<String, Vec<String>> {
+		self.features
+			.iter()
+			.map(|(name, f)| (name.clone(), f.entries.iter().map(|e| e.value.clone()).collect()))
+			.collect()
+	}
+}
+
+impl GenFeature {
+	/// Whether any entry has a comment.
+	pub fn has_comments(&self) -> bool {
+		self.entries.iter().any(|e| e.comment.is_some() || e.trailing.is_some())
+	}
+
+	/// Render the array of entries.
+	pub fn to_toml(&self) -> String {
+		let comma = if self.trailing_comma && !self.entries.is_empty() { "," } else { "" };
+		if !self.multiline && !self.has_comments() {
+			let values =
+				self.entries.iter().map(|e| format!("\"{}\"", e.value)).collect::<Vec<_>>();
+			return format!("[{}{}{}{}]", self.padding, values.join(", "), comma, self.padding)
+		}
+
+		let mut out = String::from("[\n");
+		for (i, entry) in self.entries.iter().enumerate() {
+			if let Some(comment) = &entry.comment {
+				out.push_str(&format!("{}#{}\n", self.indent, comment));
+			}
+			let last = i + 1 == self.entries.len();
+			out.push_str(&format!(
+				"{}\"{}\"{}",
+				self.indent,
+				entry.value,
+				if last { comma } else { "," }
+			));
+			if let Some(trailing) = &entry.trailing {
+				out.push_str(&format!(" #{}", trailing));
+			}
+			out.push('\n');
+		}
+		out.push(']');
+		out
+	}
+}
+
+/// A feature entry like `dep`, `dep/feature`, `dep?/feature` or `dep:dep`.
+pub fn entry() -> impl Strategy<Value = String> {
+	prop_oneof!["[a-c]{1,2}", "[a-c]{1,2}/[a-c]{1,2}", "[a-c]{1,2}\\?/[a-c]{1,2}", "dep:[a-c]{1,2}",]
+}
+
+/// The text of a comment without the leading `#`.
+pub fn comment() -> impl Strategy<Value = String> {
+	"[ a-zA-Z0-9,.]{0,40}"
+}
+
+/// An entry that has a comment in about every tenth case.
+pub fn gen_entry() -> impl Strategy<Value = GenEntry> {
+	(entry(), option::weighted(0.1, comment()), option::weighted(0.1, comment()))
+		.prop_map(|(value, comment, trailing)| GenEntry { value, comment, trailing })
+}
+
+/// A feature with up to `max_entries` entries.
+pub fn gen_feature(max_entries: usize) -> impl Strategy<Value = GenFeature> {
+	(
+		collection::vec(gen_entry(), 0..=max_entries),
+		any::<bool>(),
+		prop_oneof![Just("\t"), Just("  "), Just("    ")],
+		prop_oneof![Just(""), Just(" "), Just("  ")],
+		any::<bool>(),
+	)
+		.prop_map(|(entries, multiline, indent, padding, trailing_comma)| GenFeature {
+			entries,
+			multiline,
+			indent: indent.into(),
+			padding: padding.into(),
+			trailing_comma,
+		})
+}
+
+/// A `[features]` table with up to `max_features` features.
+pub fn features_table(max_features: usize) -> impl Strategy<Value = GenFeatures> {
+	collection::btree_map("[a-z][a-z0-9-]{0,30}", gen_feature(12), 0..=max_features)
+		.prop_map(|features| GenFeatures { features })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		autofix::{AutoFixer, ConflictResolution},
+		cmd::fmt::Mode,
+	};
+
+	/// Canonicalize all features of `manifest` with the same `modes`.
+	fn canonicalize(
+		manifest: &str,
+		modes: &[Mode],
+		line_width: u32,
+	) -> (AutoFixer, Result<(), Vec<String>>) {
+		let mut fixer = AutoFixer::from_raw(manifest).unwrap();
+		let modes = fixer.features().into_keys().map(|f| (f, modes.to_vec())).collect();
+		let res = fixer.canonicalize_features("gen", &modes, line_width, ConflictResolution::Error);
+		(fixer, res)
+	}
+
+	fn sorted(mut entries: Vec<String>) -> Vec<String> {
+		entries.sort();
+		entries
+	}
+
+	proptest! {
+		#[test]
+		fn generated_manifests_parse(table in features_table(8)) {
+			let fixer = AutoFixer::from_raw(&table.to_manifest()).unwrap();
+			prop_assert_eq!(fixer.features(), table.entries());
+		}
+
+		#[test]
+		fn output_reparses(table in features_table(8), line_width in 0u32..120) {
+			let (fixer, _) = canonicalize(&table.to_manifest(), &[], line_width);
+			let output = fixer.to_string();
+
+			let reparsed = AutoFixer::from_raw(&output).unwrap();
+			prop_assert_eq!(reparsed.features(), fixer.features());
+		}
+
+		#[test]
+		fn canonicalize_is_idempotent(table in features_table(8), line_width in 0u32..120) {
+			let (fixer, res) = canonicalize(&table.to_manifest(), &[], line_width);
+			prop_assume!(res.is_ok());
+			let once = fixer.to_string();
+
+			let (fixer, res) = canonicalize(&once, &[], line_width);
+			prop_assert!(res.is_ok());
+			prop_assert_eq!(&fixer.to_string(), &once);
+
+			// Check the re-parsed output since the formatter keeps the commas in the decor.
+			let fixer = AutoFixer::from_raw(&once).unwrap();
+			let resolution = ConflictResolution::Error;
+			for fname in fixer.features().into_keys() {
+				let canonical = fixer
+					.is_feature_canonical("gen", &fname, &Default::default(), line_width, resolution)
+					.unwrap();
+				prop_assert!(canonical, "feature '{}' is not canonical:\n{}", fname, once);
+			}
+		}
+
+		#[test]
+		fn sort_keeps_entries(table in features_table(8)) {
+			let (fixer, res) = canonicalize(&table.to_manifest(), &[Mode::Sort], 80);
+			prop_assert!(res.is_ok());
+
+			let expected = table.entries().into_iter().map(|(f, e)| (f, sorted(e))).collect();
+			prop_assert_eq!(fixer.features(), expected);
+		}
+
+		#[test]
+		fn sort_and_dedup_keeps_entries(table in features_table(8)) {
+			let (fixer, res) = canonicalize(&table.to_manifest(), &[Mode::Sort, Mode::Dedub], 80);
+			prop_assume!(res.is_ok());
+
+			let expected = table
+				.entries()
+				.into_iter()
+				.map(|(f, e)| {
+					let mut e = sorted(e);
+					e.dedup();
+					(f, e)
+				})
+				.collect();
+			prop_assert_eq!(fixer.features(), expected);
+		}
+	}
+}
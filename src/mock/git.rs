@@ -84,60 +84,89 @@ pub fn git_reset(dir: &Path) -> Result<(), anyhow::Error> {
 	Ok(())
 }
 
+/// Environment variable that points to a directory with git bundles of the test repos.
+///
+/// A repo like `paritytech/polkadot-sdk` is loaded from `paritytech/polkadot-sdk.bundle` in that
+/// directory before anything is fetched from GitHub.
+pub const REPO_CACHE_ENV: &str = "ZEPTER_TEST_REPO_CACHE";
+
+/// Check out `repo` at `rev` and return the path of the checkout.
+///
+/// All refs of a repo share a single cached bare repository, and every ref gets its own
+/// worktree. Missing commits are loaded from the bundle in [`REPO_CACHE_ENV`], if any, and only
+/// then fetched from GitHub.
 pub fn clone_repo(repo: &str, rev: &str) -> Result<PathBuf, anyhow::Error> {
 	let dir = std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".into());
-	let repos_dir = std::path::Path::new(&dir).join("test-repos");
-	let dir = repos_dir.join(repo);
-	colour::white_ln!("Checking out '{repo}' at '{}'", &rev[..10]);
+	let repos_dir = std::env::current_dir()?.join(dir).join("test-repos");
+	let bare = repos_dir.join(format!("{}.git", repo));
+	let short = &rev[..rev.len().min(10)];
+	let worktree = repos_dir.join(format!("{}@{}", repo, short));
+	colour::white_ln!("Checking out '{repo}' at '{short}'");
+
+	if !bare.exists() {
+		std::fs::create_dir_all(&bare)?;
+		git(&bare, &["init", "--bare", "--quiet"])?;
+	}
+	if !has_commit(&bare, rev) {
+		fetch(&bare, repo, rev)?;
+	}
 
-	// Check if the repo is already cloned
-	if Path::new(&dir).exists() {
-		git_reset(&dir)?;
+	if worktree.exists() {
+		git_reset(&worktree)?;
 	} else {
-		std::fs::create_dir_all(&dir)?;
-
-		let mut cmd = Command::new("git");
-		cmd.current_dir(&dir);
-		cmd.arg("init");
-		cmd.arg("--quiet");
-		cmd.assert().try_success()?;
-
-		// add remote
-		let mut cmd = Command::new("git");
-		cmd.current_dir(&dir);
-		cmd.arg("remote");
-		cmd.arg("add");
-		cmd.arg("origin");
-		cmd.arg(&format!("https://github.com/{}", repo));
-		cmd.assert().try_success()?;
-
-		fetch(&dir, rev)?;
+		// Forget worktrees whose directory was deleted.
+		git(&bare, &["worktree", "prune"])?;
+		let path = worktree.to_string_lossy();
+		git(&bare, &["worktree", "add", "--quiet", "--detach", "--force", &path, rev])?;
 	}
+	Ok(worktree)
+}
 
-	if checkout(&dir, rev).is_err() {
-		fetch(&dir, rev)?;
-		checkout(&dir, rev)?;
+/// Fetch `rev` into the bare repo `dir`, preferring the bundle of the repo cache.
+pub fn fetch(dir: &Path, repo: &str, rev: &str) -> Result<(), anyhow::Error> {
+	if let Some(bundle) = cached_bundle(repo) {
+		colour::white_ln!("Loading '{repo}' from bundle {}", bundle.display());
+		let bundle = bundle.to_string_lossy();
+		git(dir, &["fetch", "--quiet", &bundle, "+refs/*:refs/bundle/*"])?;
+		if has_commit(dir, rev) {
+			return Ok(())
+		}
 	}
-	Ok(dir)
+
+	let url = format!("https://github.com/{}", repo);
+	git(dir, &["fetch", "--quiet", "--depth", "1", &url, rev]).map_err(|e| {
+		anyhow::anyhow!(
+			"Failed to fetch '{}' at '{}'. Set {} to a directory with bundles to run offline: {}",
+			repo,
+			rev,
+			REPO_CACHE_ENV,
+			e
+		)
+	})
 }
 
-pub fn fetch(dir: &PathBuf, rev: &str) -> Result<(), anyhow::Error> {
+/// The bundle of `repo` in the [`REPO_CACHE_ENV`] directory, if it exists.
+///
+/// Bundles can be created from a clone with `git bundle create <repo>.bundle --all`.
+pub fn cached_bundle(repo: &str) -> Option<PathBuf> {
+	let dir = std::env::var_os(REPO_CACHE_ENV)?;
+	let bundle = Path::new(&dir).join(format!("{}.bundle", repo));
+	bundle.exists().then_some(bundle)
+}
+
+/// Whether the repo at `dir` contains the commit `rev`.
+fn has_commit(dir: &Path, rev: &str) -> bool {
 	let mut cmd = Command::new("git");
 	cmd.current_dir(dir);
-	cmd.arg("fetch");
-	cmd.arg("--depth");
-	cmd.arg("1");
-	cmd.arg("origin");
-	cmd.arg(rev);
-	cmd.assert().try_success()?;
-	Ok(())
+	cmd.args(["cat-file", "-e", &format!("{}^{{commit}}", rev)]);
+	cmd.output().map_or(false, |o| o.status.success())
 }
 
-pub fn checkout(dir: &PathBuf, rev: &str) -> Result<(), anyhow::Error> {
+/// Run git with `args` in `dir`.
+fn git(dir: &Path, args: &[&str]) -> Result<(), anyhow::Error> {
 	let mut cmd = Command::new("git");
 	cmd.current_dir(dir);
-	cmd.arg("checkout");
-	cmd.arg(rev);
+	cmd.args(args);
 	cmd.assert().try_success()?;
 	Ok(())
 }
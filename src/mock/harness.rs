@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: GPL-3.0-only
+// SPDX-FileCopyrightText: Oliver Tale-Yazdi <oliver@tasty.limo>
+
+//! Runs YAML case files against the zepter binary.
+//!
+//! This is the harness of the UI tests of this repo. Other crates can use it for their own policy
+//! tests by depending on zepter with the `testing` feature:
+//!
+//! ```no_run
+//! use zepter::mock::harness::{run_case_files, HarnessOptions};
+//!
+//! run_case_files("tests/zepter/**/*.yaml", &HarnessOptions::from_env());
+//! ```
+
+use super::{git_diff, git_reset, normalize, Case, CaseFile};
+use assert_cmd::{assert::OutputAssertExt, Command};
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+/// How [`run_case_files`] runs the cases.
+#[derive(Debug, Clone)]
+pub struct HarnessOptions {
+	/// The zepter binary to run. Defaults to the one built by this workspace.
+	pub binary: PathBuf,
+	/// Write the actual stdout, stderr and diff back into the case files instead of failing.
+	pub overwrite: bool,
+	/// Print `FAILED` but continue with the next case instead of aborting.
+	pub keep_going: bool,
+	/// Keep the mocked workspaces in the temp dir and print their paths.
+	pub persist: bool,
+}
+
+impl Default for HarnessOptions {
+	fn default() -> Self {
+		Self {
+			binary: assert_cmd::cargo::cargo_bin("zepter"),
+			overwrite: false,
+			keep_going: false,
+			persist: false,
+		}
+	}
+}
+
+impl HarnessOptions {
+	/// Read the options from the `OVERWRITE`, `KEEP_GOING` and `PERSIST` environment variables.
+	///
+	/// The binary can be set with `ZEPTER_BIN`.
+	pub fn from_env() -> Self {
+		let mut options = Self {
+			overwrite: std::env::var("OVERWRITE").is_ok(),
+			keep_going: std::env::var("KEEP_GOING").is_ok(),
+			persist: std::env::var("PERSIST").is_ok(),
+			..Default::default()
+		};
+		if let Some(binary) = std::env::var_os("ZEPTER_BIN") {
+			options.binary = binary.into();
+		}
+		options
+	}
+}
+
+/// Run all case files that match the `pattern` glob, like `tests/ui/**/*.yaml`.
+///
+/// Checks the exit code, stdout, stderr, git diff and expected files of every case. Panics if a
+/// case fails or no case file was found. In overwrite mode the case files are updated instead.
+pub fn run_case_files(pattern: &str, options: &HarnessOptions) {
+	let files = glob::glob(pattern).unwrap();
+	let (mut failed, mut good) = (0, 0);
+
+	if options.overwrite {
+		colour::white_ln!("Running tests in OVERWRITE mode\n");
+	}
+
+	for file in files.filter_map(Result::ok).filter(|f| f.is_file()) {
+		let mut config = CaseFile::from_file(&file);
+		let (workspace, ctx) = config.init().unwrap();
+		let mut cout_overwrites = HashMap::new();
+		let mut cerr_overwrites = HashMap::new();
+		let mut diff_overwrites = HashMap::new();
+		let m = config.cases().len();
+
+		for (i, case) in config.cases().iter().enumerate() {
+			let _init = case.init(workspace.as_path()).unwrap();
+			colour::white!("{} {}/{} ", file.display(), i + 1, m);
+			git_reset(workspace.as_path()).unwrap();
+			let mut cmd = command(options, &config, case, workspace.as_path(), i);
+
+			let res = cmd.output().unwrap();
+			if let Some(code) = case.code {
+				res.clone().assert().code(code);
+			} else {
+				res.clone().assert().success();
+			}
+
+			let stdout = redact(options, &res.stdout, workspace.as_path());
+			let stderr = redact(options, &res.stderr, workspace.as_path());
+
+			match (stdout == case.stdout, stderr == case.stderr) {
+				(true, true) => {
+					colour::white!("cout:");
+					colour::green!("OK");
+					colour::white!(" ");
+					good += 1;
+				},
+				(false, _) if !options.overwrite => {
+					colour::white!("cerr:");
+					colour::red!("FAIL");
+					colour::white!(" ");
+					if !options.keep_going {
+						pretty_assertions::assert_eq!(&stdout, &normalize(&case.stdout),);
+						unreachable!()
+					}
+				},
+				(true, false) if !options.overwrite => {
+					colour::white!("cerr:");
+					colour::red!("FAIL");
+					colour::white!(" ");
+					if !options.keep_going {
+						pretty_assertions::assert_eq!(&stderr, &normalize(&case.stderr),);
+						unreachable!()
+					}
+				},
+				(true, false) => {
+					colour::white!("cerr:");
+					colour::yellow!("OVERWRITE");
+					colour::white!(" ");
+					cerr_overwrites.insert(i, stderr);
+
+					failed += 1;
+				},
+				(false, _) => {
+					colour::white!("cout:");
+					colour::yellow!("OVERWRITE");
+					colour::white!(" ");
+					cout_overwrites.insert(i, stdout);
+
+					failed += 1;
+				},
+			}
+
+			let got = git_diff(workspace.as_path()).unwrap();
+			if got != case.diff {
+				if options.overwrite {
+					diff_overwrites.insert(i, got);
+					colour::white!("diff:");
+					colour::yellow_ln!("OVERWRITE");
+					colour::white!("");
+				} else {
+					colour::white!("diff:");
+					colour::red_ln!("FAILED");
+					colour::white!("");
+					if !options.keep_going {
+						pretty_assertions::assert_eq!(got, case.diff);
+					}
+				}
+			} else {
+				colour::white!("diff:");
+				colour::green_ln!("OK");
+				colour::white!("");
+			}
+			if let Err(err) = case.check_files(workspace.as_path()) {
+				colour::white!("files:");
+				colour::red_ln!("FAILED");
+				colour::white!("");
+				if !options.keep_going {
+					panic!("{}", err);
+				}
+				println!("{}", err);
+				failed += 1;
+			}
+			git_reset(workspace.as_path()).unwrap();
+		}
+
+		if options.persist {
+			if let Some(ctx) = ctx {
+				let path = ctx.persist();
+				colour::white_ln!("Persisted to {:?}", path);
+			} else {
+				colour::red_ln!("Cannot persist test");
+			}
+		}
+
+		if options.overwrite {
+			if cout_overwrites.is_empty() &&
+				cerr_overwrites.is_empty() &&
+				diff_overwrites.is_empty()
+			{
+				continue
+			}
+
+			for (i, stdout) in cout_overwrites {
+				config.case_mut(i).stdout = stdout;
+			}
+			for (i, stderr) in cerr_overwrites {
+				config.case_mut(i).stderr = stderr;
+			}
+			for (i, diff) in diff_overwrites {
+				config.case_mut(i).diff = diff;
+			}
+
+			config.to_file(&file).unwrap();
+			println!("Updated {}", file.display());
+		}
+	}
+
+	if failed > 0 {
+		if options.overwrite {
+			println!("Updated {} test(s)", failed);
+		} else {
+			panic!("{} test(s) failed", failed);
+		}
+	}
+	if failed == 0 && good == 0 {
+		panic!("No tests found");
+	}
+	println!();
+}
+
+/// The zepter command of a case with the default args of its case file.
+pub fn command(
+	options: &HarnessOptions,
+	config: &CaseFile,
+	case: &Case,
+	workspace: &Path,
+	i: usize,
+) -> Command {
+	let mut cmd = Command::new(&options.binary);
+	// CI sets this, but we want deterministic output.
+	cmd.env_remove("CARGO_TERM_COLOR");
+	for arg in case.cmd.split_whitespace() {
+		cmd.arg(arg);
+	}
+	if !case.stdin.is_empty() {
+		cmd.write_stdin(case.stdin.clone());
+	}
+
+	if config.default_args() {
+		let toml_path = workspace.join("Cargo.toml");
+		cmd.args(["--manifest-path", toml_path.as_path().to_str().unwrap(), "--log", "warn"]);
+		if i > 0 {
+			cmd.arg("--offline");
+		}
+	} else {
+		cmd.current_dir(workspace);
+	}
+	cmd
+}
+
+/// Replace machine specific paths in the output with placeholders.
+pub fn redact(options: &HarnessOptions, output: &[u8], workspace: &Path) -> String {
+	let mut output = String::from_utf8_lossy(output).to_string();
+
+	for (path, placeholder) in [(options.binary.as_path(), "$ZEPTER"), (workspace, "$WORKSPACE")] {
+		if let Ok(canonical) = path.canonicalize() {
+			output = output.replace(canonical.to_str().unwrap(), placeholder);
+		}
+		output = output.replace(path.to_str().unwrap(), placeholder);
+	}
+	// Durations in the workflow summary differ between runs.
+	let duration = regex::Regex::new(r"(?m) in \d+(\.\d+)?(ns|µs|ms|s)$").unwrap();
+	let output = duration.replace_all(&output, " in $$DURATION");
+	// Same for the rows of the `--verbose` timing table.
+	let timing =
+		regex::Regex::new(r"(?m)^  ([a-z][a-z ]*[a-z]) +\d+(\.\d+)?(ns|µs|ms|s)$").unwrap();
+	timing.replace_all(&output, "  $1 $$DURATION").to_string()
+}
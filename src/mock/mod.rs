@@ -5,11 +5,14 @@
 
 #![cfg(feature = "testing")]
 
+pub mod features;
 pub mod git;
+pub mod harness;
 pub use git::*;
 
+use cargo_metadata::{DependencyKind, Metadata, MetadataCommand};
 use std::{
-	collections::{BTreeMap, HashMap},
+	collections::{BTreeMap, BTreeSet, HashMap},
 	fs,
 	io::Write,
 	path::{Path, PathBuf},
@@ -25,6 +28,11 @@ pub type ModuleName = String;
 pub struct Case {
 	pub cmd: String,
 
+	/// Written to the stdin of the command.
+	#[serde(skip_serializing_if = "String::is_empty")]
+	#[serde(default)]
+	pub stdin: String,
+
 	#[serde(skip_serializing_if = "String::is_empty")]
 	#[serde(default)]
 	pub stdout: String,
@@ -43,6 +51,23 @@ pub struct Case {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	#[serde(default)]
 	pub config: Option<ZepterConfig>,
+
+	/// Files that must have a specific content after the command ran.
+	///
+	/// Paths are relative to the workspace root.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	#[serde(default)]
+	pub expect_files: BTreeMap<String, ExpectedFile>,
+}
+
+/// The expected content of a file.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ExpectedFile {
+	/// The exact content, including whitespace.
+	Content(String),
+	/// Snippets that must all be contained in the file.
+	Contains { contains: Vec<String> },
 }
 
 /// A specific github repo checkout.
@@ -70,6 +95,29 @@ impl Case {
 		let cfg_path = cfg.write(root)?;
 		Ok(CaseCleanupGuard { cfg_path: Some(cfg_path) })
 	}
+
+	/// Check that all [`Self::expect_files`] have the expected content.
+	pub fn check_files(&self, root: &Path) -> Result<(), String> {
+		for (path, expected) in self.expect_files.iter() {
+			let got = fs::read_to_string(root.join(path))
+				.map_err(|e| format!("Failed to read {}: {}", path, e))?;
+			expected.check(&got).map_err(|e| format!("{}: {}", path, e))?;
+		}
+		Ok(())
+	}
+}
+
+impl ExpectedFile {
+	pub fn check(&self, got: &str) -> Result<(), String> {
+		match self {
+			Self::Content(content) if content == got => Ok(()),
+			Self::Content(content) => Err(format!("expected content:\n{}\ngot:\n{}", content, got)),
+			Self::Contains { contains } => match contains.iter().find(|c| !got.contains(*c)) {
+				Some(missing) => Err(format!("missing {:?} in:\n{}", missing, got)),
+				None => Ok(()),
+			},
+		}
+	}
 }
 
 impl Drop for CaseCleanupGuard {
@@ -150,8 +198,9 @@ impl UiCaseFile {
 			ctx.create_crate(module)?;
 		}
 		ctx.create_workspace(&self.crates)?;
-		git_init(ctx.root.path())?;
 		self.generate_config(ctx.root.path())?;
+		// Configs are committed as well so that modifications to them show up in the diff.
+		git_init(ctx.root.path())?;
 		Ok(ctx)
 	}
 
@@ -267,9 +316,9 @@ impl Context {
 		let toml_path = self.root.path().join(module.path()).join("Cargo.toml");
 		assert!(toml_path.exists(), "Crate must exist");
 		// Add the deps
-		let mut out_deps = HashMap::<cargo_metadata::DependencyKind, String>::new();
+		let mut out_deps = HashMap::<(Option<String>, DependencyKind), String>::new();
 		for dep in module.deps.iter().flatten() {
-			out_deps.entry(dep.kind()).or_default().push_str(&dep.def());
+			out_deps.entry((dep.target(), dep.kind())).or_default().push_str(&dep.def());
 		}
 
 		let mut txt = String::from("[features]\n");
@@ -281,14 +330,25 @@ impl Context {
 			txt.push_str("]\n");
 		}
 
-		let deps = format!(
+		let mut deps = format!(
 			"{}\n[dev-dependencies]\n{}\n[build-dependencies]\n{}\n",
-			out_deps.remove(&cargo_metadata::DependencyKind::Normal).unwrap_or_default(),
-			out_deps
-				.remove(&cargo_metadata::DependencyKind::Development)
-				.unwrap_or_default(),
-			out_deps.remove(&cargo_metadata::DependencyKind::Build).unwrap_or_default(),
+			out_deps.remove(&(None, DependencyKind::Normal)).unwrap_or_default(),
+			out_deps.remove(&(None, DependencyKind::Development)).unwrap_or_default(),
+			out_deps.remove(&(None, DependencyKind::Build)).unwrap_or_default(),
 		);
+		// Target specific deps go into their own sections in a deterministic order.
+		let targets = out_deps.keys().filter_map(|(t, _)| t.clone()).collect::<BTreeSet<_>>();
+		for target in targets {
+			for (kind, section) in [
+				(DependencyKind::Normal, "dependencies"),
+				(DependencyKind::Development, "dev-dependencies"),
+				(DependencyKind::Build, "build-dependencies"),
+			] {
+				if let Some(defs) = out_deps.remove(&(Some(target.clone()), kind)) {
+					deps.push_str(&format!("[target.'{}'.{}]\n{}\n", target, section, defs));
+				}
+			}
+		}
 
 		let output = format!("{}\n{}", deps, txt);
 		// Append to the toml
@@ -308,6 +368,23 @@ impl Context {
 		Ok(())
 	}
 
+	/// Create a workspace of the `crates` and load its metadata.
+	///
+	/// The crates are given in the YAML format of case files. They only have path dependencies on
+	/// each other, so that the workspace is resolved offline.
+	pub fn workspace_metadata(&self, crates: &str) -> Result<Metadata, anyhow::Error> {
+		let crates: Vec<CrateConfig> = serde_yaml::from_str(crates)?;
+		for module in crates.iter() {
+			self.create_crate(module)?;
+		}
+		self.create_workspace(&crates)?;
+
+		Ok(MetadataCommand::new()
+			.manifest_path(self.root.path().join("Cargo.toml"))
+			.other_options(vec!["--offline".into()])
+			.exec()?)
+	}
+
 	pub fn cargo(&self, cmd: &str, sub_dir: Option<&str>) -> Result<(), anyhow::Error> {
 		assert!(self.root.path().exists());
 		let dir = match sub_dir {
@@ -352,19 +429,38 @@ pub enum CrateDependency {
 		#[serde(skip_serializing_if = "is_false")]
 		optional: Option<bool>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		kind: Option<cargo_metadata::DependencyKind>,
+		kind: Option<DependencyKind>,
+		/// Features of the dependency that are enabled.
+		#[serde(skip_serializing_if = "Option::is_none")]
+		features: Option<Vec<String>>,
+		/// Set to `false` to disable the default features of the dependency.
+		#[serde(rename = "default-features", skip_serializing_if = "Option::is_none")]
+		default_features: Option<bool>,
+		/// Target cfg like `cfg(unix)` to declare the dependency in a `[target.'cfg'.*]` section.
+		#[serde(skip_serializing_if = "Option::is_none")]
+		target: Option<String>,
 	},
 }
 
 impl CrateDependency {
 	fn def(&self) -> String {
-		let option = if self.optional() { ", optional = true".to_string() } else { String::new() };
+		let mut options = String::new();
+		if let Self::Explicit { default_features: Some(default), .. } = self {
+			options.push_str(&format!(", default-features = {}", default));
+		}
+		if let Self::Explicit { features: Some(features), .. } = self {
+			let features = features.iter().map(|f| format!("\"{}\"", f)).collect::<Vec<_>>();
+			options.push_str(&format!(", features = [{}]", features.join(", ")));
+		}
+		if self.optional() {
+			options.push_str(", optional = true");
+		}
 		let mut ret = if let Some(rename) = self.rename() {
 			format!("{} = {{ package = \"{}\", ", rename, self.name())
 		} else {
 			format!("{} = {{ ", self.name())
 		};
-		ret.push_str(&format!("version = \"*\", path = \"../{}\"{}}}\n", self.path(), option));
+		ret.push_str(&format!("version = \"*\", path = \"../{}\"{}}}\n", self.path(), options));
 		ret
 	}
 
@@ -372,10 +468,17 @@ impl CrateDependency {
 		crate_name_to_path(&self.name())
 	}
 
-	fn kind(&self) -> cargo_metadata::DependencyKind {
+	fn kind(&self) -> DependencyKind {
 		match self {
 			Self::Explicit { kind, .. } => kind.unwrap_or_default(),
-			Self::Implicit(_) => cargo_metadata::DependencyKind::Normal,
+			Self::Implicit(_) => DependencyKind::Normal,
+		}
+	}
+
+	fn target(&self) -> Option<String> {
+		match self {
+			Self::Explicit { target, .. } => target.clone(),
+			Self::Implicit(_) => None,
 		}
 	}
 
@@ -4,13 +4,20 @@
 #![cfg(test)]
 
 use crate::{
-	autofix::AutoFixer,
-	cmd::fmt::Mode::{self, Canonicalize, Dedub, Sort},
-	kind_to_str,
+	autofix::{
+		AutoFixer, ConflictResolution, DefaultFeatureConflictFix, EntryMatch, FixTransaction,
+		ManifestError,
+	},
+	cmd::{
+		fmt::Mode::{self, Canonicalize, Dedub, Sort},
+		GlobalArgs,
+	},
+	manifest::kind_to_str,
 };
 use cargo_metadata::DependencyKind::*;
+use clap::Parser;
 use rstest::*;
-use std::{collections::BTreeMap as Map, vec};
+use std::{collections::BTreeMap as Map, path::Path, vec};
 
 #[rstest]
 // Keeps comments
@@ -208,6 +215,23 @@ std = [
 ]
 "#
 )]
+#[case(
+	r#"[features]
+runtime-benchmarks = [
+  "sp-runtime/runtime-benchmarks",
+]
+std = []
+"#,
+	r#"[features]
+runtime-benchmarks = [
+  "sp-runtime/runtime-benchmarks",
+  "frame-support/runtime-benchmarks"
+]
+std = [
+  "frame-system/std"
+]
+"#
+)]
 fn add_to_features_works(#[case] before: &str, #[case] after: &str) {
 	let mut fixer = AutoFixer::from_raw(before).unwrap();
 	fixer
@@ -721,6 +745,21 @@ F0 = [
 "#
 	)
 )]
+// Comment behind the trailing comma
+#[case(
+	r#"[features]
+F0 = [ "A/F0", # comment
+]
+"#,
+	Some(
+		r#"[features]
+F0 = [
+	"A/F0",
+	# comment
+]
+"#
+	)
+)]
 fn format_all_features_works(#[case] input: &str, #[case] modify: Option<&str>) {
 	let mut fixer = AutoFixer::from_raw(input).unwrap();
 	fixer.format_all_feature(10).unwrap();
@@ -929,7 +968,9 @@ fn canon_some_features_with_modes_works(
 ) {
 	let mut fixer = AutoFixer::from_raw(input).unwrap();
 	let modes = modes.into_iter().map(|(f, m)| (f.into(), m)).collect::<Map<_, _>>();
-	fixer.canonicalize_features("krate", &modes, 0).unwrap();
+	fixer
+		.canonicalize_features("krate", &modes, 0, ConflictResolution::Error)
+		.unwrap();
 	pretty_assertions::assert_str_eq!(fixer.to_string(), modify.unwrap_or(input));
 }
 
@@ -1147,6 +1188,35 @@ fn deduplicate_feature_works(#[case] input: &str, #[case] modify: Result<Option<
 	}
 }
 
+#[rstest]
+#[case(ConflictResolution::Error, Ok(vec!["a/std", "a?/std", "b?/std"]))]
+#[case(ConflictResolution::PreferWeak, Ok(vec!["a?/std", "b?/std"]))]
+#[case(ConflictResolution::PreferStrong, Ok(vec!["a/std", "b?/std"]))]
+fn resolve_conflicts_works(
+	#[case] resolution: ConflictResolution,
+	#[case] expected: Result<Vec<&str>, &str>,
+) {
+	let mut fixer =
+		AutoFixer::from_raw("[features]\ndefault = [\"a/std\", \"a?/std\", \"b?/std\"]\n").unwrap();
+	let feature = fixer.get_feature_mut("default").unwrap();
+	let res = AutoFixer::resolve_conflicts("krate", "default", feature, resolution);
+
+	let entries = feature.iter().map(|v| v.as_str().unwrap()).collect::<Vec<_>>();
+	assert_eq!(res.map(|()| entries), expected.map_err(Into::into));
+}
+
+#[test]
+fn resolve_conflicts_keeps_comments() {
+	let mut fixer =
+		AutoFixer::from_raw("[features]\ndefault = [\n\t# Why\n\t\"a/std\",\n\t\"a?/std\",\n]\n")
+			.unwrap();
+	let feature = fixer.get_feature_mut("default").unwrap();
+	let res =
+		AutoFixer::resolve_conflicts("krate", "default", feature, ConflictResolution::PreferWeak);
+
+	assert_eq!(res, Err("feature 'default': has a comment 'a/std'".into()));
+}
+
 #[rstest]
 #[case("", false, Err("No workspace entry found"))]
 // simple
@@ -1199,7 +1269,7 @@ log = { default-features = true }
 		r#"[workspace]
 
 [workspace.dependencies]
-log = { default-features = true , version = "0.4.20" }
+log = { default-features = true, version = "0.4.20" }
 "#
 	))
 )]
@@ -1260,7 +1330,7 @@ log = { random = "321", hey = true, git = "123" }
 		r#"[workspace]
 
 [workspace.dependencies]
-log = { random = "321", hey = true, git = "123" , version = "0.4.20" }
+log = { random = "321", hey = true, git = "123", version = "0.4.20" }
 "#
 	))
 )]
@@ -1275,7 +1345,24 @@ log = { random = "321", default-features = true, hey = true, git = "123" }
 		r#"[workspace]
 
 [workspace.dependencies]
-log = { random = "321", default-features = true, hey = true, git = "123" , version = "0.4.20" }
+log = { random = "321", default-features = true, hey = true, git = "123", version = "0.4.20" }
+"#
+	))
+)]
+#[case(
+	r#"[workspace]
+
+[workspace.dependencies.log]
+features = ["std"] # Comment
+"#,
+	false,
+	Ok(Some(
+		r#"[workspace]
+
+[workspace.dependencies.log]
+features = ["std"] # Comment
+version = "0.4.20"
+default-features = false
 "#
 	))
 )]
@@ -1285,7 +1372,7 @@ fn inject_workspace_dep_works(
 	#[case] output: Result<Option<&str>, &str>,
 ) {
 	let mut fixer = AutoFixer::from_raw(input).unwrap();
-	let res = fixer.add_workspace_dep_inner("log", None, "^0.4.20", default, None);
+	let res = fixer.add_workspace_dep_inner("log", None, "^0.4.20", default, None, None);
 
 	match output {
 		Ok(modify) => {
@@ -1298,6 +1385,55 @@ fn inject_workspace_dep_works(
 	}
 }
 
+#[rstest]
+#[case(
+	r#"[workspace.dependencies]
+# Errors.
+anyhow = "1"
+# Serialization.
+serde = { version = "1" } # Keep
+"#,
+	Some(
+		r#"[workspace.dependencies]
+# Errors.
+anyhow = "1"
+log = { version = "0.4.20" }
+# Serialization.
+serde = { version = "1" } # Keep
+"#
+	)
+)]
+#[case(
+	r#"[workspace.dependencies]
+serde = "1"
+anyhow = "1"
+"#,
+	Some(
+		r#"[workspace.dependencies]
+serde = "1"
+anyhow = "1"
+log = { version = "0.4.20" }
+"#
+	)
+)]
+#[case(
+	r#"[workspace.dependencies]
+anyhow = "1"
+# Pinned for reasons.
+log = { version = "^0.4.20" } # Comment
+serde = "1"
+"#,
+	None
+)]
+fn inject_workspace_dep_keeps_formatting(#[case] input: &str, #[case] output: Option<&str>) {
+	let input = format!("[workspace]\n\n{}", input);
+	let mut fixer = AutoFixer::from_raw(&input).unwrap();
+	fixer.add_workspace_dep_inner("log", None, "^0.4.20", true, None, None).unwrap();
+
+	let output = output.map(|o| format!("[workspace]\n\n{}", o));
+	pretty_assertions::assert_str_eq!(output.as_deref().unwrap_or(&input), fixer.to_string());
+}
+
 #[rstest]
 #[case(
 	r#"[dependencies]
@@ -1320,7 +1456,7 @@ log = { random = "321", default-features = true, version = "0.4.20", hey = true
 	Some(true),
 	Ok(Some(
 		r#"[dependencies]
-log = { random = "321", default-features = true, hey = true , workspace = true }
+log = { random = "321", default-features = true, workspace = true, hey = true }
 "#
 	))
 )]
@@ -1357,6 +1493,32 @@ log = { workspace = true }
 "#
 	))
 )]
+#[case(
+	r#"[dependencies.log]
+version = "0.4.20" # Comment
+features = ["std"]
+package = "log2"
+"#,
+	Some(false),
+	Ok(Some(
+		r#"[dependencies.log]
+workspace = true # Comment
+features = ["std"]
+default-features = false
+"#
+	))
+)]
+#[case(
+	r#"[dependencies]
+log = { version = "0.4.20", default-features = false, features = ["std"] }
+"#,
+	Some(true),
+	Ok(Some(
+		r#"[dependencies]
+log = { workspace = true, default-features = true, features = ["std"] }
+"#
+	))
+)]
 fn lift_to_workspace_works(
 	#[case] input: &str,
 	#[case] default: Option<bool>,
@@ -1370,6 +1532,7 @@ fn lift_to_workspace_works(
 			"log",
 			&kind,
 			default,
+			None,
 			&crate::cmd::transpose::SourceLocationSelector::Remote,
 		);
 
@@ -1385,3 +1548,1280 @@ fn lift_to_workspace_works(
 		}
 	}
 }
+
+#[rstest]
+#[case(
+	r#"[workspace]"#,
+	Some(vec!["serde", "std"]),
+	Ok(r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["serde", "std"] }
+"#)
+)]
+#[case(
+	r#"[workspace]"#,
+	Some(vec![]),
+	Ok(r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20" }
+"#)
+)]
+#[case(
+	r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["std", "serde"] }
+"#,
+	Some(vec!["serde", "std"]),
+	Ok(r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["std", "serde"] }
+"#)
+)]
+#[case(
+	r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["std"] }
+"#,
+	None,
+	Ok(
+		r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["std"] }
+"#
+	)
+)]
+#[case(
+	r#"[workspace]
+
+[workspace.dependencies]
+log = { version = "0.4.20", features = ["std"] }
+"#,
+	Some(vec![]),
+	Err("Dependency 'log' already exists in the workspace with a different 'features' field: 'std' vs ''")
+)]
+fn inject_workspace_dep_features_works(
+	#[case] input: &str,
+	#[case] features: Option<Vec<&str>>,
+	#[case] output: Result<&str, &str>,
+) {
+	let features = features.map(|f| f.into_iter().map(String::from).collect::<Vec<_>>());
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	let res =
+		fixer.add_workspace_dep_inner("log", None, "^0.4.20", true, features.as_deref(), None);
+
+	match output {
+		Ok(modify) => {
+			res.unwrap();
+			pretty_assertions::assert_str_eq!(fixer.to_string(), modify);
+		},
+		Err(modify) => {
+			assert_eq!(res, Err(modify.into()));
+		},
+	}
+}
+
+#[rstest]
+// Only keeps the features that the workspace does not enable
+#[case(
+	r#"[dependencies]
+log = { version = "0.4.20", features = ["std", "serde"] }
+"#,
+	Some(vec!["serde"]),
+	r#"[dependencies]
+log = { workspace = true, features = ["serde"] }
+"#
+)]
+#[case(
+	r#"[dependencies.log]
+version = "0.4.20"
+features = ["std"] # Comment
+"#,
+	Some(vec![]),
+	r#"[dependencies.log]
+workspace = true
+"#
+)]
+#[case(
+	r#"[dependencies]
+log = { version = "0.4.20", features = ["std"] }
+"#,
+	None,
+	r#"[dependencies]
+log = { workspace = true, features = ["std"] }
+"#
+)]
+fn lift_to_workspace_features_works(
+	#[case] input: &str,
+	#[case] features: Option<Vec<&str>>,
+	#[case] output: &str,
+) {
+	let features = features.map(|f| f.into_iter().map(String::from).collect::<Vec<_>>());
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	fixer
+		.lift_dependency(
+			"log",
+			&Normal,
+			None,
+			features.as_deref(),
+			&crate::cmd::transpose::SourceLocationSelector::Remote,
+		)
+		.unwrap();
+
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output);
+}
+
+#[rstest]
+// Adds `package` and keeps the position
+#[case(
+	r#"[dependencies]
+a = "1"
+parity-scale-codec = { version = "3", default-features = false } # comment
+z = "1"
+
+[features]
+std = ["a/std", "parity-scale-codec/std"]
+runtime-benchmarks = ["parity-scale-codec?/bench"]
+"#,
+	Ok(Some(
+		r#"[dependencies]
+a = "1"
+codec = { version = "3", default-features = false, package = "parity-scale-codec" } # comment
+z = "1"
+
+[features]
+std = ["a/std", "codec/std"]
+runtime-benchmarks = ["codec?/bench"]
+"#
+	))
+)]
+// Removes `package` when renaming back
+#[case(
+	r#"[dependencies]
+parity-scale-codec = { package = "codec", version = "3" }
+"#,
+	Ok(Some(
+		r#"[dependencies]
+codec = { version = "3" }
+"#
+	))
+)]
+// Converts plain versions and renames optional deps
+#[case(
+	r#"[dependencies]
+parity-scale-codec = "3"
+
+[features]
+full = ["dep:parity-scale-codec"]
+"#,
+	Ok(Some(
+		r#"[dependencies]
+codec = { version = "3", package = "parity-scale-codec" }
+
+[features]
+full = ["dep:codec"]
+"#
+	))
+)]
+// Keeps workspace inheritance
+#[case(
+	r#"[workspace.dependencies]
+parity-scale-codec = { version = "3" }
+
+[dev-dependencies]
+parity-scale-codec = { workspace = true }
+"#,
+	Ok(Some(
+		r#"[workspace.dependencies]
+codec = { version = "3", package = "parity-scale-codec" }
+
+[dev-dependencies]
+codec = { workspace = true }
+"#
+	))
+)]
+// Supports dependency tables
+#[case(
+	r#"[dependencies.parity-scale-codec]
+version = "3"
+
+[dependencies.a]
+version = "1"
+"#,
+	Ok(Some(
+		r#"[dependencies.codec]
+version = "3"
+package = "parity-scale-codec"
+
+[dependencies.a]
+version = "1"
+"#
+	))
+)]
+// Keeps an existing `package`
+#[case(
+	r#"[dependencies]
+parity-scale-codec = { package = "scale-codec", version = "3" }
+"#,
+	Ok(Some(
+		r#"[dependencies]
+codec = { package = "scale-codec", version = "3" }
+"#
+	))
+)]
+#[case(
+	r#"[dependencies]
+a = "1"
+"#,
+	Ok(None)
+)]
+#[case(
+	r#"[dependencies]
+codec = "1"
+parity-scale-codec = "3"
+"#,
+	Err("Dependency 'codec' already exists")
+)]
+fn rename_dependency_works(#[case] input: &str, #[case] output: Result<Option<&str>, &str>) {
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	let res = fixer.rename_dependency("parity-scale-codec", "codec");
+
+	match output {
+		Ok(modify) => {
+			assert_eq!(res, Ok(modify.is_some()));
+			pretty_assertions::assert_str_eq!(fixer.to_string(), modify.unwrap_or(input));
+		},
+		Err(err) => assert_eq!(res, Err(err.into())),
+	}
+}
+
+#[rstest]
+#[case(
+	r#"[dependencies]
+a = { path = "../a", features = ["std"] }
+"#,
+	Some(
+		r#"[dependencies]
+a = { version = "1.2.3", features = ["std"] }
+"#
+	)
+)]
+#[case(
+	r#"[dependencies]
+a = { version = "1", path = "../a" }
+"#,
+	Some(
+		r#"[dependencies]
+a = { version = "1" }
+"#
+	)
+)]
+#[case(
+	r#"[target.'cfg(unix)'.dev-dependencies.a]
+path = "../a" # Comment
+default-features = false
+"#,
+	Some(
+		r#"[target.'cfg(unix)'.dev-dependencies.a]
+version = "1.2.3" # Comment
+default-features = false
+"#
+	)
+)]
+#[case(
+	r#"[workspace.dependencies]
+a = { path = "../a" }
+b = { path = "../b" }
+"#,
+	Some(
+		r#"[workspace.dependencies]
+a = { version = "1.2.3" }
+b = { path = "../b" }
+"#
+	)
+)]
+#[case(
+	r#"[dependencies]
+a = "1"
+b = { path = "../a" }
+"#,
+	None
+)]
+fn replace_path_with_version_works(#[case] input: &str, #[case] output: Option<&str>) {
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	let res = fixer.replace_path_with_version("a", "1.2.3");
+
+	assert_eq!(res, Ok(output.is_some()));
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output.unwrap_or(input));
+}
+
+#[rstest]
+#[case(
+	r#"[package]
+name = "a"
+rust-version = "1.70"
+edition = "2021"
+"#,
+	Some(
+		r#"[package]
+name = "a"
+rust-version.workspace = true
+edition = "2021"
+"#
+	)
+)]
+#[case(
+	r#"[package]
+name = "a"
+"#,
+	Some(
+		r#"[package]
+name = "a"
+rust-version.workspace = true
+"#
+	)
+)]
+#[case(
+	r#"[package]
+name = "a"
+rust-version.workspace = true
+"#,
+	None
+)]
+fn inherit_rust_version_works(#[case] input: &str, #[case] output: Option<&str>) {
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	let res = fixer.inherit_rust_version();
+
+	assert_eq!(res, Ok(output.is_some()));
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output.unwrap_or(input));
+	assert_eq!(fixer.rust_version(), Some(crate::autofix::RustVersion::Workspace));
+}
+
+#[rstest]
+#[case(
+	r#"[features]
+std = ["a/try", "b?/try"]
+try = ["a/try"]
+"#,
+	r#"[features]
+std = ["a/bench", "b?/bench"]
+bench = ["a/bench"]
+"#,
+	(true, false, 3)
+)]
+#[case(
+	r#"[features]
+default = ["try"]
+try = ["a/try", "b"]
+bench = ["a/bench", "try"]
+"#,
+	r#"[features]
+default = ["bench"]
+bench = [
+	"a/bench",
+	"b"
+]
+"#,
+	(false, true, 3)
+)]
+#[case(
+	r#"[features]
+std = ["a/try", "try"]
+"#,
+	r#"[features]
+std = ["a/bench", "try"]
+"#,
+	(false, false, 1)
+)]
+fn merge_feature_works(
+	#[case] input: &str,
+	#[case] output: &str,
+	#[case] (renamed, merged, references): (bool, bool, usize),
+) {
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	let res = fixer.merge_feature("try", "bench").unwrap();
+
+	assert_eq!(res, crate::autofix::FeatureMerge { renamed, merged, references });
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output);
+}
+
+#[test]
+fn path_dependencies_works() {
+	let fixer = AutoFixer::from_raw(
+		r#"[dependencies]
+a = { path = "../a" }
+b = "1"
+c = { package = "d", path = "d" }
+
+[target.'cfg(unix)'.build-dependencies]
+e = { path = "/e" }
+
+[workspace.dependencies]
+f = { path = "../f" }
+"#,
+	)
+	.unwrap();
+	let deps = fixer
+		.path_dependencies()
+		.into_iter()
+		.map(|d| (d.key, d.package, d.path))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		deps,
+		vec![
+			("a".into(), "a".into(), "../a".into()),
+			("c".into(), "d".into(), "d".into()),
+			("e".into(), "e".into(), "/e".into()),
+			("f".into(), "f".into(), "../f".into()),
+		]
+	);
+}
+
+#[test]
+fn workspace_dependency_features_works() {
+	let fixer = AutoFixer::from_raw(
+		r#"[dependencies]
+a = { workspace = true }
+b = { workspace = true, features = ["x"] }
+c = { version = "1", features = ["y"] }
+
+[target.'cfg(unix)'.dev-dependencies]
+a = { workspace = true }
+
+[workspace.dependencies]
+a = { path = "a", features = ["std", "serde"] }
+b = { version = "1", features = [] }
+d = { workspace = true }
+"#,
+	)
+	.unwrap();
+
+	assert_eq!(
+		fixer.workspace_dependency_features().into_iter().collect::<Vec<_>>(),
+		vec![("a".to_string(), vec!["std".to_string(), "serde".to_string()])]
+	);
+	assert_eq!(fixer.inherited_dependencies(), vec!["a".to_string(), "b".to_string()]);
+}
+
+#[test]
+fn default_feature_conflicts_works() {
+	let fixer = AutoFixer::from_raw(
+		r#"[dependencies]
+a = { version = "1", default-features = false, features = ["default"] }
+b = { version = "1", default-features = false, features = ["std"] }
+c = { version = "1", features = ["default"] }
+
+[dev-dependencies]
+a = { version = "1", default-features = false, features = ["default"] }
+
+[target.'cfg(unix)'.dependencies]
+d = { version = "1", default_features = false, features = ["std", "default"] }
+
+[workspace.dependencies]
+a = { version = "1", default-features = false, features = ["default"] }
+"#,
+	)
+	.unwrap();
+	let deps = fixer
+		.default_feature_conflicts()
+		.into_iter()
+		.map(|d| (d.key, d.workspace))
+		.collect::<Vec<_>>();
+
+	assert_eq!(deps, vec![("a".into(), false), ("d".into(), false), ("a".into(), true)]);
+}
+
+#[rstest]
+#[case(
+	DefaultFeatureConflictFix::DefaultFeature,
+	r#"a = { version = "1", default-features = false }
+b = { version = "1", default-features = false, features = ["std"] }
+c = { version = "1", default_features = false, features = ["std", "x"] }
+"#
+)]
+#[case(
+	DefaultFeatureConflictFix::DefaultFeaturesKey,
+	r#"a = { version = "1", features = ["default"] }
+b = { version = "1", features = ["default", "std"] }
+c = { version = "1", features = ["std", "default", "x"] }
+"#
+)]
+fn resolve_default_feature_conflict_works(
+	#[case] remove: DefaultFeatureConflictFix,
+	#[case] output: &str,
+) {
+	let mut fixer = AutoFixer::from_raw(
+		r#"[dependencies]
+a = { version = "1", default-features = false, features = ["default"] }
+b = { version = "1", default-features = false, features = ["default", "std"] }
+c = { version = "1", default_features = false, features = ["std", "default", "x"] }
+"#,
+	)
+	.unwrap();
+
+	for dep in ["a", "b", "c"] {
+		assert!(fixer.resolve_default_feature_conflict(dep, remove).unwrap());
+		assert!(!fixer.resolve_default_feature_conflict(dep, remove).unwrap());
+	}
+	assert_eq!(fixer.to_string(), format!("[dependencies]\n{}", output));
+}
+
+#[test]
+fn build_feature_dag_collects_malformed_entries() {
+	let mut meta = cargo_metadata::MetadataCommand::new()
+		.manifest_path(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+		.no_deps()
+		.exec()
+		.unwrap();
+	let pkg = meta.packages.iter_mut().find(|p| p.name == "zepter").unwrap();
+	pkg.features.insert(
+		"broken".into(),
+		vec!["unknown/std".into(), "dep:unknown".into(), "missing".into(), "logging".into()],
+	);
+
+	let (dag, errors) = crate::cmd::lint::try_build_feature_dag(
+		&crate::cmd::PackageIndex::new(&meta),
+		&meta.packages,
+	);
+	let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+	assert_eq!(
+		errors,
+		vec![
+			"crate 'zepter' feature 'broken': unknown dependency 'unknown' in 'dep:unknown'",
+			"crate 'zepter' feature 'broken': unknown feature 'missing' in 'missing'",
+			"crate 'zepter' feature 'broken': unknown dependency 'unknown' in 'unknown/std'",
+		]
+	);
+	// The valid entry is still added.
+	assert!(dag
+		.edges
+		.iter()
+		.any(|(from, to)| from.1 == "broken" && to.iter().any(|t| t.1 == "logging")));
+}
+
+#[test]
+fn package_index_works() {
+	let ctx = crate::mock::Context::new();
+	let meta = ctx.workspace_metadata("[{ name: A, deps: [B] }, { name: B }]").unwrap();
+	let index = crate::cmd::PackageIndex::new(&meta);
+
+	let a = index.by_name("A")[0];
+	assert_eq!(index.get(&a.id.repr).unwrap().name, "A");
+	assert_eq!(index.get(&format!("{}?", a.id.repr)).unwrap().name, "A");
+	assert!(index.get("unknown").is_none());
+	assert!(index.by_name("unknown").is_empty());
+
+	let dep = a.dependencies.iter().find(|d| d.name == "B").unwrap();
+	assert_eq!(index.resolve_dep(a, dep).unwrap().pkg.name, "B");
+}
+
+#[test]
+fn package_index_resolves_versions_per_target() {
+	use serde_json::json;
+
+	let pkg = |name: &str, version: &str, deps: serde_json::Value| {
+		json!({
+			"name": name,
+			"version": version,
+			"id": format!("{} {}", name, version),
+			"dependencies": deps,
+			"targets": [],
+			"features": {},
+			"manifest_path": format!("/{}-{}/Cargo.toml", name, version),
+		})
+	};
+	let dep = |req: &str, target: &str| {
+		json!({
+			"name": "b",
+			"req": req,
+			"kind": null,
+			"optional": false,
+			"uses_default_features": true,
+			"features": [],
+			"target": target,
+		})
+	};
+	let node_dep = |id: &str, target: &str| json!({ "name": "b", "pkg": id, "dep_kinds": [{ "kind": null, "target": target }] });
+	let meta = json!({
+		"packages": [
+			pkg("a", "1.0.0", json!([dep("^1", "cfg(unix)"), dep("^2", "cfg(windows)")])),
+			pkg("b", "1.0.0", json!([])),
+			pkg("b", "2.0.0", json!([])),
+		],
+		"workspace_members": ["a 1.0.0"],
+		"workspace_default_members": ["a 1.0.0"],
+		"resolve": {
+			"root": null,
+			"nodes": [{
+				"id": "a 1.0.0",
+				"dependencies": ["b 1.0.0", "b 2.0.0"],
+				"deps": [node_dep("b 1.0.0", "cfg(unix)"), node_dep("b 2.0.0", "cfg(windows)")],
+			}],
+		},
+		"workspace_root": "/",
+		"target_directory": "/target",
+		"version": 1,
+	});
+	let meta = serde_json::from_value::<cargo_metadata::Metadata>(meta).unwrap();
+	let index = crate::cmd::PackageIndex::new(&meta);
+	let a = index.by_name("a")[0];
+
+	// Both dependencies are called `b`, but each resolves to its own version.
+	let resolved = a
+		.dependencies
+		.iter()
+		.map(|dep| index.resolve_dep(a, dep).unwrap().pkg.version.to_string())
+		.collect::<Vec<_>>();
+	assert_eq!(resolved, vec!["1.0.0", "2.0.0"]);
+	assert!(index.has_multiple_versions("b"));
+	assert!(!index.has_multiple_versions("a"));
+}
+
+#[test]
+fn package_index_reports_unresolved_deps() {
+	use crate::cmd::UnresolvedDepsMode;
+
+	let ctx = crate::mock::Context::new();
+	let meta = ctx.workspace_metadata("[{ name: A, deps: [B] }, { name: B }]").unwrap();
+	let index = crate::cmd::PackageIndex::new(&meta);
+	let a = index.by_name("A")[0];
+	let mut unknown = a.dependencies.iter().find(|d| d.name == "B").unwrap().clone();
+	unknown.name = "unknown".into();
+	let mut optional = unknown.clone();
+	optional.name = "optional".into();
+	optional.optional = true;
+
+	assert!(index.report_unresolved(UnresolvedDepsMode::Error).is_ok());
+	assert!(index.resolve_dep(a, &unknown).is_none());
+	assert!(index.resolve_dep(a, &optional).is_none());
+	// Reporting the same dependency twice does not duplicate it.
+	assert!(index.resolve_dep(a, &unknown).is_none());
+
+	assert!(index.report_unresolved(UnresolvedDepsMode::Ignore).is_ok());
+	assert!(index.report_unresolved(UnresolvedDepsMode::Warn).is_ok());
+	assert_eq!(
+		index.report_unresolved(UnresolvedDepsMode::Error).unwrap_err(),
+		"Could not resolve 2 dependencies:\n  'optional' of 'A': optional and not enabled\n  \
+		 'unknown' of 'A': not in the resolve graph"
+	);
+}
+
+#[rstest]
+#[case(&[], "zepter lint feature-typos --fix")]
+#[case(&[("--packages", "a,b")], "zepter lint feature-typos --packages a,b --fix")]
+#[case(&[("--hint", "two words")], "zepter lint feature-typos --hint 'two words' --fix")]
+#[case(&[("--packages", "pallet-*")], "zepter lint feature-typos --packages 'pallet-*' --fix")]
+#[case(&[("--hint", "it's")], "zepter lint feature-typos --hint 'it'\\''s' --fix")]
+#[case(&[("--hint", "")], "zepter lint feature-typos --hint '' --fix")]
+fn fix_command_works(#[case] args: &[(&str, &str)], #[case] output: &str) {
+	let cargo_args = crate::cmd::CargoArgs {
+		manifest_path: vec![],
+		workspace: false,
+		offline: false,
+		locked: crate::cmd::LockedMode::Never,
+		all_features: false,
+		unresolved_deps: crate::cmd::UnresolvedDepsMode::Ignore,
+	};
+	let mut cmd = crate::cmd::FixCommand::new("lint feature-typos", &cargo_args);
+	for (flag, value) in args {
+		cmd = cmd.arg(flag, value);
+	}
+
+	assert_eq!(cmd.flag("--fix").to_string(), output);
+}
+
+#[rstest]
+// Subset is removed together with the empty table.
+#[case(
+	r#"[dependencies]
+a = { version = "1", features = ["std", "serde"] }
+
+[dev-dependencies]
+a = { version = "1", features = ["std"] }
+"#,
+	r#"[dependencies]
+a = { version = "1", features = ["std", "serde"] }
+"#
+)]
+// Features are merged into an existing list.
+#[case(
+	r#"[dependencies]
+a = { workspace = true, features = ["std"] }
+
+[dev-dependencies]
+a = { workspace = true, features = ["std", "test"] }
+b = "2"
+"#,
+	r#"[dependencies]
+a = { workspace = true, features = ["std", "test"] }
+
+[dev-dependencies]
+b = "2"
+"#
+)]
+// A plain version is turned into an inline table.
+#[case(
+	r#"[dependencies]
+a = "1"
+
+[dev-dependencies]
+a = { version = "1", features = ["test"] }
+"#,
+	r#"[dependencies]
+a = { version = "1", features = ["test"] }
+"#
+)]
+// Target specific tables.
+#[case(
+	r#"[target.'cfg(unix)'.dependencies]
+a = { version = "1", default-features = false }
+
+[target.'cfg(unix)'.dev-dependencies]
+a = { version = "1", default-features = false }
+"#,
+	r#"[target.'cfg(unix)'.dependencies]
+a = { version = "1", default-features = false }
+"#
+)]
+// Conflicts are not touched.
+#[case(
+	r#"[dependencies]
+a = "1"
+b = { version = "1", default-features = false }
+c = { version = "1", optional = true }
+
+[dev-dependencies]
+a = "2"
+b = "1"
+c = "1"
+"#,
+	r#"[dependencies]
+a = "1"
+b = { version = "1", default-features = false }
+c = { version = "1", optional = true }
+
+[dev-dependencies]
+a = "2"
+b = "1"
+c = "1"
+"#
+)]
+fn resolve_duplicate_dependency_works(#[case] input: &str, #[case] output: &str) {
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+	for dup in fixer.duplicate_dependencies() {
+		fixer.resolve_duplicate_dependency(&dup).unwrap();
+	}
+
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output);
+}
+
+#[test]
+fn duplicate_dependencies_works() {
+	use crate::autofix::DuplicateResolution::*;
+	let fixer = AutoFixer::from_raw(
+		r#"[dependencies]
+a = "1"
+b = { version = "1", default-features = false }
+c = { version = "1", optional = true }
+d = { version = "1" }
+
+[dev-dependencies]
+a = "2"
+b = "1"
+c = "1"
+d = { version = "1", features = ["x"] }
+"#,
+	)
+	.unwrap();
+	let found = fixer
+		.duplicate_dependencies()
+		.into_iter()
+		.map(|d| (d.key, d.resolution))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		found,
+		vec![
+			("a".into(), Conflict("the versions or sources differ".into())),
+			("b".into(), Conflict("only the dev dependency enables the default features".into())),
+			("c".into(), Conflict("the normal dependency is optional".into())),
+			("d".into(), MergeFeatures(vec!["x".into()])),
+		]
+	);
+}
+
+#[rstest]
+#[case("a/std", Some("[features]\nstd = [\n\t\"b/std\",\n]\n"))]
+#[case("b/std", Some("[features]\nstd = [\n\t\"a/std\",\n]\n"))]
+#[case("c/std", None)]
+fn remove_from_feature_works(#[case] value: &str, #[case] output: Option<&str>) {
+	let input = "[features]\nstd = [\n\t\"a/std\",\n\t\"b/std\",\n]\n";
+	let mut fixer = AutoFixer::from_raw(input).unwrap();
+
+	assert_eq!(fixer.remove_from_feature("std", value).unwrap(), output.is_some());
+	pretty_assertions::assert_str_eq!(fixer.to_string(), output.unwrap_or(input));
+	assert!(fixer.remove_from_feature("unknown", value).is_err());
+}
+
+#[rstest]
+#[case("", vec![])]
+#[case("[patch.crates-io]\nserde = { path = \"serde\" }\n", vec!["serde"])]
+#[case("[patch.crates-io]\nserde2 = { package = \"serde\", git = \"x\" }\n[patch.'https://github.com/a/b']\nlog = { path = \"log\" }\n", vec!["log", "serde"])]
+#[case("[replace]\n\"foo:0.1.0\" = { path = \"foo\" }\n", vec!["foo"])]
+fn patched_in_works(#[case] input: &str, #[case] expected: Vec<&str>) {
+	let patched = crate::cmd::patched_in(input).unwrap();
+	assert_eq!(patched.iter().map(String::as_str).collect::<Vec<_>>(), expected);
+}
+
+#[rstest]
+#[case("[features]\nstd = []\n", "std", Some(vec![]))]
+#[case("[features]\n# Use the std.\n#\n# More.\nstd = []\n", "std", Some(vec!["Use the std.", "", "More."]))]
+#[case("[features]\n# Not mine.\n\nstd = []\n", "std", Some(vec![]))]
+#[case("[features]\nstd = []\n", "foo", None)]
+fn feature_doc_works(
+	#[case] input: &str,
+	#[case] feature: &str,
+	#[case] expected: Option<Vec<&str>>,
+) {
+	let fixer = AutoFixer::from_raw(input).unwrap();
+	let expected = expected.map(|e| e.into_iter().map(String::from).collect::<Vec<_>>());
+	assert_eq!(fixer.feature_doc(feature), expected);
+}
+
+#[test]
+fn features_works() {
+	let fixer = AutoFixer::from_raw(
+		"[dependencies]\nlog = { version = \"*\", optional = true }\n\n[features]\nstd = [\"dep:log\", \"serde?/std\"]\nempty = []\n",
+	)
+	.unwrap();
+	let features = fixer.features();
+
+	assert_eq!(features.len(), 2);
+	assert_eq!(features["std"], vec!["dep:log", "serde?/std"]);
+	assert!(features["empty"].is_empty());
+	assert!(AutoFixer::from_raw("").unwrap().features().is_empty());
+}
+
+#[test]
+fn document_feature_works() {
+	let mut fixer = AutoFixer::from_raw("[features]\nstd = []\n\nfoo = []\n").unwrap();
+	fixer.document_feature("std", "Use the std.").unwrap();
+	fixer.document_feature("foo", "Foo.").unwrap();
+	assert!(fixer.document_feature("bar", "Bar.").is_err());
+
+	assert_eq!(fixer.to_string(), "[features]\n# Use the std.\nstd = []\n\n# Foo.\nfoo = []\n");
+	assert_eq!(fixer.feature_doc("foo"), Some(vec!["Foo.".into()]));
+}
+
+#[test]
+fn concurrent_saves_do_not_interleave() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\n").unwrap();
+
+	let contents = (0..8)
+		.map(|i| {
+			format!(
+				"[features]\n{}",
+				(0..1000).map(|j| format!("f{i}_{j} = []\n")).collect::<String>()
+			)
+		})
+		.collect::<Vec<_>>();
+	std::thread::scope(|s| {
+		for content in contents.iter() {
+			let manifest = &manifest;
+			s.spawn(move || {
+				let mut fixer = AutoFixer::from_raw(content).unwrap();
+				fixer.manifest = Some(manifest.clone());
+				fixer.save().unwrap();
+			});
+		}
+	});
+
+	let written = std::fs::read_to_string(&manifest).unwrap();
+	assert!(contents.contains(&written));
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1, "Lock files are removed");
+}
+
+#[test]
+fn save_waits_for_old_lock_of_running_process() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\n").unwrap();
+	// Owned by a running process and older than any write takes.
+	let lock = dir.path().join(".Cargo.toml.zepter-lock");
+	std::fs::write(&lock, std::process::id().to_string()).unwrap();
+	let old = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+	std::fs::File::options()
+		.write(true)
+		.open(&lock)
+		.unwrap()
+		.set_modified(old)
+		.unwrap();
+	let release = std::thread::spawn(move || {
+		std::thread::sleep(std::time::Duration::from_millis(200));
+		std::fs::remove_file(lock).unwrap();
+	});
+
+	let start = std::time::Instant::now();
+	let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+	fixer.add_feature("foo").unwrap();
+	fixer.save().unwrap();
+	release.join().unwrap();
+
+	assert!(start.elapsed() >= std::time::Duration::from_millis(200), "Waits for the owner");
+	assert_eq!(std::fs::read_to_string(&manifest).unwrap(), "[features]\nfoo = []\n");
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1, "Lock file is removed");
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn save_removes_lock_of_exited_process() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\n").unwrap();
+	let mut child = std::process::Command::new("true").spawn().unwrap();
+	child.wait().unwrap();
+	std::fs::write(dir.path().join(".Cargo.toml.zepter-lock"), child.id().to_string()).unwrap();
+
+	let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+	fixer.add_feature("foo").unwrap();
+	fixer.save().unwrap();
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1, "Lock file is removed");
+}
+
+#[test]
+fn interrupted_save_keeps_manifest() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\nstd = []\n").unwrap();
+	// Writing the temporary file fails since there is a directory in its place.
+	std::fs::create_dir(dir.path().join(".Cargo.toml.zepter-tmp")).unwrap();
+
+	let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+	fixer.add_feature("foo").unwrap();
+	let err = fixer.save().unwrap_err();
+
+	assert!(matches!(err, ManifestError::Io { action: "write", .. }), "{err}");
+	assert_eq!(std::fs::read_to_string(&manifest).unwrap(), "[features]\nstd = []\n");
+}
+
+#[test]
+fn save_replaces_stale_temporary_file() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\nstd = []\n").unwrap();
+	// Left behind by a process that crashed in the middle of writing.
+	std::fs::write(dir.path().join(".Cargo.toml.zepter-tmp"), "[featu").unwrap();
+
+	let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+	fixer.add_feature("foo").unwrap();
+	fixer.save().unwrap();
+
+	let written = std::fs::read_to_string(&manifest).unwrap();
+	assert!(written.contains("foo = []"), "{written}");
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1, "Temporary file is moved");
+}
+
+#[test]
+#[cfg(unix)]
+fn save_keeps_permissions() {
+	use std::os::unix::fs::PermissionsExt;
+
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+	std::fs::write(&manifest, "[features]\n").unwrap();
+	std::fs::set_permissions(&manifest, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+	let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+	fixer.add_feature("foo").unwrap();
+	fixer.save().unwrap();
+
+	let mode = std::fs::metadata(&manifest).unwrap().permissions().mode();
+	assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn manifest_errors_are_distinguished() {
+	let dir = tempfile::tempdir().unwrap();
+	let manifest = dir.path().join("Cargo.toml");
+
+	let err = AutoFixer::from_manifest(&manifest).err().unwrap();
+	assert!(matches!(err, ManifestError::Io { action: "read", .. }), "{err}");
+
+	std::fs::write(&manifest, "[features\n").unwrap();
+	let err = AutoFixer::from_manifest(&manifest).err().unwrap();
+	assert!(matches!(err, ManifestError::Parse { path: Some(_), .. }), "{err}");
+	assert!(err.to_string().starts_with("Failed to parse manifest "));
+}
+
+#[rstest]
+#[case("pub fn a() {}\n", "#![cfg_attr(not(feature = \"std\"), no_std)]\n\npub fn a() {}\n")]
+#[case(
+	"// License\n\n//! Docs\n#![deny(warnings)]\n",
+	"// License\n\n//! Docs\n#![cfg_attr(not(feature = \"std\"), no_std)]\n#![deny(warnings)]\n"
+)]
+#[case("", "#![cfg_attr(not(feature = \"std\"), no_std)]\n")]
+fn insert_cfg_attr_works(#[case] input: &str, #[case] output: &str) {
+	assert_eq!(crate::cmd::lint::insert_cfg_attr(input), output);
+}
+
+#[test]
+fn merge_metadata_works() {
+	let ctx = crate::mock::Context::new();
+	let meta = ctx.workspace_metadata("[{ name: A, deps: [B] }, { name: B }]").unwrap();
+	let mut other = meta.clone();
+	other.workspace_root = "/other".into();
+	let extra = other.packages.iter_mut().find(|p| p.name == "B").unwrap();
+	extra.id.repr = "extra".into();
+	let label = meta.workspace_root.file_name().unwrap().to_string();
+
+	let (merged, origins) = crate::cmd::merge_metadata(vec![meta.clone(), other]);
+
+	// Only the renamed package is new.
+	assert_eq!(merged.packages.len(), meta.packages.len() + 1);
+	assert_eq!(merged.workspace_members, meta.workspace_members);
+	assert_eq!(merged.resolve.unwrap().nodes.len(), meta.resolve.unwrap().nodes.len());
+	assert_eq!(origins.len(), meta.packages.len() + 1);
+	assert_eq!(origins["extra"], "other");
+	assert!(meta.packages.iter().all(|p| origins[&p.id.repr] == label));
+}
+
+#[test]
+fn manifest_dependency_edits_work() {
+	use crate::manifest::{self, DependencyEntry};
+
+	let mut doc = "[dependencies]\nlog = \"0.4\" # logging\nserde = { version = \"1\" }\n"
+		.parse::<toml_edit::DocumentMut>()
+		.unwrap();
+	let entry = DependencyEntry { path: Some("../a".into()), ..Default::default() };
+
+	manifest::add_dependency(&mut doc, &Normal, "a", &entry).unwrap();
+	assert!(manifest::add_dependency(&mut doc, &Normal, "a", &entry).is_err());
+	manifest::set_default_features(&mut doc, &Normal, "log", false).unwrap();
+	manifest::set_features(&mut doc, &Normal, "serde", &["derive".into()]).unwrap();
+	assert_eq!(
+		doc.to_string(),
+		"[dependencies]\na = { path = \"../a\" }\nlog = { version = \"0.4\", default-features = false } # logging\nserde = { version = \"1\", features = [\"derive\"] }\n"
+	);
+	assert_eq!(manifest::get_dependency(&doc, &Normal, "a"), Some(entry));
+	assert_eq!(
+		manifest::get_dependency(&doc, &Normal, "serde").unwrap().features,
+		vec!["derive".to_string()]
+	);
+
+	manifest::set_features(&mut doc, &Normal, "serde", &[]).unwrap();
+	assert!(manifest::set_features(&mut doc, &Build, "serde", &[]).is_err());
+	assert!(manifest::remove_dependency(&mut doc, &Normal, "a"));
+	assert!(!manifest::remove_dependency(&mut doc, &Normal, "a"));
+	manifest::add_dependency(
+		&mut doc,
+		&Development,
+		"log",
+		&DependencyEntry { version: Some("0.4".into()), ..Default::default() },
+	)
+	.unwrap();
+	assert_eq!(
+		doc.to_string(),
+		"[dependencies]\nlog = { version = \"0.4\", default-features = false } # logging\nserde = { version = \"1\" }\n\n[dev-dependencies]\nlog = \"0.4\"\n"
+	);
+
+	assert!(manifest::remove_dependency(&mut doc, &Development, "log"));
+	assert!(doc.get("dev-dependencies").is_none());
+
+	manifest::remove_default_features(&mut doc, &Normal, "log").unwrap();
+	assert!(manifest::remove_default_features(&mut doc, &Normal, "a").is_err());
+	assert_eq!(
+		doc.to_string(),
+		"[dependencies]\nlog = { version = \"0.4\" } # logging\nserde = { version = \"1\" }\n"
+	);
+}
+
+/// Two manifests with a fixer each that adds the feature `foo`.
+fn two_fixers(dir: &Path) -> Vec<AutoFixer> {
+	["A", "B"]
+		.iter()
+		.map(|name| {
+			let manifest = dir.join(format!("{name}.toml"));
+			std::fs::write(&manifest, "[features]\n").unwrap();
+			let mut fixer = AutoFixer::from_manifest(&manifest).unwrap();
+			fixer.add_feature("foo").unwrap();
+			fixer
+		})
+		.collect()
+}
+
+#[test]
+fn fix_transaction_validates_before_writing() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut transaction = FixTransaction::default();
+	for fixer in two_fixers(dir.path()) {
+		transaction.push(fixer);
+	}
+	// Modified by someone else after it was read.
+	std::fs::write(dir.path().join("B.toml"), "[features]\nbar = []\n").unwrap();
+
+	let err = transaction.commit(|fixer| fixer.save().map_err(Into::into)).unwrap_err();
+	assert!(err.starts_with("Did not write any of the 2 fixed manifests: Manifest "), "{err}");
+	assert_eq!(std::fs::read_to_string(dir.path().join("A.toml")).unwrap(), "[features]\n");
+}
+
+#[test]
+fn validate_does_not_touch_the_write_in_progress() {
+	let dir = tempfile::tempdir().unwrap();
+	let fixer = two_fixers(dir.path()).remove(0);
+	// Temporary file of a concurrent `write_manifest`.
+	let tmp = dir.path().join(".A.toml.zepter-tmp");
+	std::fs::write(&tmp, "[features]\nbar = []\n").unwrap();
+
+	fixer.validate().unwrap();
+	assert_eq!(std::fs::read_to_string(&tmp).unwrap(), "[features]\nbar = []\n");
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3, "No probe files");
+}
+
+#[test]
+fn fix_transaction_rolls_back_on_failure() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut transaction = FixTransaction::default();
+	for fixer in two_fixers(dir.path()) {
+		transaction.push(fixer);
+	}
+	assert_eq!(transaction.len(), 2);
+
+	let mut saved = 0;
+	let err = transaction
+		.commit(|fixer| {
+			saved += 1;
+			if saved == 2 {
+				return Err("Disk full".into())
+			}
+			fixer.save().map_err(Into::into)
+		})
+		.unwrap_err();
+	assert_eq!(err, "Disk full. Restored 1 already written manifest");
+	for name in ["A.toml", "B.toml"] {
+		assert_eq!(std::fs::read_to_string(dir.path().join(name)).unwrap(), "[features]\n");
+	}
+	assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2, "No lock or temporary files");
+}
+
+#[test]
+fn fix_transaction_writes_all() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut transaction = FixTransaction::default();
+	for fixer in two_fixers(dir.path()) {
+		transaction.push(fixer);
+	}
+	transaction.push(AutoFixer::from_raw("[features]\n").unwrap());
+
+	assert_eq!(transaction.commit(|fixer| fixer.save().map_err(Into::into)), Ok(2));
+	for name in ["A.toml", "B.toml"] {
+		let written = std::fs::read_to_string(dir.path().join(name)).unwrap();
+		assert!(written.contains("foo = []"), "{written}");
+	}
+}
+
+#[test]
+fn fix_transaction_writes_in_path_order() {
+	let dir = tempfile::tempdir().unwrap();
+	let mut transaction = FixTransaction::default();
+	for fixer in two_fixers(dir.path()).into_iter().rev() {
+		transaction.push(fixer);
+	}
+
+	let mut order = Vec::new();
+	transaction
+		.commit(|fixer| {
+			order.push(fixer.manifest.clone().unwrap());
+			Ok(())
+		})
+		.unwrap();
+	assert_eq!(order, vec![dir.path().join("A.toml"), dir.path().join("B.toml")]);
+}
+
+#[test]
+fn git_diff_safety_checks_only_given_manifests() {
+	let dir = tempfile::tempdir().unwrap();
+	let git = |args: &[&str]| {
+		let status = std::process::Command::new("git")
+			.current_dir(dir.path())
+			.args(["-c", "user.name=zepter", "-c", "user.email=zepter@localhost"])
+			.args(args)
+			.output()
+			.unwrap()
+			.status;
+		assert!(status.success(), "git {:?}", args);
+	};
+	git(&["init", "-q"]);
+	for krate in ["a", "b c", "d"] {
+		std::fs::create_dir(dir.path().join(krate)).unwrap();
+		std::fs::write(dir.path().join(krate).join("Cargo.toml"), "[package]\n").unwrap();
+	}
+	git(&["add", "."]);
+	git(&["commit", "-q", "-m", "init"]);
+	// A path that is quoted by plain porcelain and a rename.
+	std::fs::write(dir.path().join("b c/Cargo.toml"), "[features]\n").unwrap();
+	std::fs::create_dir(dir.path().join("e")).unwrap();
+	git(&["mv", "d/Cargo.toml", "e/Cargo.toml"]);
+
+	let global = |args: &[&str]| {
+		GlobalArgs::try_parse_from(std::iter::once("zepter").chain(args.iter().copied())).unwrap()
+	};
+	let manifest = |krate: &str| dir.path().join(krate).join("Cargo.toml");
+	let safe = global(&["--git-diff-safety"]);
+
+	assert_eq!(safe.check_git_clean([manifest("a").as_path()]), Ok(()));
+	let err = safe.check_git_clean([manifest("a"), manifest("b c")].iter().map(|p| p.as_path()));
+	assert!(err.unwrap_err().starts_with("1 manifest in the working directory contains"));
+	let err = safe.check_git_clean([manifest("e").as_path()]).unwrap_err();
+	assert!(err.contains("\n\ne/Cargo.toml\n\n"), "{}", err);
+	assert!(global(&["--git-diff-safety", "--allow-dirty"])
+		.check_git_clean([manifest("b c").as_path()])
+		.is_ok());
+}
+
+#[rstest]
+#[case(
+	&["a/", "a?/"],
+	EntryMatch::Prefix,
+	vec![("std", "a/std"), ("std", "a?/std"), ("foo", "a/foo")],
+	vec!["ab/std", "b/std"],
+	vec![]
+)]
+#[case(
+	&["a/std"],
+	EntryMatch::Exact,
+	vec![("std", "a/std")],
+	vec!["a?/std", "ab/std", "b/std"],
+	vec!["a/foo"]
+)]
+#[case(&["c/"], EntryMatch::Prefix, vec![], vec!["a/std", "a?/std", "ab/std", "b/std"], vec!["a/foo"])]
+fn remove_from_features_works(
+	#[case] patterns: &[&str],
+	#[case] matching: EntryMatch,
+	#[case] removed: Vec<(&str, &str)>,
+	#[case] std: Vec<&str>,
+	#[case] foo: Vec<&str>,
+) {
+	let before =
+		"[features]\nstd = [\"a/std\", \"a?/std\", \"ab/std\", \"b/std\"]\nfoo = [\"a/foo\"]\n";
+	let mut fixer = AutoFixer::from_raw(before).unwrap();
+
+	let got = fixer
+		.remove_from_features(patterns, matching)
+		.into_iter()
+		.map(|r| (r.feature, r.entry))
+		.collect::<Vec<_>>();
+	let removed = removed.into_iter().map(|(f, e)| (f.into(), e.into())).collect::<Vec<_>>();
+	assert_eq!(got, removed);
+	let features = fixer.features();
+	assert_eq!(features["std"], std);
+	assert_eq!(features["foo"], foo);
+}